@@ -0,0 +1,64 @@
+//! Message lookups and decoding against a descriptor set with thousands of message types.
+//! Run with `cargo bench --bench descriptor_lookup`.
+
+use my_bdd::proto_dyn::ProtoDyn;
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+use std::hint::black_box;
+use std::time::Instant;
+
+const PACKAGES: usize = 50;
+const MESSAGES_PER_PACKAGE: usize = 100;
+
+fn descriptor_set() -> Vec<u8> {
+    let file = |p: usize| FileDescriptorProto {
+        name: Some(format!("pkg{}.proto", p)),
+        package: Some(format!("company.pkg{}.v1", p)),
+        syntax: Some("proto3".into()),
+        message_type: (0..MESSAGES_PER_PACKAGE)
+            .map(|m| DescriptorProto {
+                name: Some(format!("Message{}_{}", p, m)),
+                field: vec![FieldDescriptorProto {
+                    name: Some("seq".into()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::Uint64 as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    FileDescriptorSet { file: (0..PACKAGES).map(file).collect() }.encode_to_vec()
+}
+
+fn bench(label: &str, iterations: usize, mut f: impl FnMut(usize)) {
+    let started = Instant::now();
+    for i in 0..iterations {
+        f(i);
+    }
+    let elapsed = started.elapsed();
+    println!("{:<28} {:>8} iterations  {:>8.0} ns/iter", label, iterations, elapsed.as_nanos() as f64 / iterations as f64);
+}
+
+fn main() {
+    let bytes = descriptor_set();
+    let started = Instant::now();
+    let proto = ProtoDyn::from_descriptor_set(&bytes).unwrap();
+    println!("load and index {} messages: {:?}", PACKAGES * MESSAGES_PER_PACKAGE, started.elapsed());
+
+    let short: Vec<String> = (0..PACKAGES * MESSAGES_PER_PACKAGE).map(|i| format!("Message{}_{}", i / MESSAGES_PER_PACKAGE, i % MESSAGES_PER_PACKAGE)).collect();
+    let full: Vec<String> = (0..short.len()).map(|i| format!("company.pkg{}.v1.{}", i / MESSAGES_PER_PACKAGE, short[i])).collect();
+    bench("message_desc (full name)", 100_000, |i| {
+        black_box(proto.message_desc(&full[i % full.len()]).unwrap());
+    });
+    bench("message_desc (short name)", 100_000, |i| {
+        black_box(proto.message_desc(&short[i % short.len()]).unwrap());
+    });
+    let payload = [0x08, 0x2a];
+    bench("decode_message (short name)", 100_000, |i| {
+        black_box(proto.decode_message(&short[i % short.len()], &payload).unwrap());
+    });
+}
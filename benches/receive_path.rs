@@ -0,0 +1,87 @@
+//! Throughput of the receive buffer and of decoding collected messages, at 100k messages.
+//! Run with `cargo bench --bench receive_path`.
+
+use anyhow::Result;
+use my_bdd::broker::Broker;
+use my_bdd::config::BufferConfig;
+use my_bdd::matchers::MatchOptions;
+use my_bdd::proto_dyn::ProtoDyn;
+use my_bdd::receiver::Receiver;
+use my_bdd::transport::{Publisher, Subscriber};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MESSAGES: usize = 100_000;
+
+struct Discard;
+
+impl Publisher for Discard {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, _: &str, _: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Hands out prepared messages as fast as they are asked for
+struct Replay(VecDeque<(String, Vec<u8>)>);
+
+impl Subscriber for Replay {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        let next = self.0.pop_front();
+        if next.is_none() {
+            std::thread::sleep(timeout);
+        }
+        Ok(next)
+    }
+}
+
+/// Mostly PongReply telemetry with some Status chatter, ending in one PingRequest
+fn traffic() -> VecDeque<(String, Vec<u8>)> {
+    let proto = ProtoDyn::new().unwrap();
+    let pong = proto.encode_message(&proto.build_from_json("PongReply", &json!({ "message": "Hello" })).unwrap()).unwrap();
+    let status = proto.encode_message(&proto.build_from_json("Status", &json!({ "state": "READY" })).unwrap()).unwrap();
+    let mut messages: VecDeque<_> =
+        (0..MESSAGES - 1).map(|i| if i % 4 == 0 { ("Status".to_string(), status.clone()) } else { ("PongReply".to_string(), pong.clone()) }).collect();
+    messages.push_back(("PingRequest".to_string(), Vec::new()));
+    messages
+}
+
+fn rate(label: &str, count: usize, elapsed: Duration) {
+    println!("{:<36} {:>8} messages in {:>10.3?}  {:>10.0} msg/s", label, count, elapsed, count as f64 / elapsed.as_secs_f64());
+}
+
+fn main() {
+    let receiver = Receiver::spawn(Box::new(Replay(traffic())), BufferConfig::default()).unwrap();
+    let started = Instant::now();
+    receiver.wait_until(started + Duration::from_secs(60), |inbox| (inbox.len() == MESSAGES).then_some(())).expect("all buffered");
+    rate("buffer", MESSAGES, started.elapsed());
+
+    let broker = Broker::with_transport(Box::new(Discard), Box::new(Replay(traffic()))).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while broker.recent(1).first().is_none_or(|m| m.topic != "PingRequest") {
+        assert!(Instant::now() < deadline, "traffic not buffered in time");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let started = Instant::now();
+    let pongs = broker.captured("PongReply").unwrap();
+    rate("decode PongReply to JSON", pongs.len(), started.elapsed());
+
+    // the only match is the last message, so every expectation scans the whole buffer
+    let started = Instant::now();
+    broker.expect_message("PingRequest", &json!({}), 60_000).unwrap();
+    rate("expect past other topics", MESSAGES, started.elapsed());
+    let started = Instant::now();
+    assert!(broker.expect_message("Status", &json!({ "state": "BUSY" }), 0).is_err());
+    rate("expect Status, no match", MESSAGES / 4, started.elapsed());
+    let mut broker = broker;
+    broker.set_match_options(MatchOptions { skip_json: true, ..Default::default() });
+    let started = Instant::now();
+    assert!(broker.expect_message("Status", &json!({ "state": "BUSY" }), 0).is_err());
+    rate("expect Status, no match, skip_json", MESSAGES / 4, started.elapsed());
+}
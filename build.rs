@@ -1,8 +1,24 @@
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
 
-use std::{env, fs, path::PathBuf};
+const DESCRIPTOR_TARGET: &str = "src/descriptor.bin";
+/// Directories searched for .proto files, separated like PATH (default: proto)
+const DIRS_ENV: &str = "BDD_PROTO_DIRS";
+/// Extra import paths, e.g. for the well-known types, separated like PATH
+const INCLUDES_ENV: &str = "BDD_PROTO_INCLUDES";
 
 fn main() {
-    println!("cargo:rerun-if-changed=proto");
+    // checked against the embedded copy at runtime, in case copying it into src/ fails;
+    // empty when nothing was compiled
+    let hash = compile_protos().unwrap_or_default();
+    println!("cargo:rustc-env=BDD_DESCRIPTOR_HASH={}", hash);
+}
+
+/// Compile the protos; the hash of the descriptor set when it was compiled for embedding
+fn compile_protos() -> Option<String> {
+    println!("cargo:rerun-if-env-changed=PROTOC");
+    println!("cargo:rerun-if-env-changed={}", DIRS_ENV);
+    println!("cargo:rerun-if-env-changed={}", INCLUDES_ENV);
+    println!("cargo:rerun-if-changed=Cargo.toml");
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let descriptor_path = out_dir.join("descriptor.bin");
@@ -11,10 +27,15 @@ fn main() {
     config.file_descriptor_set_path(&descriptor_path);
     config.out_dir(out_dir.clone());
 
-    let proto_dir = PathBuf::from("proto");
+    let metadata = bdd_metadata();
+    let proto_dirs = paths(DIRS_ENV, &metadata, "proto_dirs").unwrap_or_else(|| vec![PathBuf::from("proto")]);
+    let mut includes = proto_dirs.clone();
+    includes.extend(paths(INCLUDES_ENV, &metadata, "proto_includes").unwrap_or_default());
+
     let mut protos: Vec<PathBuf> = vec![];
-    if proto_dir.exists() {
-        for entry in walkdir::WalkDir::new(&proto_dir) {
+    for proto_dir in proto_dirs.iter().filter(|d| d.exists()) {
+        println!("cargo:rerun-if-changed={}", proto_dir.display());
+        for entry in walkdir::WalkDir::new(proto_dir) {
             let entry = entry.unwrap();
             if entry.path().extension().and_then(|s| s.to_str()) == Some("proto") {
                 protos.push(entry.into_path());
@@ -22,12 +43,81 @@ fn main() {
         }
     }
 
-    if !protos.is_empty() {
-        config.compile_protos(&protos, &["proto"]).expect("Failed to compile protos");
+    let embedded = env::var_os("CARGO_FEATURE_EMBEDDED_DESCRIPTOR").is_some();
+    let typed = env::var_os("CARGO_FEATURE_TYPED_MESSAGES").is_some();
+    if typed {
+        // an empty module tree when there are no protos
+        config.include_file("messages.rs");
+        // prost::Name impls, so typed helpers can find the message type
+        config.enable_type_names();
+        if protos.is_empty() {
+            fs::write(out_dir.join("messages.rs"), "").unwrap();
+        }
+    }
+    // without either feature the schema comes from bdd.toml at runtime and protoc isn't needed
+    if protos.is_empty() || !(embedded || typed) {
+        return None;
     }
+    if let Err(reason) = find_protoc() {
+        // a prebuilt descriptor set is enough to build and run the harness, but not to generate structs
+        if !typed && fs::metadata(DESCRIPTOR_TARGET).is_ok_and(|m| m.len() > 0) {
+            println!("cargo:warning={}; using the existing {} (.proto changes are not compiled in)", reason, DESCRIPTOR_TARGET);
+            return None;
+        }
+        panic!(
+            "{}.\nInstall protoc (apt install protobuf-compiler, brew install protobuf, or a release from \
+             https://github.com/protocolbuffers/protobuf/releases) and put it on PATH or set PROTOC=/path/to/protoc, \
+             or provide a prebuilt {} (protoc --include_imports --descriptor_set_out=...).",
+            reason, DESCRIPTOR_TARGET
+        );
+    }
+    config.compile_protos(&protos, &includes).expect("Failed to compile protos");
 
-    let descriptor_target = PathBuf::from("src/descriptor.bin");
-    let _ = fs::copy(&descriptor_path, &descriptor_target);
+    if !embedded {
+        return None;
+    }
+    let compiled = fs::read(&descriptor_path).expect("read compiled descriptor set");
+    if let Err(e) = fs::copy(&descriptor_path, DESCRIPTOR_TARGET) {
+        println!("cargo:warning=could not update {}: {}", DESCRIPTOR_TARGET, e);
+    }
+    Some(descriptor_hash(&compiled))
 }
 
+/// `[package.metadata.bdd]` of this crate's Cargo.toml
+fn bdd_metadata() -> toml::Table {
+    let manifest = fs::read_to_string("Cargo.toml").expect("read Cargo.toml");
+    let mut table: toml::Table = manifest.parse().expect("parse Cargo.toml");
+    let section = |t: &mut toml::Table, key: &str| match t.remove(key) {
+        Some(toml::Value::Table(inner)) => inner,
+        _ => toml::Table::new(),
+    };
+    let mut package = section(&mut table, "package");
+    let mut metadata = section(&mut package, "metadata");
+    section(&mut metadata, "bdd")
+}
+
+/// Paths from env var `var`, else from the metadata array `key`; relative paths are taken from
+/// the crate root
+fn paths(var: &str, metadata: &toml::Table, key: &str) -> Option<Vec<PathBuf>> {
+    if let Some(value) = env::var_os(var).filter(|v| !v.is_empty()) {
+        return Some(env::split_paths(&value).collect());
+    }
+    let list = metadata.get(key)?.as_array().unwrap_or_else(|| panic!("[package.metadata.bdd] {} must be an array", key));
+    Some(list.iter().map(|v| PathBuf::from(v.as_str().unwrap_or_else(|| panic!("[package.metadata.bdd] {} must hold strings", key)))).collect())
+}
 
+/// Check that the protoc prost-build will run (PROTOC, else from PATH) can be started
+fn find_protoc() -> Result<(), String> {
+    let protoc = env::var_os("PROTOC").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("protoc"));
+    match Command::new(&protoc).arg("--version").output() {
+        Ok(_) => Ok(()),
+        Err(e) if protoc == Path::new("protoc") => Err(format!("protoc not found on PATH ({})", e)),
+        Err(e) => Err(format!("PROTOC={} cannot be run ({})", protoc.display(), e)),
+    }
+}
+
+/// Same as my_bdd::proto_dyn::descriptor_hash
+fn descriptor_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+    format!("fnv1a64:{:016x}", hash)
+}
@@ -0,0 +1,55 @@
+/* C API of the my_bdd message engine (libmy_bdd.so / my_bdd.dll). */
+#ifndef MY_BDD_H
+#define MY_BDD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct BddBroker BddBroker;
+typedef struct BddProto BddProto;
+
+/* Broker on the ZMQ transport connected to tcp://<ip>:4246 / :4247; NULL on failure. */
+BddBroker *bdd_broker_new(const char *ip);
+
+/* Send message `name` built from `json` (NULL for an empty message). 0 on success, -1 on failure. */
+int bdd_broker_send(BddBroker *broker, const char *name, const char *json);
+
+/* Wait up to timeout_ms for `name` partially matching `json`. 0 on a match, -1 on timeout or error.
+   On a match the received body is stored in *out_json unless out_json is NULL; free it with bdd_string_free. */
+int bdd_broker_expect(BddBroker *broker, const char *name, const char *json, int timeout_ms, char **out_json);
+
+void bdd_broker_free(BddBroker *broker);
+
+/* Message types from `path` (a .proto, compiled with protoc, or an encoded FileDescriptorSet), or with
+   NULL those the test suite uses; NULL on failure. */
+BddProto *bdd_proto_new(const char *path);
+
+/* Full names of the message types as a JSON array in *out_json; free it with bdd_string_free. */
+int bdd_proto_message_names(const BddProto *proto, char **out_json);
+
+/* Encode `name` built from `json` (NULL for an empty message); free *out with bdd_bytes_free. */
+int bdd_proto_encode(const BddProto *proto, const char *name, const char *json, uint8_t **out, size_t *out_len);
+
+/* Decode `len` bytes as `name` into JSON in *out_json; free it with bdd_string_free. */
+int bdd_proto_decode(const BddProto *proto, const char *name, const uint8_t *bytes, size_t len, char **out_json);
+
+void bdd_proto_free(BddProto *proto);
+
+/* Whether `actual` partially matches `expected`, matchers included: 1 on a match, 0 on none, -1 on error. */
+int bdd_json_match(const char *expected, const char *actual);
+
+void bdd_string_free(char *s);
+void bdd_bytes_free(uint8_t *bytes, size_t len);
+
+/* Reason for the last failure on this thread, or NULL; valid until the next call on this thread. */
+const char *bdd_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,136 @@
+"""Python bindings for the my_bdd message engine, over its C API (include/my_bdd.h).
+
+Loads the cdylib named by $MY_BDD_LIB, or else libmy_bdd.so / libmy_bdd.dylib / my_bdd.dll
+from the library search path. Failures raise BddError with the library's reason.
+"""
+
+import ctypes
+import json
+import os
+import sys
+
+__all__ = ["BddError", "Broker", "ProtoDyn", "json_match"]
+
+
+class BddError(Exception):
+    """A call into the library failed"""
+
+
+def _load():
+    path = os.environ.get("MY_BDD_LIB")
+    if not path:
+        path = {"win32": "my_bdd.dll", "darwin": "libmy_bdd.dylib"}.get(sys.platform, "libmy_bdd.so")
+    lib = ctypes.CDLL(path)
+    c_str, c_int, c_size = ctypes.c_char_p, ctypes.c_int, ctypes.c_size_t
+    out_str, bytes_p = ctypes.POINTER(ctypes.c_void_p), ctypes.POINTER(ctypes.c_uint8)
+    for name, args, ret in [
+        ("bdd_broker_new", [c_str], ctypes.c_void_p),
+        ("bdd_broker_send", [ctypes.c_void_p, c_str, c_str], c_int),
+        ("bdd_broker_expect", [ctypes.c_void_p, c_str, c_str, c_int, out_str], c_int),
+        ("bdd_broker_free", [ctypes.c_void_p], None),
+        ("bdd_proto_new", [c_str], ctypes.c_void_p),
+        ("bdd_proto_message_names", [ctypes.c_void_p, out_str], c_int),
+        ("bdd_proto_encode", [ctypes.c_void_p, c_str, c_str, ctypes.POINTER(bytes_p), ctypes.POINTER(c_size)], c_int),
+        ("bdd_proto_decode", [ctypes.c_void_p, c_str, ctypes.c_char_p, c_size, out_str], c_int),
+        ("bdd_proto_free", [ctypes.c_void_p], None),
+        ("bdd_json_match", [c_str, c_str], c_int),
+        ("bdd_string_free", [ctypes.c_void_p], None),
+        ("bdd_bytes_free", [bytes_p, c_size], None),
+        ("bdd_last_error", [], c_str),
+    ]:
+        fn = getattr(lib, name)
+        fn.argtypes, fn.restype = args, ret
+    return lib
+
+
+_lib = _load()
+
+
+def _check(result):
+    if result is None or result == -1:
+        reason = _lib.bdd_last_error()
+        raise BddError(reason.decode() if reason else "unknown failure")
+    return result
+
+
+def _json_arg(body):
+    return None if body is None else json.dumps(body).encode()
+
+
+def _take_string(ptr):
+    """The library's string at `ptr`, freed"""
+    try:
+        return ctypes.string_at(ptr.value).decode()
+    finally:
+        _lib.bdd_string_free(ptr)
+
+
+class Broker:
+    """Connection to the SUT on the ZMQ transport, like `Given I run broker at <ip>`"""
+
+    def __init__(self, ip):
+        self._ptr = _check(_lib.bdd_broker_new(ip.encode()))
+
+    def send(self, name, body=None):
+        """Send message `name` built from the dict `body` (None for an empty message)"""
+        _check(_lib.bdd_broker_send(self._ptr, name.encode(), _json_arg(body)))
+
+    def expect(self, name, body=None, timeout_ms=5000):
+        """Wait for `name` partially matching `body`; returns the message received as a dict"""
+        out = ctypes.c_void_p()
+        _check(_lib.bdd_broker_expect(self._ptr, name.encode(), _json_arg(body), timeout_ms, ctypes.byref(out)))
+        return json.loads(_take_string(out))
+
+    def close(self):
+        if getattr(self, "_ptr", None):
+            _lib.bdd_broker_free(self._ptr)
+            self._ptr = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        self.close()
+
+
+class ProtoDyn:
+    """Message types of a .proto file or descriptor set, or with no path those the test suite uses"""
+
+    def __init__(self, path=None):
+        self._ptr = _check(_lib.bdd_proto_new(None if path is None else os.fspath(path).encode()))
+
+    def message_names(self):
+        out = ctypes.c_void_p()
+        _check(_lib.bdd_proto_message_names(self._ptr, ctypes.byref(out)))
+        return json.loads(_take_string(out))
+
+    def encode(self, name, body=None):
+        """Message `name` built from the dict `body`, encoded"""
+        out, size = ctypes.POINTER(ctypes.c_uint8)(), ctypes.c_size_t()
+        _check(_lib.bdd_proto_encode(self._ptr, name.encode(), _json_arg(body), ctypes.byref(out), ctypes.byref(size)))
+        try:
+            return ctypes.string_at(out, size.value)
+        finally:
+            _lib.bdd_bytes_free(out, size)
+
+    def decode(self, name, data):
+        """Encoded message `name` as a dict"""
+        out = ctypes.c_void_p()
+        _check(_lib.bdd_proto_decode(self._ptr, name.encode(), bytes(data), len(data), ctypes.byref(out)))
+        return json.loads(_take_string(out))
+
+    def close(self):
+        if getattr(self, "_ptr", None):
+            _lib.bdd_proto_free(self._ptr)
+            self._ptr = None
+
+    def __del__(self):
+        self.close()
+
+
+def json_match(expected, actual):
+    """Whether `actual` partially matches `expected`, with the matchers the steps use ({"$regex": ...})"""
+    return _check(_lib.bdd_json_match(json.dumps(expected).encode(), json.dumps(actual).encode())) == 1
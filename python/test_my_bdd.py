@@ -0,0 +1,46 @@
+"""Tests for the bindings; `cargo test --test python` runs them against the cdylib it built."""
+
+import unittest
+
+from my_bdd import BddError, Broker, ProtoDyn, json_match
+
+
+class ProtoDynTest(unittest.TestCase):
+    def setUp(self):
+        self.proto = ProtoDyn()
+
+    def test_round_trip(self):
+        data = self.proto.encode("Status", {"state": "BUSY"})
+        self.assertEqual(self.proto.decode("Status", data), {"state": 3})
+        self.assertEqual(self.proto.decode("PongReply", self.proto.encode("PongReply", {"message": "Hello"})), {"message": "Hello"})
+        self.assertEqual(self.proto.encode("PingRequest"), b"")
+
+    def test_message_names(self):
+        self.assertIn("company.project.v1.PongReply", self.proto.message_names())
+
+    def test_errors_carry_the_reason(self):
+        with self.assertRaisesRegex(BddError, "Pong"):
+            self.proto.encode("Pong", {})
+        with self.assertRaisesRegex(BddError, "unknown field"):
+            self.proto.encode("PongReply", {"msg": "hi"})
+
+
+class MatchTest(unittest.TestCase):
+    def test_partial_match(self):
+        self.assertTrue(json_match({"message": "Hello"}, {"message": "Hello", "seq": 3}))
+        self.assertFalse(json_match({"message": "Hello"}, {"message": "Bye"}))
+        self.assertTrue(json_match({"message": {"$ieq": "hello"}}, {"message": "HELLO"}))
+        with self.assertRaisesRegex(BddError, "nope"):
+            json_match({"message": {"$nope": 1}}, {"message": "Hello"})
+
+
+class BrokerTest(unittest.TestCase):
+    def test_expect_times_out(self):
+        with Broker("127.0.0.1") as broker:
+            broker.send("PingRequest")
+            with self.assertRaisesRegex(BddError, "timeout"):
+                broker.expect("PongReply", {"message": "Hello"}, timeout_ms=50)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, bail, Result};
+use prost_reflect::Kind;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::broker::Captured;
+use crate::jsonpath;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Avg,
+    Sum,
+    Count,
+}
+
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "min" | "minimum" => Self::Min,
+            "max" | "maximum" => Self::Max,
+            "avg" | "average" | "mean" => Self::Avg,
+            "sum" | "total" => Self::Sum,
+            "count" => Self::Count,
+            _ => bail!("unknown aggregate {}", s),
+        })
+    }
+}
+
+impl Aggregate {
+    pub fn apply(self, values: &[f64]) -> Result<f64> {
+        if values.is_empty() && self != Self::Count {
+            bail!("no values to aggregate");
+        }
+        Ok(match self {
+            Self::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Self::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Self::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Self::Sum => values.iter().sum(),
+            Self::Count => values.len() as f64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Above,
+    Below,
+    AtLeast,
+    AtMost,
+    Equal,
+}
+
+impl FromStr for Comparison {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "above" | "greater than" => Self::Above,
+            "below" | "less than" => Self::Below,
+            "at least" => Self::AtLeast,
+            "at most" => Self::AtMost,
+            "equal to" | "exactly" => Self::Equal,
+            _ => bail!("unknown comparison {}", s),
+        })
+    }
+}
+
+impl Comparison {
+    pub fn holds(self, value: f64, limit: f64) -> bool {
+        match self {
+            Self::Above => value > limit,
+            Self::Below => value < limit,
+            Self::AtLeast => value >= limit,
+            Self::AtMost => value <= limit,
+            Self::Equal => value == limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Monotonic {
+    Increasing,
+    StrictlyIncreasing,
+    Decreasing,
+    StrictlyDecreasing,
+}
+
+impl Monotonic {
+    pub fn new(increasing: bool, strict: bool) -> Self {
+        match (increasing, strict) {
+            (true, false) => Self::Increasing,
+            (true, true) => Self::StrictlyIncreasing,
+            (false, false) => Self::Decreasing,
+            (false, true) => Self::StrictlyDecreasing,
+        }
+    }
+
+    fn ordered(self, prev: f64, next: f64) -> bool {
+        match self {
+            Self::Increasing => next >= prev,
+            Self::StrictlyIncreasing => next > prev,
+            Self::Decreasing => next <= prev,
+            Self::StrictlyDecreasing => next < prev,
+        }
+    }
+
+    /// Index of the first value breaking the order, if any
+    pub fn first_violation(self, values: &[f64]) -> Option<usize> {
+        values.windows(2).position(|w| !self.ordered(w[0], w[1])).map(|i| i + 1)
+    }
+}
+
+/// Numeric value of `field` (a JSONPath, or a plain or dotted field name) in every message. A field
+/// missing from a parent that is there holds `default`, as proto3 leaves out fields at their
+/// default; see [`scalar_default`]. Messages lacking the field otherwise are skipped.
+pub fn values(messages: &[Captured], field: &str, default: Option<f64>) -> Result<Vec<f64>> {
+    let path = jsonpath::field_path(field);
+    let mut out = Vec::with_capacity(messages.len());
+    for m in messages {
+        let found = jsonpath::select(&m.body, &path)?;
+        if found.is_empty() {
+            out.extend(default.filter(|_| jsonpath::parent_present(&m.body, &path)));
+        }
+        for v in found {
+            out.push(as_number(v).ok_or_else(|| anyhow!("{} is not numeric in {}", field, m.body))?);
+        }
+    }
+    Ok(out)
+}
+
+/// What a singular proto field of `kind` holds when a message leaves it out: 0, false as 0, or an
+/// enum's first value; `None` for strings, bytes and messages, which have no numeric default
+pub fn scalar_default(kind: &Kind) -> Option<f64> {
+    match kind {
+        Kind::Enum(e) => Some(f64::from(e.default_value().number())),
+        Kind::String | Kind::Bytes | Kind::Message(_) => None,
+        _ => Some(0.0),
+    }
+}
+
+fn as_number(v: &JsonValue) -> Option<f64> {
+    match v {
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        JsonValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Inter-arrival statistics of a periodic topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateStats {
+    pub count: usize,
+    pub rate_hz: f64,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl RateStats {
+    /// Statistics over arrival times (oldest first); needs at least two arrivals
+    pub fn from_arrivals(times: &[Instant]) -> Result<Self> {
+        if times.len() < 2 {
+            bail!("need at least 2 messages to measure a rate, got {}", times.len());
+        }
+        let intervals: Vec<Duration> = times.windows(2).map(|w| w[1].saturating_duration_since(w[0])).collect();
+        let span = times[times.len() - 1].saturating_duration_since(times[0]);
+        Ok(Self {
+            count: times.len(),
+            rate_hz: intervals.len() as f64 / span.as_secs_f64().max(f64::EPSILON),
+            min_interval: intervals.iter().copied().min().unwrap_or_default(),
+            max_interval: intervals.iter().copied().max().unwrap_or_default(),
+        })
+    }
+
+    /// Check the mean rate and every interval are within `tolerance_pct` of `hz`
+    pub fn check(&self, hz: f64, tolerance_pct: f64) -> Result<()> {
+        let tol = tolerance_pct / 100.0;
+        if (self.rate_hz - hz).abs() > hz * tol {
+            bail!("measured {:.3} Hz over {} messages, expected {} Hz ± {}%", self.rate_hz, self.count, hz, tolerance_pct);
+        }
+        let period = 1.0 / hz;
+        let (lo, hi) = (period * (1.0 - tol), period * (1.0 + tol));
+        for (label, interval) in [("shortest", self.min_interval), ("longest", self.max_interval)] {
+            let secs = interval.as_secs_f64();
+            if secs < lo || secs > hi {
+                bail!(
+                    "{} interval {:?} outside {:.3}s..{:.3}s ({} Hz ± {}%)",
+                    label, interval, lo, hi, hz, tolerance_pct
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of scanning a sequence-number field for losses and repeats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceReport {
+    /// Inclusive ranges of sequence numbers never seen between the first and last one
+    pub missing: Vec<(i64, i64)>,
+    /// Sequence numbers seen more than once
+    pub duplicates: Vec<i64>,
+    /// Messages arriving with a lower number than their predecessor (not a failure on its own)
+    pub reordered: usize,
+}
+
+impl SequenceReport {
+    pub fn scan(values: &[i64]) -> Self {
+        let mut seen: BTreeMap<i64, usize> = BTreeMap::new();
+        for v in values {
+            *seen.entry(*v).or_default() += 1;
+        }
+        let mut missing = Vec::new();
+        let mut prev: Option<i64> = None;
+        for v in seen.keys() {
+            if let Some(p) = prev {
+                if *v > p + 1 {
+                    missing.push((p + 1, v - 1));
+                }
+            }
+            prev = Some(*v);
+        }
+        Self {
+            missing,
+            duplicates: seen.iter().filter(|(_, n)| **n > 1).map(|(v, _)| *v).collect(),
+            reordered: values.windows(2).filter(|w| w[1] < w[0]).count(),
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.duplicates.is_empty()
+    }
+
+    pub fn lost(&self) -> i64 {
+        self.missing.iter().map(|(a, b)| b - a + 1).sum()
+    }
+}
+
+impl std::fmt::Display for SequenceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self
+            .missing
+            .iter()
+            .map(|(a, b)| if a == b { a.to_string() } else { format!("{}..={}", a, b) })
+            .collect();
+        write!(f, "{} lost [{}], duplicated {:?}, {} reordered", self.lost(), ranges.join(", "), self.duplicates, self.reordered)
+    }
+}
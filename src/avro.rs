@@ -0,0 +1,478 @@
+//! Apache Avro binary encoding, behind the `avro` codec. Schemas are Avro JSON schemas (.avsc).
+//! In the JSON view records are objects, enums their symbol, bytes and fixed base64, and a union
+//! the bare value of the first branch it fits. Logical types are read as their underlying type.
+//!
+//! With `[avro] registry` set, payloads use the Confluent wire format: a zero magic byte and the
+//! big-endian schema id ahead of the Avro body. Decoding fetches the writer schema by that id;
+//! encoding uses the latest schema registered under `<topic>-value`.
+//!
+//! There is no Kafka transport: Avro payloads travel over ZMQ (or another [`crate::transport`]),
+//! and the ZMQ topic takes the Kafka topic's place in the subject name.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::codec::{self, Codec, Reader, MAX_DEPTH};
+use crate::config::AvroConfig;
+use crate::{http, tls};
+
+/// Confluent wire-format magic byte
+pub const MAGIC: u8 = 0;
+/// Longest wait for a Schema Registry answer
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+enum Type {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record { name: String, fields: Vec<Field> },
+    Enum { name: String, symbols: Vec<String> },
+    Array(Box<Type>),
+    Map(Box<Type>),
+    Union(Vec<Type>),
+    Fixed { name: String, size: usize },
+    /// A named type declared elsewhere in the schema, by full name
+    Named(String),
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    ty: Type,
+    default: Option<JsonValue>,
+}
+
+/// A parsed Avro schema
+#[derive(Debug, Clone)]
+pub struct Schema {
+    root: Type,
+    named: HashMap<String, Type>,
+}
+
+impl Schema {
+    /// Parse a schema from its JSON form
+    pub fn parse(json: &JsonValue) -> Result<Self> {
+        let mut named = HashMap::new();
+        let root = parse_type(json, "", &mut named)?;
+        Ok(Self { root, named })
+    }
+
+    /// Parse a schema from JSON text, e.g. an .avsc file or a registry answer
+    pub fn parse_str(text: &str) -> Result<Self> {
+        Self::parse(&serde_json::from_str(text).context("schema is not JSON")?)
+    }
+
+    /// Avro binary encoding of `value`
+    pub fn encode(&self, value: &JsonValue) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write(&self.root, value, "", &mut out, 0)?;
+        Ok(out)
+    }
+
+    /// JSON view of an Avro binary body
+    pub fn decode(&self, bytes: &[u8]) -> Result<JsonValue> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let value = self.read(&self.root, &mut reader, 0)?;
+        reader.finish()?;
+        Ok(value)
+    }
+
+    fn resolve<'a>(&'a self, ty: &'a Type) -> &'a Type {
+        match ty {
+            // parsing checked every reference
+            Type::Named(name) => &self.named[name],
+            ty => ty,
+        }
+    }
+
+    /// Whether `value` can be written as `ty`, to pick a union branch
+    fn fits(&self, ty: &Type, value: &JsonValue) -> bool {
+        match (self.resolve(ty), value) {
+            (Type::Null, JsonValue::Null) | (Type::Boolean, JsonValue::Bool(_)) => true,
+            (Type::Int, JsonValue::Number(n)) => n.as_i64().is_some_and(|i| i32::try_from(i).is_ok()),
+            (Type::Long, JsonValue::Number(n)) => n.as_i64().is_some(),
+            (Type::Float | Type::Double, JsonValue::Number(_)) => true,
+            (Type::Bytes | Type::String | Type::Fixed { .. }, JsonValue::String(_)) => true,
+            (Type::Enum { symbols, .. }, JsonValue::String(s)) => symbols.contains(s),
+            (Type::Record { .. } | Type::Map(_), JsonValue::Object(_)) => true,
+            (Type::Array(_), JsonValue::Array(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn write(&self, ty: &Type, value: &JsonValue, path: &str, out: &mut Vec<u8>, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("value nested more than {} deep", MAX_DEPTH);
+        }
+        let at = || if path.is_empty() { "the message".to_string() } else { path.to_string() };
+        let wrong = |expected: &str| anyhow!("{}: expected {}, got {}", at(), expected, value);
+        match self.resolve(ty) {
+            Type::Null => value.is_null().then_some(()).ok_or_else(|| wrong("null"))?,
+            Type::Boolean => out.push(value.as_bool().ok_or_else(|| wrong("a boolean"))? as u8),
+            Type::Int => {
+                let i = value.as_i64().filter(|i| i32::try_from(*i).is_ok()).ok_or_else(|| wrong("an int"))?;
+                write_long(i, out);
+            }
+            Type::Long => write_long(value.as_i64().ok_or_else(|| wrong("a long"))?, out),
+            Type::Float => out.extend((value.as_f64().ok_or_else(|| wrong("a float"))? as f32).to_le_bytes()),
+            Type::Double => out.extend(value.as_f64().ok_or_else(|| wrong("a double"))?.to_le_bytes()),
+            Type::String => write_bytes(value.as_str().ok_or_else(|| wrong("a string"))?.as_bytes(), out),
+            Type::Bytes => write_bytes(&base64(value).ok_or_else(|| wrong("base64 bytes"))?, out),
+            Type::Fixed { name, size } => {
+                let bytes = base64(value).ok_or_else(|| wrong(&format!("base64 {}", name)))?;
+                if bytes.len() != *size {
+                    bail!("{}: {} is {} bytes, got {}", at(), name, size, bytes.len());
+                }
+                out.extend(bytes);
+            }
+            Type::Enum { name, symbols } => {
+                let index = value.as_str().and_then(|s| symbols.iter().position(|sym| sym == s));
+                write_long(index.ok_or_else(|| wrong(&format!("a {} symbol ({})", name, symbols.join(", "))))? as i64, out);
+            }
+            Type::Record { name, fields } => {
+                let object = value.as_object().ok_or_else(|| wrong(&format!("a {} record", name)))?;
+                if let Some(unknown) = object.keys().find(|k| !fields.iter().any(|f| &f.name == *k)) {
+                    bail!("{}: {} has no field {}", at(), name, unknown);
+                }
+                for field in fields {
+                    let path = join(path, &field.name);
+                    match (object.get(&field.name), &field.default) {
+                        (Some(v), _) => self.write(&field.ty, v, &path, out, depth + 1)?,
+                        (None, Some(default)) => self.write(&field.ty, default, &path, out, depth + 1)?,
+                        (None, None) if self.fits(&field.ty, &JsonValue::Null) || self.union_has_null(&field.ty) => {
+                            self.write(&field.ty, &JsonValue::Null, &path, out, depth + 1)?
+                        }
+                        (None, None) => bail!("{}: missing, and it has no default", path),
+                    }
+                }
+            }
+            Type::Array(items) => {
+                let values = value.as_array().ok_or_else(|| wrong("an array"))?;
+                if !values.is_empty() {
+                    write_long(values.len() as i64, out);
+                    for (i, v) in values.iter().enumerate() {
+                        self.write(items, v, &format!("{}[{}]", path, i), out, depth + 1)?;
+                    }
+                }
+                out.push(0);
+            }
+            Type::Map(values) => {
+                let object = value.as_object().ok_or_else(|| wrong("a map"))?;
+                if !object.is_empty() {
+                    write_long(object.len() as i64, out);
+                    for (k, v) in object {
+                        write_bytes(k.as_bytes(), out);
+                        self.write(values, v, &join(path, k), out, depth + 1)?;
+                    }
+                }
+                out.push(0);
+            }
+            Type::Union(branches) => {
+                let index = branches.iter().position(|b| self.fits(b, value)).ok_or_else(|| wrong("a value of one of the union's branches"))?;
+                write_long(index as i64, out);
+                self.write(&branches[index], value, path, out, depth + 1)?;
+            }
+            Type::Named(_) => unreachable!("resolved above"),
+        }
+        Ok(())
+    }
+
+    fn union_has_null(&self, ty: &Type) -> bool {
+        matches!(self.resolve(ty), Type::Union(branches) if branches.iter().any(|b| matches!(b, Type::Null)))
+    }
+
+    fn read(&self, ty: &Type, r: &mut Reader, depth: usize) -> Result<JsonValue> {
+        if depth > MAX_DEPTH {
+            bail!("Avro value nested more than {} deep", MAX_DEPTH);
+        }
+        Ok(match self.resolve(ty) {
+            Type::Null => JsonValue::Null,
+            Type::Boolean => match r.byte()? {
+                0 => JsonValue::Bool(false),
+                1 => JsonValue::Bool(true),
+                b => bail!("invalid Avro boolean {}", b),
+            },
+            Type::Int => JsonValue::from(i32::try_from(read_long(r)?).context("Avro int out of range")?),
+            Type::Long => JsonValue::from(read_long(r)?),
+            Type::Float => codec::float(f32::from_le_bytes(r.take(4)?.try_into()?) as f64)?,
+            Type::Double => codec::float(f64::from_le_bytes(r.take(8)?.try_into()?))?,
+            Type::Bytes => codec::binary(read_bytes(r)?),
+            Type::String => codec::text(read_bytes(r)?)?,
+            Type::Fixed { size, .. } => codec::binary(r.take(*size)?),
+            Type::Enum { name, symbols } => {
+                let index = read_long(r)?;
+                let symbol = usize::try_from(index).ok().and_then(|i| symbols.get(i));
+                JsonValue::String(symbol.ok_or_else(|| anyhow!("{} has no symbol {}", name, index))?.clone())
+            }
+            Type::Record { fields, .. } => {
+                let mut object = Map::new();
+                for field in fields {
+                    object.insert(field.name.clone(), self.read(&field.ty, r, depth + 1)?);
+                }
+                JsonValue::Object(object)
+            }
+            Type::Array(items) => {
+                let mut values = Vec::new();
+                while let Some(count) = read_block(r)? {
+                    for _ in 0..count {
+                        values.push(self.read(items, r, depth + 1)?);
+                    }
+                }
+                JsonValue::Array(values)
+            }
+            Type::Map(values) => {
+                let mut object = Map::new();
+                while let Some(count) = read_block(r)? {
+                    for _ in 0..count {
+                        let key = String::from_utf8(read_bytes(r)?.to_vec()).context("map key is not UTF-8")?;
+                        object.insert(key, self.read(values, r, depth + 1)?);
+                    }
+                }
+                JsonValue::Object(object)
+            }
+            Type::Union(branches) => {
+                let index = read_long(r)?;
+                let branch = usize::try_from(index).ok().and_then(|i| branches.get(i));
+                self.read(branch.ok_or_else(|| anyhow!("union has no branch {}", index))?, r, depth + 1)?
+            }
+            Type::Named(_) => unreachable!("resolved above"),
+        })
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_string() } else { format!("{}.{}", path, name) }
+}
+
+fn base64(value: &JsonValue) -> Option<Vec<u8>> {
+    general_purpose::STANDARD.decode(value.as_str()?).ok()
+}
+
+/// Full name of a named type: dotted names stand alone, others take the namespace
+fn full_name(name: &str, namespace: &str) -> String {
+    if name.contains('.') || namespace.is_empty() { name.to_string() } else { format!("{}.{}", namespace, name) }
+}
+
+fn parse_type(json: &JsonValue, namespace: &str, named: &mut HashMap<String, Type>) -> Result<Type> {
+    match json {
+        JsonValue::String(name) => reference(name, namespace, named),
+        JsonValue::Array(branches) => Ok(Type::Union(branches.iter().map(|b| parse_type(b, namespace, named)).collect::<Result<_>>()?)),
+        JsonValue::Object(object) => {
+            let ty = object.get("type").ok_or_else(|| anyhow!("schema without a type: {}", json))?;
+            let kind = match ty.as_str() {
+                Some(kind @ ("record" | "error" | "enum" | "fixed" | "array" | "map")) => kind,
+                // {"type": "long", "logicalType": ...} or a nested schema
+                _ => return parse_type(ty, namespace, named),
+            };
+            if kind == "array" {
+                let items = object.get("items").ok_or_else(|| anyhow!("array without items"))?;
+                return Ok(Type::Array(Box::new(parse_type(items, namespace, named)?)));
+            }
+            if kind == "map" {
+                let values = object.get("values").ok_or_else(|| anyhow!("map without values"))?;
+                return Ok(Type::Map(Box::new(parse_type(values, namespace, named)?)));
+            }
+            let name = object.get("name").and_then(JsonValue::as_str).ok_or_else(|| anyhow!("{} without a name", kind))?;
+            let namespace = object.get("namespace").and_then(JsonValue::as_str).unwrap_or(namespace);
+            let name = full_name(name, namespace);
+            let namespace = name.rsplit_once('.').map_or("", |(ns, _)| ns);
+            if named.contains_key(&name) {
+                bail!("type {} is defined twice", name);
+            }
+            let ty = match kind {
+                "enum" => {
+                    let symbols = object.get("symbols").and_then(JsonValue::as_array).ok_or_else(|| anyhow!("enum {} without symbols", name))?;
+                    let symbols = symbols.iter().map(|s| s.as_str().map(str::to_string).ok_or_else(|| anyhow!("enum {} symbol {} is not a string", name, s)));
+                    Type::Enum { name: name.clone(), symbols: symbols.collect::<Result<_>>()? }
+                }
+                "fixed" => {
+                    let size = object.get("size").and_then(JsonValue::as_u64).ok_or_else(|| anyhow!("fixed {} without a size", name))?;
+                    Type::Fixed { name: name.clone(), size: usize::try_from(size)? }
+                }
+                _ => {
+                    // declared before its fields, which may refer back to it
+                    named.insert(name.clone(), Type::Null);
+                    let fields = object.get("fields").and_then(JsonValue::as_array).ok_or_else(|| anyhow!("record {} without fields", name))?;
+                    let fields = fields
+                        .iter()
+                        .map(|f| {
+                            let field = f.get("name").and_then(JsonValue::as_str).ok_or_else(|| anyhow!("record {} has a field without a name", name))?;
+                            let ty = f.get("type").ok_or_else(|| anyhow!("{}.{} without a type", name, field))?;
+                            let ty = parse_type(ty, namespace, named).with_context(|| format!("{}.{}", name, field))?;
+                            Ok(Field { name: field.to_string(), ty, default: f.get("default").cloned() })
+                        })
+                        .collect::<Result<_>>()?;
+                    Type::Record { name: name.clone(), fields }
+                }
+            };
+            named.insert(name.clone(), ty);
+            Ok(Type::Named(name))
+        }
+        other => bail!("invalid schema {}", other),
+    }
+}
+
+fn reference(name: &str, namespace: &str, named: &HashMap<String, Type>) -> Result<Type> {
+    Ok(match name {
+        "null" => Type::Null,
+        "boolean" => Type::Boolean,
+        "int" => Type::Int,
+        "long" => Type::Long,
+        "float" => Type::Float,
+        "double" => Type::Double,
+        "bytes" => Type::Bytes,
+        "string" => Type::String,
+        _ => {
+            let full = full_name(name, namespace);
+            match (named.contains_key(&full), named.contains_key(name)) {
+                (true, _) => Type::Named(full),
+                (false, true) => Type::Named(name.to_string()),
+                _ => bail!("unknown type {}", name),
+            }
+        }
+    })
+}
+
+fn write_long(n: i64, out: &mut Vec<u8>) {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+    while zigzag >= 0x80 {
+        out.push(zigzag as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    out.push(zigzag as u8);
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_long(bytes.len() as i64, out);
+    out.extend(bytes);
+}
+
+fn read_long(r: &mut Reader) -> Result<i64> {
+    let mut zigzag = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = r.byte()?;
+        zigzag |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+        }
+    }
+    bail!("Avro varint longer than 10 bytes")
+}
+
+fn read_bytes<'a>(r: &mut Reader<'a>) -> Result<&'a [u8]> {
+    let len = read_long(r)?;
+    r.take_len(u64::try_from(len).map_err(|_| anyhow!("negative Avro length {}", len))?)
+}
+
+/// Item count of the next array or map block; None at the closing empty block
+fn read_block(r: &mut Reader) -> Result<Option<u64>> {
+    match read_long(r)? {
+        0 => Ok(None),
+        n if n < 0 => {
+            // a negative count is followed by the block's size in bytes
+            read_long(r)?;
+            Ok(Some(n.unsigned_abs()))
+        }
+        n => Ok(Some(n as u64)),
+    }
+}
+
+/// Schema a topic is encoded with, and its registry id
+type Writer = (Option<u32>, Arc<Schema>);
+
+/// The `avro` codec: schemas from `[avro] schemas` files, or from a Confluent Schema Registry
+pub struct Avro {
+    config: AvroConfig,
+    /// Registry schemas by id
+    by_id: Mutex<HashMap<u32, Arc<Schema>>>,
+    by_topic: Mutex<HashMap<String, Writer>>,
+}
+
+impl Avro {
+    pub fn new(config: AvroConfig) -> Self {
+        Self { config, by_id: Mutex::default(), by_topic: Mutex::default() }
+    }
+
+    fn registry_get(&self, registry: &str, path: &str) -> Result<JsonValue> {
+        let profile = tls::profile(self.config.tls.as_deref())?;
+        http::get_json(&format!("{}{}", registry.trim_end_matches('/'), path), &profile, REGISTRY_TIMEOUT)
+    }
+
+    /// The schema a registry answer carries
+    fn registry_schema(answer: &JsonValue) -> Result<Schema> {
+        if let Some(kind) = answer.get("schemaType").and_then(JsonValue::as_str).filter(|k| *k != "AVRO") {
+            bail!("the registry schema is {}, not Avro", kind);
+        }
+        let text = answer.get("schema").and_then(JsonValue::as_str).ok_or_else(|| anyhow!("registry answer without a schema: {}", answer))?;
+        Schema::parse_str(text)
+    }
+
+    /// The schema `topic` is encoded with, and its registry id
+    fn writer(&self, topic: &str) -> Result<Writer> {
+        if let Some(known) = self.by_topic.lock().unwrap_or_else(|e| e.into_inner()).get(topic) {
+            return Ok(known.clone());
+        }
+        let known = match &self.config.registry {
+            Some(registry) => {
+                let subject = format!("{}-value", topic);
+                let answer = self.registry_get(registry, &format!("/subjects/{}/versions/latest", subject))?;
+                let id = answer.get("id").and_then(JsonValue::as_u64).and_then(|id| u32::try_from(id).ok());
+                let id = id.ok_or_else(|| anyhow!("registry answer for {} without an id: {}", subject, answer))?;
+                let schema = Arc::new(Self::registry_schema(&answer).with_context(|| format!("schema {}", id))?);
+                self.by_id.lock().unwrap_or_else(|e| e.into_inner()).insert(id, schema.clone());
+                (Some(id), schema)
+            }
+            None => {
+                let path = self.config.schemas.get(topic).ok_or_else(|| anyhow!("no Avro schema for {}; add it to [avro] schemas", topic))?;
+                let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+                (None, Arc::new(Schema::parse_str(&text).with_context(|| format!("{}", path.display()))?))
+            }
+        };
+        self.by_topic.lock().unwrap_or_else(|e| e.into_inner()).insert(topic.to_string(), known.clone());
+        Ok(known)
+    }
+
+    fn by_id(&self, registry: &str, id: u32) -> Result<Arc<Schema>> {
+        if let Some(schema) = self.by_id.lock().unwrap_or_else(|e| e.into_inner()).get(&id) {
+            return Ok(schema.clone());
+        }
+        let answer = self.registry_get(registry, &format!("/schemas/ids/{}", id))?;
+        let schema = Arc::new(Self::registry_schema(&answer).with_context(|| format!("schema {}", id))?);
+        self.by_id.lock().unwrap_or_else(|e| e.into_inner()).insert(id, schema.clone());
+        Ok(schema)
+    }
+}
+
+impl Codec for Avro {
+    fn encode(&self, topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        let (id, schema) = self.writer(topic)?;
+        let mut out = Vec::new();
+        if let Some(id) = id {
+            out.push(MAGIC);
+            out.extend(id.to_be_bytes());
+        }
+        out.extend(schema.encode(body)?);
+        Ok(out)
+    }
+
+    fn decode(&self, topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        let Some(registry) = &self.config.registry else { return self.writer(topic)?.1.decode(payload) };
+        let (header, body) = payload.split_at_checked(5).ok_or_else(|| anyhow!("{} bytes are too short for the wire-format header", payload.len()))?;
+        if header[0] != MAGIC {
+            bail!("magic byte {} is not the Confluent wire format", header[0]);
+        }
+        let id = u32::from_be_bytes(header[1..].try_into()?);
+        self.by_id(registry, id)?.decode(body).with_context(|| format!("schema {}", id))
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use my_bdd::conformance;
+use my_bdd::proto_dyn::ProtoDyn;
+
+/// Write a skeleton feature with one scenario per message type of the descriptor set
+#[derive(Debug, Parser)]
+struct Args {
+    /// Message names, short or fully qualified; all messages when omitted
+    messages: Vec<String>,
+    /// Only messages of this proto package
+    #[arg(long)]
+    package: Option<String>,
+    /// Message the SUT answers with, e.g. an Ack and an error; repeat for alternatives
+    #[arg(long = "reply")]
+    replies: Vec<String>,
+    /// Write the feature here instead of stdout
+    #[arg(long)]
+    out: Option<std::path::PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let proto = ProtoDyn::new()?;
+    let mut messages = conformance::message_types(&proto, &args.messages)?;
+    if let Some(package) = &args.package {
+        messages.retain(|m| m.package_name() == package);
+    }
+    for reply in &args.replies {
+        proto.message_desc(reply)?;
+    }
+    let text = conformance::feature(&proto, &messages, &args.replies);
+    match args.out {
+        Some(path) => std::fs::write(&path, text).with_context(|| format!("write {}", path.display())),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use clap::Parser;
+use my_bdd::history::{self, Summary};
+use std::path::PathBuf;
+
+/// Summarize the last runs of the history file: flaky scenarios, slowest steps, failure topics
+#[derive(Debug, Parser)]
+struct Args {
+    /// History file the runs were appended to (`--history` of the test run)
+    #[arg(long, default_value = history::DEFAULT_PATH)]
+    history: PathBuf,
+    /// How many of the most recent runs to look at
+    #[arg(long, default_value_t = 20)]
+    last: usize,
+    /// How many of the slowest steps to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let runs = history::load(&args.history, args.last)?;
+    if runs.is_empty() {
+        anyhow::bail!("no runs in {}", args.history.display());
+    }
+    print!("{}", Summary::of(&runs, args.top));
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use my_bdd::resume::Results;
+use my_bdd::shard;
+use std::path::PathBuf;
+
+/// Combine the scenario results (`--results`) or event streams (`--events`) of CI shards
+#[derive(Debug, Parser)]
+struct Args {
+    /// Results files (.json) or event streams (.ndjson) of the shards, all of one kind
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Write the combined file here
+    #[arg(long, short)]
+    out: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let ndjson = |p: &PathBuf| p.extension().is_some_and(|e| e == "ndjson");
+    match args.inputs.iter().filter(|p| ndjson(p)).count() {
+        0 => merge_results(&args),
+        n if n == args.inputs.len() => merge_events(&args),
+        _ => bail!("inputs mix results files and event streams"),
+    }
+}
+
+/// Exits with 1 when a scenario failed on any shard, like the runs themselves
+fn merge_results(args: &Args) -> Result<()> {
+    let mut merged = Results::default();
+    for input in &args.inputs {
+        if !input.exists() {
+            bail!("{} not found", input.display());
+        }
+        merged.merge(Results::load(input)?);
+    }
+    merged.save(&args.out)?;
+    let (passed, failed) = merged.counts();
+    println!("{} scenarios ({} passed, {} failed)", passed + failed, passed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn merge_events(args: &Args) -> Result<()> {
+    let streams = args
+        .inputs
+        .iter()
+        .map(|p| std::fs::read_to_string(p).with_context(|| format!("read {}", p.display())))
+        .collect::<Result<Vec<_>>>()?;
+    let text: String = shard::merge_events(&streams)?.iter().map(|e| format!("{}\n", e)).collect();
+    std::fs::write(&args.out, text).with_context(|| format!("write {}", args.out.display()))
+}
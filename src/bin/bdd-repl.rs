@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::Parser;
+use my_bdd::broker::Broker;
+use my_bdd::repl::Command;
+use std::io::{BufRead, Write};
+
+/// Interactive shell for poking the SUT before writing scenarios
+#[derive(Debug, Parser)]
+struct Args {
+    /// Transport to the SUT: zmq, someip or can (when enabled)
+    #[arg(long, default_value = "zmq")]
+    transport: String,
+    /// SUT address (CAN: interface name)
+    #[arg(long, default_value = "127.0.0.1")]
+    sut: String,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let broker = Broker::open(&args.transport, Some(&args.sut))?;
+    println!("connected to {} over {}; type help for commands", args.sut, args.transport);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("bdd> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Command::parse(&line) {
+            Ok(Command::Quit) => break,
+            Ok(cmd) => match cmd.run(&broker) {
+                Ok(out) => println!("{}", out),
+                Err(e) => println!("error: {:#}", e),
+            },
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use my_bdd::watch::{Rerun, Snapshot, Suite};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// Run the BDD suite (`cargo test --test bdd`); with --watch, keep running what each change affects
+#[derive(Debug, Parser)]
+struct Args {
+    /// After the first run, watch the feature files, the files their steps name, bdd.toml and the
+    /// protos, and rerun the scenarios a change affects
+    #[arg(long)]
+    watch: bool,
+    /// Directory holding the feature files
+    #[arg(long, default_value = "tests/features")]
+    features: PathBuf,
+    /// How often to look for changes
+    #[arg(long, default_value = "500ms", value_parser = humantime::parse_duration)]
+    interval: Duration,
+    /// Passed on to the runner, e.g. `-- --tags @smoke`
+    #[arg(last = true)]
+    runner_args: Vec<String>,
+}
+
+/// `cargo test --test bdd` over everything, or over the scenarios `rerun` names
+fn run(args: &Args, rerun: &Rerun) -> Result<bool> {
+    let mut cmd = Command::new("cargo");
+    cmd.args(["test", "--test", "bdd", "--"]);
+    if let Rerun::Scenarios(features) = rerun {
+        let paths: Vec<String> = features.keys().map(|p| p.display().to_string()).collect();
+        let names: BTreeSet<&String> = features.values().flatten().collect();
+        let names: Vec<String> = names.into_iter().map(|n| regex::escape(n)).collect();
+        cmd.arg("--input").arg(if paths.len() == 1 { paths[0].clone() } else { format!("{{{}}}", paths.join(",")) });
+        cmd.arg("--name").arg(format!("^(?:{})$", names.join("|")));
+    }
+    cmd.args(&args.runner_args);
+    let status = cmd.status().context("run cargo test")?;
+    Ok(status.success())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut suite = Suite::load(&args.features)?;
+    let mut snapshot = Snapshot::take(suite.watched(&args.features)?);
+    let passed = run(&args, &Rerun::Everything)?;
+    if !args.watch {
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+    loop {
+        std::thread::sleep(args.interval);
+        let changed = Snapshot::take(suite.watched(&args.features)?).changed_since(&snapshot);
+        if changed.is_empty() {
+            continue;
+        }
+        let after = Suite::load(&args.features)?;
+        let rerun = suite.affected(&after, &changed);
+        // files a changed step names only now are watched from here on
+        snapshot = Snapshot::take(after.watched(&args.features)?);
+        suite = after;
+        let list: Vec<String> = changed.iter().map(|p| p.display().to_string()).collect();
+        match &rerun {
+            Rerun::Nothing => {
+                eprintln!("changed: {}; no scenario affected", list.join(", "));
+                continue;
+            }
+            Rerun::Everything => eprintln!("changed: {}; running everything", list.join(", ")),
+            Rerun::Scenarios(features) => {
+                let count: usize = features.values().map(BTreeSet::len).sum();
+                eprintln!("changed: {}; running {} scenario(s)", list.join(", "), count);
+            }
+        }
+        run(&args, &rerun)?;
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use my_bdd::proto_dyn::ProtoDyn;
+
+/// Print or write JSON Schema documents for message types of the descriptor set
+#[derive(Debug, Parser)]
+struct Args {
+    /// Message names, short or fully qualified; all messages when omitted
+    messages: Vec<String>,
+    /// Write one <full name>.schema.json per message into this directory instead of stdout
+    #[arg(long)]
+    out: Option<std::path::PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let proto = ProtoDyn::new()?;
+    let names = if args.messages.is_empty() { proto.message_names() } else { args.messages };
+    let Some(dir) = args.out else {
+        let [name] = &names[..] else { bail!("{} messages selected; pick one or write them with --out <dir>", names.len()) };
+        println!("{}", serde_json::to_string_pretty(&proto.json_schema(name)?)?);
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+    for name in &names {
+        let desc = proto.message_desc(name)?;
+        let path = dir.join(format!("{}.schema.json", desc.full_name()));
+        let text = serde_json::to_string_pretty(&proto.json_schema(name)?)? + "\n";
+        std::fs::write(&path, text).with_context(|| format!("write {}", path.display()))?;
+    }
+    Ok(())
+}
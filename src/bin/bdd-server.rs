@@ -0,0 +1,26 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use my_bdd::broker::Broker;
+use std::net::TcpListener;
+
+/// Run the message engine as an HTTP control server for a remote orchestrator
+#[derive(Debug, Parser)]
+struct Args {
+    /// Address to serve the control API on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    /// Transport to the SUT: zmq, someip or can (when enabled)
+    #[arg(long, default_value = "zmq")]
+    transport: String,
+    /// SUT address (CAN: interface name)
+    #[arg(long, default_value = "127.0.0.1")]
+    sut: String,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let broker = Broker::open(&args.transport, Some(&args.sut))?;
+    let listener = TcpListener::bind(&args.listen).with_context(|| format!("listen on {}", args.listen))?;
+    println!("serving {} broker for {} on http://{}", args.transport, args.sut, args.listen);
+    my_bdd::http::serve(listener, |req| my_bdd::server::handle(&broker, req))
+}
@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use my_bdd::catalog;
+
+/// Print or write the catalog of available Gherkin steps
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, value_enum, default_value = "markdown")]
+    format: Format,
+    /// Write to this file instead of stdout
+    #[arg(long)]
+    out: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Markdown,
+    Json,
+    /// Step definitions for the VS Code Cucumber autocomplete extensions
+    Vscode,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let steps = catalog::steps();
+    let text = match args.format {
+        Format::Markdown => catalog::markdown(&steps),
+        Format::Json => serde_json::to_string_pretty(&steps)? + "\n",
+        Format::Vscode => catalog::autocomplete(&steps),
+    };
+    match args.out {
+        Some(path) => std::fs::write(&path, text).with_context(|| format!("write {}", path.display())),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
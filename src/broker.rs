@@ -1,104 +1,914 @@
-use anyhow::{Result, Context};
-use serde_json::Value as JsonValue;
-use zmq::{Context as ZmqContext, Socket, PUB, SUB};
-use crate::proto_dyn::ProtoDyn;
+use anyhow::{anyhow, Result, Context};
+use serde_json::{json, Value as JsonValue};
+use crate::codec::{self, Codec};
+use crate::config::{Config, FramingConfig, Readiness};
+use crate::report::Traffic;
+use crate::matchers::MatchOptions;
+use crate::network::Network;
+use crate::proto_dyn::{BoundFields, ProtoDyn};
+use crate::validate::Violation;
+use crate::receiver::{Received, Receiver};
+use crate::session::SessionLog;
+use crate::transport::{self, Publisher, Subscriber};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
-use prost_reflect::ReflectMessage;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use prost_reflect::{DynamicMessage, EnumDescriptor, Kind, MessageDescriptor, ReflectMessage};
+
+/// How long connecting waits with `[readiness] check = "delay"`
+const CONNECT_DELAY: Duration = Duration::from_millis(200);
+/// Readiness timeout when `[readiness]` sets none
+pub const READY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the `probe` check sends its probe
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A received message decoded for assertions over several messages
+#[derive(Debug, Clone)]
+pub struct Captured {
+    pub topic: String,
+    pub body: JsonValue,
+    pub at: Instant,
+    /// Encoded payload size in bytes
+    pub size: usize,
+}
+
+/// The message that satisfied an expectation
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    pub topic: String,
+    pub body: JsonValue,
+    /// Encoded payload size in bytes
+    pub size: usize,
+    pub at: Instant,
+    /// How long the expectation waited
+    pub waited: Duration,
+}
 
 pub struct Broker {
-    //ctx: ZmqContext,
-    pub_sock: Socket,
-    sub_sock: Socket,
+    publisher: Box<dyn Publisher>,
+    receiver: Receiver,
     proto: ProtoDyn,
+    match_options: MatchOptions,
+    /// Check outgoing messages before sending them (`[send] validate`)
+    validate_sends: bool,
+    /// Fail sends setting deprecated fields instead of warning (`[send] deny_deprecated`)
+    deny_deprecated: bool,
+    /// Topics not carrying protobuf, with their codec
+    codecs: HashMap<String, Arc<dyn Codec>>,
+    /// Topics whose frames end with a CRC
+    framing: HashMap<String, FramingConfig>,
+    /// Where sends and matched receives are recorded, when running in a scenario
+    session: Option<SessionLog>,
+    /// The last message sent on each topic, as encoded
+    sent: Mutex<HashMap<String, JsonValue>>,
+    sent_traffic: Mutex<BTreeMap<String, Traffic>>,
+    /// Topics whose next message goes out twice
+    duplicate_next: Mutex<HashSet<String>>,
+    /// Send times of the last duplicated message on each topic
+    duplicated: Mutex<HashMap<String, [SystemTime; 2]>>,
+    started: Instant,
+    /// Where `connect` last connected to, for `bounce`
+    address: Option<String>,
+    /// Set once shutdown added the traffic to the report
+    reported: bool,
 }
 
 impl fmt::Debug for Broker {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Broker")
-            .field("ctx", &"ZmqContext")
-            .field("pub_sock", &"Socket(PUB)")
-            .field("sub_sock", &"Socket(SUB)")
+            .field("publisher", &"dyn Publisher")
+            .field("receiver", &self.receiver)
             .field("proto", &"ProtoDyn")
+            .field("match_options", &self.match_options)
+            .field("validate_sends", &self.validate_sends)
+            .field("codecs", &self.codecs.keys().collect::<Vec<_>>())
+            .field("framing", &self.framing)
+            .field("session", &self.session.is_some())
             .finish()
     }
 }
 
 impl Broker {
+    /// Broker on the default ZMQ PUB/SUB transport, sending from `[network] bind_address`
     pub fn new() -> Result<Self> {
-        let ctx = ZmqContext::new();
-        let pub_sock = ctx.socket(PUB).context("create pub")?;
-        let sub_sock = ctx.socket(SUB).context("create sub")?;
-        sub_sock.set_subscribe(b"").context("subscribe")?;
+        let (publisher, subscriber) = transport::zmq_pair(Network::configured()?)?;
+        Self::with_transport(Box::new(publisher), Box::new(subscriber))
+    }
+
+    /// Broker on transport `kind` (zmq, someip, can) connected to `address`, matching with the
+    /// configured options. CAN takes the interface as address, or the configured one when None.
+    pub fn open(kind: &str, address: Option<&str>) -> Result<Self> {
+        let mut broker = match kind {
+            "zmq" => Self::new()?,
+            #[cfg(feature = "someip")]
+            "someip" => {
+                let (publisher, subscriber) = crate::someip::someip_pair(&Config::global().someip, &Network::configured()?)?;
+                Self::with_transport(Box::new(publisher), Box::new(subscriber))?
+            }
+            #[cfg(feature = "can")]
+            "can" => {
+                // bound to the interface when opened, nothing to connect
+                let (publisher, subscriber) = crate::can::can_pair(&Config::global().can, address)?;
+                let mut broker = Self::with_transport(Box::new(publisher), Box::new(subscriber))?;
+                broker.set_match_options(MatchOptions::from_config(&Config::global().matching));
+                broker.set_validate_sends(Config::global().send.validate);
+                broker.set_deny_deprecated(Config::global().send.deny_deprecated);
+                return Ok(broker);
+            }
+            other => anyhow::bail!("unknown or disabled transport {}", other),
+        };
+        broker.connect(address.ok_or_else(|| anyhow::anyhow!("{} connection needs an address", kind))?)?;
+        broker.set_match_options(MatchOptions::from_config(&Config::global().matching));
+        broker.set_validate_sends(Config::global().send.validate);
+        broker.set_deny_deprecated(Config::global().send.deny_deprecated);
+        Ok(broker)
+    }
+
+    /// Broker sending through `publisher` and buffering everything `subscriber` receives
+    pub fn with_transport(publisher: Box<dyn Publisher>, subscriber: Box<dyn Subscriber>) -> Result<Self> {
         let proto = ProtoDyn::new().context("proto")?;
-        Ok(Self { pub_sock, sub_sock, proto })
+        let receiver = Receiver::spawn(subscriber, Config::global().buffer).context("start receiver")?;
+        let framing = Config::global().framing.clone();
+        for (topic, f) in &framing {
+            receiver.set_framing(topic, Some(*f));
+        }
+        Ok(Self {
+            publisher,
+            receiver,
+            proto,
+            match_options: MatchOptions::default(),
+            validate_sends: false,
+            deny_deprecated: false,
+            codecs: codec::configured()?,
+            framing,
+            session: None,
+            sent: Mutex::default(),
+            sent_traffic: Mutex::default(),
+            duplicate_next: Mutex::default(),
+            duplicated: Mutex::default(),
+            started: Instant::now(),
+            address: None,
+            reported: false,
+        })
+    }
+
+    /// Encode sends and decode what arrives with `proto` instead of the `[proto]` message types,
+    /// for a connection speaking another schema version
+    pub fn set_schema(&mut self, proto: ProtoDyn) {
+        self.proto = proto;
     }
 
-    /// Connects publisher to tcp://<ip>:4246 and subscriber to tcp://<ip>:4247 (matches your Python helper)
-    pub fn connect(&self, ip: &str) -> Result<()> {
-        self.pub_sock.connect(&format!(r"tcp://{}:4246", ip))?;
-        self.sub_sock.connect(&format!(r"tcp://{}:4247", ip))?;
-        std::thread::sleep(std::time::Duration::from_millis(200));
+    /// Load the connection's message types again from their files; see [`ProtoDyn::reload`]
+    pub fn reload_schema(&self) -> Result<()> {
+        self.proto.reload()
+    }
+
+    /// Options used when matching received messages against expectations
+    pub fn set_match_options(&mut self, options: MatchOptions) {
+        self.match_options = options;
+    }
+
+    /// Check every message against the descriptor and its validation rules before sending it,
+    /// failing the send with the offending field paths
+    pub fn set_validate_sends(&mut self, validate: bool) {
+        self.validate_sends = validate;
+    }
+
+    /// Fail sends setting fields marked deprecated; otherwise they are sent with a warning
+    pub fn set_deny_deprecated(&mut self, deny: bool) {
+        self.deny_deprecated = deny;
+    }
+
+    /// Encode and decode `topic` with the codec registered as `name` (`protobuf`, `json`, `cbor`,
+    /// `msgpack`...), instead of what `[codecs]` says
+    pub fn set_codec(&mut self, topic: &str, name: &str) -> Result<()> {
+        match codec::named(name)? {
+            Some(codec) => self.codecs.insert(topic.to_string(), codec),
+            None => self.codecs.remove(topic),
+        };
         Ok(())
     }
 
-    /// Send protobuf message by name (message_name) with JSON body
+    /// Append a CRC to `topic` frames on send, and verify and strip it on receive; None stops both
+    pub fn set_framing(&mut self, topic: &str, framing: Option<FramingConfig>) {
+        self.receiver.set_framing(topic, framing);
+        match framing {
+            Some(framing) => self.framing.insert(topic.to_string(), framing),
+            None => self.framing.remove(topic),
+        };
+    }
+
+    /// Record messages sent and messages matched by expectations in `log`
+    pub fn set_session_log(&mut self, log: SessionLog) {
+        self.session = Some(log);
+    }
+
+    /// Stop the receiver and disconnect, dropping anything still queued. Also runs on drop,
+    /// so a failing or panicking step doesn't leave connections or threads behind.
+    pub fn shutdown(&mut self) {
+        self.receiver.stop();
+        self.publisher.close();
+        if self.session.is_some() && !std::mem::replace(&mut self.reported, true) {
+            crate::report::record_traffic(&self.traffic());
+        }
+    }
+
+    /// Messages and bytes sent and received so far, by topic
+    pub fn traffic(&self) -> BTreeMap<String, Traffic> {
+        let mut traffic = self.receiver.inbox().traffic().clone();
+        for (topic, sent) in self.sent_traffic.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            traffic.entry(topic.clone()).or_default().add(sent);
+        }
+        traffic
+    }
+
+    /// How long ago the broker was created, which traffic rates are over
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Connects publisher and subscriber to `ip`; with ZMQ that is tcp://<ip>:4246 and tcp://<ip>:4247 (matches your Python helper).
+    /// Returns once the connection is ready by the `[readiness]` check.
+    pub fn connect(&mut self, ip: &str) -> Result<()> {
+        log::debug!(target: "transport", "connecting to {}", ip);
+        self.publisher.connect(ip)?;
+        self.receiver.connect(ip)?;
+        self.address = Some(ip.to_string());
+        let config = &Config::global().readiness;
+        self.wait_ready(config.check, config.timeout.unwrap_or(READY_TIMEOUT))
+            .with_context(|| format!("connect to {}", ip))
+    }
+
+    /// Drop the connections for `down`, then connect to the same address again and wait until
+    /// ready. What the SUT sends meanwhile is lost, as in a real outage; buffered messages stay.
+    pub fn bounce(&mut self, down: Duration) -> Result<()> {
+        let address = self.address.clone().ok_or_else(|| anyhow!("not connected to an address, nothing to reconnect to"))?;
+        log::info!(target: "transport", "disconnecting from {} for {:?}", address, down);
+        self.publisher.close();
+        self.receiver.disconnect()?;
+        self.emit("disconnected", json!({ "address": address, "down_ms": down.as_millis() as u64 }));
+        std::thread::sleep(down);
+        self.connect(&address).context("reconnect")
+    }
+
+    /// Stop reading for `period` without blocking the caller; what the SUT sends meanwhile queues
+    /// up in the transport, or is dropped once the queues are full
+    pub fn stall(&self, period: Duration) {
+        log::info!(target: "transport", "not reading for {:?}", period);
+        self.receiver.stall(Instant::now() + period);
+        self.emit("stalled", json!({ "stall_ms": period.as_millis() as u64 }));
+    }
+
+    /// Wait until the connection carries messages, as far as `check` can tell
+    pub fn wait_ready(&mut self, check: Readiness, timeout: Duration) -> Result<()> {
+        match check {
+            Readiness::Delay => std::thread::sleep(CONNECT_DELAY),
+            Readiness::Monitor | Readiness::Subscription => self.publisher.wait_ready(check, timeout)?,
+            Readiness::Probe => {
+                let config = &Config::global().readiness;
+                let (Some(probe), Some(reply)) = (&config.probe, &config.reply) else {
+                    anyhow::bail!("[readiness] check = \"probe\" needs probe and reply messages");
+                };
+                self.probe(probe, reply, timeout)?;
+            }
+        }
+        log::debug!(target: "transport", "connection ready ({:?})", check);
+        Ok(())
+    }
+
+    /// Send `probe` until a `reply` arrives, then drop the replies so no expectation matches them
+    pub fn probe(&self, probe: &str, reply: &str, timeout: Duration) -> Result<()> {
+        let payload = self.proto.encode_message(&self.proto.build_from_json(probe, &json!({}))?)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.publisher.send(probe, &payload)?;
+            let next = (Instant::now() + PROBE_INTERVAL).min(deadline);
+            if self.receiver.wait_until(next, |inbox| inbox.iter().any(|m| m.topic == reply).then_some(())).is_some() {
+                self.receiver.inbox().clear(Some(reply));
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!("no {} in answer to {} within {}", reply, probe, humantime::format_duration(timeout));
+            }
+        }
+    }
+
+    /// Send message `message_name` with JSON body, encoded with the topic's codec (protobuf unless
+    /// `[codecs]` says otherwise)
     pub fn send_message(&self, message_name: &str, body: &JsonValue) -> Result<()> {
+        self.send_message_on_topic(message_name, message_name, body)
+    }
+
+    /// Like send_message, but on `topic` rather than the topic named after the message, e.g. a
+    /// topic with a routing suffix or one topic per device. Codec and framing are the message's.
+    pub fn send_message_on_topic(&self, topic: &str, message_name: &str, body: &JsonValue) -> Result<()> {
+        if let Some(codec) = self.codecs.get(message_name) {
+            return self.send_encoded(topic, message_name, body, codec.encode(message_name, body)?, body.clone());
+        }
         let dm = self.proto.build_from_json(message_name, body)?;
-        let payload = self.proto.encode_message(&dm)?;
-        let topic = message_name.as_bytes();
-        self.pub_sock.send_multipart(&[topic, &payload], 0).context("send multipart")?;
+        let deprecated = crate::validate::deprecated_fields(&dm.descriptor(), body);
+        if !deprecated.is_empty() {
+            if self.deny_deprecated {
+                anyhow::bail!("{} not sent, it sets deprecated fields: {}", message_name, deprecated.join(", "));
+            }
+            log::warn!(target: "transport", "{} sets deprecated fields: {}", message_name, deprecated.join(", "));
+            if let Some(log) = &self.session {
+                log.note(format!("{} sets deprecated fields: {}", message_name, deprecated.join(", ")));
+            }
+        }
+        if self.validate_sends {
+            let mut violations = crate::validate::check_outgoing(&dm.descriptor(), body);
+            violations.extend(self.proto.validate(&dm));
+            if !violations.is_empty() {
+                let list: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+                anyhow::bail!("{} not sent, it fails validation:\n{}", message_name, list.join("\n"));
+            }
+        }
+        self.send_encoded(topic, message_name, body, self.proto.encode_message(&dm)?, self.proto.to_json_value(&dm))
+    }
+
+    /// Like send_message, but never validated; for deliberately invalid messages
+    pub fn send_message_unchecked(&self, message_name: &str, body: &JsonValue) -> Result<()> {
+        if let Some(codec) = self.codecs.get(message_name) {
+            return self.send_encoded(message_name, message_name, body, codec.encode(message_name, body)?, body.clone());
+        }
+        let dm = self.proto.build_from_json(message_name, body)?;
+        self.send_encoded(message_name, message_name, body, self.proto.encode_message(&dm)?, self.proto.to_json_value(&dm))
+    }
+
+    /// Like send_message, but encoded with the message type of `schema`, e.g. an older or newer
+    /// version of the SUT's protos, to test how its decoder copes. Never validated.
+    pub fn send_message_with(&self, schema: &ProtoDyn, message_name: &str, body: &JsonValue) -> Result<()> {
+        let dm = schema.build_from_json(message_name, body)?;
+        self.send_encoded(message_name, message_name, body, schema.encode_message(&dm)?, schema.to_json_value(&dm))
+    }
+
+    /// Send `body` with a CRC that doesn't match it, to check the SUT rejects the frame.
+    /// Errors when `[framing]` gives the topic no CRC.
+    pub fn send_corrupted(&self, message_name: &str, body: &JsonValue) -> Result<()> {
+        let framing = self.framing.get(message_name).ok_or_else(|| anyhow!("{} frames carry no CRC; set one under [framing]", message_name))?;
+        let (payload, sent) = match self.codecs.get(message_name) {
+            Some(codec) => (codec.encode(message_name, body)?, body.clone()),
+            None => {
+                let dm = self.proto.build_from_json(message_name, body)?;
+                (self.proto.encode_message(&dm)?, self.proto.to_json_value(&dm))
+            }
+        };
+        self.send_frame(message_name, message_name, body, framing.corrupt(&payload), sent)
+    }
+
+    /// Send `payload`, `body` encoded as a `message_name`, on `topic`; `sent` is the body as
+    /// encoded, for `last_sent`
+    fn send_encoded(&self, topic: &str, message_name: &str, body: &JsonValue, payload: Vec<u8>, sent: JsonValue) -> Result<()> {
+        let frame = match self.framing.get(message_name) {
+            Some(framing) => framing.append(&payload),
+            None => payload,
+        };
+        self.send_frame(topic, message_name, body, frame, sent)
+    }
+
+    fn send_frame(&self, topic: &str, message_name: &str, body: &JsonValue, payload: Vec<u8>, sent: JsonValue) -> Result<()> {
+        let first = SystemTime::now();
+        self.publisher.send(topic, &payload)?;
+        if !self.codecs.contains_key(message_name) {
+            if let Ok(desc) = self.proto.message_desc(message_name) {
+                crate::coverage::record_sent(&desc, body);
+            }
+        }
+        let duplicate = self.duplicate_next.lock().unwrap_or_else(|e| e.into_inner()).remove(topic);
+        let sends = if duplicate {
+            let second = SystemTime::now();
+            self.publisher.send(topic, &payload)?;
+            self.duplicated.lock().unwrap_or_else(|e| e.into_inner()).insert(topic.to_string(), [first, second]);
+            2
+        } else {
+            1
+        };
+        {
+            let mut traffic = self.sent_traffic.lock().unwrap_or_else(|e| e.into_inner());
+            let traffic = traffic.entry(topic.to_string()).or_default();
+            traffic.sent += sends;
+            traffic.sent_bytes += sends * payload.len() as u64;
+        }
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).insert(message_name.to_string(), sent);
+        log::debug!(target: "transport", "sent {} on {} ({} bytes){}", message_name, topic, payload.len(), if duplicate { " twice" } else { "" });
+        let mut event = json!({ "topic": topic, "body": body, "size": payload.len() });
+        if topic != message_name {
+            event["message"] = json!(message_name);
+        }
+        self.emit("message_sent", event.clone());
+        if duplicate {
+            event["duplicate"] = json!(true);
+            self.emit("message_sent", event);
+        }
+        if let Some(log) = &self.session {
+            log.sent(topic, body);
+            if let Some([first, second]) = self.duplicated(topic).filter(|_| duplicate) {
+                log.sent(topic, body);
+                log.note(format!(
+                    "{} duplicated: sent at {} and {} ({:?} apart)",
+                    topic,
+                    humantime::format_rfc3339_micros(first),
+                    humantime::format_rfc3339_micros(second),
+                    second.duration_since(first).unwrap_or_default()
+                ));
+            }
+        }
         Ok(())
     }
 
+    /// Send the next message on `topic` twice, back to back, to exercise the SUT's deduplication.
+    /// Whichever kind of send comes next is doubled, corrupted frames included.
+    pub fn duplicate_next(&self, topic: &str) {
+        self.duplicate_next.lock().unwrap_or_else(|e| e.into_inner()).insert(topic.to_string());
+    }
 
-    /// Wait for a matching message and return JSON body when partial match found (timeout_ms in ms)
-    pub fn expect_message(&self, message_name: &str, expected: &JsonValue, timeout_ms: i32) -> Result<JsonValue> {
-        self.sub_sock.set_rcvtimeo(timeout_ms).context("set rcvtimeo")?;
-        loop {
-            let parts = match self.sub_sock.recv_multipart(0) {
-                Ok(p) => p,
-                Err(e) if e == zmq::Error::EAGAIN => anyhow::bail!(format!("timeout waiting for {}", message_name)),
-                Err(e) => return Err(e).context("recv_multipart failed"),
+    /// When the last duplicated message on `topic` was sent, the first and the second time
+    pub fn duplicated(&self, topic: &str) -> Option<[SystemTime; 2]> {
+        self.duplicated.lock().unwrap_or_else(|e| e.into_inner()).get(topic).copied()
+    }
+
+    /// The last `message_name` sent, with the fields it was encoded with; None before the first send
+    pub fn last_sent(&self, message_name: &str) -> Option<JsonValue> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).get(message_name).cloned()
+    }
+
+    /// Only buffer received messages for which `filter(topic, payload)` is true, e.g. to ignore chatty
+    /// peers on a shared bus by topic prefix. Rejected messages are never decoded or counted as dropped.
+    /// Replaces any earlier filter; messages already buffered stay.
+    pub fn set_ingress_filter(&self, filter: impl Fn(&str, &[u8]) -> bool + Send + Sync + 'static) {
+        self.receiver.set_filter(Some(Arc::new(filter)));
+    }
+
+    /// Buffer every received message again
+    pub fn clear_ingress_filter(&self) {
+        self.receiver.set_filter(None);
+    }
+
+    /// Messages dropped from the receive buffer because it was full, by topic
+    pub fn dropped(&self) -> BTreeMap<String, u64> {
+        self.receiver.inbox().dropped().clone()
+    }
+
+    /// Received frames discarded because their CRC didn't check out, by topic
+    pub fn crc_errors(&self) -> BTreeMap<String, u64> {
+        self.receiver.inbox().crc_errors().clone()
+    }
+
+    /// Messages found waiting when reading resumed after a stall, by topic
+    pub fn queued(&self) -> BTreeMap<String, u64> {
+        self.receiver.inbox().queued().clone()
+    }
+
+    /// Drop buffered received messages, all of them or only those on `topic`; returns how many were dropped.
+    /// Later expectations only see messages arriving after the call.
+    pub fn clear_received(&self, topic: Option<&str>) -> usize {
+        self.receiver.inbox().clear(topic)
+    }
+
+    /// Wait for a matching message and return it when partial match found (timeout_ms in ms).
+    /// Buffered messages not consumed by an earlier expectation are checked first.
+    pub fn expect_message(&self, message_name: &str, expected: &JsonValue, timeout_ms: i32) -> Result<MatchResult> {
+        self.expect_message_since(message_name, expected, timeout_ms, None)
+    }
+
+    /// Like expect_message, but only messages received after `since` can match
+    pub fn expect_message_after(&self, message_name: &str, expected: &JsonValue, timeout_ms: i32, since: Instant) -> Result<MatchResult> {
+        self.expect_message_since(message_name, expected, timeout_ms, Some(since))
+    }
+
+    /// Like expect_message, but waiting until `deadline` rather than for a timeout, so several
+    /// expectations can share one deadline
+    pub fn expect_until(&self, message_name: &str, expected: &JsonValue, deadline: Instant) -> Result<MatchResult> {
+        self.expect_message_until(message_name, expected, deadline, None)
+    }
+
+    /// Like expect_until, but only messages received after `since` can match
+    pub fn expect_after_until(&self, message_name: &str, expected: &JsonValue, since: Instant, deadline: Instant) -> Result<MatchResult> {
+        self.expect_message_until(message_name, expected, deadline, Some(since))
+    }
+
+    fn expect_message_since(&self, message_name: &str, expected: &JsonValue, timeout_ms: i32, since: Option<Instant>) -> Result<MatchResult> {
+        self.expect_message_until(message_name, expected, deadline_in(timeout_ms), since)
+    }
+
+    fn expect_message_until(&self, message_name: &str, expected: &JsonValue, deadline: Instant, since: Option<Instant>) -> Result<MatchResult> {
+        match self.wait_for_message(message_name, expected, deadline, since)? {
+            Some(got) => Ok(got),
+            None => anyhow::bail!(format!("timeout waiting for {}", message_name)),
+        }
+    }
+
+    /// Error if a `topic` message arrives after `since`, waiting until `window` from now is over
+    pub fn expect_silence(&self, topic: &str, since: Instant, window: Duration) -> Result<()> {
+        let deadline = Instant::now() + window;
+        let arrived = self.receiver.wait_until(deadline, |inbox| inbox.iter().find(|m| m.topic == topic && m.at > since).map(|m| m.at));
+        if let Some(at) = arrived {
+            anyhow::bail!("{} arrived {:?} in, expected none within {:?}", topic, at.duration_since(since), window);
+        }
+        Ok(())
+    }
+
+    /// Send `message_name` and wait `timeout` for a `reply` matching `expected`, sending again up
+    /// to `retries` more times while none arrives. A late reply to an earlier send counts. No
+    /// attempt waits past `deadline`, and none starts after it. Returns the reply and how many
+    /// sends it took.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_and_expect(
+        &self,
+        message_name: &str,
+        body: &JsonValue,
+        reply: &str,
+        expected: &JsonValue,
+        timeout: Duration,
+        retries: u32,
+        deadline: Instant,
+    ) -> Result<(MatchResult, u32)> {
+        let since = Instant::now();
+        for attempt in 1..=retries + 1 {
+            self.send_message(message_name, body)?;
+            if let Some(got) = self.wait_for_message(reply, expected, (Instant::now() + timeout).min(deadline), Some(since))? {
+                return Ok((got, attempt));
+            }
+            log::debug!(target: "matcher", "no {} after sending {} (attempt {})", reply, message_name, attempt);
+            if Instant::now() >= deadline {
+                anyhow::bail!("no {} before the deadline, after {} of up to {} {} sends", reply, attempt, retries + 1, message_name);
+            }
+        }
+        anyhow::bail!("no {} within {} of any of {} {} sends", reply, humantime::format_duration(timeout), retries + 1, message_name)
+    }
+
+    /// The first message matching the expectation before `deadline`; None when it timed out
+    fn wait_for_message(&self, message_name: &str, expected: &JsonValue, deadline: Instant, since: Option<Instant>) -> Result<Option<MatchResult>> {
+        Ok(self.wait_for_any(&[(message_name, expected)], None, deadline, since)?.map(|(_, got)| got))
+    }
+
+    /// Wait for the first message to arrive that matches one of `alternatives` (message name and
+    /// expected JSON). Returns which alternative matched and the message.
+    pub fn expect_one_of(&self, alternatives: &[(&str, &JsonValue)], timeout_ms: i32) -> Result<(usize, MatchResult)> {
+        match self.wait_for_any(alternatives, None, deadline_in(timeout_ms), None)? {
+            Some(found) => Ok(found),
+            None => {
+                let mut names: Vec<&str> = Vec::new();
+                for (name, _) in alternatives {
+                    if !names.contains(name) {
+                        names.push(name);
+                    }
+                }
+                anyhow::bail!("timeout waiting for any of {}", names.join(", "))
+            }
+        }
+    }
+
+    /// Wait for a `message_name` matching `expected` on any topic matching `pattern` (see
+    /// [`topic_matches`]). The result's `topic` is the topic it arrived on.
+    pub fn expect_on_topic(&self, pattern: &str, message_name: &str, expected: &JsonValue, timeout_ms: i32) -> Result<MatchResult> {
+        match self.wait_for_any(&[(message_name, expected)], Some(pattern), deadline_in(timeout_ms), None)? {
+            Some((_, got)) => Ok(got),
+            None => anyhow::bail!("timeout waiting for {} on a topic matching {}", message_name, pattern),
+        }
+    }
+
+    /// Messages arrive on the topic named after them, unless `pattern` gives the topics to look at
+    fn wait_for_any(&self, alternatives: &[(&str, &JsonValue)], pattern: Option<&str>, deadline: Instant, since: Option<Instant>) -> Result<Option<(usize, MatchResult)>> {
+        let started = Instant::now();
+        // everything about the expectations that doesn't depend on the message is worked out once
+        let mut prepared = Vec::with_capacity(alternatives.len());
+        for (message_name, expected) in alternatives {
+            // topics with their own codec have no descriptor
+            let desc = match self.codecs.contains_key(*message_name) {
+                true => None,
+                false => Some(self.proto.message_desc(&format!("company.project.v1.{}", message_name))?),
             };
-            if parts.len() != 2 { continue; }
-            let topic = String::from_utf8_lossy(&parts[0]).to_string();
-            let payload = &parts[1];
-            // decode by topic name
-            let msg_name = format!("company.project.v1.{}", topic);
-            let dm = match self.proto.decode_message(msg_name.as_str(), payload) {
-                Ok(m) => m,
-                Err(_) => continue,
+            let expected = match &desc {
+                Some(desc) => self.normalize_json_for_comparison(expected, desc)?,
+                None => (*expected).clone(),
             };
-            let got_json = self.proto.to_json_value(&dm);
-            if topic != message_name { continue; }
-            //println!("Decoding topic '{}' with descriptor '{}'", topic, dm.descriptor().full_name());
-            println!("Decoded: {:?}", dm);
-            for f in dm.descriptor().fields() {
-                println!(
-                    "Field {}: {:?}",
-                    f.name(),
-                    dm.get_field(&f)
-                );
+            if let Some(desc) = &desc {
+                crate::coverage::record_asserted(desc, &expected);
+            }
+            let bound = desc.as_ref().filter(|_| self.match_options.skip_json).map(|desc| BoundFields::bind(desc, &expected, self.match_options));
+            log::debug!(target: "matcher", "expecting {} matching {} within {:?}", message_name, expected, deadline.saturating_duration_since(started));
+            prepared.push((*message_name, desc, expected, bound));
+        }
+        let mut label = alternatives.iter().map(|(name, _)| *name).collect::<Vec<_>>().join("|");
+        if let Some(pattern) = pattern {
+            label = format!("{} on {}", label, pattern);
+        }
+        let mut scanned: Option<u64> = None;
+        let found = self.receiver.wait_until(deadline, |inbox| {
+            if let Err(e) = inbox.check_overflow() {
+                return Some(Err(e));
+            }
+            for msg in inbox.iter_mut() {
+                if scanned.is_some_and(|seq| msg.seq <= seq) { continue; }
+                scanned = Some(msg.seq);
+                if msg.consumed || since.is_some_and(|t| msg.at <= t) { continue; }
+                for (i, (message_name, desc, expected, bound)) in prepared.iter().enumerate() {
+                    let on_topic = match pattern {
+                        Some(pattern) => topic_matches(pattern, &msg.topic),
+                        None => msg.topic == *message_name,
+                    };
+                    if !on_topic { continue; }
+                    let matched = match desc {
+                        Some(desc) => self.match_received(msg, desc, expected, bound.as_ref()),
+                        None => self.match_decoded(msg, message_name, expected),
+                    };
+                    match matched {
+                        Ok(Some(body)) => {
+                            msg.consumed = true;
+                            return Some(Ok((i, MatchResult {
+                                topic: msg.topic.clone(),
+                                body,
+                                size: msg.payload.len(),
+                                at: msg.at,
+                                waited: started.elapsed(),
+                            })));
+                        }
+                        Ok(None) => {}
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
             }
+            None
+        });
+        crate::report::record_wait(&label, started.elapsed(), matches!(found, Some(Ok(_))));
+        match &found {
+            Some(Ok((_, got))) => log::debug!(target: "matcher", "{} matched after {:?}", got.topic, got.waited),
+            Some(Err(e)) => log::debug!(target: "matcher", "{} failed: {:#}", label, e),
+            None => log::debug!(target: "matcher", "{} timed out after {:?}", label, started.elapsed()),
+        }
+        if let Some(Ok((i, _))) = &found {
+            if let (_, Some(desc), expected, _) = &prepared[*i] {
+                crate::coverage::record_matched(desc, expected);
+            }
+        }
+        if let (Some(log), Some(Ok((_, got)))) = (&self.session, &found) {
+            log.received(&got.topic, &got.body);
+        }
+        if crate::events::enabled() {
+            let outcome = match &found {
+                Some(Ok((_, got))) => {
+                    self.emit("message_received", json!({ "topic": got.topic, "body": got.body, "size": got.size }));
+                    "matched"
+                }
+                Some(Err(_)) => "error",
+                None => "timeout",
+            };
+            let expected = match prepared.as_slice() {
+                [(_, _, expected, _)] => expected.clone(),
+                many => JsonValue::Array(many.iter().map(|(name, _, expected, _)| json!({ "message": name, "expected": expected })).collect()),
+            };
+            let waited = started.elapsed().as_millis() as u64;
+            self.emit("match_attempted", json!({ "message": label, "expected": expected, "outcome": outcome, "waited_ms": waited }));
+        }
+        found.transpose()
+    }
+
+    /// Send a generated struct on the topic of its message name (feature `typed-messages`)
+    #[cfg(feature = "typed-messages")]
+    pub fn send_typed<T: prost::Message + prost::Name>(&self, value: &T) -> Result<()> {
+        let dm = self.proto.from_typed(value)?;
+        let body = self.proto.to_json_value(&dm);
+        self.send_encoded(T::NAME, T::NAME, &body, self.proto.encode_message(&dm)?, body.clone())
+    }
 
-            // Convert expected enum strings to numbers for comparison
-            let normalized_expected = self.normalize_json_for_comparison(expected, &dm)?;
-            println!("Expected:{:?}", normalized_expected);
-            println!("Received{:?}", got_json);
-            if crate::proto_dyn::json_partial_match(&normalized_expected, &got_json) {
-                return Ok(got_json);
+    /// Wait for the next `T` message, like [`Broker::expect_message`] with an empty expectation,
+    /// and return it as the generated struct (feature `typed-messages`)
+    #[cfg(feature = "typed-messages")]
+    pub fn expect_typed<T: prost::Message + prost::Name + Default>(&self, timeout_ms: i32) -> Result<T> {
+        self.expect_typed_matching(timeout_ms, |_: &T| true)
+    }
+
+    /// Wait for the next `T` message `accept` returns true for (feature `typed-messages`)
+    #[cfg(feature = "typed-messages")]
+    pub fn expect_typed_matching<T: prost::Message + prost::Name + Default>(&self, timeout_ms: i32, accept: impl Fn(&T) -> bool) -> Result<T> {
+        let started = Instant::now();
+        let deadline = deadline_in(timeout_ms);
+        let mut scanned: Option<u64> = None;
+        let found = self.receiver.wait_until(deadline, |inbox| {
+            if let Err(e) = inbox.check_overflow() {
+                return Some(Err(e));
             }
+            for msg in inbox.iter_mut() {
+                if scanned.is_some_and(|seq| msg.seq <= seq) { continue; }
+                scanned = Some(msg.seq);
+                if msg.consumed || msg.topic != T::NAME { continue; }
+                let value = match self.decode_received(msg).and_then(|dm| self.proto.to_typed::<T>(&dm)) {
+                    Ok(v) => v,
+                    Err(e) => return Some(Err(e)),
+                };
+                if accept(&value) {
+                    msg.consumed = true;
+                    return Some(Ok(value));
+                }
+            }
+            None
+        });
+        crate::report::record_wait(T::NAME, started.elapsed(), matches!(found, Some(Ok(_))));
+        found.unwrap_or_else(|| Err(anyhow!("timeout waiting for {}", T::NAME)))
+    }
+
+    /// Every message on `topic` received during the next `window`, decoded, in arrival order
+    pub fn collect(&self, topic: &str, window: Duration) -> Result<Vec<Captured>> {
+        let since = Instant::now();
+        std::thread::sleep(window);
+        self.captured_since(topic, since)
+    }
+
+    /// Arrival times of messages on `topic` during the next `window`, without decoding them
+    pub fn arrivals(&self, topic: &str, window: Duration) -> Vec<Instant> {
+        let since = Instant::now();
+        std::thread::sleep(window);
+        self.receiver.inbox().iter().filter(|m| m.topic == topic && m.at > since).map(|m| m.at).collect()
+    }
+
+    /// Buffered messages on `topic` received after `since`, decoded
+    pub fn captured_since(&self, topic: &str, since: Instant) -> Result<Vec<Captured>> {
+        self.captured_where(topic, Some(since))
+    }
+
+    /// Every buffered message on `topic`, decoded
+    pub fn captured(&self, topic: &str) -> Result<Vec<Captured>> {
+        self.captured_where(topic, None)
+    }
+
+    fn captured_where(&self, topic: &str, since: Option<Instant>) -> Result<Vec<Captured>> {
+        let inbox = self.receiver.inbox();
+        let mut matching = inbox.iter().filter(|m| m.topic == topic && since.is_none_or(|t| m.at > t)).peekable();
+        if matching.peek().is_none() {
+            return Ok(Vec::new());
+        }
+        if self.codecs.contains_key(topic) {
+            return matching.map(|m| Ok(Captured { topic: m.topic.clone(), body: self.decode_json(m)?, at: m.at, size: m.payload.len() })).collect();
         }
+        // resolved once rather than per message
+        let desc = self.proto.message_desc(&format!("company.project.v1.{}", topic))?;
+        matching
+            .map(|m| {
+                let msg = DynamicMessage::decode(desc.clone(), &m.payload[..]).with_context(|| format!("decode {}", topic))?;
+                Ok(Captured { topic: m.topic.clone(), body: self.proto.to_json_value(&msg), at: m.at, size: m.payload.len() })
+            })
+            .collect()
+    }
+
+    /// The last `n` buffered messages on any topic, oldest first; bodies that don't decode are null
+    pub fn recent(&self, n: usize) -> Vec<Captured> {
+        let inbox = self.receiver.inbox();
+        let mut recent: Vec<Captured> = inbox
+            .iter()
+            .rev()
+            .take(n)
+            .map(|m| {
+                let body = self.decode_json(m).unwrap_or(JsonValue::Null);
+                Captured { topic: m.topic.clone(), body, at: m.at, size: m.payload.len() }
+            })
+            .collect();
+        recent.reverse();
+        recent
+    }
+
+    /// Message names known to the descriptor pool
+    pub fn message_names(&self) -> Vec<String> {
+        self.proto.message_names()
+    }
+
+    /// Check that JSONPath `path` names a field of message `name`
+    pub fn check_field(&self, name: &str, path: &str) -> Result<()> {
+        self.proto.check_field(name, path)
+    }
+
+    /// Descriptor of message `name`
+    pub fn message_desc(&self, name: &str) -> Result<MessageDescriptor> {
+        self.proto.message_desc(name)
+    }
+
+    /// Kind of the field at JSONPath `path` in message `name`, and whether it is a whole list or map
+    pub fn field_kind(&self, name: &str, path: &str) -> Result<(Kind, bool)> {
+        self.proto.field_kind(name, path)
+    }
+
+    /// Enum type of the field at JSONPath `path` in message `name`
+    pub fn enum_field(&self, name: &str, path: &str) -> Result<EnumDescriptor> {
+        self.proto.enum_field(name, path)
+    }
+
+    /// Validation failures of the most recent message received on `topic`
+    pub fn validate_last(&self, topic: &str) -> Result<Vec<Violation>> {
+        let inbox = self.receiver.inbox();
+        if self.codecs.contains_key(topic) {
+            anyhow::bail!("{} is not protobuf, and validation rules come from the descriptor set", topic);
+        }
+        let last = inbox.iter().rev().find(|m| m.topic == topic).ok_or_else(|| anyhow!("no {} message received", topic))?;
+        Ok(self.proto.validate(&self.decode_received(last)?))
+    }
+
+    /// JSON body of the most recent message received on `topic`, if any
+    pub fn last_message(&self, topic: &str) -> Result<Option<JsonValue>> {
+        Ok(self.last_captured(topic)?.map(|c| c.body))
+    }
+
+    /// The most recent message received on `topic`, decoded, if any
+    pub fn last_captured(&self, topic: &str) -> Result<Option<Captured>> {
+        let inbox = self.receiver.inbox();
+        let last = match inbox.iter().rev().find(|m| m.topic == topic) {
+            Some(m) => {
+                let body = self.decode_json(m)?;
+                Some(Captured { topic: m.topic.clone(), body, at: m.at, size: m.payload.len() })
+            }
+            None => None,
+        };
+        Ok(last)
+    }
+
+    /// Write an event to the event stream, tagged with this connection's scenario and name
+    fn emit(&self, kind: &str, mut fields: JsonValue) {
+        if !crate::events::enabled() {
+            return;
+        }
+        if let Some(session) = &self.session {
+            if let Some(label) = session.label() {
+                fields["connection"] = json!(label);
+            }
+            if let Some(scope) = session.scope() {
+                fields = scope.with(fields);
+            }
+        }
+        crate::events::emit(kind, std::time::SystemTime::now(), fields);
+    }
+
+    /// Decode a buffered message by its topic name
+    fn decode_received(&self, msg: &Received) -> Result<DynamicMessage> {
+        let msg_name = format!("company.project.v1.{}", msg.topic);
+        self.proto.decode_message(msg_name.as_str(), &msg.payload)
+    }
+
+    /// A buffered message as JSON, decoded with its topic's codec
+    fn decode_json(&self, msg: &Received) -> Result<JsonValue> {
+        match self.codecs.get(&msg.topic) {
+            Some(codec) => codec.decode(&msg.topic, &msg.payload).with_context(|| format!("decode {}", msg.topic)),
+            None => Ok(self.proto.to_json_value(&self.decode_received(msg)?)),
+        }
+    }
+
+    /// Decode a received message of a topic with its own codec and partially match it against `expected`
+    fn match_decoded(&self, msg: &Received, message_name: &str, expected: &JsonValue) -> Result<Option<JsonValue>> {
+        let decoded = match self.codecs.get(message_name) {
+            Some(codec) => codec.decode(message_name, &msg.payload).with_context(|| format!("decode {}", msg.topic)),
+            None => self.decode_json(msg),
+        };
+        let body = match decoded {
+            Ok(body) => body,
+            Err(e) => {
+                log::trace!(target: "matcher", "{} #{} does not decode: {:#}", msg.topic, msg.seq, e);
+                return Ok(None);
+            }
+        };
+        if crate::proto_dyn::json_partial_match_with(expected, &body, self.match_options)? {
+            return Ok(Some(body));
+        }
+        log::trace!(target: "matcher", "{} #{} differs: {}", msg.topic, msg.seq, body);
+        Ok(None)
+    }
+
+    /// Decode a received message of the expected type and partially match it against `expected`.
+    /// Only messages the bound fields don't rule out are converted to JSON.
+    fn match_received(&self, msg: &Received, desc: &MessageDescriptor, expected: &JsonValue, bound: Option<&BoundFields>) -> Result<Option<JsonValue>> {
+        let dm = match DynamicMessage::decode(desc.clone(), &msg.payload[..]) {
+            Ok(dm) => dm,
+            Err(e) => {
+                log::trace!(target: "matcher", "{} #{} does not decode: {}", msg.topic, msg.seq, e);
+                return Ok(None);
+            }
+        };
+        if let Some(bound) = bound {
+            if !bound.may_match(&dm) {
+                if log::log_enabled!(target: "matcher", log::Level::Trace) {
+                    log::trace!(target: "matcher", "{} #{} differs: {}", msg.topic, msg.seq, self.proto.to_json_value(&dm));
+                }
+                return Ok(None);
+            }
+            if bound.is_complete() {
+                return Ok(Some(self.proto.to_json_value(&dm)));
+            }
+        }
+        let got_json = self.proto.to_json_value(&dm);
+        if crate::proto_dyn::json_partial_match_with(expected, &got_json, self.match_options)? {
+            return Ok(Some(got_json));
+        }
+        log::trace!(target: "matcher", "{} #{} differs: {}", msg.topic, msg.seq, got_json);
+        Ok(None)
     }
 
     /// Convert enum string values in expected JSON to their numeric equivalents
-    fn normalize_json_for_comparison(&self, expected: &JsonValue, message: &prost_reflect::DynamicMessage) -> Result<JsonValue> {
+    fn normalize_json_for_comparison(&self, expected: &JsonValue, desc: &MessageDescriptor) -> Result<JsonValue> {
         match expected {
             JsonValue::Object(map) => {
                 let mut normalized = serde_json::Map::new();
                 for (key, value) in map {
                     // Find the field descriptor for this key
-                    if let Some(field_desc) = message.descriptor().fields().find(|f| f.name() == key) {
+                    if let Some(field_desc) = desc.fields().find(|f| f.name() == key) {
                         if field_desc.kind().as_enum().is_some() {
                             // This is an enum field, convert string to number
                             if let JsonValue::String(enum_name) = value {
@@ -133,3 +943,44 @@ impl Broker {
         }
     }
 }
+
+/// Whether `topic` matches `pattern`, where `*` stands for any run of characters (`/` included)
+/// and `?` for one character, e.g. `tlm/*` or `tlm/device??/status`
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let (pattern, topic): (Vec<char>, Vec<char>) = (pattern.chars().collect(), topic.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and the topic position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < topic.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == topic[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The deadline of a wait of `timeout_ms` starting now; a negative timeout doesn't wait
+fn deadline_in(timeout_ms: i32) -> Instant {
+    Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64)
+}
+
+impl Drop for Broker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
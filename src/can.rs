@@ -0,0 +1,177 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use crate::config::CanConfig;
+use crate::transport::{Publisher, Subscriber};
+
+/// Largest payload of a classic CAN frame
+pub const CAN_MAX_PAYLOAD: usize = libc::CAN_MAX_DLEN;
+/// Highest standard (11-bit) identifier; larger ones are sent as extended frames
+const CAN_SFF_MAX: u32 = 0x7FF;
+
+/// Topic <-> CAN identifier table from `[can.ids]`
+#[derive(Debug, Clone, Default)]
+pub struct CanIds {
+    by_topic: HashMap<String, u32>,
+    by_id: HashMap<u32, String>,
+}
+
+impl CanIds {
+    pub fn new(ids: &HashMap<String, u32>) -> Result<Self> {
+        let mut by_id = HashMap::new();
+        for (topic, id) in ids {
+            if *id > libc::CAN_EFF_MASK {
+                bail!("CAN id 0x{:X} for {} does not fit in 29 bits", id, topic);
+            }
+            if let Some(other) = by_id.insert(*id, topic.clone()) {
+                bail!("CAN id 0x{:X} is mapped to both {} and {}", id, other, topic);
+            }
+        }
+        Ok(Self { by_topic: ids.clone(), by_id })
+    }
+
+    pub fn id(&self, topic: &str) -> Result<u32> {
+        self.by_topic.get(topic).copied().ok_or_else(|| anyhow!("no CAN id for {}; add it under [can.ids]", topic))
+    }
+
+    /// Topic for a received identifier; unmapped ids become "0x<id>" so they never decode as a message
+    pub fn topic(&self, id: u32) -> String {
+        self.by_id.get(&id).cloned().unwrap_or_else(|| format!("0x{:X}", id))
+    }
+}
+
+/// Raw SocketCAN socket bound to one interface, shared by both transport halves
+#[derive(Debug)]
+struct CanSocket {
+    fd: OwnedFd,
+}
+
+impl CanSocket {
+    fn open(interface: &str) -> Result<Self> {
+        let name = CString::new(interface).context("interface name")?;
+        // SAFETY: plain libc calls; the fd is owned right after creation and the address is fully initialised
+        unsafe {
+            let raw = libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW);
+            if raw < 0 {
+                return Err(io::Error::last_os_error()).context("open CAN socket");
+            }
+            let fd = OwnedFd::from_raw_fd(raw);
+            let index = libc::if_nametoindex(name.as_ptr());
+            if index == 0 {
+                return Err(io::Error::last_os_error()).with_context(|| format!("CAN interface {}", interface));
+            }
+            let mut addr: libc::sockaddr_can = std::mem::zeroed();
+            addr.can_family = libc::AF_CAN as libc::sa_family_t;
+            addr.can_ifindex = index as libc::c_int;
+            let len = std::mem::size_of::<libc::sockaddr_can>() as libc::socklen_t;
+            if libc::bind(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, len) < 0 {
+                return Err(io::Error::last_os_error()).with_context(|| format!("bind CAN interface {}", interface));
+            }
+            Ok(Self { fd })
+        }
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        Ok(Self { fd: self.fd.try_clone().context("clone CAN socket")? })
+    }
+
+    fn send(&self, id: u32, data: &[u8]) -> Result<()> {
+        if data.len() > CAN_MAX_PAYLOAD {
+            bail!("payload is {} bytes, a CAN frame carries at most {}", data.len(), CAN_MAX_PAYLOAD);
+        }
+        // SAFETY: can_frame is plain data; zeroed is a valid value
+        let mut frame: libc::can_frame = unsafe { std::mem::zeroed() };
+        frame.can_id = if id > CAN_SFF_MAX { id | libc::CAN_EFF_FLAG } else { id };
+        frame.can_dlc = data.len() as u8;
+        frame.data[..data.len()].copy_from_slice(data);
+        let size = std::mem::size_of::<libc::can_frame>();
+        // SAFETY: writes exactly one frame from a live stack value
+        let written = unsafe { libc::write(self.fd.as_raw_fd(), &frame as *const _ as *const libc::c_void, size) };
+        if written != size as isize {
+            return Err(io::Error::last_os_error()).context("write CAN frame");
+        }
+        Ok(())
+    }
+
+    /// One data frame as (id, payload); error, remote and short frames are skipped
+    fn recv(&self, timeout: Duration) -> Result<Option<(u32, Vec<u8>)>> {
+        let mut pfd = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        // SAFETY: polls a single live descriptor
+        let ready = unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as libc::c_int) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(err).context("poll CAN socket");
+        }
+        if ready == 0 {
+            return Ok(None);
+        }
+        // SAFETY: can_frame is plain data and the read is bounded by its size
+        let mut frame: libc::can_frame = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::can_frame>();
+        let read = unsafe { libc::read(self.fd.as_raw_fd(), &mut frame as *mut _ as *mut libc::c_void, size) };
+        if read < 0 {
+            return Err(io::Error::last_os_error()).context("read CAN frame");
+        }
+        if read as usize != size || frame.can_id & (libc::CAN_ERR_FLAG | libc::CAN_RTR_FLAG) != 0 {
+            return Ok(None);
+        }
+        let id = if frame.can_id & libc::CAN_EFF_FLAG != 0 { frame.can_id & libc::CAN_EFF_MASK } else { frame.can_id & CAN_SFF_MAX };
+        let len = (frame.can_dlc as usize).min(CAN_MAX_PAYLOAD);
+        Ok(Some((id, frame.data[..len].to_vec())))
+    }
+}
+
+/// Sends each topic as frames with its mapped CAN id
+#[derive(Debug)]
+pub struct CanPublisher {
+    sock: CanSocket,
+    ids: CanIds,
+}
+
+/// Receives frames and names them by their mapped topic
+#[derive(Debug)]
+pub struct CanSubscriber {
+    sock: CanSocket,
+    ids: CanIds,
+}
+
+/// Publisher and subscriber on the configured interface. They share one socket, so frames we
+/// send are not looped back to our own subscriber.
+pub fn can_pair(config: &CanConfig, interface: Option<&str>) -> Result<(CanPublisher, CanSubscriber)> {
+    let interface = interface
+        .or(config.interface.as_deref())
+        .ok_or_else(|| anyhow!("no CAN interface; name it in the step or as [can] interface"))?;
+    let ids = CanIds::new(&config.ids)?;
+    let sock = CanSocket::open(interface)?;
+    let sub = CanSubscriber { sock: sock.try_clone()?, ids: ids.clone() };
+    Ok((CanPublisher { sock, ids }, sub))
+}
+
+impl Publisher for CanPublisher {
+    /// The socket is bound when opened; there is nothing to connect
+    fn connect(&mut self, _address: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        let id = self.ids.id(topic)?;
+        self.sock.send(id, payload).with_context(|| format!("send {} as CAN id 0x{:X}", topic, id))
+    }
+}
+
+impl Subscriber for CanSubscriber {
+    fn connect(&mut self, _address: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        Ok(self.sock.recv(timeout)?.map(|(id, data)| (self.ids.topic(id), data)))
+    }
+}
@@ -0,0 +1,277 @@
+use cucumber::codegen::{inventory, StepConstructor, WorldInventory};
+use cucumber::Parameter;
+use serde::Serialize;
+
+use crate::params::{DurationParam, MessageName};
+use crate::steps::MyWorld;
+
+/// A registered step definition, as found in the step attributes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepDef {
+    /// Given, When or Then
+    pub keyword: &'static str,
+    /// Regex from the step attribute
+    pub pattern: String,
+    /// Each parameter in order: its cucumber expression type (`{message}`) or capture group regex
+    pub parameters: Vec<String>,
+    /// Step text with parameters shown as placeholders
+    pub example: String,
+    /// Source file and line of the step function
+    pub location: String,
+}
+
+/// Every step registered for [`MyWorld`], in source order
+pub fn steps() -> Vec<StepDef> {
+    let mut found = Vec::new();
+    collect::<<MyWorld as WorldInventory>::Given>("Given", &mut found);
+    collect::<<MyWorld as WorldInventory>::When>("When", &mut found);
+    collect::<<MyWorld as WorldInventory>::Then>("Then", &mut found);
+    found.sort_by_key(|(line, def)| (def.location.split(':').next().unwrap_or_default().to_string(), *line));
+    found.into_iter().map(|(_, def)| def).collect()
+}
+
+fn collect<T: inventory::Collect + StepConstructor<MyWorld>>(keyword: &'static str, out: &mut Vec<(u32, StepDef)>) {
+    for step in inventory::iter::<T> {
+        let (location, regex, _) = step.inner();
+        let pattern = regex().as_str().to_string();
+        out.push((
+            location.line,
+            StepDef {
+                keyword,
+                parameters: capture_groups(&pattern),
+                example: example(&pattern),
+                pattern,
+                location: format!("{}:{}", location.path, location.line),
+            },
+        ));
+    }
+}
+
+/// Markdown table of `steps`
+pub fn markdown(steps: &[StepDef]) -> String {
+    let mut out = String::from("| Step | Parameters | Pattern | Defined at |\n|---|---|---|---|\n");
+    for s in steps {
+        let params: Vec<String> = s.parameters.iter().map(|p| format!("`{}`", p)).collect();
+        out.push_str(&format!(
+            "| {} {} | {} | `{}` | {} |\n",
+            s.keyword,
+            s.example.replace('|', "\\|"),
+            params.join(", ").replace('|', "\\|"),
+            s.pattern.replace('|', "\\|"),
+            s.location
+        ));
+    }
+    out
+}
+
+/// `steps` as `Given(/regex/);` lines, a file the Cucumber autocomplete extensions for VS Code
+/// read as step definitions (see `cucumberautocomplete.steps`)
+pub fn autocomplete(steps: &[StepDef]) -> String {
+    let mut out = String::from("// Generated by bdd-steps --format vscode; do not edit\n");
+    for s in steps {
+        out.push_str(&format!("{}(/{}/);\n", s.keyword, escape_slashes(&s.pattern)));
+    }
+    out
+}
+
+/// Escape `/` for a JavaScript regex literal, leaving already escaped ones alone
+fn escape_slashes(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if c == '/' && !escaped {
+            out.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        out.push(c);
+    }
+    out
+}
+
+/// Every parameter of `pattern`: expression parameter types by name, other capture groups as written
+pub fn capture_groups(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '[' => i = class_end(&chars, i) + 1,
+            '(' => {
+                let end = group_end(&chars, i);
+                let inner: String = chars[i + 1..end].iter().collect();
+                if let Some(name) = parameter_type(&inner) {
+                    groups.push(format!("{{{}}}", name));
+                } else if !inner.starts_with('?') {
+                    groups.push(inner.clone());
+                } else {
+                    groups.extend(capture_groups(inner.trim_start_matches("?:")));
+                }
+                i = end + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    groups
+}
+
+/// Readable step text for `pattern`: anchors and escapes removed, optional groups dropped and
+/// capture groups replaced by a placeholder (or their first alternative when they list literals)
+pub fn example(pattern: &str) -> String {
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    let pattern = pattern.strip_suffix('$').unwrap_or(pattern);
+    render(&pattern.chars().collect::<Vec<_>>())
+}
+
+fn render(chars: &[char]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                match chars[i + 1] {
+                    'd' | 'w' | 'S' => out.push_str("<value>"),
+                    's' => out.push(' '),
+                    c => out.push(c),
+                }
+                i += 2;
+                i = skip_quantifier(chars, i);
+            }
+            '[' => {
+                let end = class_end(chars, i);
+                out.push_str("<value>");
+                i = skip_quantifier(chars, end + 1);
+            }
+            '(' => {
+                let end = group_end(chars, i);
+                let inner: String = chars[i + 1..end].iter().collect();
+                let optional = chars.get(end + 1) == Some(&'?');
+                i = skip_quantifier(chars, end + 1);
+                if optional {
+                    continue;
+                }
+                match inner.strip_prefix("?:") {
+                    _ if parameter_type(&inner).is_some() => out.push_str(&placeholder(&inner)),
+                    Some(alts) => out.push_str(&render(&first_alternative(alts).chars().collect::<Vec<_>>())),
+                    None => out.push_str(&placeholder(&inner)),
+                }
+            }
+            '?' | '*' | '+' | '.' => i += 1,
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Cucumber expression parameter type whose generated regex is `group`. `{endpoint}` is plain
+/// `\S+`, which can't be told apart from a regex step's group.
+fn parameter_type(group: &str) -> Option<&'static str> {
+    match group {
+        _ if group.starts_with(r#"?:"(?P<"#) => Some("string"),
+        r"(?:-?\d+)|(?:\d+)" => Some("int"),
+        r"[^\s]+" => Some("word"),
+        ".*" => Some(""),
+        _ if group == MessageName::REGEX => Some(MessageName::NAME),
+        _ if group == DurationParam::REGEX => Some(DurationParam::NAME),
+        _ => None,
+    }
+}
+
+fn placeholder(group: &str) -> String {
+    match parameter_type(group) {
+        Some("string") => return "\"<text>\"".to_string(),
+        Some("int") => return "<number>".to_string(),
+        Some("") => return "<...>".to_string(),
+        Some("word") | None => {}
+        Some(name) => return format!("<{}>", name),
+    }
+    let alts = split_alternatives(group);
+    if alts.len() > 1 && alts.iter().all(|a| a.chars().all(|c| c.is_alphanumeric() || c == ' ')) {
+        return alts[0].to_string();
+    }
+    match group {
+        r"\w+" => "<name>".to_string(),
+        r"\d+" | r"-?\d+" | r"\d+(?:\.\d+)?" | r"-?\d+(?:\.\d+)?" => "<number>".to_string(),
+        r"\S+" => "<value>".to_string(),
+        r#"[^"]+"# | r#"[^"]*"# | ".*" => "<text>".to_string(),
+        ".+" => "<...>".to_string(),
+        _ if group.is_empty() => String::new(),
+        _ => "<value>".to_string(),
+    }
+}
+
+fn first_alternative(s: &str) -> &str {
+    split_alternatives(s).into_iter().next().unwrap_or_default()
+}
+
+/// Split on top-level `|`
+fn split_alternatives(s: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut parts = Vec::new();
+    let (mut depth, mut start, mut k) = (0, 0, 0);
+    while k < chars.len() {
+        match chars[k].1 {
+            '\\' => k += 1,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '|' if depth == 0 => {
+                parts.push(&s[start..chars[k].0]);
+                start = chars[k].0 + 1;
+            }
+            _ => {}
+        }
+        k += 1;
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn skip_quantifier(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && matches!(chars[i], '?' | '*' | '+') {
+        i += 1;
+    }
+    i
+}
+
+/// Index of the `]` closing the class opened at `open`
+fn class_end(chars: &[char], open: usize) -> usize {
+    let mut i = open + 1;
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < chars.len() && chars[i] != ']' {
+        if chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    i.min(chars.len().saturating_sub(1))
+}
+
+/// Index of the `)` closing the group opened at `open`
+fn group_end(chars: &[char], open: usize) -> usize {
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => i = class_end(chars, i),
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len().saturating_sub(1)
+}
@@ -0,0 +1,467 @@
+//! Payload encodings other than protobuf. Topics carry protobuf unless `[codecs]` names another
+//! codec for them; built in are `json`, `cbor`, `msgpack`, `avro` and `layout`, and downstream
+//! crates can add more with [`register`]. Codecs convert between payloads and the JSON view steps
+//! and matchers use, so send and expect steps work the same on every topic. Byte strings decode to
+//! base64, as protobuf `bytes` fields do.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::{Map, Number, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::avro::Avro;
+use crate::config::Config;
+use crate::layout::Layouts;
+
+/// Codec name for topics decoded through the descriptor set
+pub const PROTOBUF: &str = "protobuf";
+
+/// Converts a topic's payloads to and from JSON
+pub trait Codec: Send + Sync {
+    fn encode(&self, topic: &str, body: &JsonValue) -> Result<Vec<u8>>;
+    fn decode(&self, topic: &str, payload: &[u8]) -> Result<JsonValue>;
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn Codec>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut m: HashMap<String, Arc<dyn Codec>> = HashMap::new();
+        m.insert("json".to_string(), Arc::new(Json));
+        m.insert("cbor".to_string(), Arc::new(Cbor));
+        m.insert("msgpack".to_string(), Arc::new(MessagePack));
+        m.insert("avro".to_string(), Arc::new(Avro::new(Config::global().avro.clone())));
+        m.insert("layout".to_string(), Arc::new(Layouts(Config::global().layouts.clone())));
+        RwLock::new(m)
+    })
+}
+
+/// Register (or replace) the codec `[codecs]` refers to as `name`
+pub fn register(name: &str, codec: impl Codec + 'static) -> Result<()> {
+    if name == PROTOBUF || name.is_empty() {
+        bail!("codec name {:?} is reserved", name);
+    }
+    registry().write().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), Arc::new(codec));
+    Ok(())
+}
+
+/// The codec registered as `name`; None for protobuf
+pub fn named(name: &str) -> Result<Option<Arc<dyn Codec>>> {
+    if name == PROTOBUF {
+        return Ok(None);
+    }
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    match registry.get(name) {
+        Some(codec) => Ok(Some(codec.clone())),
+        None => {
+            let mut known: Vec<&str> = registry.keys().map(String::as_str).chain([PROTOBUF]).collect();
+            known.sort();
+            bail!("unknown codec {} (known: {})", name, known.join(", "))
+        }
+    }
+}
+
+/// Codecs of the topics `[codecs]` doesn't leave to protobuf
+pub fn configured() -> Result<HashMap<String, Arc<dyn Codec>>> {
+    let mut codecs = HashMap::new();
+    for (topic, name) in &Config::global().codecs {
+        if let Some(codec) = named(name).with_context(|| format!("[codecs] {}", topic))? {
+            codecs.insert(topic.clone(), codec);
+        }
+    }
+    Ok(codecs)
+}
+
+/// Whether `[codecs]` sends `topic` as something other than protobuf, so it needs no descriptor
+pub fn is_configured(topic: &str) -> bool {
+    Config::global().codecs.get(topic).is_some_and(|name| name != PROTOBUF)
+}
+
+/// The payload is the JSON text itself
+pub struct Json;
+
+impl Codec for Json {
+    fn encode(&self, _topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(body)?)
+    }
+
+    fn decode(&self, _topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+}
+
+/// CBOR (RFC 8949). Tags are dropped, keeping the tagged value; undefined decodes to null.
+pub struct Cbor;
+
+impl Codec for Cbor {
+    fn encode(&self, _topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        cbor_encode(body, &mut out);
+        Ok(out)
+    }
+
+    fn decode(&self, _topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        let mut reader = Reader { bytes: payload, pos: 0 };
+        let value = cbor_decode(&mut reader, 0)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+/// MessagePack. Extension types are rejected.
+pub struct MessagePack;
+
+impl Codec for MessagePack {
+    fn encode(&self, _topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        msgpack_encode(body, &mut out);
+        Ok(out)
+    }
+
+    fn decode(&self, _topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        let mut reader = Reader { bytes: payload, pos: 0 };
+        let value = msgpack_decode(&mut reader, 0)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+/// Deepest nesting decoded, so a hostile payload can't overflow the stack
+pub(crate) const MAX_DEPTH: usize = 128;
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or_else(|| anyhow!("payload ends early, at byte {}", self.bytes.len()))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    /// `n` bytes, for a length read from the payload
+    pub(crate) fn take_len(&mut self, n: u64) -> Result<&'a [u8]> {
+        self.take(usize::try_from(n)?)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Big-endian unsigned integer of `n` bytes
+    pub(crate) fn uint(&mut self, n: usize) -> Result<u64> {
+        Ok(self.take(n)?.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    pub(crate) fn finish(&self) -> Result<()> {
+        if self.pos != self.bytes.len() {
+            bail!("{} bytes left after the value", self.bytes.len() - self.pos);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn text(bytes: &[u8]) -> Result<JsonValue> {
+    Ok(JsonValue::String(String::from_utf8(bytes.to_vec()).context("text string is not UTF-8")?))
+}
+
+pub(crate) fn binary(bytes: &[u8]) -> JsonValue {
+    JsonValue::String(general_purpose::STANDARD.encode(bytes))
+}
+
+pub(crate) fn float(f: f64) -> Result<JsonValue> {
+    Number::from_f64(f).map(JsonValue::Number).ok_or_else(|| anyhow!("{} has no JSON representation", f))
+}
+
+/// Map keys as JSON object keys: strings as they are, anything else as its JSON text
+fn key(k: JsonValue) -> String {
+    match k {
+        JsonValue::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+fn cbor_head(major: u8, n: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend([major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(n.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_encode(v: &JsonValue, out: &mut Vec<u8>) {
+    match v {
+        JsonValue::Null => out.push(0xf6),
+        JsonValue::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        JsonValue::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => cbor_head(0, u, out),
+            (None, Some(i)) => cbor_head(1, (-1 - i) as u64, out),
+            _ => {
+                out.push(0xfb);
+                out.extend(n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        },
+        JsonValue::String(s) => {
+            cbor_head(3, s.len() as u64, out);
+            out.extend(s.as_bytes());
+        }
+        JsonValue::Array(items) => {
+            cbor_head(4, items.len() as u64, out);
+            items.iter().for_each(|i| cbor_encode(i, out));
+        }
+        JsonValue::Object(map) => {
+            cbor_head(5, map.len() as u64, out);
+            for (k, v) in map {
+                cbor_encode(&JsonValue::String(k.clone()), out);
+                cbor_encode(v, out);
+            }
+        }
+    }
+}
+
+/// Length or value following an initial byte; None for indefinite length
+fn cbor_arg(r: &mut Reader, info: u8) -> Result<Option<u64>> {
+    Ok(Some(match info {
+        0..=23 => info as u64,
+        24 => r.uint(1)?,
+        25 => r.uint(2)?,
+        26 => r.uint(4)?,
+        27 => r.uint(8)?,
+        31 => return Ok(None),
+        _ => bail!("invalid CBOR additional information {}", info),
+    }))
+}
+
+fn cbor_decode(r: &mut Reader, depth: usize) -> Result<JsonValue> {
+    if depth > MAX_DEPTH {
+        bail!("CBOR nested more than {} deep", MAX_DEPTH);
+    }
+    let initial = r.byte()?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    if major == 7 {
+        return match info {
+            20 => Ok(JsonValue::Bool(false)),
+            21 => Ok(JsonValue::Bool(true)),
+            22 | 23 => Ok(JsonValue::Null),
+            25 => float(half(r.uint(2)? as u16)),
+            26 => float(f32::from_bits(r.uint(4)? as u32) as f64),
+            27 => float(f64::from_bits(r.uint(8)?)),
+            31 => bail!("unexpected CBOR break"),
+            n => bail!("unsupported CBOR simple value {}", n),
+        };
+    }
+    let arg = cbor_arg(r, info)?;
+    match (major, arg) {
+        (0, Some(n)) => Ok(JsonValue::from(n)),
+        (1, Some(n)) => match i64::try_from(n) {
+            Ok(n) => Ok(JsonValue::from(-1 - n)),
+            Err(_) => float(-1.0 - n as f64),
+        },
+        (2 | 3, Some(n)) => {
+            let bytes = r.take_len(n)?;
+            if major == 2 { Ok(binary(bytes)) } else { text(bytes) }
+        }
+        (2 | 3, None) => {
+            // indefinite length: definite chunks of the same type up to the break
+            let mut bytes = Vec::new();
+            while r.peek() != Some(0xff) {
+                let chunk = r.byte()?;
+                if chunk >> 5 != major {
+                    bail!("CBOR string chunk of another type");
+                }
+                let n = cbor_arg(r, chunk & 0x1f)?.ok_or_else(|| anyhow!("nested indefinite CBOR string"))?;
+                bytes.extend(r.take_len(n)?);
+            }
+            r.byte()?;
+            if major == 2 { Ok(binary(&bytes)) } else { text(&bytes) }
+        }
+        (4, len) => {
+            let mut items = Vec::new();
+            while len.map_or(r.peek() != Some(0xff), |n| (items.len() as u64) < n) {
+                items.push(cbor_decode(r, depth + 1)?);
+            }
+            if len.is_none() {
+                r.byte()?;
+            }
+            Ok(JsonValue::Array(items))
+        }
+        (5, len) => {
+            let mut map = Map::new();
+            let mut pairs = 0u64;
+            while len.map_or(r.peek() != Some(0xff), |n| pairs < n) {
+                let k = key(cbor_decode(r, depth + 1)?);
+                map.insert(k, cbor_decode(r, depth + 1)?);
+                pairs += 1;
+            }
+            if len.is_none() {
+                r.byte()?;
+            }
+            Ok(JsonValue::Object(map))
+        }
+        // tag: keep the tagged value
+        (6, Some(_)) => cbor_decode(r, depth + 1),
+        _ => bail!("invalid CBOR initial byte {:#04x}", initial),
+    }
+}
+
+/// IEEE 754 half precision
+fn half(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let mant = (bits & 0x3ff) as f64;
+    sign * match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mant / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+fn msgpack_len(n: usize, fix: u8, fix_max: usize, markers: [u8; 3], out: &mut Vec<u8>) {
+    match n {
+        _ if n <= fix_max => out.push(fix | n as u8),
+        _ if n <= 0xff && markers[0] != 0 => out.extend([markers[0], n as u8]),
+        0..=0xffff => {
+            out.push(markers[1]);
+            out.extend((n as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(markers[2]);
+            out.extend((n as u32).to_be_bytes());
+        }
+    }
+}
+
+fn msgpack_encode(v: &JsonValue, out: &mut Vec<u8>) {
+    match v {
+        JsonValue::Null => out.push(0xc0),
+        JsonValue::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        JsonValue::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) if u < 0x80 => out.push(u as u8),
+            (Some(u), _) if u <= 0xff => out.extend([0xcc, u as u8]),
+            (Some(u), _) if u <= 0xffff => {
+                out.push(0xcd);
+                out.extend((u as u16).to_be_bytes());
+            }
+            (Some(u), _) if u <= 0xffff_ffff => {
+                out.push(0xce);
+                out.extend((u as u32).to_be_bytes());
+            }
+            (Some(u), _) => {
+                out.push(0xcf);
+                out.extend(u.to_be_bytes());
+            }
+            (None, Some(i)) if i >= -32 => out.push(i as i8 as u8),
+            (None, Some(i)) if i >= i8::MIN as i64 => out.extend([0xd0, i as i8 as u8]),
+            (None, Some(i)) if i >= i16::MIN as i64 => {
+                out.push(0xd1);
+                out.extend((i as i16).to_be_bytes());
+            }
+            (None, Some(i)) if i >= i32::MIN as i64 => {
+                out.push(0xd2);
+                out.extend((i as i32).to_be_bytes());
+            }
+            (None, Some(i)) => {
+                out.push(0xd3);
+                out.extend(i.to_be_bytes());
+            }
+            _ => {
+                out.push(0xcb);
+                out.extend(n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        },
+        JsonValue::String(s) => {
+            msgpack_len(s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb], out);
+            out.extend(s.as_bytes());
+        }
+        JsonValue::Array(items) => {
+            msgpack_len(items.len(), 0x90, 15, [0, 0xdc, 0xdd], out);
+            items.iter().for_each(|i| msgpack_encode(i, out));
+        }
+        JsonValue::Object(map) => {
+            msgpack_len(map.len(), 0x80, 15, [0, 0xde, 0xdf], out);
+            for (k, v) in map {
+                msgpack_encode(&JsonValue::String(k.clone()), out);
+                msgpack_encode(v, out);
+            }
+        }
+    }
+}
+
+fn msgpack_decode(r: &mut Reader, depth: usize) -> Result<JsonValue> {
+    if depth > MAX_DEPTH {
+        bail!("MessagePack nested more than {} deep", MAX_DEPTH);
+    }
+    let marker = r.byte()?;
+    let array = |r: &mut Reader, n: u64| -> Result<JsonValue> {
+        (0..n).map(|_| msgpack_decode(r, depth + 1)).collect::<Result<_>>().map(JsonValue::Array)
+    };
+    let map = |r: &mut Reader, n: u64| -> Result<JsonValue> {
+        let mut map = Map::new();
+        for _ in 0..n {
+            let k = key(msgpack_decode(r, depth + 1)?);
+            map.insert(k, msgpack_decode(r, depth + 1)?);
+        }
+        Ok(JsonValue::Object(map))
+    };
+    match marker {
+        0x00..=0x7f => Ok(JsonValue::from(marker)),
+        0x80..=0x8f => map(r, (marker & 0x0f) as u64),
+        0x90..=0x9f => array(r, (marker & 0x0f) as u64),
+        0xa0..=0xbf => text(r.take_len((marker & 0x1f) as u64)?),
+        0xc0 => Ok(JsonValue::Null),
+        0xc2 => Ok(JsonValue::Bool(false)),
+        0xc3 => Ok(JsonValue::Bool(true)),
+        0xc4..=0xc6 => {
+            let n = r.uint(1 << (marker - 0xc4))?;
+            Ok(binary(r.take_len(n)?))
+        }
+        0xca => float(f32::from_bits(r.uint(4)? as u32) as f64),
+        0xcb => float(f64::from_bits(r.uint(8)?)),
+        0xcc..=0xcf => Ok(JsonValue::from(r.uint(1 << (marker - 0xcc))?)),
+        0xd0..=0xd3 => {
+            let width = 1 << (marker - 0xd0);
+            let raw = r.uint(width)?;
+            // sign-extend from the encoded width
+            let shift = 64 - 8 * width as u32;
+            Ok(JsonValue::from(((raw << shift) as i64) >> shift))
+        }
+        0xd9..=0xdb => {
+            let n = r.uint(1 << (marker - 0xd9))?;
+            text(r.take_len(n)?)
+        }
+        0xdc | 0xdd => {
+            let n = r.uint(if marker == 0xdc { 2 } else { 4 })?;
+            array(r, n)
+        }
+        0xde | 0xdf => {
+            let n = r.uint(if marker == 0xde { 2 } else { 4 })?;
+            map(r, n)
+        }
+        0xe0..=0xff => Ok(JsonValue::from(marker as i8)),
+        0xc7..=0xc9 | 0xd4..=0xd8 => bail!("MessagePack extension types are not supported"),
+        _ => bail!("invalid MessagePack marker {:#04x}", marker),
+    }
+}
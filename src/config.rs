@@ -0,0 +1,530 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Environment variable overriding the config file location (default: ./bdd.toml)
+pub const CONFIG_ENV: &str = "BDD_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &str = "bdd.toml";
+
+/// Harness configuration, read from bdd.toml. Every section is optional.
+///
+/// ```toml
+/// [scenario]
+/// timeout = "60s"
+///
+/// [matching]
+/// ignore_fields = ["timestamp", "header.seq_no"]
+/// normalize_strings = true
+/// skip_json = true
+///
+/// [sequence_fields]
+/// Telemetry = "seq"
+///
+/// [hosts.dut]
+/// address = "10.0.0.5"
+/// user = "root"
+///
+/// [can]
+/// interface = "can0"
+/// ids = { Telemetry = 0x101 }
+///
+/// [someip]
+/// ids = { Telemetry = { service = 0x1234, method = 0x8001 } }
+///
+/// [proto]
+/// files = ["../api/proto/telemetry.proto"]
+/// includes = ["../api/proto"]
+///
+/// [buffer]
+/// capacity = 10000
+/// policy = "drop-oldest"
+///
+/// [network]
+/// bind_address = "eth1"
+/// resolve = "ipv6"
+///
+/// [send]
+/// validate = true
+///
+/// [codecs]
+/// Telemetry = "cbor"
+/// Orders = "avro"
+///
+/// [avro]
+/// registry = "http://schema-registry:8081"
+///
+/// [framing]
+/// Telemetry = { crc = "crc32", byte_order = "little" }
+///
+/// [layouts.LegacyStatus]
+/// byte_order = "little"
+/// fields = [{ name = "version", bits = 4 }, { name = "mode", bits = 4 }, { name = "seq", type = "u16" }]
+///
+/// [readiness]
+/// check = "probe"
+/// probe = "PingRequest"
+/// reply = "PongReply"
+///
+/// [endpoints]
+/// sim = "127.0.0.1:{auto_port}"
+///
+/// [secrets]
+/// mqtt_password = { env = "MQTT_PASSWORD" }
+/// curve_key = { file = "/run/secrets/curve.key" }
+///
+/// [tls.lab]
+/// ca = "certs/lab-ca.pem"
+/// insecure_skip_verify = false
+///
+/// [triage]
+/// dir = "target/triage"
+/// logs = ["/var/log/sut/gateway.log"]
+///
+/// [[translations]]
+/// pattern = "^j'envoie le message (\\w+)$"
+/// step = "I send message ${1}"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub scenario: ScenarioConfig,
+    pub matching: MatchingConfig,
+    /// Sequence-number field per topic, used by the lost/duplicated check
+    pub sequence_fields: HashMap<String, String>,
+    /// Remote host profiles by name, for the ssh steps
+    pub hosts: HashMap<String, HostConfig>,
+    /// Devices by id, for `Given device "<id>" exists`
+    pub devices: HashMap<String, DeviceConfig>,
+    pub can: CanConfig,
+    pub someip: SomeIpConfig,
+    /// Localized step texts mapped onto the English step definitions, tried in order
+    pub translations: Vec<Translation>,
+    pub proto: ProtoConfig,
+    /// Further descriptor sets by name, for connections speaking another schema version
+    pub schemas: HashMap<String, SchemaConfig>,
+    pub buffer: BufferConfig,
+    pub triage: TriageConfig,
+    pub network: NetworkConfig,
+    pub readiness: ReadinessConfig,
+    pub send: SendConfig,
+    pub operator: OperatorConfig,
+    /// Payload codec by topic (`json`, `cbor`, `msgpack`, `avro`, `layout` or a registered one); protobuf when unset
+    pub codecs: HashMap<String, String>,
+    pub avro: AvroConfig,
+    /// Checksum appended to each frame, by topic
+    pub framing: HashMap<String, FramingConfig>,
+    /// Packed-struct layouts by topic, for topics using the `layout` codec
+    pub layouts: HashMap<String, LayoutConfig>,
+    /// Endpoint templates by name, given to scenarios as variables; `{auto_port}` gets a free port
+    pub endpoints: HashMap<String, String>,
+    /// Credentials by name, referenced as `${secret:<name>}` and redacted from all output
+    pub secrets: HashMap<String, SecretSource>,
+    /// TLS settings by profile name for https connections; `default` applies when a step names none
+    pub tls: HashMap<String, TlsProfile>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsProfile {
+    /// CA bundle (PEM) the server certificate must chain to, instead of the system CAs
+    pub ca: Option<PathBuf>,
+    /// Client certificate (PEM) for servers requiring mutual TLS
+    pub cert: Option<PathBuf>,
+    /// Private key of `cert`
+    pub key: Option<PathBuf>,
+    /// Accept any server certificate; for lab rigs with self-signed certificates only
+    pub insecure_skip_verify: bool,
+}
+
+/// Where a secret's value comes from
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum SecretSource {
+    /// Environment variable
+    Env(String),
+    /// File holding just the value, e.g. a mounted CI secret
+    File(PathBuf),
+    /// Shell command printing the value, e.g. `pass show broker/curve`
+    Command(String),
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SendConfig {
+    /// Check outgoing messages against the descriptor and validation rules, failing the send step
+    pub validate: bool,
+    /// Fail sends setting fields marked deprecated, instead of warning
+    pub deny_deprecated: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperatorConfig {
+    /// Address operator actions can also be confirmed on with `POST /confirm`, for runs without a console
+    pub listen: Option<String>,
+}
+
+/// Schemas for the `avro` codec
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvroConfig {
+    /// Confluent Schema Registry URL; payloads then carry the wire-format header
+    pub registry: Option<String>,
+    /// TLS profile for an https registry
+    pub tls: Option<String>,
+    /// Schema file (.avsc) by topic, used when there is no registry
+    pub schemas: HashMap<String, PathBuf>,
+}
+
+/// CRC a topic's frames end with: computed and appended on send, verified and stripped on receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FramingConfig {
+    pub crc: Crc,
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Crc {
+    /// CRC-32 (IEEE 802.3, zlib)
+    Crc32,
+    /// CRC-32C (Castagnoli)
+    Crc32c,
+    /// CRC-16/CCITT-FALSE
+    Crc16Ccitt,
+    /// CRC-16/MODBUS
+    Crc16Modbus,
+    /// CRC-8 (polynomial 0x07)
+    Crc8,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+/// Fields of a packed struct, in wire order
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Byte order of multi-byte fields that don't set their own
+    pub byte_order: ByteOrder,
+    pub fields: Vec<LayoutField>,
+}
+
+/// A typed field, or with `bits` an unsigned bitfield packed most significant bit first
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Option<FieldType>,
+    pub bits: Option<u32>,
+    /// Byte count of `bytes` and `pad` fields
+    pub len: Option<usize>,
+    pub byte_order: Option<ByteOrder>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// One byte, zero or not
+    Bool,
+    /// Raw bytes, base64 in JSON
+    Bytes,
+    /// Reserved bytes, zero on send and left out of the JSON
+    Pad,
+}
+
+/// How connecting makes sure the connection carries messages before the scenario goes on
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadinessConfig {
+    pub check: Readiness,
+    /// Longest wait before connecting fails; 5s when unset
+    #[serde(deserialize_with = "opt_duration")]
+    pub timeout: Option<Duration>,
+    /// Message sent by the `probe` check
+    pub probe: Option<String>,
+    /// Message the SUT answers `probe` with
+    pub reply: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Readiness {
+    /// Wait a fixed 200 ms and hope subscriptions have propagated
+    #[default]
+    Delay,
+    /// Wait until both ZMQ sockets finished their handshake with the SUT
+    Monitor,
+    /// As `monitor`, and until the SUT subscribed to our messages
+    Subscription,
+    /// Send `probe` until the SUT answers with `reply`
+    Probe,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Local IP address or interface name (e.g. "eth1") ZMQ and SOME/IP traffic goes out from
+    pub bind_address: Option<String>,
+    /// Address families SUT hostnames may resolve to
+    pub resolve: IpFamily,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    /// Whatever the resolver returns first
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    pub fn allows(self, ip: std::net::IpAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Ipv4 => ip.is_ipv4(),
+            Self::Ipv6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Any => "IP",
+            Self::Ipv4 => "IPv4",
+            Self::Ipv6 => "IPv6",
+        })
+    }
+}
+
+/// Triage bundles packed when a run has failures
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TriageConfig {
+    /// Where bundles are written; no bundles when unset
+    pub dir: Option<PathBuf>,
+    /// SUT log files copied into the bundle
+    pub logs: Vec<PathBuf>,
+}
+
+/// Limits of each broker's receive buffer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BufferConfig {
+    /// Messages kept; unbounded when unset
+    pub capacity: Option<usize>,
+    /// What happens when a message arrives at a full buffer
+    pub policy: EvictionPolicy,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// Evict the oldest buffered message to make room
+    #[default]
+    DropOldest,
+    /// Discard the arriving message
+    DropNewest,
+    /// Discard the arriving message and fail every later expectation
+    Fail,
+}
+
+/// Message types loaded at test start instead of the descriptor set built into the crate
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtoConfig {
+    /// Encoded FileDescriptorSet, e.g. from protoc --include_imports --descriptor_set_out
+    pub descriptor: Option<PathBuf>,
+    /// .proto sources to compile; take precedence over `descriptor`
+    pub files: Vec<PathBuf>,
+    /// Import paths; the directory of each file when empty
+    pub includes: Vec<PathBuf>,
+    /// Hash the embedded descriptor set must have, e.g. "fnv1a64:1f0e3dad99908345"
+    pub expected_hash: Option<String>,
+    /// Fail instead of warning when the embedded descriptor set is stale
+    pub fail_on_stale: bool,
+    /// Check `descriptor` or `files` this often and reload the message types when they change
+    #[serde(deserialize_with = "opt_duration")]
+    pub watch: Option<Duration>,
+}
+
+/// A descriptor set besides `[proto]`, bound to a connection with `with schema "<name>"`
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaConfig {
+    /// Encoded FileDescriptorSet
+    pub descriptor: Option<PathBuf>,
+    /// .proto sources to compile; take precedence over `descriptor`
+    pub files: Vec<PathBuf>,
+    /// Import paths; the directory of each file when empty
+    pub includes: Vec<PathBuf>,
+    /// Check the files this often and reload the schema when they change
+    #[serde(deserialize_with = "opt_duration")]
+    pub watch: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Translation {
+    /// Regex matched against the whole step text
+    pub pattern: String,
+    /// English step text; `${1}`, `${2}`... insert the pattern's capture groups
+    pub step: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SomeIpConfig {
+    /// Local UDP address to send from and receive on
+    pub bind: String,
+    /// SUT port used when the step gives a bare host
+    pub port: u16,
+    pub client_id: u16,
+    pub interface_version: u8,
+    /// Service and method id per topic
+    pub ids: HashMap<String, SomeIpId>,
+}
+
+impl Default for SomeIpConfig {
+    fn default() -> Self {
+        Self { bind: "0.0.0.0:30490".to_string(), port: 30490, client_id: 1, interface_version: 1, ids: HashMap::new() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SomeIpId {
+    pub service: u16,
+    pub method: u16,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanConfig {
+    /// SocketCAN interface, e.g. "can0" or "vcan0"
+    pub interface: Option<String>,
+    /// CAN identifier per topic
+    pub ids: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostConfig {
+    pub address: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Private key passed to ssh -i
+    pub identity: Option<PathBuf>,
+    /// Extra ssh -o options, e.g. "StrictHostKeyChecking=no"
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Put in front of the message name to make the topic the device sends on, e.g. "tlm/pump1/"
+    pub topic_prefix: Option<String>,
+    /// Fields identifying the device, filled into every message it sends that has them
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchingConfig {
+    /// Fields never compared: a bare name applies at any depth, a dotted path only at that path
+    pub ignore_fields: Vec<String>,
+    /// Trim and collapse whitespace in strings before comparing them
+    pub normalize_strings: bool,
+    /// Compare scalar and enum fields of expected messages without converting candidates to JSON
+    pub skip_json: bool,
+    /// Named expectation fragments, used in expected bodies as `"$match:<name>"`
+    pub macros: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioConfig {
+    /// Default per-scenario deadline; a scenario tag @timeout:<duration> overrides it
+    #[serde(deserialize_with = "opt_duration")]
+    pub timeout: Option<Duration>,
+    /// Time all of a scenario's expectations may wait in total; a tag @expect_budget:<duration> overrides it
+    #[serde(deserialize_with = "opt_duration")]
+    pub expect_budget: Option<Duration>,
+}
+
+impl Config {
+    /// Load config from `path`; a missing file yields the defaults
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parse {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Config file path from BDD_CONFIG, falling back to ./bdd.toml
+    pub fn path() -> PathBuf {
+        std::env::var_os(CONFIG_ENV).map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Process-wide config, loaded once on first use. Panics on an invalid config file,
+    /// since nothing sensible can run with it.
+    pub fn global() -> &'static Config {
+        static CONFIG: OnceLock<Config> = OnceLock::new();
+        CONFIG.get_or_init(|| Self::load(&Self::path()).unwrap_or_else(|e| panic!("invalid config: {:#}", e)))
+    }
+}
+
+/// Parse durations written as "500ms", "2s", "1m 30s", "5 s" or "2.5s"
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let text = s.trim();
+    if let Ok(d) = humantime::parse_duration(text) {
+        return Ok(d);
+    }
+    // humantime wants integers directly followed by their unit
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (num, unit) = (&text[..split], text[split..].trim());
+    let scale = match unit {
+        "ms" => 1e-3,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+        "h" | "hour" | "hours" => 3600.0,
+        _ => anyhow::bail!("invalid duration '{}'", s),
+    };
+    let value: f64 = num.parse().with_context(|| format!("invalid duration '{}'", s))?;
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+pub(crate) fn opt_duration<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Option<Duration>, D::Error> {
+    let s: Option<String> = Option::deserialize(d)?;
+    s.map(|s| parse_duration(&s).map_err(serde::de::Error::custom)).transpose()
+}
@@ -0,0 +1,82 @@
+//! Skeleton conformance features: one scenario per message type, sending it with sample data
+//! and expecting a reply, to start covering a new proto package before writing real scenarios.
+
+use anyhow::Result;
+use prost_reflect::{Kind, MessageDescriptor};
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::proto_dyn::ProtoDyn;
+
+/// A body for `desc` with every field the send steps can fill set to a sample value: the first
+/// field of each oneof, and no repeated or map fields. Messages nested in themselves are left out.
+pub fn sample(desc: &MessageDescriptor) -> JsonValue {
+    sample_within(desc, &mut Vec::new())
+}
+
+fn sample_within(desc: &MessageDescriptor, path: &mut Vec<String>) -> JsonValue {
+    path.push(desc.full_name().to_string());
+    let mut body = Map::new();
+    for field in desc.fields() {
+        if field.is_list() || field.is_map() {
+            continue;
+        }
+        if let Some(oneof) = field.containing_oneof() {
+            if oneof.fields().next().is_some_and(|first| first.number() != field.number()) {
+                continue;
+            }
+        }
+        let value = match field.kind() {
+            Kind::Bool => json!(true),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => json!(-1),
+            Kind::Uint32 | Kind::Fixed32 | Kind::Uint64 | Kind::Fixed64 => json!(1),
+            Kind::Float | Kind::Double => json!(1.5),
+            Kind::String => json!(field.name()),
+            // 0x01 0x02
+            Kind::Bytes => json!("AQI="),
+            Kind::Enum(e) => {
+                let value = e.values().find(|v| v.number() != 0).or_else(|| e.values().next());
+                json!(value.map(|v| v.name().to_string()).unwrap_or_default())
+            }
+            Kind::Message(m) if path.iter().any(|p| p == m.full_name()) => continue,
+            Kind::Message(m) => sample_within(&m, path),
+        };
+        body.insert(field.name().to_string(), value);
+    }
+    path.pop();
+    JsonValue::Object(body)
+}
+
+/// Message types a conformance feature covers: the named ones, or every message except map entries
+/// and `google.protobuf` types
+pub fn message_types(proto: &ProtoDyn, names: &[String]) -> Result<Vec<MessageDescriptor>> {
+    if !names.is_empty() {
+        return names.iter().map(|n| proto.message_desc(n)).collect();
+    }
+    let all = proto.message_names().iter().map(|n| proto.message_desc(n)).collect::<Result<Vec<_>>>()?;
+    Ok(all.into_iter().filter(|m| !m.is_map_entry() && m.package_name() != "google.protobuf").collect())
+}
+
+/// Feature text with a scenario per message in `messages`, each sending the message with
+/// [`sample`] data and expecting one of `replies`; with none, a placeholder `Reply` to rename
+pub fn feature(proto: &ProtoDyn, messages: &[MessageDescriptor], replies: &[String]) -> String {
+    let expect = match replies {
+        [] => "Then I expect message Reply".to_string(),
+        [reply] => format!("Then I expect message {}", reply),
+        _ => format!("Then I expect one of: {}", replies.join(" | ")),
+    };
+    let mut out = String::from("# Generated by bdd-conformance; adjust the bodies and expected replies\nFeature: Protocol conformance\n");
+    for desc in messages {
+        // short names unless another package's message already took it
+        let name = match proto.message_desc(desc.name()) {
+            Ok(found) if found.full_name() == desc.full_name() => desc.name(),
+            _ => desc.full_name(),
+        };
+        let body = serde_json::to_string_pretty(&sample(desc)).unwrap_or_default();
+        out.push_str(&format!("\n  Scenario: {} is answered\n    When I send message {}\n      \"\"\"\n", desc.full_name(), name));
+        for line in body.lines() {
+            out.push_str(&format!("      {}\n", line));
+        }
+        out.push_str(&format!("      \"\"\"\n    {}\n", expect));
+    }
+    out
+}
@@ -0,0 +1,127 @@
+//! Which message types and fields the run sent and asserted, for a report of the parts of the
+//! protocol no scenario touches. Fields count once they appear in a sent body or an expectation;
+//! fields of nested messages count for the nested type.
+
+use prost_reflect::{Kind, MessageDescriptor};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+/// Sent, asserted and matched fields of the brokers so far, drained by the report at the end of the run
+static RUN: Mutex<Coverage> = Mutex::new(Coverage { sent: BTreeMap::new(), asserted: BTreeMap::new(), matched: BTreeMap::new() });
+
+/// Fields seen by message type full name; a type is in the map once touched at all
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    pub sent: BTreeMap<String, BTreeSet<String>>,
+    pub asserted: BTreeMap<String, BTreeSet<String>>,
+    /// Fields of expectations a message matched, a subset of `asserted`
+    pub matched: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Record a `desc` message sent with `body` (called by the broker)
+pub fn record_sent(desc: &MessageDescriptor, body: &JsonValue) {
+    if let Ok(mut run) = RUN.lock() {
+        mark(&mut run.sent, desc, body);
+    }
+}
+
+/// Record an expectation of a `desc` message matching `expected` (called by the broker)
+pub fn record_asserted(desc: &MessageDescriptor, expected: &JsonValue) {
+    if let Ok(mut run) = RUN.lock() {
+        mark(&mut run.asserted, desc, expected);
+    }
+}
+
+/// Record that a `desc` message matched `expected` (called by the broker)
+pub fn record_matched(desc: &MessageDescriptor, expected: &JsonValue) {
+    if let Ok(mut run) = RUN.lock() {
+        mark(&mut run.matched, desc, expected);
+    }
+}
+
+/// The run's coverage so far, leaving it empty
+pub fn take() -> Coverage {
+    RUN.lock().map(|mut run| std::mem::take(&mut *run)).unwrap_or_default()
+}
+
+fn mark(seen: &mut BTreeMap<String, BTreeSet<String>>, desc: &MessageDescriptor, body: &JsonValue) {
+    seen.entry(desc.full_name().to_string()).or_default();
+    let JsonValue::Object(members) = body else { return };
+    for (name, value) in members {
+        // matcher operators and anything else that isn't a field
+        let Some(field) = desc.get_field_by_name(name) else { continue };
+        seen.entry(desc.full_name().to_string()).or_default().insert(field.name().to_string());
+        if let Kind::Message(nested) = field.kind() {
+            match value {
+                JsonValue::Array(items) => items.iter().for_each(|item| mark(seen, &nested, item)),
+                value => mark(seen, &nested, value),
+            }
+        }
+    }
+}
+
+impl Coverage {
+    pub fn is_empty(&self) -> bool {
+        self.sent.is_empty() && self.asserted.is_empty()
+    }
+
+    /// Blind spots among `types`: types never sent, types never asserted, and fields of asserted
+    /// types that no expectation named
+    pub fn gaps(&self, types: &[MessageDescriptor]) -> Vec<String> {
+        let mut gaps = Vec::new();
+        for desc in types {
+            let name = desc.full_name();
+            if !self.sent.contains_key(name) {
+                gaps.push(format!("{} never sent", name));
+            }
+            match self.asserted.get(name) {
+                None => gaps.push(format!("{} never asserted", name)),
+                Some(fields) => {
+                    for field in desc.fields().filter(|f| !fields.contains(f.name())) {
+                        gaps.push(format!("{}.{} never asserted", name, field.name()));
+                    }
+                }
+            }
+        }
+        gaps
+    }
+
+    /// Per asserted type among `types`, the fields some matching message was held to, e.g.
+    /// `company.project.v1.Status: matched state`
+    pub fn matched_fields(&self, types: &[MessageDescriptor]) -> Vec<String> {
+        types
+            .iter()
+            .filter(|d| self.asserted.contains_key(d.full_name()))
+            .map(|d| {
+                let fields: Vec<String> = match self.matched.get(d.full_name()) {
+                    Some(seen) => d.fields().filter(|f| seen.contains(f.name())).map(|f| f.name().to_string()).collect(),
+                    None => Vec::new(),
+                };
+                match fields.as_slice() {
+                    [] => format!("{}: no field matched", d.full_name()),
+                    _ => format!("{}: matched {}", d.full_name(), fields.join(", ")),
+                }
+            })
+            .collect()
+    }
+
+    /// `<n> of <m> message types sent, ...` over `types`
+    pub fn summary(&self, types: &[MessageDescriptor]) -> String {
+        let sent = types.iter().filter(|d| self.sent.contains_key(d.full_name())).count();
+        let asserted = types.iter().filter(|d| self.asserted.contains_key(d.full_name())).count();
+        let fields: usize = types.iter().map(|d| d.fields().len()).sum();
+        let fields_asserted: usize = types
+            .iter()
+            .map(|d| self.asserted.get(d.full_name()).map_or(0, |seen| d.fields().filter(|f| seen.contains(f.name())).count()))
+            .sum();
+        format!(
+            "{} of {} message types sent, {} asserted; {} of {} fields asserted",
+            sent,
+            types.len(),
+            asserted,
+            fields_asserted,
+            fields
+        )
+    }
+}
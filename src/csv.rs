@@ -0,0 +1,145 @@
+//! Sensor traces recorded as CSV, replayed as messages by `I send <message> messages from CSV
+//! "<path>" mapping columns to fields`. Quoting follows RFC 4180: fields may be quoted, with `""`
+//! for a quote inside.
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost_reflect::Kind;
+use serde_json::{json, Value as JsonValue};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::broker::Broker;
+use crate::jsonpath;
+use crate::sequence::{Entry, Sequence};
+
+/// A CSV file: the header row, and the rows after it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("CSV {}", path.display()))
+    }
+
+    /// Comma-separated records, the first one naming the columns. Blank lines are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut records = records(text)?.into_iter();
+        let header = records.next().ok_or_else(|| anyhow!("no header row"))?;
+        let mut rows = Vec::new();
+        for (i, row) in records.enumerate() {
+            if row.len() != header.len() {
+                // row numbers as in the file, header being row 1
+                bail!("row {} has {} fields, the header {}", i + 2, row.len(), header.len());
+            }
+            rows.push(row);
+        }
+        Ok(Self { header, rows })
+    }
+
+    fn column(&self, name: &str) -> Result<usize> {
+        self.header.iter().position(|h| h == name).ok_or_else(|| anyhow!("no column {} (columns: {})", name, self.header.join(", ")))
+    }
+}
+
+fn records(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                let record = std::mem::take(&mut record);
+                if record != [""] {
+                    records.push(record);
+                }
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quoted field");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// One message per row, with each mapped column's value at its field path (`speed`,
+/// `engine.rpm`). Values are read as the field's type; an empty cell leaves the field out.
+pub fn messages(broker: &Broker, message: &str, table: &Table, mapping: &[(String, String)]) -> Result<Vec<JsonValue>> {
+    let mut columns = Vec::new();
+    for (column, field) in mapping {
+        let path = if field.starts_with('$') { field.clone() } else { format!("$.{}", field) };
+        let (kind, whole) = broker.field_kind(message, &path)?;
+        if whole {
+            bail!("{} of {} is a repeated or map field; map columns to single values", field, message);
+        }
+        columns.push((table.column(column)?, column, path, kind));
+    }
+    let mut messages = Vec::with_capacity(table.rows.len());
+    for (i, row) in table.rows.iter().enumerate() {
+        let mut body = json!({});
+        for (index, column, path, kind) in &columns {
+            let cell = row[*index].trim();
+            if cell.is_empty() {
+                continue;
+            }
+            let value = cell_value(kind, cell).with_context(|| format!("row {}, column {}", i + 2, column))?;
+            jsonpath::set(&mut body, path, value)?;
+        }
+        messages.push(body);
+    }
+    Ok(messages)
+}
+
+/// A cell's text as JSON for a field of `kind`
+fn cell_value(kind: &Kind, cell: &str) -> Result<JsonValue> {
+    let invalid = |what: &str| anyhow!("{:?} is not {}", cell, what);
+    Ok(match kind {
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            json!(cell.parse::<i64>().map_err(|_| invalid("an integer"))?)
+        }
+        Kind::Uint32 | Kind::Fixed32 | Kind::Uint64 | Kind::Fixed64 => json!(cell.parse::<u64>().map_err(|_| invalid("an unsigned integer"))?),
+        Kind::Float | Kind::Double => json!(cell.parse::<f64>().map_err(|_| invalid("a number"))?),
+        Kind::Bool => match cell.to_ascii_lowercase().as_str() {
+            "true" | "1" => json!(true),
+            "false" | "0" => json!(false),
+            _ => return Err(invalid("a bool (true, false, 1 or 0)")),
+        },
+        Kind::Enum(_) => cell.parse::<i64>().map_or_else(|_| json!(cell), |n| json!(n)),
+        Kind::String | Kind::Bytes => json!(cell),
+        Kind::Message(m) => bail!("{} is a message; map columns to its fields", m.name()),
+    })
+}
+
+/// The rows as a sequence: back to back, or `rate` messages per second
+pub fn sequence(message: &str, messages: Vec<JsonValue>, rate: Option<f64>) -> Result<Sequence> {
+    let interval = match rate {
+        Some(rate) if !(rate > 0.0 && rate.is_finite()) => bail!("rate must be a positive number of messages per second, got {}", rate),
+        Some(rate) => Some(Duration::from_secs_f64(1.0 / rate)),
+        None => None,
+    };
+    let entries = messages
+        .into_iter()
+        .enumerate()
+        .map(|(i, body)| Entry { delay: interval.filter(|_| i > 0), message: message.to_string(), body: Some(body) })
+        .collect();
+    Ok(Sequence { entries })
+}
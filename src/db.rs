@@ -0,0 +1,77 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value as JsonValue;
+use std::process::Command;
+
+use crate::matchers::MatchOptions;
+use crate::proto_dyn::json_partial_match_with;
+
+/// Database the SUT persists to, queried through its command-line client
+/// (`sqlite3` for `sqlite://<path>`, `psql` for `postgres://...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Database {
+    Sqlite(String),
+    Postgres(String),
+}
+
+impl Database {
+    pub fn parse(url: &str) -> Result<Self> {
+        if let Some(path) = url.strip_prefix("sqlite://") {
+            return Ok(Self::Sqlite(path.to_string()));
+        }
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(Self::Postgres(url.to_string()));
+        }
+        bail!("unsupported database URL {} (use sqlite://<path> or postgres://...)", url)
+    }
+
+    /// Run `sql` and return the rows as JSON objects keyed by column name
+    pub fn query(&self, sql: &str) -> Result<Vec<JsonValue>> {
+        let sql = sql.trim().trim_end_matches(';');
+        let mut cmd = match self {
+            Self::Sqlite(path) => {
+                let mut c = Command::new("sqlite3");
+                c.args(["-json", "-bail", path, sql]);
+                c
+            }
+            Self::Postgres(url) => {
+                let mut c = Command::new("psql");
+                let wrapped = format!("SELECT coalesce(json_agg(t), '[]') FROM ({}) t", sql);
+                c.args([url.as_str(), "-X", "-A", "-t", "-q", "-v", "ON_ERROR_STOP=1", "-c", &wrapped]);
+                c
+            }
+        };
+        let out = cmd.output().with_context(|| format!("run {:?}", cmd.get_program()))?;
+        if !out.status.success() {
+            bail!("query failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+        }
+        let text = String::from_utf8_lossy(&out.stdout);
+        // sqlite3 prints nothing at all for an empty result
+        if text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        match serde_json::from_str(text.trim()).with_context(|| format!("parse query output {}", text.trim()))? {
+            JsonValue::Array(rows) => Ok(rows),
+            other => Err(anyhow!("query output is not a row list: {}", other)),
+        }
+    }
+}
+
+/// Match every expected row against a distinct actual row, in any order.
+/// Returns the first expected row without a match.
+pub fn unmatched_row<'a>(expected: &'a [JsonValue], rows: &[JsonValue], opts: MatchOptions) -> Result<Option<&'a JsonValue>> {
+    let mut used = vec![false; rows.len()];
+    for want in expected {
+        let mut found = false;
+        for (i, row) in rows.iter().enumerate() {
+            if !used[i] && json_partial_match_with(want, row, opts)? {
+                used[i] = true;
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            return Ok(Some(want));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,157 @@
+//! Pausing the run on a scenario's world: `--pause-on-failure` opens a prompt when a scenario
+//! fails, `--step` when each scenario starts and before it is torn down. The prompt takes the REPL
+//! commands against the scenario's connections, with `${name}` variables substituted, and can run
+//! steps by their text.
+
+use anyhow::{bail, Result};
+use cucumber::gherkin::{Step, StepType};
+use futures::FutureExt as _;
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::operator;
+use crate::repl::{self, Command};
+use crate::steps::{find_step, MyWorld};
+
+static PAUSE_ON_FAILURE: AtomicBool = AtomicBool::new(false);
+static STEP: AtomicBool = AtomicBool::new(false);
+
+pub const HELP: &str = "\
+use [connection]                   send and expect on a connection opened by the scenario (none: the default)
+connections                        the scenario's connections
+vars                               the scenario's variables
+step <text>                        run a step, e.g. step I send message PingRequest
+continue                           tear the scenario down and go on with the run
+abort                              stop the run here";
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Open a prompt on a failed scenario's connections and variables before it is torn down
+    #[arg(long, global = true)]
+    pub pause_on_failure: bool,
+    /// Open the prompt when every scenario starts and again before it is torn down
+    #[arg(long, global = true)]
+    pub step: bool,
+}
+
+impl Cli {
+    pub fn init(&self) {
+        PAUSE_ON_FAILURE.store(self.pause_on_failure, Ordering::Relaxed);
+        STEP.store(self.step, Ordering::Relaxed);
+    }
+}
+
+/// Whether to pause when a scenario fails
+pub fn pause_on_failure() -> bool {
+    PAUSE_ON_FAILURE.load(Ordering::Relaxed) || stepping()
+}
+
+/// Whether to pause at the start and end of every scenario
+pub fn stepping() -> bool {
+    STEP.load(Ordering::Relaxed)
+}
+
+/// One command at the pause prompt
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Repl(Command),
+    Use(Option<String>),
+    Connections,
+    Vars,
+    Step(String),
+    Continue,
+    Abort,
+}
+
+impl Action {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        Ok(match word {
+            "use" => Self::Use(Some(rest.to_string()).filter(|r| !r.is_empty())),
+            "connections" => Self::Connections,
+            "vars" => Self::Vars,
+            "step" if rest.is_empty() => bail!("step <text>: missing step text"),
+            "step" => Self::Step(rest.to_string()),
+            "continue" | "c" => Self::Continue,
+            "abort" => Self::Abort,
+            _ => match Command::parse(line)? {
+                Command::Quit => Self::Abort,
+                cmd => Self::Repl(cmd),
+            },
+        })
+    }
+}
+
+/// Prompt on stdin until `continue`, printing `why` first. `abort` (or end of input) ends the
+/// process with status 1.
+pub async fn pause(world: &mut MyWorld, why: &str) {
+    println!("paused: {}", why);
+    println!("type help for commands, continue to go on, abort to stop the run");
+    let mut connection: Option<String> = None;
+    loop {
+        print!("{}> ", connection.as_deref().unwrap_or("bdd"));
+        let _ = std::io::stdout().flush();
+        let Some(line) = operator::read_line() else { std::process::exit(1) };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let action = world.expand(&line).and_then(|line| Action::parse(&line));
+        let out = match action {
+            Ok(Action::Continue) => return,
+            Ok(Action::Abort) => std::process::exit(1),
+            Ok(Action::Repl(Command::Help)) => Ok(format!("{}\n{}", repl::HELP, HELP)),
+            Ok(Action::Repl(cmd)) => world.connection(connection.as_deref()).and_then(|broker| cmd.run(broker)),
+            Ok(Action::Use(name)) => match name {
+                Some(n) if !world.connections.contains_key(&n) => Err(anyhow::anyhow!("no connection \"{}\"", n)),
+                name => {
+                    connection = name;
+                    Ok(String::new())
+                }
+            },
+            Ok(Action::Connections) => {
+                let mut names: Vec<&String> = world.connections.keys().collect();
+                names.sort();
+                Ok(names.iter().map(|n| n.as_str()).collect::<Vec<_>>().join("\n"))
+            }
+            Ok(Action::Vars) => {
+                let mut vars: Vec<(&String, &String)> = world.vars.iter().collect();
+                vars.sort();
+                Ok(vars.iter().map(|(k, v)| format!("{} = {}", k, crate::secrets::redact(v))).collect::<Vec<_>>().join("\n"))
+            }
+            Ok(Action::Step(text)) => run_step(world, &text).await.map(|()| "passed".to_string()),
+            Err(e) => Err(e),
+        };
+        match out {
+            Ok(out) if out.is_empty() => {}
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+}
+
+/// Run the step matching `text` on `world`, turning its panic into an error
+async fn run_step(world: &mut MyWorld, text: &str) -> Result<()> {
+    let parent = Step {
+        keyword: String::new(),
+        ty: StepType::When,
+        value: text.to_string(),
+        docstring: None,
+        table: None,
+        span: Default::default(),
+        position: Default::default(),
+    };
+    let (step_fn, context) = find_step(text, &parent)?;
+    if let Err(panic) = AssertUnwindSafe(step_fn(world, context)).catch_unwind().await {
+        let error = panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "step panicked".to_string());
+        bail!("{}", error);
+    }
+    Ok(())
+}
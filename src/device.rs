@@ -0,0 +1,51 @@
+//! Devices a scenario speaks for: each sends on its own topics and fills the fields identifying it
+//! into what it sends, so steps only give what the scenario is about.
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::config::{Config, DeviceConfig};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Device {
+    pub id: String,
+    /// Put in front of the message name to make the topic; None sends on the message's own topic
+    pub topic_prefix: Option<String>,
+    /// Filled into messages that have these fields and don't set them
+    pub fields: Map<String, JsonValue>,
+}
+
+impl Device {
+    pub fn new(id: &str, config: &DeviceConfig) -> Self {
+        Self { id: id.to_string(), topic_prefix: config.topic_prefix.clone(), fields: config.fields.clone() }
+    }
+
+    /// The device configured under `[devices.<id>]`
+    pub fn configured(id: &str) -> Result<Self> {
+        let devices = &Config::global().devices;
+        let config = devices.get(id).ok_or_else(|| {
+            let mut known: Vec<&String> = devices.keys().collect();
+            known.sort();
+            anyhow!("unknown device \"{}\"; add it under [devices.{}] in bdd.toml (configured: {:?})", id, id, known)
+        })?;
+        Ok(Self::new(id, config))
+    }
+
+    /// Topic the device sends `message_name` on
+    pub fn topic(&self, message_name: &str) -> String {
+        format!("{}{}", self.topic_prefix.as_deref().unwrap_or_default(), message_name)
+    }
+
+    /// `body` with the device's fields added where the message has them (`has_field`) and the body
+    /// doesn't set them
+    pub fn fill(&self, body: &JsonValue, has_field: impl Fn(&str) -> bool) -> Result<JsonValue> {
+        let JsonValue::Object(members) = body else { return Err(anyhow!("device {}: message body must be a JSON object, got {}", self.id, body)) };
+        let mut filled = members.clone();
+        for (name, value) in &self.fields {
+            if !filled.contains_key(name) && has_field(name) {
+                filled.insert(name.clone(), value.clone());
+            }
+        }
+        Ok(JsonValue::Object(filled))
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use cucumber::{event, gherkin, parser, writer, Event, World, Writer};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+/// File the stream goes to, unless it is stdout
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Write a newline-delimited JSON event stream of the run to this file (`-` for stdout)
+    #[arg(long, value_name = "path", global = true)]
+    pub events: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Open the event stream if `--events` was given
+    pub fn init(&self) -> Result<()> {
+        match &self.events {
+            Some(path) => open(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Send events to `path` (`-` for stdout) from now on. Only the first call takes effect.
+pub fn open(path: &std::path::Path) -> Result<()> {
+    let out: Box<dyn Write + Send> = if path.as_os_str() == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(BufWriter::new(File::create(path).with_context(|| format!("create {}", path.display()))?))
+    };
+    if SINK.set(Mutex::new(out)).is_ok() && path.as_os_str() != "-" {
+        let _ = PATH.set(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// The file events are written to, when it isn't stdout
+pub fn path() -> Option<&'static std::path::Path> {
+    PATH.get().map(PathBuf::as_path)
+}
+
+/// Whether an event stream is open; check before building expensive event payloads
+pub fn enabled() -> bool {
+    SINK.get().is_some()
+}
+
+/// Write one event line `{"event": kind, "at": <RFC 3339>, ...fields}`; a no-op without a stream
+pub fn emit(kind: &str, at: SystemTime, fields: JsonValue) {
+    let Some(sink) = SINK.get() else { return };
+    let mut line = Map::new();
+    line.insert("event".to_string(), json!(kind));
+    line.insert("at".to_string(), json!(humantime::format_rfc3339_micros(at).to_string()));
+    if let JsonValue::Object(fields) = fields {
+        line.extend(fields);
+    }
+    let mut out = sink.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = writeln!(out, "{}", crate::secrets::redact(&JsonValue::Object(line).to_string()));
+}
+
+/// Write out buffered events; done when the run finishes
+pub fn flush() {
+    if let Some(sink) = SINK.get() {
+        let _ = sink.lock().unwrap_or_else(|e| e.into_inner()).flush();
+    }
+}
+
+/// Identifies the scenario an event belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    pub feature: String,
+    pub scenario: String,
+    pub line: usize,
+}
+
+impl Scope {
+    pub fn new(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Self {
+        Self { feature: feature.name.clone(), scenario: scenario.name.clone(), line: scenario.position.line }
+    }
+
+    /// `fields` with the scope added
+    pub fn with(&self, mut fields: JsonValue) -> JsonValue {
+        if let JsonValue::Object(map) = &mut fields {
+            map.insert("feature".to_string(), json!(self.feature));
+            map.insert("scenario".to_string(), json!(self.scenario));
+            map.insert("line".to_string(), json!(self.line));
+        }
+        fields
+    }
+}
+
+/// Writer emitting run, scenario and step events to the event stream. Meant to be tee'd next to the
+/// regular output writer; events carry their own timestamps, so order in the stream doesn't matter.
+#[derive(Debug, Default)]
+pub struct EventStream {
+    /// Whether a step failed, per running scenario
+    failed: HashMap<(String, usize), bool>,
+}
+
+impl EventStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn on_scenario<W>(&mut self, feature: &gherkin::Feature, scenario: &gherkin::Scenario, ev: &event::Scenario<W>, at: SystemTime) {
+        let scope = Scope::new(feature, scenario);
+        let key = (feature.name.clone(), scenario.position.line);
+        match ev {
+            event::Scenario::Started => {
+                self.failed.insert(key, false);
+                emit("scenario_started", at, scope.with(json!({})));
+            }
+            event::Scenario::Step(step, ev) | event::Scenario::Background(step, ev) => {
+                let text = format!("{}{}", step.keyword, step.value);
+                let fields = json!({ "step": text, "step_line": step.position.line });
+                match ev {
+                    event::Step::Started => {}
+                    event::Step::Passed(..) => emit("step_passed", at, scope.with(fields)),
+                    event::Step::Skipped => emit("step_skipped", at, scope.with(fields)),
+                    event::Step::Failed(_, _, _, err) => {
+                        self.failed.insert(key, true);
+                        let mut fields = fields;
+                        fields["error"] = json!(err.to_string());
+                        emit("step_failed", at, scope.with(fields));
+                    }
+                }
+            }
+            event::Scenario::Hook(which, event::Hook::Failed(_, _)) => {
+                self.failed.insert(key, true);
+                emit("hook_failed", at, scope.with(json!({ "hook": which.to_string() })));
+            }
+            event::Scenario::Finished => {
+                let failed = self.failed.remove(&key).unwrap_or_default();
+                emit("scenario_finished", at, scope.with(json!({ "passed": !failed })));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World> Writer<W> for EventStream {
+    type Cli = cucumber::cli::Empty;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, _: &Self::Cli) {
+        if !enabled() {
+            return;
+        }
+        let Ok(Event { value, at, .. }) = ev else { return };
+        match value {
+            event::Cucumber::Started => emit("run_started", at, json!({})),
+            event::Cucumber::Feature(feature, event::Feature::Scenario(scenario, ev))
+            | event::Cucumber::Feature(feature, event::Feature::Rule(_, event::Rule::Scenario(scenario, ev))) => {
+                self.on_scenario(&feature, &scenario, &ev.event, at)
+            }
+            event::Cucumber::Finished => {
+                emit("run_finished", at, json!({}));
+                flush();
+            }
+            _ => {}
+        }
+    }
+}
+
+// every event carries its scenario and timestamp, so arrival order doesn't matter
+impl writer::Normalized for EventStream {}
@@ -0,0 +1,248 @@
+//! C API over [`Broker`], [`ProtoDyn`] and the matcher engine for test executors that can't run the
+//! Gherkin suite (see include/my_bdd.h). python/my_bdd.py wraps it for Python.
+//!
+//! Functions returning `int` give 0 on success and -1 on failure; pointer-returning ones give NULL.
+//! The reason for the last failure on the calling thread is available from `bdd_last_error`.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use crate::broker::Broker;
+use crate::proto_dyn::{json_partial_match_checked, ProtoDyn};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into the last-error message
+fn guard<T>(fallback: T, f: impl FnOnce() -> Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            set_error(format!("{:#}", e));
+            fallback
+        }
+        Err(panic) => {
+            let reason = panic.downcast_ref::<&str>().map(|s| s.to_string()).or_else(|| panic.downcast_ref::<String>().cloned());
+            set_error(format!("panic: {}", reason.unwrap_or_default()));
+            fallback
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(p: *const c_char, what: &str) -> Result<&'a str> {
+    if p.is_null() {
+        return Err(anyhow!("{} is NULL", what));
+    }
+    CStr::from_ptr(p).to_str().map_err(|_| anyhow!("{} is not UTF-8", what))
+}
+
+unsafe fn json_arg(p: *const c_char) -> Result<JsonValue> {
+    if p.is_null() {
+        return Ok(serde_json::json!({}));
+    }
+    Ok(serde_json::from_str(str_arg(p, "json")?)?)
+}
+
+unsafe fn broker_arg<'a>(broker: *mut Broker) -> Result<&'a mut Broker> {
+    broker.as_mut().ok_or_else(|| anyhow!("broker is NULL"))
+}
+
+unsafe fn proto_arg<'a>(proto: *const ProtoDyn) -> Result<&'a ProtoDyn> {
+    proto.as_ref().ok_or_else(|| anyhow!("proto is NULL"))
+}
+
+/// Hand `text` to the caller through `out`, unless `out` is NULL
+unsafe fn out_string(out: *mut *mut c_char, text: String) -> Result<()> {
+    if !out.is_null() {
+        *out = CString::new(text)?.into_raw();
+    }
+    Ok(())
+}
+
+/// Create a broker on the ZMQ transport connected to `ip`; NULL on failure.
+///
+/// # Safety
+/// `ip` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_broker_new(ip: *const c_char) -> *mut Broker {
+    guard(ptr::null_mut(), || {
+        let mut broker = Broker::new()?;
+        broker.connect(str_arg(ip, "ip")?)?;
+        Ok(Box::into_raw(Box::new(broker)))
+    })
+}
+
+/// Send message `name` built from the JSON body `json` (NULL for an empty message).
+///
+/// # Safety
+/// `broker` must come from `bdd_broker_new`; strings must be NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_broker_send(broker: *mut Broker, name: *const c_char, json: *const c_char) -> c_int {
+    guard(-1, || {
+        broker_arg(broker)?.send_message(str_arg(name, "name")?, &json_arg(json)?)?;
+        Ok(0)
+    })
+}
+
+/// Wait up to `timeout_ms` for message `name` partially matching `json`. On a match, and if
+/// `out_json` is not NULL, stores the received body there; release it with `bdd_string_free`.
+///
+/// # Safety
+/// `broker` must come from `bdd_broker_new`; strings must be NUL-terminated; `out_json` must be
+/// NULL or point to writable storage for one pointer.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_broker_expect(
+    broker: *mut Broker,
+    name: *const c_char,
+    json: *const c_char,
+    timeout_ms: c_int,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    guard(-1, || {
+        let got = broker_arg(broker)?.expect_message(str_arg(name, "name")?, &json_arg(json)?, timeout_ms)?;
+        out_string(out_json, got.body.to_string())?;
+        Ok(0)
+    })
+}
+
+/// Shut the broker down and free it. NULL is ignored.
+///
+/// # Safety
+/// `broker` must come from `bdd_broker_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_broker_free(broker: *mut Broker) {
+    if !broker.is_null() {
+        drop(Box::from_raw(broker));
+    }
+}
+
+/// Message types from `path` (a .proto compiled with protoc, or an encoded FileDescriptorSet), or
+/// with NULL those the test suite uses; NULL on failure.
+///
+/// # Safety
+/// `path` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_proto_new(path: *const c_char) -> *mut ProtoDyn {
+    guard(ptr::null_mut(), || {
+        let proto = if path.is_null() { ProtoDyn::new()? } else { ProtoDyn::load(Path::new(str_arg(path, "path")?))? };
+        Ok(Box::into_raw(Box::new(proto)))
+    })
+}
+
+/// Store the full names of the message types, as a JSON array, in `*out_json`; release it with
+/// `bdd_string_free`.
+///
+/// # Safety
+/// `proto` must come from `bdd_proto_new`; `out_json` must point to writable storage for one pointer.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_proto_message_names(proto: *const ProtoDyn, out_json: *mut *mut c_char) -> c_int {
+    guard(-1, || {
+        let names = proto_arg(proto)?.message_names();
+        out_string(out_json, serde_json::to_string(&names)?)?;
+        Ok(0)
+    })
+}
+
+/// Encode message `name` built from `json` (NULL for an empty message) into `*out` and `*out_len`;
+/// release the bytes with `bdd_bytes_free`.
+///
+/// # Safety
+/// `proto` must come from `bdd_proto_new`; strings must be NUL-terminated; `out` and `out_len` must
+/// point to writable storage.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_proto_encode(proto: *const ProtoDyn, name: *const c_char, json: *const c_char, out: *mut *mut u8, out_len: *mut usize) -> c_int {
+    guard(-1, || {
+        if out.is_null() || out_len.is_null() {
+            return Err(anyhow!("out is NULL"));
+        }
+        let proto = proto_arg(proto)?;
+        let bytes = proto.encode_message(&proto.build_from_json(str_arg(name, "name")?, &json_arg(json)?)?)?;
+        *out_len = bytes.len();
+        *out = Box::into_raw(bytes.into_boxed_slice()).cast();
+        Ok(0)
+    })
+}
+
+/// Decode `len` bytes at `bytes` as message `name` and store it as JSON in `*out_json`; release it
+/// with `bdd_string_free`.
+///
+/// # Safety
+/// `proto` must come from `bdd_proto_new`; `name` must be NUL-terminated; `bytes` must point to
+/// `len` readable bytes (or be NULL with `len` 0); `out_json` must point to writable storage.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_proto_decode(proto: *const ProtoDyn, name: *const c_char, bytes: *const u8, len: usize, out_json: *mut *mut c_char) -> c_int {
+    guard(-1, || {
+        let proto = proto_arg(proto)?;
+        let bytes = match (bytes.is_null(), len) {
+            (_, 0) => &[][..],
+            (true, _) => return Err(anyhow!("bytes is NULL")),
+            (false, len) => std::slice::from_raw_parts(bytes, len),
+        };
+        let msg = proto.decode_message(str_arg(name, "name")?, bytes)?;
+        out_string(out_json, proto.to_json_value(&msg).to_string())?;
+        Ok(0)
+    })
+}
+
+/// Free message types from `bdd_proto_new`. NULL is ignored.
+///
+/// # Safety
+/// `proto` must come from `bdd_proto_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_proto_free(proto: *mut ProtoDyn) {
+    if !proto.is_null() {
+        drop(Box::from_raw(proto));
+    }
+}
+
+/// Whether `actual` partially matches `expected`, with the same matchers (`{"$regex": ...}` and the
+/// rest) as the steps: 1 on a match, 0 on none, -1 when either isn't JSON or a matcher is unknown.
+///
+/// # Safety
+/// Both must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_json_match(expected: *const c_char, actual: *const c_char) -> c_int {
+    guard(-1, || {
+        let actual: JsonValue = serde_json::from_str(str_arg(actual, "actual")?)?;
+        Ok(json_partial_match_checked(&serde_json::from_str(str_arg(expected, "expected")?)?, &actual)? as c_int)
+    })
+}
+
+/// Free bytes returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `bytes` and `len` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_bytes_free(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+/// Free a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `s` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bdd_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Reason for the last failure on this thread, or NULL. Valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn bdd_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
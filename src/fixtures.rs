@@ -0,0 +1,39 @@
+//! Scenario state kept in a file and loaded in one step, so Backgrounds stay short: variables,
+//! devices and matcher macros.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::DeviceConfig;
+
+/// A fixture file, TOML, JSON or YAML by its extension
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fixtures {
+    /// Scenario variables, as if set by steps
+    pub vars: HashMap<String, String>,
+    /// Devices that exist from now on, as under `[devices.<id>]`
+    pub devices: HashMap<String, DeviceConfig>,
+    /// Matcher macros added to or overriding `[matching.macros]` for the scenario
+    pub macros: HashMap<String, JsonValue>,
+}
+
+impl Fixtures {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read fixtures {}", path.display()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::parse_toml(&text),
+            Some("json") => serde_json::from_str(&text).map_err(Into::into),
+            Some("yaml" | "yml") => crate::sequence::yaml_to_json(&text).and_then(|json| serde_json::from_value(json).map_err(Into::into)),
+            _ => bail!("fixtures {}: expected a .toml, .json or .yaml file", path.display()),
+        }
+        .with_context(|| format!("parse fixtures {}", path.display()))
+    }
+
+    pub fn parse_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+}
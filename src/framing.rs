@@ -0,0 +1,95 @@
+//! Checksums on links that append a CRC to every frame. `[framing]` names the algorithm per
+//! topic; sends get the CRC appended, and received frames are verified and stripped before they
+//! are buffered, so frames failing the check never reach an expectation.
+
+use anyhow::{bail, Result};
+
+use crate::config::{ByteOrder, Crc, FramingConfig};
+
+/// Width, polynomial (reversed for reflected algorithms), initial value, reflection, final xor
+struct Params {
+    width: u32,
+    poly: u32,
+    init: u32,
+    reflected: bool,
+    xor_out: u32,
+}
+
+impl Crc {
+    fn params(self) -> Params {
+        match self {
+            Crc::Crc32 => Params { width: 32, poly: 0xEDB8_8320, init: 0xFFFF_FFFF, reflected: true, xor_out: 0xFFFF_FFFF },
+            Crc::Crc32c => Params { width: 32, poly: 0x82F6_3B78, init: 0xFFFF_FFFF, reflected: true, xor_out: 0xFFFF_FFFF },
+            Crc::Crc16Ccitt => Params { width: 16, poly: 0x1021, init: 0xFFFF, reflected: false, xor_out: 0 },
+            Crc::Crc16Modbus => Params { width: 16, poly: 0xA001, init: 0xFFFF, reflected: true, xor_out: 0 },
+            Crc::Crc8 => Params { width: 8, poly: 0x07, init: 0, reflected: false, xor_out: 0 },
+        }
+    }
+
+    /// Bytes the CRC takes at the end of a frame
+    pub fn size(self) -> usize {
+        self.params().width as usize / 8
+    }
+
+    pub fn checksum(self, data: &[u8]) -> u32 {
+        let p = self.params();
+        let mask = u32::MAX >> (32 - p.width);
+        let mut crc = p.init;
+        for &byte in data {
+            if p.reflected {
+                crc ^= byte as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 { (crc >> 1) ^ p.poly } else { crc >> 1 };
+                }
+            } else {
+                crc ^= (byte as u32) << (p.width - 8);
+                for _ in 0..8 {
+                    crc = if crc & (1 << (p.width - 1)) != 0 { (crc << 1) ^ p.poly } else { crc << 1 } & mask;
+                }
+            }
+        }
+        (crc ^ p.xor_out) & mask
+    }
+}
+
+impl FramingConfig {
+    fn trailer(&self, crc: u32) -> Vec<u8> {
+        match self.byte_order {
+            ByteOrder::Big => crc.to_be_bytes()[4 - self.crc.size()..].to_vec(),
+            ByteOrder::Little => crc.to_le_bytes()[..self.crc.size()].to_vec(),
+        }
+    }
+
+    /// `payload` followed by its CRC
+    pub fn append(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = payload.to_vec();
+        frame.extend(self.trailer(self.crc.checksum(payload)));
+        frame
+    }
+
+    /// `payload` followed by a CRC with every bit of its last byte flipped, which no payload matches
+    pub fn corrupt(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = self.append(payload);
+        if let Some(last) = frame.last_mut() {
+            *last = !*last;
+        }
+        frame
+    }
+
+    /// The payload of a frame whose CRC checks out
+    pub fn strip<'a>(&self, frame: &'a [u8]) -> Result<&'a [u8]> {
+        let Some(split) = frame.len().checked_sub(self.crc.size()) else {
+            bail!("{} byte frame is shorter than its {:?} CRC", frame.len(), self.crc);
+        };
+        let (payload, carried) = frame.split_at(split);
+        let expected = self.trailer(self.crc.checksum(payload));
+        if carried != expected {
+            bail!("{:?} mismatch: frame carries {}, payload gives {}", self.crc, hex(carried), hex(&expected));
+        }
+        Ok(payload)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
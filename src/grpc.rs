@@ -0,0 +1,722 @@
+//! gRPC client for unary and streaming calls. HTTP/2 is spoken directly on the connection:
+//! cleartext with prior knowledge (h2c) for `http://` URLs, and for `https://` TLS through openssl
+//! (see [`crate::tls`]) with ALPN `h2`. Requests and replies are dynamic messages of the method's
+//! types, given and matched as JSON. Each call is one HTTP/2 stream; a background thread reads the
+//! connection and buffers every reply until an expectation consumes it.
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost_reflect::MethodDescriptor;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::TlsProfile;
+use crate::hpack;
+use crate::http::{self, Connection, Url};
+use crate::matchers::MatchOptions;
+use crate::proto_dyn::{json_partial_match_with, ProtoDyn};
+
+/// What a client sends first on an HTTP/2 connection
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// frame types
+pub const DATA: u8 = 0x0;
+pub const HEADERS: u8 = 0x1;
+pub const RST_STREAM: u8 = 0x3;
+pub const SETTINGS: u8 = 0x4;
+pub const PUSH_PROMISE: u8 = 0x5;
+pub const PING: u8 = 0x6;
+pub const GOAWAY: u8 = 0x7;
+pub const WINDOW_UPDATE: u8 = 0x8;
+pub const CONTINUATION: u8 = 0x9;
+
+// frame flags
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY: u8 = 0x20;
+
+// settings
+pub const ENABLE_PUSH: u16 = 0x2;
+pub const INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const MAX_FRAME_SIZE: u16 = 0x5;
+
+/// Flow-control window every stream and connection starts with
+const INITIAL_WINDOW: i64 = 65_535;
+/// Largest frame payload; this client never raises SETTINGS_MAX_FRAME_SIZE, so peers send no larger
+pub const MAX_FRAME: usize = 16_384;
+/// RST_STREAM code for a call given up on
+const CANCEL: u32 = 0x8;
+/// RST_STREAM code for streams a GOAWAY left unprocessed
+const REFUSED_STREAM: u32 = 0x7;
+
+/// One HTTP/2 frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub kind: u8,
+    pub flags: u8,
+    pub stream: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(kind: u8, flags: u8, stream: u32, payload: Vec<u8>) -> Self {
+        Self { kind, flags, stream, payload }
+    }
+
+    /// SETTINGS with the given parameters
+    pub fn settings(params: &[(u16, u32)]) -> Self {
+        let payload = params.iter().flat_map(|(id, value)| id.to_be_bytes().into_iter().chain(value.to_be_bytes())).collect();
+        Self::new(SETTINGS, 0, 0, payload)
+    }
+
+    /// The next frame; None when the connection closed between frames. Payloads over
+    /// [`MAX_FRAME`] are refused.
+    pub fn read(reader: &mut dyn Read) -> io::Result<Option<Self>> {
+        let mut head = [0u8; 9];
+        if reader.read(&mut head[..1])? == 0 {
+            return Ok(None);
+        }
+        reader.read_exact(&mut head[1..])?;
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        if len > MAX_FRAME {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("HTTP/2 frame of {} bytes is over the {} byte limit", len, MAX_FRAME)));
+        }
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+        let stream = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+        Ok(Some(Self { kind: head[3], flags: head[4], stream, payload }))
+    }
+
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let len = (self.payload.len() as u32).to_be_bytes();
+        writer.write_all(&[len[1], len[2], len[3], self.kind, self.flags])?;
+        writer.write_all(&self.stream.to_be_bytes())?;
+        writer.write_all(&self.payload)?;
+        writer.flush()
+    }
+
+    /// Payload of a DATA or HEADERS frame without its padding and priority fields
+    pub fn body(&self) -> Result<&[u8]> {
+        let mut body = &self.payload[..];
+        let mut pad = 0;
+        if self.flags & PADDED != 0 {
+            let Some((&n, rest)) = body.split_first() else { bail!("padded frame without a pad length") };
+            (pad, body) = (n as usize, rest);
+        }
+        if self.kind == HEADERS && self.flags & PRIORITY != 0 {
+            body = body.get(5..).ok_or_else(|| anyhow!("HEADERS frame too short for its priority"))?;
+        }
+        if pad > body.len() {
+            bail!("frame padding longer than its payload");
+        }
+        Ok(&body[..body.len() - pad])
+    }
+}
+
+/// `msg` in the gRPC length-prefixed framing, uncompressed
+pub fn frame_message(msg: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(msg.len() + 5);
+    out.push(0);
+    out.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    out.extend_from_slice(msg);
+    out
+}
+
+/// Split the complete length-prefixed messages off the front of `buf`
+pub fn take_messages(buf: &mut Vec<u8>) -> Result<Vec<Vec<u8>>> {
+    let mut messages = Vec::new();
+    let mut start = 0;
+    while let Some(head) = buf.get(start..start + 5) {
+        if head[0] != 0 {
+            bail!("compressed gRPC message, though no compression was offered");
+        }
+        let len = u32::from_be_bytes([head[1], head[2], head[3], head[4]]) as usize;
+        let Some(msg) = buf.get(start + 5..start + 5 + len) else { break };
+        messages.push(msg.to_vec());
+        start += 5 + len;
+    }
+    buf.drain(..start);
+    Ok(messages)
+}
+
+/// gRPC status codes by number
+pub const STATUS_CODES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// How a call ended, from `grpc-status` and `grpc-message`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub code: u32,
+    pub message: String,
+}
+
+impl Status {
+    /// Code by name (`NOT_FOUND`) or number
+    pub fn code_of(name: &str) -> Option<u32> {
+        name.parse().ok().or_else(|| STATUS_CODES.iter().position(|c| c.eq_ignore_ascii_case(name)).map(|i| i as u32))
+    }
+
+    pub fn name(&self) -> &'static str {
+        STATUS_CODES.get(self.code as usize).copied().unwrap_or("UNKNOWN")
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.message.as_str() {
+            "" => write!(f, "{}", self.name()),
+            message => write!(f, "{}: {}", self.name(), message),
+        }
+    }
+}
+
+/// `grpc-message` is percent-encoded
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+struct Item {
+    bytes: Vec<u8>,
+    consumed: bool,
+}
+
+/// One call as the reader thread sees it
+#[derive(Default)]
+struct Call {
+    /// A reply still arriving
+    partial: Vec<u8>,
+    items: Vec<Item>,
+    status: Option<Status>,
+    /// `:status` when it wasn't 200
+    http_status: Option<String>,
+    /// RST_STREAM code
+    reset: Option<u32>,
+    /// The server will send nothing more
+    ended: bool,
+    send_window: i64,
+}
+
+impl Call {
+    /// How the call ended, for error messages
+    fn outcome(&self) -> String {
+        match (&self.status, self.reset, &self.http_status) {
+            (Some(status), _, _) => format!("status {}", status),
+            (None, Some(code), _) => format!("reset with HTTP/2 error {}", code),
+            (None, None, Some(http)) => format!("HTTP status {}", http),
+            (None, None, None) => "no grpc-status".to_string(),
+        }
+    }
+}
+
+struct State {
+    calls: HashMap<u32, Call>,
+    next_id: u32,
+    send_window: i64,
+    initial_window: i64,
+    max_frame: usize,
+    /// Why no new calls can start: the server sent GOAWAY
+    goaway: Option<String>,
+    /// Why the connection is unusable
+    closed: Option<String>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, frame: &Frame) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        frame.write(&mut **writer).context("write to the gRPC connection")
+    }
+
+    /// Wait for the reader thread to change something; false once `deadline` passed
+    fn wait<'a>(&self, state: MutexGuard<'a, State>, deadline: Instant) -> (MutexGuard<'a, State>, bool) {
+        let now = Instant::now();
+        if now >= deadline {
+            return (state, false);
+        }
+        let state = self.changed.wait_timeout(state, deadline - now).map(|(guard, _)| guard).unwrap_or_else(|e| e.into_inner().0);
+        (state, true)
+    }
+}
+
+/// HTTP/2 connection to a gRPC server; calls on it are [`GrpcStream`]s
+pub struct GrpcClient {
+    url: String,
+    target: Url,
+    proto: ProtoDyn,
+    /// Taken when a failed connection is torn down
+    connection: Option<Connection>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GrpcClient {
+    /// Connect to `url`, using `profile` if it is https, and wait up to `timeout` for the server's
+    /// SETTINGS. Calls use the services and message types of `proto`.
+    pub fn connect(url: &str, profile: &TlsProfile, proto: ProtoDyn, timeout: Duration) -> Result<Self> {
+        let target = Url::parse(url)?;
+        let (connection, mut writer, mut reader) = http::open(&target, url, profile, Some("h2"), timeout)?;
+        let hello = writer.write_all(PREFACE).and_then(|_| Frame::settings(&[(ENABLE_PUSH, 0)]).write(&mut writer));
+        if let Err(e) = hello {
+            return Err(connection.failure(anyhow::Error::new(e).context(format!("send HTTP/2 preface to {}", url))));
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                calls: HashMap::new(),
+                next_id: 1,
+                send_window: INITIAL_WINDOW,
+                initial_window: INITIAL_WINDOW,
+                max_frame: MAX_FRAME,
+                goaway: None,
+                closed: None,
+            }),
+            changed: Condvar::new(),
+            writer: Mutex::new(writer),
+        });
+        let (settled_tx, settled) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("bdd-grpc".to_string())
+            .spawn(move || {
+                let why = match read_frames(&mut reader, &thread_shared, &settled_tx) {
+                    Ok(()) => "the server closed the connection".to_string(),
+                    Err(e) => format!("{:#}", e),
+                };
+                let _ = settled_tx.send(Err(anyhow!(why.clone())));
+                thread_shared.state().closed.get_or_insert(why);
+                thread_shared.changed.notify_all();
+            })
+            .context("spawn gRPC thread")?;
+        let mut client = Self { url: url.to_string(), target, proto, connection: Some(connection), shared, thread: Some(thread) };
+        // a server that isn't HTTP/2 fails here instead of on the first call
+        match settled.recv_timeout(timeout) {
+            Ok(Ok(())) => Ok(client),
+            Ok(Err(e)) => Err(client.fail(e.context(format!("connect {}", url)))),
+            Err(_) => Err(client.fail(anyhow!("no HTTP/2 SETTINGS from {} within {:?}", url, timeout))),
+        }
+    }
+
+    /// Tear down a connection that never got going
+    fn fail(&mut self, error: anyhow::Error) -> anyhow::Error {
+        let error = match self.connection.take() {
+            Some(connection) => connection.failure(error),
+            None => error,
+        };
+        self.close();
+        error
+    }
+
+    /// Start a call of `method` (`Service/Method`); its requests go out with [`GrpcStream::send`]
+    pub fn open(&self, method: &str) -> Result<GrpcStream> {
+        let desc = self.proto.method_desc(method)?;
+        let path = format!("/{}/{}", desc.parent_service().full_name(), desc.name());
+        let authority = format!("{}:{}", self.target.host, self.target.port);
+        let scheme = if self.target.tls { "https" } else { "http" };
+        let block = hpack::encode(&[
+            (":method", "POST"),
+            (":scheme", scheme),
+            (":path", &path),
+            (":authority", &authority),
+            ("content-type", "application/grpc"),
+            ("te", "trailers"),
+        ]);
+        // stream ids must go out in order, so the id is taken with the writer held
+        let mut writer = self.shared.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let id = {
+            let mut state = self.shared.state();
+            if let Some(why) = state.closed.as_ref().or(state.goaway.as_ref()) {
+                bail!("can't call {} on {}: {}", path, self.url, why);
+            }
+            let id = state.next_id;
+            state.next_id += 2;
+            let send_window = state.initial_window;
+            state.calls.insert(id, Call { send_window, ..Call::default() });
+            id
+        };
+        Frame::new(HEADERS, END_HEADERS, id, block).write(&mut **writer).with_context(|| format!("call {} on {}", path, self.url))?;
+        log::debug!(target: "transport", "gRPC {} opened as stream {}", path, id);
+        Ok(GrpcStream { id, path, method: desc, proto: self.proto.clone(), shared: self.shared.clone(), half_closed: false })
+    }
+
+    /// Close the connection and stop the reader thread
+    pub fn close(&mut self) {
+        if let Some(connection) = &mut self.connection {
+            connection.close();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for GrpcClient {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl std::fmt::Debug for GrpcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcClient").field("url", &self.url).field("calls", &self.shared.state().calls.len()).finish()
+    }
+}
+
+/// Read frames until the connection closes, answering SETTINGS and PING and handing replies to
+/// their calls. The first frame must be the server's SETTINGS, which is reported on `settled`.
+fn read_frames(reader: &mut dyn Read, shared: &Shared, settled: &mpsc::Sender<Result<()>>) -> Result<()> {
+    let mut decoder = hpack::Decoder::default();
+    let mut settling = true;
+    // a header block awaiting CONTINUATION: stream, END_STREAM, the block so far
+    let mut pending: Option<(u32, bool, Vec<u8>)> = None;
+    loop {
+        let frame = match Frame::read(reader) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(e) if settling => return Err(anyhow::Error::new(e).context("not an HTTP/2 server")),
+            Err(e) => return Err(e.into()),
+        };
+        if settling && (frame.kind != SETTINGS || frame.flags & ACK != 0) {
+            bail!("not an HTTP/2 server: its first frame is type {}, not SETTINGS", frame.kind);
+        }
+        if let Some((id, _, _)) = &pending {
+            if frame.kind != CONTINUATION || frame.stream != *id {
+                bail!("HTTP/2 protocol error: header block of stream {} interrupted", id);
+            }
+        }
+        match frame.kind {
+            SETTINGS if frame.flags & ACK == 0 => {
+                apply_settings(shared, &frame.payload)?;
+                shared.send(&Frame::new(SETTINGS, ACK, 0, Vec::new()))?;
+                if settling {
+                    settling = false;
+                    let _ = settled.send(Ok(()));
+                }
+            }
+            PING if frame.flags & ACK == 0 => shared.send(&Frame::new(PING, ACK, 0, frame.payload))?,
+            WINDOW_UPDATE => {
+                let increment = (word(&frame.payload, 0) & 0x7fff_ffff) as i64;
+                let mut state = shared.state();
+                match frame.stream {
+                    0 => state.send_window += increment,
+                    id => {
+                        if let Some(call) = state.calls.get_mut(&id) {
+                            call.send_window += increment;
+                        }
+                    }
+                }
+                drop(state);
+                shared.changed.notify_all();
+            }
+            HEADERS => {
+                let block = frame.body()?.to_vec();
+                let end = frame.flags & END_STREAM != 0;
+                if frame.flags & END_HEADERS != 0 {
+                    headers(shared, &mut decoder, frame.stream, end, &block)?;
+                } else {
+                    pending = Some((frame.stream, end, block));
+                }
+            }
+            CONTINUATION => {
+                let Some((id, end, mut block)) = pending.take() else { bail!("HTTP/2 protocol error: CONTINUATION without HEADERS") };
+                block.extend_from_slice(&frame.payload);
+                if frame.flags & END_HEADERS != 0 {
+                    headers(shared, &mut decoder, id, end, &block)?;
+                } else {
+                    pending = Some((id, end, block));
+                }
+            }
+            DATA => {
+                let body = frame.body()?;
+                let end = frame.flags & END_STREAM != 0;
+                let mut open = false;
+                let mut state = shared.state();
+                if let Some(call) = state.calls.get_mut(&frame.stream) {
+                    call.partial.extend_from_slice(body);
+                    for bytes in take_messages(&mut call.partial)? {
+                        call.items.push(Item { bytes, consumed: false });
+                    }
+                    call.ended |= end;
+                    open = !call.ended;
+                }
+                drop(state);
+                shared.changed.notify_all();
+                // replies are buffered as they come, so the window is handed back at once
+                if !frame.payload.is_empty() {
+                    let increment = (frame.payload.len() as u32).to_be_bytes().to_vec();
+                    shared.send(&Frame::new(WINDOW_UPDATE, 0, 0, increment.clone()))?;
+                    if open {
+                        shared.send(&Frame::new(WINDOW_UPDATE, 0, frame.stream, increment))?;
+                    }
+                }
+            }
+            RST_STREAM => {
+                let code = word(&frame.payload, 0);
+                if let Some(call) = shared.state().calls.get_mut(&frame.stream) {
+                    call.reset = Some(code);
+                    call.ended = true;
+                }
+                shared.changed.notify_all();
+            }
+            GOAWAY => {
+                let (last, code) = (word(&frame.payload, 0) & 0x7fff_ffff, word(&frame.payload, 4));
+                let debug = String::from_utf8_lossy(frame.payload.get(8..).unwrap_or_default()).into_owned();
+                let mut state = shared.state();
+                state.goaway = Some(format!("the server sent GOAWAY (HTTP/2 error {}) {}", code, debug).trim_end().to_string());
+                for (_, call) in state.calls.iter_mut().filter(|(id, call)| **id > last && !call.ended) {
+                    call.reset = Some(REFUSED_STREAM);
+                    call.ended = true;
+                }
+                drop(state);
+                shared.changed.notify_all();
+            }
+            PUSH_PROMISE => bail!("HTTP/2 protocol error: the server pushed a stream though push is disabled"),
+            // SETTINGS and PING acknowledgements, PRIORITY and unknown types
+            _ => {}
+        }
+    }
+}
+
+/// The big-endian 32-bit word at `at`; 0 when the payload is too short
+fn word(payload: &[u8], at: usize) -> u32 {
+    payload.get(at..at + 4).map_or(0, |w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+}
+
+fn apply_settings(shared: &Shared, payload: &[u8]) -> Result<()> {
+    if !payload.len().is_multiple_of(6) {
+        bail!("HTTP/2 SETTINGS payload of {} bytes", payload.len());
+    }
+    let mut state = shared.state();
+    for param in payload.chunks(6) {
+        let value = u32::from_be_bytes([param[2], param[3], param[4], param[5]]);
+        match u16::from_be_bytes([param[0], param[1]]) {
+            INITIAL_WINDOW_SIZE => {
+                let delta = value as i64 - state.initial_window;
+                state.initial_window = value as i64;
+                for call in state.calls.values_mut() {
+                    call.send_window += delta;
+                }
+            }
+            MAX_FRAME_SIZE => state.max_frame = value as usize,
+            _ => {}
+        }
+    }
+    drop(state);
+    shared.changed.notify_all();
+    Ok(())
+}
+
+/// A header block for stream `id`: the response headers, trailers, or both for a trailers-only
+/// answer. Every block is decoded, even for calls given up on, to keep the HPACK table in step.
+fn headers(shared: &Shared, decoder: &mut hpack::Decoder, id: u32, end: bool, block: &[u8]) -> Result<()> {
+    let headers = decoder.decode(block)?;
+    let get = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    let mut state = shared.state();
+    if let Some(call) = state.calls.get_mut(&id) {
+        if let Some(status) = get(":status").filter(|s| *s != "200") {
+            call.http_status = Some(status.to_string());
+        }
+        if let Some(code) = get("grpc-status") {
+            let message = percent_decode(get("grpc-message").unwrap_or_default());
+            // a code that isn't a number is UNKNOWN
+            call.status = Some(Status { code: code.parse().unwrap_or(2), message });
+        }
+        call.ended |= end;
+    }
+    drop(state);
+    shared.changed.notify_all();
+    Ok(())
+}
+
+/// One call: requests go out with [`send`](Self::send), replies are buffered until matched
+pub struct GrpcStream {
+    id: u32,
+    /// `/package.Service/Method`
+    path: String,
+    method: MethodDescriptor,
+    proto: ProtoDyn,
+    shared: Arc<Shared>,
+    half_closed: bool,
+}
+
+impl GrpcStream {
+    pub fn method(&self) -> &MethodDescriptor {
+        &self.method
+    }
+
+    /// Send a request built from `body`, waiting up to `timeout` for flow-control window
+    pub fn send(&mut self, body: &JsonValue, timeout: Duration) -> Result<()> {
+        if self.half_closed {
+            bail!("{} no longer takes requests; its stream was closed for sending", self.path);
+        }
+        let msg = self.proto.build_from_json(self.method.input().full_name(), body)?;
+        let data = frame_message(&self.proto.encode_message(&msg)?);
+        self.send_data(&data, Instant::now() + timeout)?;
+        log::debug!(target: "transport", "gRPC {} → {}", self.path, body);
+        Ok(())
+    }
+
+    /// Tell the server no more requests follow
+    pub fn close_send(&mut self) -> Result<()> {
+        if !self.half_closed {
+            self.shared.send(&Frame::new(DATA, END_STREAM, self.id, Vec::new()))?;
+            self.half_closed = true;
+        }
+        Ok(())
+    }
+
+    fn send_data(&self, mut data: &[u8], deadline: Instant) -> Result<()> {
+        while !data.is_empty() {
+            let mut state = self.shared.state();
+            let n = loop {
+                if let Some(why) = &state.closed {
+                    bail!("{}: {}", self.path, why);
+                }
+                let (window, max_frame) = (state.send_window, state.max_frame);
+                let call = state.calls.get_mut(&self.id).ok_or_else(|| anyhow!("{} is no longer open", self.path))?;
+                if let Some(code) = call.reset {
+                    bail!("{}: the server reset the stream (HTTP/2 error {})", self.path, code);
+                }
+                let n = data.len().min(max_frame).min(window.min(call.send_window).max(0) as usize);
+                if n > 0 {
+                    call.send_window -= n as i64;
+                    state.send_window -= n as i64;
+                    break n;
+                }
+                let (guard, waited) = self.shared.wait(state, deadline);
+                state = guard;
+                if !waited {
+                    bail!("{}: the server gave no flow-control window to send in", self.path);
+                }
+            };
+            drop(state);
+            self.shared.send(&Frame::new(DATA, 0, self.id, data[..n].to_vec()))?;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout` until `count` unconsumed replies partially match `expected`, and
+    /// consume them; returns them as JSON
+    pub fn expect_items(&self, count: usize, expected: &JsonValue, timeout: Duration, opts: MatchOptions) -> Result<Vec<JsonValue>> {
+        let deadline = Instant::now() + timeout;
+        let output = self.method.output();
+        let mut state = self.shared.state();
+        loop {
+            let closed = state.closed.clone();
+            let call = state.calls.get_mut(&self.id).ok_or_else(|| anyhow!("{} is no longer open", self.path))?;
+            let mut matching = Vec::new();
+            for (i, item) in call.items.iter().enumerate().filter(|(_, item)| !item.consumed) {
+                let reply = self.proto.to_json_value(&self.proto.decode_message(output.full_name(), &item.bytes)?);
+                if json_partial_match_with(expected, &reply, opts)? {
+                    matching.push((i, reply));
+                    if matching.len() == count {
+                        break;
+                    }
+                }
+            }
+            if matching.len() == count {
+                for (i, _) in &matching {
+                    call.items[*i].consumed = true;
+                }
+                log::debug!(target: "matcher", "gRPC {}: {} item(s) matching {}", self.path, count, expected);
+                return Ok(matching.into_iter().map(|(_, reply)| reply).collect());
+            }
+            let found = matching.len();
+            if call.ended {
+                bail!("{} ended after {} of {} items matching {} ({} received, {})", self.path, found, count, expected, call.items.len(), call.outcome());
+            }
+            if let Some(why) = closed {
+                bail!("{}: {} ({} of {} items matching {})", self.path, why, found, count, expected);
+            }
+            let received = call.items.len();
+            let (guard, waited) = self.shared.wait(state, deadline);
+            state = guard;
+            if !waited {
+                bail!("{} of {} {} items matching {} within {:?} ({} received)", found, count, self.path, expected, timeout, received);
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the server to end the call; its status
+    pub fn finish(&self, timeout: Duration) -> Result<Status> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state();
+        loop {
+            let closed = state.closed.clone();
+            let call = state.calls.get(&self.id).ok_or_else(|| anyhow!("{} is no longer open", self.path))?;
+            if call.ended {
+                return call.status.clone().ok_or_else(|| anyhow!("{} ended with {}", self.path, call.outcome()));
+            }
+            if let Some(why) = closed {
+                bail!("{}: {}", self.path, why);
+            }
+            let (guard, waited) = self.shared.wait(state, deadline);
+            state = guard;
+            if !waited {
+                bail!("{} did not end within {:?}", self.path, timeout);
+            }
+        }
+    }
+}
+
+impl Drop for GrpcStream {
+    /// Cancel the call unless the server already ended it
+    fn drop(&mut self) {
+        let call = self.shared.state().calls.remove(&self.id);
+        if call.is_some_and(|call| !call.ended) {
+            let _ = self.shared.send(&Frame::new(RST_STREAM, 0, self.id, CANCEL.to_be_bytes().to_vec()));
+        }
+    }
+}
+
+impl std::fmt::Debug for GrpcStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcStream").field("path", &self.path).field("stream", &self.id).field("half_closed", &self.half_closed).finish()
+    }
+}
@@ -0,0 +1,284 @@
+//! Per-scenario results and step timings of every run, appended to a local history file, and a
+//! summary of the last runs: flaky scenarios, slowest steps and the topics failures involve.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use cucumber::gherkin::{Feature, Scenario};
+use cucumber::{cli, event, parser, writer, Event, World, Writer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::codec;
+use crate::proto_dyn::ProtoDyn;
+
+/// Where runs are appended unless `--history` says otherwise
+pub const DEFAULT_PATH: &str = "target/bdd-history.ndjson";
+
+#[derive(clap::Args, Clone, Debug)]
+#[group(skip)]
+pub struct Cli {
+    /// File each run's scenario results and step timings are appended to, one line per run
+    #[arg(long, value_name = "path", default_value = DEFAULT_PATH)]
+    pub history: PathBuf,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self { history: PathBuf::from(DEFAULT_PATH) }
+    }
+}
+
+/// One run: a line of the history file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    /// RFC 3339
+    pub started: String,
+    pub scenarios: Vec<ScenarioRun>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioRun {
+    /// Feature file, relative to the working directory
+    pub path: String,
+    pub line: usize,
+    pub scenario: String,
+    pub passed: bool,
+    pub duration_ms: u64,
+    pub steps: Vec<StepRun>,
+    /// Messages named by the failed step or its error
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failure_topics: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepRun {
+    /// Keyword and text
+    pub step: String,
+    pub duration_ms: u64,
+    pub passed: bool,
+}
+
+/// The last `last` runs in `path`, oldest first; none when there is no file yet. Lines that don't
+/// parse, e.g. from a run killed mid-write, are skipped.
+pub fn load(path: &Path, last: usize) -> Result<Vec<Run>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    let runs: Vec<Run> = text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    Ok(runs[runs.len().saturating_sub(last)..].to_vec())
+}
+
+/// Add `run` as the last line of `path`
+pub fn append(path: &Path, run: &Run) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).with_context(|| format!("open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(run)?).with_context(|| format!("write {}", path.display()))
+}
+
+/// Known message names among the words of `text`, in order
+fn topics_in(text: &str) -> Vec<String> {
+    let proto = ProtoDyn::new().ok();
+    let mut topics: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')) {
+        let word = word.trim_matches('.');
+        let known = proto.as_ref().is_some_and(|p| p.message_desc(word).is_ok()) || codec::is_configured(word);
+        if !word.is_empty() && known && !topics.iter().any(|t| t == word) {
+            topics.push(word.to_string());
+        }
+    }
+    topics
+}
+
+/// A flaky scenario: it both passed and failed within the runs summarized
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flaky {
+    pub path: String,
+    pub line: usize,
+    pub scenario: String,
+    pub runs: usize,
+    pub failed: usize,
+}
+
+/// A step's mean duration over the runs it appeared in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowStep {
+    pub step: String,
+    pub mean: Duration,
+    pub runs: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub runs: usize,
+    /// Most failures first
+    pub flaky: Vec<Flaky>,
+    /// Slowest first
+    pub slowest: Vec<SlowStep>,
+    /// Failing scenarios per topic, most first
+    pub failure_topics: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Summary of `runs`, keeping the `top` slowest steps
+    pub fn of(runs: &[Run], top: usize) -> Self {
+        let mut outcomes: BTreeMap<(String, usize, String), (usize, usize)> = BTreeMap::new();
+        let mut steps: BTreeMap<String, (Duration, usize)> = BTreeMap::new();
+        let mut topics: BTreeMap<String, usize> = BTreeMap::new();
+        for scenario in runs.iter().flat_map(|r| &r.scenarios) {
+            let outcome = outcomes.entry((scenario.path.clone(), scenario.line, scenario.scenario.clone())).or_default();
+            outcome.0 += 1;
+            outcome.1 += usize::from(!scenario.passed);
+            for step in &scenario.steps {
+                let total = steps.entry(step.step.clone()).or_default();
+                total.0 += Duration::from_millis(step.duration_ms);
+                total.1 += 1;
+            }
+            for topic in &scenario.failure_topics {
+                *topics.entry(topic.clone()).or_default() += 1;
+            }
+        }
+        let mut flaky: Vec<Flaky> = outcomes
+            .into_iter()
+            .filter(|(_, (runs, failed))| *failed > 0 && failed < runs)
+            .map(|((path, line, scenario), (runs, failed))| Flaky { path, line, scenario, runs, failed })
+            .collect();
+        flaky.sort_by_key(|f| std::cmp::Reverse(f.failed));
+        let mut slowest: Vec<SlowStep> = steps.into_iter().map(|(step, (total, runs))| SlowStep { step, mean: total / runs as u32, runs }).collect();
+        slowest.sort_by_key(|s| std::cmp::Reverse(s.mean));
+        slowest.truncate(top);
+        let mut failure_topics: Vec<(String, usize)> = topics.into_iter().collect();
+        failure_topics.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+        Self { runs: runs.len(), flaky, slowest, failure_topics }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} runs", self.runs)?;
+        writeln!(f, "[Flaky scenarios]")?;
+        for s in &self.flaky {
+            writeln!(f, "  {:>3}/{:<3} failed  {}:{} {}", s.failed, s.runs, s.path, s.line, s.scenario)?;
+        }
+        writeln!(f, "[Slowest steps]")?;
+        for s in &self.slowest {
+            writeln!(f, "  {:>9.3}s mean over {:>3} runs  {}", s.mean.as_secs_f64(), s.runs, s.step)?;
+        }
+        writeln!(f, "[Failure topics]")?;
+        for (topic, n) in &self.failure_topics {
+            writeln!(f, "  {:>5}  {}", n, topic)?;
+        }
+        Ok(())
+    }
+}
+
+fn relative_path(feature: &Feature) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    feature.path.as_ref().map(|p| p.strip_prefix(&cwd).unwrap_or(p).display().to_string()).unwrap_or_default()
+}
+
+/// Writer appending the run to the history file when it finishes. Meant to be tee'd next to the
+/// regular output writer; durations come from the event timestamps, like the timing report's.
+#[derive(Debug, Default)]
+pub struct History {
+    cli: Cli,
+    started: Option<SystemTime>,
+    /// Scenarios and steps running, by feature path and line
+    running: HashMap<(String, usize), (SystemTime, ScenarioRun)>,
+    step_started: HashMap<(String, usize, usize), SystemTime>,
+    finished: Vec<ScenarioRun>,
+}
+
+impl History {
+    pub fn new(cli: Cli) -> Self {
+        Self { cli, ..Self::default() }
+    }
+
+    fn on_scenario<W>(&mut self, feature: &Feature, scenario: &Scenario, ev: &event::Scenario<W>, at: SystemTime) {
+        let path = relative_path(feature);
+        let key = (path.clone(), scenario.position.line);
+        match ev {
+            event::Scenario::Started => {
+                let run = ScenarioRun { path, line: scenario.position.line, scenario: scenario.name.clone(), passed: true, ..ScenarioRun::default() };
+                self.running.insert(key, (at, run));
+            }
+            event::Scenario::Step(step, sev) | event::Scenario::Background(step, sev) => {
+                let step_key = (key.0.clone(), key.1, step.position.line);
+                let (passed, error) = match sev {
+                    event::Step::Started => {
+                        self.step_started.insert(step_key, at);
+                        return;
+                    }
+                    event::Step::Skipped => {
+                        self.step_started.remove(&step_key);
+                        return;
+                    }
+                    event::Step::Passed(..) => (true, None),
+                    event::Step::Failed(_, _, _, err) => (false, Some(err.to_string())),
+                };
+                let (Some(start), Some((_, run))) = (self.step_started.remove(&step_key), self.running.get_mut(&key)) else { return };
+                let text = format!("{}{}", step.keyword, step.value);
+                if let Some(error) = error {
+                    run.passed = false;
+                    let mut topics = topics_in(&text);
+                    for topic in topics_in(&error) {
+                        if !topics.contains(&topic) {
+                            topics.push(topic);
+                        }
+                    }
+                    run.failure_topics = topics;
+                }
+                let duration_ms = at.duration_since(start).unwrap_or_default().as_millis() as u64;
+                run.steps.push(StepRun { step: text, duration_ms, passed });
+            }
+            event::Scenario::Hook(_, event::Hook::Failed(..)) => {
+                if let Some((_, run)) = self.running.get_mut(&key) {
+                    run.passed = false;
+                }
+            }
+            event::Scenario::Finished => {
+                if let Some((start, mut run)) = self.running.remove(&key) {
+                    run.duration_ms = at.duration_since(start).unwrap_or_default().as_millis() as u64;
+                    self.finished.push(run);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        let path = &self.cli.history;
+        let started = self.started.unwrap_or_else(SystemTime::now);
+        let run = Run { started: humantime::format_rfc3339_seconds(started).to_string(), scenarios: std::mem::take(&mut self.finished) };
+        if let Err(e) = append(path, &run) {
+            eprintln!("saving run history: {:#}", e);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World> Writer<W> for History {
+    type Cli = cli::Empty;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, _: &Self::Cli) {
+        let Ok(Event { value, at, .. }) = ev else { return };
+        match value {
+            event::Cucumber::Started => self.started = Some(at),
+            event::Cucumber::Feature(feature, event::Feature::Scenario(scenario, ev))
+            | event::Cucumber::Feature(feature, event::Feature::Rule(_, event::Rule::Scenario(scenario, ev))) => {
+                self.on_scenario(&feature, &scenario, &ev.event, at)
+            }
+            event::Cucumber::Finished => self.finish(),
+            _ => {}
+        }
+    }
+}
+
+// scenarios and steps are keyed by path and line, so arrival order doesn't matter
+impl writer::Normalized for History {}
@@ -0,0 +1,340 @@
+//! HPACK (RFC 7541), the header compression of HTTP/2, for the gRPC client. Headers are encoded
+//! as literals without indexing, which every peer accepts; decoding handles the whole format,
+//! Huffman-coded strings and the dynamic table included.
+
+use anyhow::{bail, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+/// Dynamic table size both sides start with (SETTINGS_HEADER_TABLE_SIZE)
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+/// Encode `headers` as literals without indexing, naming static table entries by index
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, value) in headers {
+        match STATIC_TABLE.iter().position(|(n, _)| n == name) {
+            Some(i) => encode_int(&mut out, 0x00, 4, i + 1),
+            None => {
+                out.push(0x00);
+                encode_str(&mut out, name.as_bytes());
+            }
+        }
+        encode_str(&mut out, value.as_bytes());
+    }
+    out
+}
+
+fn encode_str(out: &mut Vec<u8>, s: &[u8]) {
+    encode_int(out, 0x00, 7, s.len());
+    out.extend_from_slice(s);
+}
+
+/// `value` with an N-bit prefix, the bits above it taken from `flags`
+fn encode_int(out: &mut Vec<u8>, flags: u8, prefix: u8, mut value: usize) {
+    let max = (1usize << prefix) - 1;
+    if value < max {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Decodes the header blocks of one connection, keeping its dynamic table between them
+#[derive(Debug)]
+pub struct Decoder {
+    table: VecDeque<(String, String)>,
+    size: usize,
+    /// Largest size the peer may set with a size update
+    max_size: usize,
+    capacity: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self { table: VecDeque::new(), size: 0, max_size: DEFAULT_TABLE_SIZE, capacity: DEFAULT_TABLE_SIZE }
+    }
+}
+
+impl Decoder {
+    /// A decoder for a peer told SETTINGS_HEADER_TABLE_SIZE `size`
+    pub fn with_table_size(size: usize) -> Self {
+        Self { max_size: size, capacity: size, ..Self::default() }
+    }
+
+    /// Entries in the dynamic table, newest first
+    pub fn table(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.table.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The headers of a complete header block, in order
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>> {
+        let mut headers = Vec::new();
+        let mut pos = 0;
+        while pos < block.len() {
+            let b = block[pos];
+            if b & 0x80 != 0 {
+                let index = decode_int(block, &mut pos, 7)?;
+                headers.push(self.entry(index)?);
+            } else if b & 0x40 != 0 {
+                let header = self.literal(block, &mut pos, 6)?;
+                self.insert(header.clone());
+                headers.push(header);
+            } else if b & 0x20 != 0 {
+                let size = decode_int(block, &mut pos, 5)?;
+                if size > self.max_size {
+                    bail!("HPACK table size update to {} is over the {} allowed", size, self.max_size);
+                }
+                self.capacity = size;
+                self.evict(0);
+            } else {
+                // without indexing, or never indexed
+                headers.push(self.literal(block, &mut pos, 4)?);
+            }
+        }
+        Ok(headers)
+    }
+
+    fn literal(&self, block: &[u8], pos: &mut usize, prefix: u8) -> Result<(String, String)> {
+        let index = decode_int(block, pos, prefix)?;
+        let name = match index {
+            0 => decode_str(block, pos)?,
+            i => self.entry(i)?.0,
+        };
+        Ok((name, decode_str(block, pos)?))
+    }
+
+    fn entry(&self, index: usize) -> Result<(String, String)> {
+        match index {
+            0 => bail!("HPACK index 0"),
+            i if i <= STATIC_TABLE.len() => {
+                let (name, value) = STATIC_TABLE[i - 1];
+                Ok((name.to_string(), value.to_string()))
+            }
+            i => match self.table.get(i - STATIC_TABLE.len() - 1) {
+                Some(entry) => Ok(entry.clone()),
+                None => bail!("HPACK index {} is past the {} dynamic table entries", i, self.table.len()),
+            },
+        }
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        let size = entry_size(&header);
+        self.evict(size);
+        // an entry larger than the whole table empties it and isn't added
+        if size <= self.capacity {
+            self.size += size;
+            self.table.push_front(header);
+        }
+    }
+
+    /// Drop the oldest entries until `room` more fits
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.capacity {
+            match self.table.pop_back() {
+                Some(entry) => self.size -= entry_size(&entry),
+                None => break,
+            }
+        }
+    }
+}
+
+fn entry_size((name, value): &(String, String)) -> usize {
+    name.len() + value.len() + 32
+}
+
+fn decode_int(block: &[u8], pos: &mut usize, prefix: u8) -> Result<usize> {
+    let max = (1usize << prefix) - 1;
+    let Some(&first) = block.get(*pos) else { bail!("HPACK block ends in an integer") };
+    *pos += 1;
+    let mut value = (first as usize) & max;
+    if value < max {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let Some(&b) = block.get(*pos) else { bail!("HPACK block ends in an integer") };
+        *pos += 1;
+        if shift > 28 {
+            bail!("HPACK integer too large");
+        }
+        value += ((b & 0x7f) as usize) << shift;
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn decode_str(block: &[u8], pos: &mut usize) -> Result<String> {
+    let huffman = block.get(*pos).is_some_and(|b| b & 0x80 != 0);
+    let len = decode_int(block, pos, 7)?;
+    let Some(raw) = block.get(*pos..*pos + len) else { bail!("HPACK string of {} bytes runs past the block", len) };
+    *pos += len;
+    let bytes = if huffman { huffman_decode(raw)? } else { raw.to_vec() };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Huffman-encode `data` with the HPACK code, padding the last byte with ones
+pub fn huffman_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let (mut bits, mut held) = (0u64, 0u32);
+    for &b in data {
+        let (code, len) = HUFFMAN[b as usize];
+        bits = (bits << len) | code as u64;
+        held += len as u32;
+        while held >= 8 {
+            held -= 8;
+            out.push((bits >> held) as u8);
+        }
+    }
+    if held > 0 {
+        out.push(((bits << (8 - held)) as u8) | (0xff >> held));
+    }
+    out
+}
+
+pub fn huffman_decode(data: &[u8]) -> Result<Vec<u8>> {
+    static CODES: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+    let codes = CODES.get_or_init(|| HUFFMAN.iter().enumerate().map(|(sym, &(code, len))| ((len, code), sym as u16)).collect());
+    let mut out = Vec::new();
+    let (mut code, mut len) = (0u32, 0u8);
+    for &byte in data {
+        for bit in (0..8).rev() {
+            code = (code << 1) | ((byte >> bit) & 1) as u32;
+            len += 1;
+            match codes.get(&(len, code)) {
+                Some(256) => bail!("EOS in a Huffman-coded HPACK string"),
+                Some(&sym) => {
+                    out.push(sym as u8);
+                    (code, len) = (0, 0);
+                }
+                None if len > 30 => bail!("invalid Huffman code in an HPACK string"),
+                None => {}
+            }
+        }
+    }
+    // what is left must be padding: fewer than 8 bits, all ones
+    if len > 7 || code != (1 << len) - 1 {
+        bail!("invalid Huffman padding in an HPACK string");
+    }
+    Ok(out)
+}
+
+/// (code, length in bits) of each byte, then EOS (RFC 7541 appendix B)
+const HUFFMAN: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28), (0xfffffe5, 28),
+    (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28),
+    (0xfffffef, 28), (0xffffff0, 28), (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10),
+    (0xf9, 8), (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6),
+    (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7),
+    (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5),
+    (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14),
+    (0x1ffd, 13), (0xffffffc, 28), (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23),
+    (0x7fffdb, 23), (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23),
+    (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21),
+    (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22),
+    (0x7fffeb, 23), (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23),
+    (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21),
+    (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28), (0x7ffffe3, 27),
+    (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23), (0x3fffea, 22), (0x3fffeb, 22),
+    (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27),
+    (0x7ffffe9, 27), (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+/// RFC 7541 appendix A
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
@@ -0,0 +1,584 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::TlsProfile;
+use crate::matchers::MatchOptions;
+use crate::proto_dyn::json_partial_match_with;
+use crate::tls::{self, TlsStream};
+
+/// Target of an `http://` or `https://host[:port]/path` URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+    /// https
+    pub tls: bool,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self> {
+        let (rest, tls) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+            (Some(rest), _) => (rest, false),
+            (None, Some(rest)) => (rest, true),
+            _ => bail!("expected an http:// or https:// URL, got {}", url),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((h, p)) if !p.contains(']') => {
+                (h, p.parse().map_err(|_| anyhow!("invalid port in {}", url))?)
+            }
+            _ => (authority, if tls { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            bail!("missing host in {}", url);
+        }
+        Ok(Self { host: host.to_string(), port, path: path.to_string(), tls })
+    }
+}
+
+/// One dispatched Server-Sent Event
+#[derive(Debug, Clone)]
+pub struct SseEvent {
+    /// Event type; "message" when the stream gives none
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+    pub at: Instant,
+    /// Set once an expectation matched this event, so it isn't matched twice
+    pub consumed: bool,
+}
+
+impl SseEvent {
+    /// Data as JSON, or as a JSON string when it isn't valid JSON
+    pub fn json(&self) -> JsonValue {
+        serde_json::from_str(&self.data).unwrap_or_else(|_| JsonValue::String(self.data.clone()))
+    }
+}
+
+/// Line-by-line parser for the text/event-stream format
+#[derive(Debug, Default)]
+pub struct SseParser {
+    event: String,
+    data: String,
+    id: Option<String>,
+}
+
+impl SseParser {
+    /// Feed one line (with or without its line ending); returns an event when a blank line dispatches one
+    pub fn feed(&mut self, line: &str) -> Option<SseEvent> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            let data = std::mem::take(&mut self.data);
+            if data.is_empty() {
+                return None;
+            }
+            return Some(SseEvent {
+                event: if event.is_empty() { "message".to_string() } else { event },
+                data: data.strip_suffix('\n').unwrap_or(&data).to_string(),
+                id: self.id.clone(),
+                at: Instant::now(),
+                consumed: false,
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body
+struct Chunked<R> {
+    inner: R,
+    left: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.left == 0 {
+            let mut line = String::new();
+            // the CRLF closing the previous chunk comes first
+            while line.trim().is_empty() {
+                line.clear();
+                if self.inner.read_line(&mut line)? == 0 {
+                    self.done = true;
+                    return Ok(0);
+                }
+            }
+            let size = line.trim().split(';').next().unwrap_or_default().trim();
+            self.left = usize::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad chunk size {:?}", size)))?;
+            if self.left == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let max = buf.len().min(self.left);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.left -= n;
+        if n == 0 {
+            self.done = true;
+        }
+        Ok(n)
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    events: Mutex<VecDeque<SseEvent>>,
+    arrived: Condvar,
+}
+
+/// The connection under an SSE subscription or a gRPC client
+pub(crate) enum Connection {
+    Tcp(TcpStream),
+    Tls(TlsStream),
+}
+
+impl Connection {
+    pub(crate) fn close(&mut self) {
+        match self {
+            Self::Tcp(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Self::Tls(stream) => stream.close(),
+        }
+    }
+
+    /// `error` with openssl's explanation when the TLS connection failed
+    pub(crate) fn failure(self, error: anyhow::Error) -> anyhow::Error {
+        match self {
+            Self::Tcp(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                error
+            }
+            Self::Tls(stream) => match stream.failure() {
+                report if report.is_empty() => error,
+                report => error.context(format!("TLS: {}", report)),
+            },
+        }
+    }
+}
+
+/// A connection with its request and response halves
+pub(crate) type Opened = (Connection, Box<dyn Write + Send>, Box<dyn Read + Send>);
+
+/// Connect to `target`, through openssl for https, waiting up to `timeout` for the TLS handshake;
+/// `alpn` is the protocol to ask for in it
+pub(crate) fn open(target: &Url, url: &str, profile: &TlsProfile, alpn: Option<&str>, timeout: Duration) -> Result<Opened> {
+    Ok(if target.tls {
+        let (stream, writer, reader) = match alpn {
+            Some(protocol) => TlsStream::connect_alpn(&target.host, target.port, profile, protocol, timeout),
+            None => TlsStream::connect(&target.host, target.port, profile, timeout),
+        }
+        .with_context(|| format!("connect {}", url))?;
+        (Connection::Tls(stream), Box::new(writer), Box::new(reader))
+    } else {
+        let stream = TcpStream::connect((target.host.trim_matches(['[', ']']), target.port)).with_context(|| format!("connect {}", url))?;
+        (Connection::Tcp(stream.try_clone()?), Box::new(stream.try_clone()?), Box::new(stream))
+    })
+}
+
+/// GET `url` and parse the JSON it answers with; https uses `profile`
+pub fn get_json(url: &str, profile: &TlsProfile, timeout: Duration) -> Result<JsonValue> {
+    let target = Url::parse(url)?;
+    let (mut connection, mut writer, reader) = open(&target, url, profile, None, timeout)?;
+    let request = write!(
+        writer,
+        "GET {} HTTP/1.1\r\nHost: {}:{}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+        target.path, target.host, target.port
+    )
+    .and_then(|_| writer.flush());
+    if let Err(e) = request {
+        return Err(connection.failure(anyhow::Error::new(e).context(format!("send request to {}", url))));
+    }
+    drop(writer);
+
+    // read on a thread, so a silent server can't block past `timeout`; closing ends it
+    let (tx, rx) = mpsc::channel();
+    let thread_url = url.to_string();
+    std::thread::Builder::new()
+        .name("bdd-http".to_string())
+        .spawn(move || {
+            let _ = tx.send(read_response(reader, &thread_url));
+        })
+        .context("spawn HTTP thread")?;
+    let body = match rx.recv_timeout(timeout) {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return Err(connection.failure(e)),
+        Err(_) => return Err(connection.failure(anyhow!("no response from {} within {:?}", url, timeout))),
+    };
+    connection.close();
+    serde_json::from_slice(&body).with_context(|| format!("{} answered with invalid JSON", url))
+}
+
+/// Read a whole response; anything but 200 is an error quoting the body
+fn read_response(stream: Box<dyn Read + Send>, url: &str) -> Result<Vec<u8>> {
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).with_context(|| format!("read response from {}", url))?;
+    if status.is_empty() {
+        bail!("{} closed the connection without a response", url);
+    }
+    let (mut chunked, mut length) = (false, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+                "content-length" => length = value.trim().parse::<u64>().ok(),
+                _ => {}
+            }
+        }
+    }
+    let mut body = Vec::new();
+    match (chunked, length) {
+        (true, _) => Chunked { inner: reader, left: 0, done: false }.read_to_end(&mut body)?,
+        (false, Some(length)) => reader.take(length).read_to_end(&mut body)?,
+        (false, None) => reader.read_to_end(&mut body)?,
+    };
+    if status.split_whitespace().nth(1) != Some("200") {
+        bail!("{} answered {}: {}", url, status.trim(), String::from_utf8_lossy(&body).trim());
+    }
+    Ok(body)
+}
+
+/// Subscription to a Server-Sent Events endpoint; a background thread buffers every event
+pub struct SseClient {
+    url: String,
+    /// Taken when a failed connection is torn down
+    connection: Option<Connection>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SseClient {
+    /// Open `url` and wait for the response headers; https uses the default TLS profile
+    pub fn connect(url: &str, timeout: Duration) -> Result<Self> {
+        Self::connect_with(url, &tls::profile(None)?, timeout)
+    }
+
+    /// Open `url`, using `profile` if it is https, and wait for the response headers
+    pub fn connect_with(url: &str, profile: &TlsProfile, timeout: Duration) -> Result<Self> {
+        let target = Url::parse(url)?;
+        let (connection, mut writer, reader) = open(&target, url, profile, None, timeout)?;
+        let request = write!(
+            writer,
+            "GET {} HTTP/1.1\r\nHost: {}:{}\r\nAccept: text/event-stream\r\nCache-Control: no-cache\r\n\r\n",
+            target.path, target.host, target.port
+        )
+        .and_then(|_| writer.flush());
+        if let Err(e) = request {
+            return Err(connection.failure(anyhow::Error::new(e).context(format!("send request to {}", url))));
+        }
+        drop(writer);
+
+        // headers are read on the reader thread too, so a silent server can't block past `timeout`
+        let (headers_tx, headers_rx) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let thread_shared = shared.clone();
+        let thread_url = url.to_string();
+        let thread = std::thread::Builder::new()
+            .name("bdd-sse".to_string())
+            .spawn(move || match read_headers(reader, &thread_url) {
+                Ok(body) => {
+                    let _ = headers_tx.send(Ok(()));
+                    read_events(body, thread_shared);
+                }
+                Err(e) => {
+                    let _ = headers_tx.send(Err(e));
+                }
+            })
+            .context("spawn SSE thread")?;
+        let mut client = Self { url: url.to_string(), connection: Some(connection), shared, thread: Some(thread) };
+        match headers_rx.recv_timeout(timeout) {
+            Ok(Ok(())) => Ok(client),
+            Ok(Err(e)) => Err(client.fail(e)),
+            Err(_) => Err(client.fail(anyhow!("no response from {} within {:?}", url, timeout))),
+        }
+    }
+
+    /// Tear down a subscription that never got going
+    fn fail(&mut self, error: anyhow::Error) -> anyhow::Error {
+        let error = match self.connection.take() {
+            Some(connection) => connection.failure(error),
+            None => error,
+        };
+        self.close();
+        error
+    }
+
+    /// Wait for an unconsumed `event` whose data partially matches `expected`, and consume it
+    pub fn expect_event(&self, event: &str, expected: &JsonValue, timeout: Duration, opts: MatchOptions) -> Result<JsonValue> {
+        let deadline = Instant::now() + timeout;
+        let mut events = self.shared.events.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            for e in events.iter_mut().filter(|e| !e.consumed && e.event == event) {
+                let data = e.json();
+                if json_partial_match_with(expected, &data, opts)? {
+                    e.consumed = true;
+                    return Ok(data);
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                let seen = events.iter().filter(|e| e.event == event).count();
+                bail!("no SSE event {} matching {} from {} within {:?} ({} {} events seen)", event, expected, self.url, timeout, seen, event);
+            }
+            events = self
+                .shared
+                .arrived
+                .wait_timeout(events, deadline - now)
+                .map(|(guard, _)| guard)
+                .unwrap_or_else(|e| e.into_inner().0);
+        }
+    }
+
+    /// Close the connection and stop the reader thread
+    pub fn close(&mut self) {
+        if let Some(connection) = &mut self.connection {
+            connection.close();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SseClient {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl std::fmt::Debug for SseClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SseClient")
+            .field("url", &self.url)
+            .field("buffered", &self.shared.events.lock().map(|e| e.len()).unwrap_or_default())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+/// Check the response status and headers; returns the body
+fn read_headers(stream: Box<dyn Read + Send>, url: &str) -> Result<Box<dyn BufRead + Send>> {
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).with_context(|| format!("read response from {}", url))?;
+    if status.is_empty() {
+        bail!("{} closed the connection without a response", url);
+    }
+    let code = status.split_whitespace().nth(1).unwrap_or_default();
+    if code != "200" {
+        bail!("{} answered {}", url, status.trim());
+    }
+    let mut chunked = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase());
+            if name == "transfer-encoding" && value.contains("chunked") {
+                chunked = true;
+            }
+            if name == "content-type" && !value.starts_with("text/event-stream") {
+                bail!("{} is not an event stream (content-type {})", url, value);
+            }
+        }
+    }
+    Ok(if chunked { Box::new(BufReader::new(Chunked { inner: reader, left: 0, done: false })) } else { Box::new(reader) })
+}
+
+fn read_events(mut body: Box<dyn BufRead + Send>, shared: Arc<Shared>) {
+    let mut parser = SseParser::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match body.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if let Some(event) = parser.feed(&line) {
+            shared.events.lock().unwrap_or_else(|e| e.into_inner()).push_back(event);
+            shared.arrived.notify_all();
+        }
+    }
+}
+
+/// Largest request body [`Request::read`] takes; a longer Content-Length is answered with 413
+pub const MAX_BODY: usize = 16 << 20;
+
+/// A request whose Content-Length is over [`MAX_BODY`]
+#[derive(Debug)]
+pub struct BodyTooLarge(pub usize);
+
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request body of {} bytes is over the {} byte limit", self.0, MAX_BODY)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// A parsed HTTP/1.1 request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read one request; None when the client closed the connection first. A body over
+    /// [`MAX_BODY`] fails with [`BodyTooLarge`] before any of it is read.
+    pub fn read(reader: &mut impl BufRead) -> Result<Option<Self>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else { bail!("malformed request line {:?}", line.trim()) };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|kv| !kv.is_empty())
+            .map(|kv| {
+                let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                (k.to_string(), v.to_string())
+            })
+            .collect();
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().map_err(|_| anyhow!("bad content-length {}", value.trim()))?;
+                }
+            }
+        }
+        if length > MAX_BODY {
+            return Err(BodyTooLarge(length).into());
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        Ok(Some(Self { method: method.to_string(), path: path.to_string(), query, body }))
+    }
+
+    /// Body as JSON; an empty body is `{}`
+    pub fn json(&self) -> Result<JsonValue> {
+        if self.body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(serde_json::json!({}));
+        }
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// A JSON response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: JsonValue,
+}
+
+impl Response {
+    pub fn ok(body: JsonValue) -> Self {
+        Self { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self { status, body: serde_json::json!({ "error": message.to_string() }) }
+    }
+
+    /// The answer to a request [`Request::read`] failed on: 413 for [`BodyTooLarge`], else 400
+    pub fn unreadable(err: &anyhow::Error) -> Self {
+        let status = if err.is::<BodyTooLarge>() { 413 } else { 400 };
+        Self::error(status, format!("{:#}", err))
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let body = self.body.to_string();
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason(self.status),
+            body.len(),
+            body
+        )?;
+        out.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        _ => "Error",
+    }
+}
+
+/// Answer requests on `listener` one connection at a time, forever
+pub fn serve(listener: TcpListener, mut handle: impl FnMut(&Request) -> Response) -> Result<()> {
+    for conn in listener.incoming() {
+        let conn = match conn {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("accept: {}", e);
+                continue;
+            }
+        };
+        let mut reader = BufReader::new(&conn);
+        let response = match Request::read(&mut reader) {
+            Ok(Some(req)) => handle(&req),
+            Ok(None) => continue,
+            Err(e) => Response::unreadable(&e),
+        };
+        if let Err(e) = response.write(&mut &conn) {
+            eprintln!("write response: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Feature files in other languages. Gherkin keywords are localized by a `# language: fr` header
+//! on the feature file; step texts are mapped onto the English step definitions through the
+//! `[[translations]]` table in bdd.toml.
+
+use anyhow::{Context, Result};
+use cucumber::gherkin::{Feature, Step};
+use cucumber::parser::{self, Parser};
+use futures::stream::{LocalBoxStream, StreamExt};
+use regex::Regex;
+use std::sync::Arc;
+
+use crate::config::Translation;
+
+/// Compiled `[[translations]]`
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Translations {
+    pub fn new(translations: &[Translation]) -> Result<Self> {
+        let rules = translations
+            .iter()
+            .map(|t| Ok((Regex::new(&t.pattern).with_context(|| format!("translation pattern {}", t.pattern))?, t.step.clone())))
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// English text for a localized step, from the first matching translation
+    pub fn translate(&self, text: &str) -> Option<String> {
+        self.rules.iter().find_map(|(re, step)| {
+            let caps = re.captures(text)?;
+            let mut out = String::new();
+            caps.expand(step, &mut out);
+            Some(out)
+        })
+    }
+
+    /// Rewrite every step of `feature` that has a translation; other steps are kept as written
+    pub fn apply(&self, feature: &mut Feature) {
+        let mut steps: Vec<&mut Vec<Step>> = Vec::new();
+        steps.extend(feature.background.as_mut().map(|b| &mut b.steps));
+        steps.extend(feature.scenarios.iter_mut().map(|s| &mut s.steps));
+        for rule in &mut feature.rules {
+            steps.extend(rule.background.as_mut().map(|b| &mut b.steps));
+            steps.extend(rule.scenarios.iter_mut().map(|s| &mut s.steps));
+        }
+        for step in steps.into_iter().flatten() {
+            if let Some(text) = self.translate(&step.value) {
+                step.value = text;
+            }
+        }
+    }
+}
+
+/// Parser translating the steps of every feature read by `inner`
+#[derive(Debug, Clone)]
+pub struct Translated<P = parser::Basic> {
+    inner: P,
+    translations: Arc<Translations>,
+}
+
+impl<P> Translated<P> {
+    pub fn new(inner: P, translations: &[Translation]) -> Result<Self> {
+        Ok(Self { inner, translations: Arc::new(Translations::new(translations)?) })
+    }
+}
+
+impl<I, P: Parser<I>> Parser<I> for Translated<P> {
+    type Cli = P::Cli;
+    type Output = LocalBoxStream<'static, parser::Result<Feature>>;
+
+    fn parse(self, input: I, cli: Self::Cli) -> Self::Output {
+        let translations = self.translations;
+        self.inner
+            .parse(input, cli)
+            .map(move |feature| {
+                feature.map(|mut f| {
+                    translations.apply(&mut f);
+                    f
+                })
+            })
+            .boxed_local()
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value as JsonValue;
+
+/// One step of a parsed JSONPath
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Field(String),
+    Index(i64),
+    Wildcard,
+}
+
+/// Parse the JSONPath subset used in steps: `$`, `.name`, `['name']`, `[2]`, `[-1]`, `.*` and `[*]`
+pub fn parse(path: &str) -> Result<Vec<Segment>> {
+    let rest = path.trim().strip_prefix('$').ok_or_else(|| anyhow!("JSONPath must start with $: {}", path))?;
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end] != '.' && chars[end] != '[' {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                if name.is_empty() {
+                    bail!("empty field name in JSONPath {}", path);
+                }
+                segments.push(if name == "*" { Segment::Wildcard } else { Segment::Field(name) });
+                i = end;
+            }
+            '[' => {
+                // a quoted name may hold ']', as an extension's ['[vendor.ext.temperature]'] does
+                let quote = chars.get(i + 1).copied().filter(|c| *c == '\'' || *c == '"');
+                let close = match quote {
+                    Some(q) => (i + 2..chars.len()).find(|&j| chars[j] == q && chars.get(j + 1) == Some(&']')).map(|j| j + 1),
+                    None => chars[i..].iter().position(|c| *c == ']').map(|p| p + i),
+                }
+                .ok_or_else(|| anyhow!("unclosed [ in JSONPath {}", path))?;
+                let inner: String = chars[i + 1..close].iter().collect();
+                let inner = inner.trim();
+                let quoted = inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+                segments.push(if let Some(name) = quoted {
+                    Segment::Field(name.to_string())
+                } else if inner == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Index(inner.parse().map_err(|_| anyhow!("invalid index [{}] in JSONPath {}", inner, path))?)
+                });
+                i = close + 1;
+            }
+            c => bail!("unexpected '{}' in JSONPath {}", c, path),
+        }
+    }
+    Ok(segments)
+}
+
+/// JSONPath for a step's field argument; a bare `state` or `header.seq_no` is taken from the root
+pub fn field_path(field: &str) -> String {
+    if field.starts_with('$') { field.to_string() } else { format!("$.{}", field) }
+}
+
+/// Whether the object holding the field at `path` is in `root`. Proto3 leaves out fields holding
+/// their default, so a field missing from a present parent holds its default.
+pub fn parent_present(root: &JsonValue, path: &str) -> bool {
+    path.rsplit_once('.').is_some_and(|(parent, _)| select_one(root, parent).is_ok_and(JsonValue::is_object))
+}
+
+/// Evaluate `path` against `root`, returning every matched node (empty when nothing matches)
+pub fn select<'a>(root: &'a JsonValue, path: &str) -> Result<Vec<&'a JsonValue>> {
+    let mut current = vec![root];
+    for segment in parse(path)? {
+        let mut next = Vec::new();
+        for node in current {
+            match (&segment, node) {
+                (Segment::Field(name), JsonValue::Object(map)) => next.extend(map.get(name)),
+                (Segment::Index(idx), JsonValue::Array(items)) => next.extend(index(*idx, items.len()).and_then(|i| items.get(i))),
+                (Segment::Wildcard, JsonValue::Array(items)) => next.extend(items.iter()),
+                (Segment::Wildcard, JsonValue::Object(map)) => next.extend(map.values()),
+                _ => {}
+            }
+        }
+        current = next;
+    }
+    Ok(current)
+}
+
+/// Evaluate a path expected to match exactly one node
+pub fn select_one<'a>(root: &'a JsonValue, path: &str) -> Result<&'a JsonValue> {
+    let found = select(root, path)?;
+    match found.as_slice() {
+        [one] => Ok(one),
+        [] => bail!("{} matched nothing", path),
+        many => bail!("{} matched {} values, expected one", path, many.len()),
+    }
+}
+
+/// Set the field at `path`, a chain of field names, creating the objects on the way
+pub fn set(root: &mut JsonValue, path: &str, value: JsonValue) -> Result<()> {
+    let segments = parse(path)?;
+    let Some((last, parents)) = segments.split_last() else { bail!("cannot set the root of {}", path) };
+    let mut node = root;
+    for segment in parents.iter().chain([last]) {
+        let Segment::Field(name) = segment else { bail!("can only set fields by name, not {}", path) };
+        let JsonValue::Object(map) = node else { bail!("{} goes below a value that is not an object", path) };
+        node = map.entry(name.clone()).or_insert_with(|| JsonValue::Object(Default::default()));
+    }
+    *node = value;
+    Ok(())
+}
+
+/// Remove every node matched by `path` from `root`, returning how many were removed
+pub fn remove(root: &mut JsonValue, path: &str) -> Result<usize> {
+    let segments = parse(path)?;
+    if segments.is_empty() {
+        bail!("cannot remove the root of {}", path);
+    }
+    Ok(remove_at(root, &segments))
+}
+
+fn remove_at(node: &mut JsonValue, segments: &[Segment]) -> usize {
+    match segments {
+        [] => 0,
+        [last] => match (last, node) {
+            (Segment::Field(name), JsonValue::Object(map)) => map.remove(name).map_or(0, |_| 1),
+            (Segment::Index(idx), JsonValue::Array(items)) => match index(*idx, items.len()) {
+                Some(i) => {
+                    items.remove(i);
+                    1
+                }
+                None => 0,
+            },
+            (Segment::Wildcard, JsonValue::Array(items)) => std::mem::take(items).len(),
+            (Segment::Wildcard, JsonValue::Object(map)) => std::mem::take(map).len(),
+            _ => 0,
+        },
+        [first, rest @ ..] => {
+            let children: Vec<&mut JsonValue> = match (first, node) {
+                (Segment::Field(name), JsonValue::Object(map)) => map.get_mut(name).into_iter().collect(),
+                (Segment::Index(idx), JsonValue::Array(items)) => {
+                    let len = items.len();
+                    index(*idx, len).and_then(|i| items.get_mut(i)).into_iter().collect()
+                }
+                (Segment::Wildcard, JsonValue::Array(items)) => items.iter_mut().collect(),
+                (Segment::Wildcard, JsonValue::Object(map)) => map.values_mut().collect(),
+                _ => Vec::new(),
+            };
+            children.into_iter().map(|c| remove_at(c, rest)).sum()
+        }
+    }
+}
+
+/// Position of `idx` (negative counts from the end) in a list of `len` items
+fn index(idx: i64, len: usize) -> Option<usize> {
+    let idx = if idx < 0 { len as i64 + idx } else { idx };
+    (0..len as i64).contains(&idx).then_some(idx as usize)
+}
@@ -0,0 +1,214 @@
+//! Packed-struct payloads, behind the `layout` codec. `[layouts.<topic>]` lists the fields in
+//! wire order: fixed-width integers, floats, bools and byte strings, or bitfields packed most
+//! significant bit first. Decoding gives a JSON object with a member per field, so legacy topics
+//! without a descriptor can be matched like any other.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+
+use crate::codec::{self, Codec};
+use crate::config::{ByteOrder, FieldType, LayoutConfig, LayoutField};
+
+/// How a field is laid out, once the config is checked
+enum Slot {
+    Bits(u32),
+    Typed { ty: FieldType, len: usize, order: ByteOrder },
+}
+
+impl FieldType {
+    /// Byte width of fixed-size types
+    fn width(self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 | FieldType::Bool => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => Some(4),
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => Some(8),
+            FieldType::Bytes | FieldType::Pad => None,
+        }
+    }
+}
+
+impl LayoutConfig {
+    /// Each field's slot; errors on fields that are neither typed nor bitfields, or typed fields
+    /// starting mid-byte
+    fn slots(&self) -> Result<Vec<(&LayoutField, Slot)>> {
+        let mut bit = 0;
+        let slots = self
+            .fields
+            .iter()
+            .map(|f| {
+                let slot = match (f.ty, f.bits, f.len) {
+                    (None, Some(bits @ 1..=64), None) => Slot::Bits(bits),
+                    (None, Some(bits), None) => bail!("{}: bitfields are 1 to 64 bits, not {}", f.name, bits),
+                    (Some(ty @ (FieldType::Bytes | FieldType::Pad)), None, Some(len)) => Slot::Typed { ty, len, order: self.byte_order },
+                    (Some(ty), None, None) if ty.width().is_some() => {
+                        Slot::Typed { ty, len: ty.width().unwrap_or_default(), order: f.byte_order.unwrap_or(self.byte_order) }
+                    }
+                    (Some(FieldType::Bytes | FieldType::Pad), None, None) => bail!("{}: bytes and pad fields need a len", f.name),
+                    _ => bail!("{}: give either a type (with len for bytes and pad) or bits", f.name),
+                };
+                match slot {
+                    Slot::Bits(bits) => bit += bits as usize,
+                    Slot::Typed { len, .. } if bit % 8 == 0 => bit += len * 8,
+                    Slot::Typed { .. } => bail!("{} starts mid-byte; pad the bitfields before it to a whole byte", f.name),
+                }
+                Ok((f, slot))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if bit % 8 != 0 {
+            bail!("the fields end mid-byte; pad the last bitfields to a whole byte");
+        }
+        Ok(slots)
+    }
+
+    /// Bytes a payload takes
+    pub fn size(&self) -> Result<usize> {
+        let bits: usize = self
+            .slots()?
+            .iter()
+            .map(|(_, slot)| match slot {
+                Slot::Bits(bits) => *bits as usize,
+                Slot::Typed { len, .. } => len * 8,
+            })
+            .sum();
+        Ok(bits / 8)
+    }
+
+    /// Pack `body`; fields it leaves out are zero
+    pub fn encode(&self, body: &JsonValue) -> Result<Vec<u8>> {
+        let slots = self.slots()?;
+        let object = body.as_object().ok_or_else(|| anyhow!("expected an object, got {}", body))?;
+        if let Some(unknown) = object.keys().find(|k| !slots.iter().any(|(f, slot)| &f.name == *k && !matches!(slot, Slot::Typed { ty: FieldType::Pad, .. }))) {
+            bail!("the layout has no field {}", unknown);
+        }
+        let mut out = Bits::default();
+        for (field, slot) in &slots {
+            let value = object.get(&field.name);
+            let wrong = |expected: &str| anyhow!("{}: expected {}, got {}", field.name, expected, value.unwrap_or(&JsonValue::Null));
+            match *slot {
+                Slot::Bits(bits) => {
+                    let n = value.map_or(Some(0), JsonValue::as_u64).filter(|&n| bits == 64 || n >> bits == 0);
+                    out.push(n.ok_or_else(|| wrong(&format!("an unsigned {}-bit number", bits)))?, bits);
+                }
+                Slot::Typed { ty: FieldType::Pad, len, .. } => out.bytes.resize(out.bytes.len() + len, 0),
+                Slot::Typed { ty: FieldType::Bytes, len, .. } => {
+                    let bytes = match value {
+                        None => vec![0; len],
+                        Some(v) => v.as_str().and_then(|s| general_purpose::STANDARD.decode(s).ok()).ok_or_else(|| wrong("base64 bytes"))?,
+                    };
+                    if bytes.len() != len {
+                        bail!("{}: expected {} bytes, got {}", field.name, len, bytes.len());
+                    }
+                    out.bytes.extend(bytes);
+                }
+                Slot::Typed { ty, len, order } => {
+                    let raw = match ty {
+                        FieldType::Bool => value.map_or(Some(false), JsonValue::as_bool).ok_or_else(|| wrong("a boolean"))? as u64,
+                        FieldType::F32 => (value.map_or(Some(0.0), JsonValue::as_f64).ok_or_else(|| wrong("a number"))? as f32).to_bits() as u64,
+                        FieldType::F64 => value.map_or(Some(0.0), JsonValue::as_f64).ok_or_else(|| wrong("a number"))?.to_bits(),
+                        FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64 => {
+                            let shift = 64 - len * 8;
+                            let n = value.map_or(Some(0), JsonValue::as_i64).filter(|&n| (n << shift) >> shift == n);
+                            n.ok_or_else(|| wrong(&format!("a signed {}-bit number", len * 8)))? as u64
+                        }
+                        _ => {
+                            let n = value.map_or(Some(0), JsonValue::as_u64).filter(|&n| len == 8 || n >> (len * 8) == 0);
+                            n.ok_or_else(|| wrong(&format!("an unsigned {}-bit number", len * 8)))?
+                        }
+                    };
+                    let bytes = raw.to_be_bytes();
+                    let bytes = &bytes[8 - len..];
+                    match order {
+                        ByteOrder::Big => out.bytes.extend(bytes),
+                        ByteOrder::Little => out.bytes.extend(bytes.iter().rev()),
+                    }
+                }
+            }
+        }
+        Ok(out.bytes)
+    }
+
+    /// Unpack `payload`, which must be exactly as long as the layout
+    pub fn decode(&self, payload: &[u8]) -> Result<JsonValue> {
+        let size = self.size()?;
+        if payload.len() != size {
+            bail!("payload is {} bytes, the layout {}", payload.len(), size);
+        }
+        let mut object = Map::new();
+        let mut bit = 0;
+        for (field, slot) in self.slots()? {
+            let value = match slot {
+                Slot::Bits(bits) => {
+                    let n = (bit..bit + bits as usize).fold(0u64, |n, i| (n << 1) | ((payload[i / 8] >> (7 - i % 8)) & 1) as u64);
+                    bit += bits as usize;
+                    JsonValue::from(n)
+                }
+                Slot::Typed { ty, len, order } => {
+                    let bytes = &payload[bit / 8..bit / 8 + len];
+                    bit += len * 8;
+                    let raw = match order {
+                        ByteOrder::Big => bytes.iter().fold(0u64, |n, b| (n << 8) | *b as u64),
+                        ByteOrder::Little => bytes.iter().rev().fold(0u64, |n, b| (n << 8) | *b as u64),
+                    };
+                    match ty {
+                        FieldType::Pad => continue,
+                        FieldType::Bytes => codec::binary(bytes),
+                        FieldType::Bool => JsonValue::Bool(raw != 0),
+                        FieldType::F32 => codec::float(f32::from_bits(raw as u32) as f64).with_context(|| field.name.clone())?,
+                        FieldType::F64 => codec::float(f64::from_bits(raw)).with_context(|| field.name.clone())?,
+                        FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64 => {
+                            let shift = 64 - len * 8;
+                            JsonValue::from(((raw << shift) as i64) >> shift)
+                        }
+                        _ => JsonValue::from(raw),
+                    }
+                }
+            };
+            object.insert(field.name.clone(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+}
+
+/// Output being packed, with bitfields filling bytes most significant bit first
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    /// Bits used in the last byte; 0 when it is full
+    used: u32,
+}
+
+impl Bits {
+    fn push(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.last_mut().expect("pushed above");
+            *last |= (((value >> i) & 1) as u8) << (7 - self.used);
+            self.used = (self.used + 1) % 8;
+        }
+    }
+}
+
+/// The `layout` codec, packing each topic as `[layouts]` describes it
+pub struct Layouts(pub HashMap<String, LayoutConfig>);
+
+impl Layouts {
+    fn layout(&self, topic: &str) -> Result<&LayoutConfig> {
+        self.0.get(topic).ok_or_else(|| anyhow!("no layout for {}; add it under [layouts.{}]", topic, topic))
+    }
+}
+
+impl Codec for Layouts {
+    fn encode(&self, topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        self.layout(topic)?.encode(body).with_context(|| format!("[layouts.{}]", topic))
+    }
+
+    fn decode(&self, topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        self.layout(topic)?.decode(payload).with_context(|| format!("[layouts.{}]", topic))
+    }
+}
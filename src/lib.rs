@@ -1,3 +1,65 @@
 pub mod proto_dyn;
 pub mod broker;
+pub mod receiver;
+pub mod transport;
+pub mod ffi;
 pub mod steps;
+pub mod report;
+pub mod coverage;
+pub mod events;
+pub mod logging;
+pub mod session;
+pub mod triage;
+pub mod resume;
+pub mod history;
+pub mod watch;
+pub mod shard;
+pub mod seed;
+pub mod config;
+pub mod device;
+pub mod fixtures;
+pub mod jsonpath;
+pub mod matchers;
+pub mod aggregate;
+pub mod http;
+pub mod tls;
+pub mod hpack;
+pub mod grpc;
+pub mod server;
+pub mod repl;
+pub mod debug;
+pub mod operator;
+pub mod catalog;
+pub mod i18n;
+pub mod suite;
+pub mod params;
+pub mod validate;
+pub mod schema;
+pub mod conformance;
+pub mod sequence;
+pub mod csv;
+pub mod codec;
+pub mod avro;
+pub mod framing;
+pub mod layout;
+/// Prost structs generated from the protos, one module per package
+#[cfg(feature = "typed-messages")]
+pub mod messages {
+    include!(concat!(env!("OUT_DIR"), "/messages.rs"));
+}
+#[cfg(feature = "db")]
+pub mod db;
+pub mod process;
+pub mod network;
+pub mod ports;
+pub mod tmpdir;
+pub mod vars;
+pub mod secrets;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+#[cfg(feature = "can")]
+pub mod can;
+#[cfg(feature = "modbus")]
+pub mod modbus;
+#[cfg(feature = "someip")]
+pub mod someip;
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, bail, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Log targets of the harness: bus I/O, expectation matching, and scenario/step output
+pub const TARGETS: [&str; 3] = ["transport", "matcher", "runner"];
+
+/// Filter directives, read from this variable before `--log`
+pub const ENV: &str = "BDD_LOG";
+
+static FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Log levels per target, e.g. `warn,transport=trace`: a bare level applies to every target
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    targets: BTreeMap<String, LevelFilter>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self { default: LevelFilter::Info, targets: BTreeMap::new() }
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut filter = Self::default();
+        filter.apply(s)?;
+        Ok(filter)
+    }
+}
+
+impl LogFilter {
+    /// `-q` logs errors only and hides step traffic; each `-v` (cucumber's own flag) goes one level
+    /// further: debug, then trace with every decoded payload
+    pub fn from_verbosity(quiet: bool, verbose: u8) -> Self {
+        let default = match (quiet, verbose) {
+            (true, _) => LevelFilter::Error,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        Self { default, targets: BTreeMap::new() }
+    }
+
+    /// Add comma-separated directives, `level` or `target=level`; later ones win
+    pub fn apply(&mut self, directives: &str) -> Result<()> {
+        for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if !TARGETS.contains(&target) {
+                        bail!("unknown log target {} (use {})", target, TARGETS.join(", "));
+                    }
+                    self.targets.insert(target.to_string(), level_filter(level)?);
+                }
+                None => {
+                    self.default = level_filter(directive)?;
+                    self.targets.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.targets.get(target).copied().unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.values().copied().fold(self.default, Ord::max)
+    }
+}
+
+fn level_filter(s: &str) -> Result<LevelFilter> {
+    s.parse().map_err(|_| anyhow!("unknown log level {} (use off, error, warn, info, debug or trace)", s))
+}
+
+/// Whether `level` is shown for `target`; info and above when [`init`] wasn't called
+pub fn enabled(target: &str, level: Level) -> bool {
+    level <= FILTER.get().map_or(LevelFilter::Info, |f| f.level(target))
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Only log errors and leave bus traffic out of the step output
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Log filter, e.g. `warn,transport=trace` (targets: transport, matcher, runner); added to BDD_LOG
+    #[arg(long, value_name = "filter", global = true)]
+    pub log: Option<String>,
+}
+
+impl Cli {
+    /// The filter for `-q`/`-v` (cucumber's verbosity count), then BDD_LOG, then `--log`
+    pub fn filter(&self, verbose: u8) -> Result<LogFilter> {
+        let mut filter = LogFilter::from_verbosity(self.quiet, verbose);
+        if let Ok(env) = std::env::var(ENV) {
+            filter.apply(&env).map_err(|e| e.context(ENV))?;
+        }
+        if let Some(directives) = &self.log {
+            filter.apply(directives).map_err(|e| e.context("--log"))?;
+        }
+        Ok(filter)
+    }
+}
+
+/// Install `filter` and a logger writing to stderr. Only the first call takes effect.
+pub fn init(filter: LogFilter) {
+    let max = filter.max_level();
+    if FILTER.set(filter).is_ok() && log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(max);
+    }
+}
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        enabled(metadata.target(), metadata.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let message = record.args().to_string();
+            let message = crate::secrets::redact(&message);
+            let _ = writeln!(std::io::stderr().lock(), "[{:<5} {}] {}", record.level(), record.target(), message);
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,293 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::config::MatchingConfig;
+
+/// A matcher directive found in an expected JSON body, e.g. `{"$semver_gte": "2.1.0"}`
+#[derive(Debug, Clone, Copy)]
+pub struct Directive<'a> {
+    /// Directive name including the leading `$`
+    pub name: &'a str,
+    /// Value given for the directive
+    pub arg: &'a JsonValue,
+    /// The whole directive object, for matchers taking extra `$`-options (`{"$within": "2s", "$of": "now"}`)
+    pub options: &'a Map<String, JsonValue>,
+}
+
+impl Directive<'_> {
+    pub fn option(&self, name: &str) -> Option<&JsonValue> {
+        self.options.get(name)
+    }
+}
+
+/// Custom comparison for a directive name. `actual` is None when the field is absent from the received message.
+pub trait Matcher: Send + Sync {
+    fn matches(&self, directive: &Directive, actual: Option<&JsonValue>) -> Result<bool>;
+}
+
+impl<F> Matcher for F
+where
+    F: Fn(&Directive, Option<&JsonValue>) -> Result<bool> + Send + Sync,
+{
+    fn matches(&self, directive: &Directive, actual: Option<&JsonValue>) -> Result<bool> {
+        self(directive, actual)
+    }
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn Matcher>>>;
+
+/// Options applied to every comparison
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchOptions {
+    /// Trim and collapse whitespace in strings before comparing them
+    pub normalize_strings: bool,
+    /// Match expected message fields on the decoded message where they bind to plain scalar or
+    /// enum fields, so only the matching message is converted to JSON
+    pub skip_json: bool,
+}
+
+impl MatchOptions {
+    /// Options from the `[matching]` section of bdd.toml
+    pub fn from_config(config: &MatchingConfig) -> Self {
+        Self { normalize_strings: config.normalize_strings, skip_json: config.skip_json }
+    }
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(builtins()))
+}
+
+fn builtins() -> HashMap<String, Arc<dyn Matcher>> {
+    let mut m: HashMap<String, Arc<dyn Matcher>> = HashMap::new();
+    m.insert("$ieq".to_string(), Arc::new(ieq));
+    m.insert("$contains_str".to_string(), Arc::new(contains_str));
+    m.insert("$within".to_string(), Arc::new(within));
+    m
+}
+
+/// Trim and collapse runs of whitespace to a single space
+pub fn normalize_str(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn str_arg<'a>(d: &Directive<'a>) -> Result<&'a str> {
+    d.arg.as_str().ok_or_else(|| anyhow!("{} expects a string, got {}", d.name, d.arg))
+}
+
+/// `{"$ieq": "ok"}`: case-insensitive, whitespace-normalized equality
+fn ieq(d: &Directive, actual: Option<&JsonValue>) -> Result<bool> {
+    let want = normalize_str(str_arg(d)?).to_lowercase();
+    Ok(actual.and_then(JsonValue::as_str).is_some_and(|got| normalize_str(got).to_lowercase() == want))
+}
+
+/// `{"$within": "2s", "$of": "now", "$unit": "ms"}`: a timestamp no further than the duration from
+/// the reference. `$of` defaults to "now" and may be an epoch number or RFC 3339 string; `$unit`
+/// (s, ms, us, ns) applies to epoch numbers and is guessed from their magnitude when omitted.
+fn within(d: &Directive, actual: Option<&JsonValue>) -> Result<bool> {
+    let tolerance = humantime::parse_duration(str_arg(d)?.trim())
+        .map_err(|e| anyhow!("$within: invalid duration {}: {}", d.arg, e))?
+        .as_secs_f64();
+    let unit = match d.option("$unit") {
+        Some(u) => Some(u.as_str().ok_or_else(|| anyhow!("$unit must be a string"))?),
+        None => None,
+    };
+    let reference = match d.option("$of") {
+        Some(of) => epoch_secs(of, unit)?,
+        None => now_secs(),
+    };
+    let Some(actual) = actual else { return Ok(false) };
+    match epoch_secs(actual, unit) {
+        Ok(t) => Ok((t - reference).abs() <= tolerance),
+        Err(_) => Ok(false),
+    }
+}
+
+fn now_secs() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default()
+}
+
+/// Seconds since the Unix epoch from a google.protobuf.Timestamp object, an epoch number or a string
+fn epoch_secs(v: &JsonValue, unit: Option<&str>) -> Result<f64> {
+    match v {
+        JsonValue::Object(map) if map.contains_key("seconds") || map.contains_key("nanos") => {
+            let secs = map.get("seconds").and_then(JsonValue::as_f64).unwrap_or(0.0);
+            let nanos = map.get("nanos").and_then(JsonValue::as_f64).unwrap_or(0.0);
+            Ok(secs + nanos / 1e9)
+        }
+        JsonValue::Number(n) => scale_epoch(n.as_f64().unwrap_or_default(), unit),
+        JsonValue::String(s) if s == "now" => Ok(now_secs()),
+        JsonValue::String(s) => match s.trim().parse::<f64>() {
+            Ok(n) => scale_epoch(n, unit),
+            Err(_) => {
+                let t = humantime::parse_rfc3339_weak(s.trim()).map_err(|e| anyhow!("invalid timestamp {}: {}", s, e))?;
+                Ok(t.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default())
+            }
+        },
+        _ => bail!("not a timestamp: {}", v),
+    }
+}
+
+fn scale_epoch(n: f64, unit: Option<&str>) -> Result<f64> {
+    let unit = match unit {
+        Some(u) => u,
+        // seconds until the year ~5000, then ms, us, ns
+        None if n.abs() < 1e11 => "s",
+        None if n.abs() < 1e14 => "ms",
+        None if n.abs() < 1e17 => "us",
+        None => "ns",
+    };
+    Ok(match unit {
+        "s" => n,
+        "ms" => n / 1e3,
+        "us" => n / 1e6,
+        "ns" => n / 1e9,
+        other => bail!("unknown timestamp unit {} (use s, ms, us or ns)", other),
+    })
+}
+
+/// `{"$contains_str": "error"}`: substring match
+fn contains_str(d: &Directive, actual: Option<&JsonValue>) -> Result<bool> {
+    let want = str_arg(d)?;
+    Ok(actual.and_then(JsonValue::as_str).is_some_and(|got| got.contains(want)))
+}
+
+/// Register (or replace) the matcher for directive `name`, which must start with `$`
+pub fn register(name: &str, matcher: impl Matcher + 'static) -> Result<()> {
+    if !name.starts_with('$') || name.len() < 2 {
+        bail!("matcher name must look like $name, got {}", name);
+    }
+    registry().write().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), Arc::new(matcher));
+    Ok(())
+}
+
+pub fn is_registered(name: &str) -> bool {
+    registry().read().unwrap_or_else(|e| e.into_inner()).contains_key(name)
+}
+
+fn lookup(name: &str) -> Option<Arc<dyn Matcher>> {
+    registry().read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+}
+
+/// An object is a directive when all its keys start with `$`
+pub fn is_directive(v: &JsonValue) -> bool {
+    match v {
+        JsonValue::Object(map) => is_directive_map(map),
+        _ => false,
+    }
+}
+
+fn is_directive_map(map: &Map<String, JsonValue>) -> bool {
+    !map.is_empty() && map.keys().all(|k| k.starts_with('$'))
+}
+
+/// Evaluate a directive object against `actual`. Every registered key must match; unregistered
+/// `$` keys are options of the others. Fails if the object has no registered key at all.
+pub fn eval(directive: &Map<String, JsonValue>, actual: Option<&JsonValue>) -> Result<bool> {
+    let mut evaluated = false;
+    for (name, arg) in directive {
+        let Some(matcher) = lookup(name) else { continue };
+        evaluated = true;
+        if !matcher.matches(&Directive { name, arg, options: directive }, actual)? {
+            return Ok(false);
+        }
+    }
+    if !evaluated {
+        let names: Vec<&str> = directive.keys().map(String::as_str).collect();
+        bail!("unknown matcher directive {}", names.join(", "));
+    }
+    Ok(true)
+}
+
+/// Prefix of a string naming a `[matching.macros]` fragment: `"$match:valid_header"`
+const MACRO_PREFIX: &str = "$match:";
+
+/// How deeply macros may use other macros, to stop cycles
+const MACRO_DEPTH: usize = 16;
+
+/// Copy of `expected` with macro references replaced by their fragments. A field is either
+/// `"$match:<name>"`, or `{"$match": "<name>", ...}` where the other fields override the fragment's.
+pub fn expand_macros(expected: &JsonValue, macros: &HashMap<String, JsonValue>) -> Result<JsonValue> {
+    expand(expected, macros, 0)
+}
+
+fn expand(v: &JsonValue, macros: &HashMap<String, JsonValue>, depth: usize) -> Result<JsonValue> {
+    let fragment = |name: &str| -> Result<JsonValue> {
+        if depth >= MACRO_DEPTH {
+            bail!("matcher macro {} nests more than {} deep; do macros use each other in a cycle?", name, MACRO_DEPTH);
+        }
+        let fragment = macros.get(name).ok_or_else(|| {
+            let mut known: Vec<&str> = macros.keys().map(String::as_str).collect();
+            known.sort();
+            anyhow!("unknown matcher macro {} (known: {})", name, known.join(", "))
+        })?;
+        expand(fragment, macros, depth + 1)
+    };
+    match v {
+        JsonValue::String(s) => match s.strip_prefix(MACRO_PREFIX) {
+            Some(name) => fragment(name),
+            None => Ok(v.clone()),
+        },
+        JsonValue::Object(map) => {
+            let mut out = Map::new();
+            for (k, child) in map {
+                if k != "$match" {
+                    out.insert(k.clone(), expand(child, macros, depth)?);
+                }
+            }
+            let Some(name) = map.get("$match") else { return Ok(JsonValue::Object(out)) };
+            let name = name.as_str().ok_or_else(|| anyhow!("$match takes a macro name, got {}", name))?;
+            match fragment(name)? {
+                JsonValue::Object(mut base) if !is_directive_map(&base) => {
+                    merge(&mut base, out);
+                    Ok(JsonValue::Object(base))
+                }
+                base if out.is_empty() => Ok(base),
+                _ => bail!("matcher macro {} is not a message fragment, so its fields can't be overridden", name),
+            }
+        }
+        JsonValue::Array(items) => items.iter().map(|i| expand(i, macros, depth)).collect::<Result<_>>().map(JsonValue::Array),
+        _ => Ok(v.clone()),
+    }
+}
+
+/// Override `base` with `overrides`, merging nested messages field by field
+fn merge(base: &mut Map<String, JsonValue>, overrides: Map<String, JsonValue>) {
+    for (k, v) in overrides {
+        match (base.get_mut(&k), v) {
+            (Some(JsonValue::Object(b)), JsonValue::Object(o)) if !is_directive_map(b) && !is_directive_map(&o) => merge(b, o),
+            (_, v) => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
+
+/// Copy of `expected` without the ignored fields, so they are never compared.
+/// A bare name (`timestamp`) is dropped at any depth, a dotted path (`header.seq_no`) only there.
+pub fn without_fields(expected: &JsonValue, ignore: &[String]) -> JsonValue {
+    if ignore.is_empty() {
+        return expected.clone();
+    }
+    strip(expected, ignore, "")
+}
+
+fn strip(v: &JsonValue, ignore: &[String], path: &str) -> JsonValue {
+    match v {
+        JsonValue::Object(map) if !is_directive(v) => {
+            let mut out = Map::new();
+            for (k, child) in map {
+                let child_path = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+                if ignore.iter().any(|i| *i == child_path || (!i.contains('.') && i == k)) {
+                    continue;
+                }
+                out.insert(k.clone(), strip(child, ignore, &child_path));
+            }
+            JsonValue::Object(out)
+        }
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(|i| strip(i, ignore, path)).collect()),
+        _ => v.clone(),
+    }
+}
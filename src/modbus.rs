@@ -0,0 +1,147 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Default Modbus TCP port
+pub const MODBUS_PORT: u16 = 502;
+
+const READ_COILS: u8 = 0x01;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Modbus TCP client for a SUT's maintenance interface
+#[derive(Debug)]
+pub struct ModbusClient {
+    stream: TcpStream,
+    unit: u8,
+    transaction: u16,
+}
+
+impl ModbusClient {
+    /// Connect to `host[:port]` addressing unit id `unit`; every request fails after `timeout`
+    pub fn connect(address: &str, unit: u8, timeout: Duration) -> Result<Self> {
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, MODBUS_PORT) };
+        let stream = TcpStream::connect(&address).with_context(|| format!("connect Modbus {}", address))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, unit, transaction: 0 })
+    }
+
+    pub fn read_holding_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>> {
+        let mut pdu = vec![READ_HOLDING_REGISTERS];
+        pdu.extend(address.to_be_bytes());
+        pdu.extend(count.to_be_bytes());
+        let data = self.call(&pdu)?;
+        let bytes = byte_count_data(&data, count as usize * 2)?;
+        Ok(bytes.chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect())
+    }
+
+    pub fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>> {
+        let mut pdu = vec![READ_COILS];
+        pdu.extend(address.to_be_bytes());
+        pdu.extend(count.to_be_bytes());
+        let data = self.call(&pdu)?;
+        let bytes = byte_count_data(&data, (count as usize).div_ceil(8))?;
+        Ok((0..count as usize).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect())
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u16) -> Result<()> {
+        let mut pdu = vec![WRITE_SINGLE_REGISTER];
+        pdu.extend(address.to_be_bytes());
+        pdu.extend(value.to_be_bytes());
+        self.call(&pdu)?;
+        Ok(())
+    }
+
+    pub fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<()> {
+        if values.is_empty() || values.len() > 123 {
+            bail!("can write 1 to 123 registers at once, got {}", values.len());
+        }
+        let mut pdu = vec![WRITE_MULTIPLE_REGISTERS];
+        pdu.extend(address.to_be_bytes());
+        pdu.extend((values.len() as u16).to_be_bytes());
+        pdu.push((values.len() * 2) as u8);
+        for v in values {
+            pdu.extend(v.to_be_bytes());
+        }
+        self.call(&pdu)?;
+        Ok(())
+    }
+
+    pub fn write_coil(&mut self, address: u16, on: bool) -> Result<()> {
+        let mut pdu = vec![WRITE_SINGLE_COIL];
+        pdu.extend(address.to_be_bytes());
+        pdu.extend(if on { [0xFF, 0x00] } else { [0x00, 0x00] });
+        self.call(&pdu)?;
+        Ok(())
+    }
+
+    /// Send one request PDU and return the response data (after the function code)
+    fn call(&mut self, pdu: &[u8]) -> Result<Vec<u8>> {
+        self.transaction = self.transaction.wrapping_add(1);
+        self.stream.write_all(&encode_frame(self.transaction, self.unit, pdu)).context("send Modbus request")?;
+        let mut header = [0u8; 7];
+        self.stream.read_exact(&mut header).context("read Modbus response")?;
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if len < 2 {
+            bail!("Modbus response length {} too short", len);
+        }
+        let mut body = vec![0u8; len - 1];
+        self.stream.read_exact(&mut body).context("read Modbus response")?;
+        let transaction = u16::from_be_bytes([header[0], header[1]]);
+        if transaction != self.transaction {
+            bail!("Modbus response for transaction {}, expected {}", transaction, self.transaction);
+        }
+        decode_pdu(pdu[0], &body)
+    }
+}
+
+/// MBAP header (transaction, protocol 0, length, unit) followed by the PDU
+pub fn encode_frame(transaction: u16, unit: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(7 + pdu.len());
+    frame.extend(transaction.to_be_bytes());
+    frame.extend(0u16.to_be_bytes());
+    frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(unit);
+    frame.extend_from_slice(pdu);
+    frame
+}
+
+/// Data of a response PDU to `function`, or the exception it carries
+pub fn decode_pdu(function: u8, pdu: &[u8]) -> Result<Vec<u8>> {
+    match pdu.split_first() {
+        Some((f, rest)) if *f == function => Ok(rest.to_vec()),
+        Some((f, rest)) if *f == function | 0x80 => {
+            let code = rest.first().copied().unwrap_or_default();
+            bail!("Modbus exception {:#04x} ({}) for function {:#04x}", code, exception_name(code), function)
+        }
+        Some((f, _)) => bail!("Modbus response has function {:#04x}, expected {:#04x}", f, function),
+        None => bail!("empty Modbus response"),
+    }
+}
+
+fn byte_count_data(data: &[u8], expected: usize) -> Result<&[u8]> {
+    let (count, bytes) = data.split_first().ok_or_else(|| anyhow!("empty Modbus read response"))?;
+    if *count as usize != expected || bytes.len() != expected {
+        bail!("Modbus read returned {} bytes, expected {}", bytes.len(), expected);
+    }
+    Ok(bytes)
+}
+
+fn exception_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "illegal function",
+        0x02 => "illegal data address",
+        0x03 => "illegal data value",
+        0x04 => "server device failure",
+        0x05 => "acknowledge",
+        0x06 => "server device busy",
+        0x0A => "gateway path unavailable",
+        0x0B => "gateway target failed to respond",
+        _ => "unknown",
+    }
+}
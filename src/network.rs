@@ -0,0 +1,201 @@
+//! Local interface or address test traffic goes out from, `[network] bind_address`, for lab
+//! machines with several NICs where the SUT is only reachable through one of them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::{Config, IpFamily};
+
+/// Where the kernel lists network interfaces (Linux)
+const INTERFACES_DIR: &str = "/sys/class/net";
+
+/// How long `ip` may take listing an interface's addresses
+const IP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `[network]` checked against this machine: how connections reach the SUT
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    /// Local address or interface to connect from
+    pub bind: Option<BindAddress>,
+    /// Address families hostnames may resolve to
+    pub family: IpFamily,
+}
+
+impl Network {
+    /// The configured `[network]`, with the bind address checked against the local interfaces
+    pub fn configured() -> Result<Self> {
+        let config = &Config::global().network;
+        Ok(Self { bind: config.bind_address.as_deref().map(BindAddress::parse).transpose()?, family: config.resolve })
+    }
+
+    /// Resolve `address` to the SUT address to connect to, using `default_port` when it has none,
+    /// and the local address to connect from when bound
+    pub fn route(&self, address: &str, default_port: u16) -> Result<(SocketAddr, Option<IpAddr>)> {
+        let endpoint: Endpoint = address.parse()?;
+        let addrs = endpoint.resolve(default_port, self.family)?;
+        let Some(bind) = &self.bind else { return Ok((addrs[0], None)) };
+        bind.source_for(&endpoint.host, &addrs).map(|(target, source)| (target, Some(source)))
+    }
+}
+
+/// A host, IPv6 literal or hostname, with an optional port: `10.0.0.5`, `sut.lab:4246`, `::1`,
+/// `[fd00::5]:4246`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Without brackets
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl FromStr for Endpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let port = |p: &str| p.parse::<u16>().with_context(|| format!("invalid port {:?} in {}", p, text));
+        let (host, port) = if let Some(rest) = text.strip_prefix('[') {
+            let (host, rest) = rest.split_once(']').ok_or_else(|| anyhow!("missing ] in {}", text))?;
+            host.parse::<Ipv6Addr>().with_context(|| format!("invalid IPv6 address {} in {}", host, text))?;
+            match rest {
+                "" => (host, None),
+                _ => (host, Some(port(rest.strip_prefix(':').ok_or_else(|| anyhow!("expected :<port> after ] in {}", text))?)?)),
+            }
+        } else if text.parse::<Ipv6Addr>().is_ok() {
+            (text, None)
+        } else if text.matches(':').count() > 1 {
+            bail!("invalid address {}: write IPv6 addresses with a port as [<address>]:<port>", text);
+        } else {
+            match text.split_once(':') {
+                Some((host, p)) => (host, Some(port(p)?)),
+                None => (text, None),
+            }
+        };
+        if host.is_empty() {
+            bail!("missing host in {:?}", text);
+        }
+        Ok(Self { host: host.to_string(), port })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.host.contains(':'), self.port) {
+            (true, Some(port)) => write!(f, "[{}]:{}", self.host, port),
+            (true, None) => write!(f, "[{}]", self.host),
+            (false, Some(port)) => write!(f, "{}:{}", self.host, port),
+            (false, None) => f.write_str(&self.host),
+        }
+    }
+}
+
+impl Endpoint {
+    /// Every address of `family` the host resolves to, with `default_port` when it has no port
+    pub fn resolve(&self, default_port: u16, family: IpFamily) -> Result<Vec<SocketAddr>> {
+        let port = self.port.unwrap_or(default_port);
+        let addrs: Vec<SocketAddr> = (self.host.as_str(), port).to_socket_addrs().with_context(|| format!("resolve {}", self.host))?.collect();
+        if addrs.is_empty() {
+            bail!("resolve {}: no addresses", self.host);
+        }
+        let matching: Vec<SocketAddr> = addrs.iter().copied().filter(|a| family.allows(a.ip())).collect();
+        if matching.is_empty() {
+            bail!("{} has no {} address (resolved to {}; see [network] resolve)", self.host, family, join(&addrs));
+        }
+        Ok(matching)
+    }
+}
+
+fn join(addrs: &[SocketAddr]) -> String {
+    addrs.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// A local address, or an interface whose address is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindAddress {
+    Ip(IpAddr),
+    Interface(String),
+}
+
+impl BindAddress {
+    /// An IP address assigned to this machine, or the name of an existing interface
+    pub fn parse(text: &str) -> Result<Self> {
+        if let Ok(ip) = text.trim_matches(['[', ']']).parse::<IpAddr>() {
+            UdpSocket::bind((ip, 0)).with_context(|| format!("bind address {} is not assigned to a local interface", ip))?;
+            return Ok(Self::Ip(ip));
+        }
+        if !Path::new(INTERFACES_DIR).join(text).exists() {
+            bail!("bind address {} is neither an IP address nor a local interface (interfaces: {})", text, interfaces().join(", "));
+        }
+        Ok(Self::Interface(text.to_string()))
+    }
+
+    /// Address to bind sockets of the IPv6 or IPv4 family to
+    pub fn ip(&self, v6: bool) -> Result<IpAddr> {
+        match self {
+            Self::Ip(ip) if ip.is_ipv6() == v6 => Ok(*ip),
+            Self::Ip(ip) => bail!("bind address {} is not {}", ip, family(v6)),
+            Self::Interface(name) => interface_ip(name, v6),
+        }
+    }
+
+    /// The first of `addrs` (what `host` resolved to) this bind address can reach, and the local
+    /// address to reach it from. Checked up front so a family mismatch fails here rather than as
+    /// a silent ZMQ reconnect loop.
+    pub fn source_for(&self, host: &str, addrs: &[SocketAddr]) -> Result<(SocketAddr, IpAddr)> {
+        if let Self::Ip(ip) = self {
+            let target = addrs.iter().find(|a| a.is_ipv6() == ip.is_ipv6()).ok_or_else(|| {
+                anyhow!("{} resolves only to {} addresses ({}), but bind address {} is {}", host, family(!ip.is_ipv6()), join(addrs), ip, family(ip.is_ipv6()))
+            })?;
+            return Ok((*target, *ip));
+        }
+        let mut error = None;
+        for target in addrs {
+            match self.ip(target.is_ipv6()) {
+                Ok(source) => return Ok((*target, source)),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or_else(|| anyhow!("no addresses for {}", host)))
+    }
+}
+
+/// Names of the local network interfaces
+pub fn interfaces() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(INTERFACES_DIR)
+        .map(|dir| dir.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// First global address of `family` on interface `name`, from `ip addr`
+fn interface_ip(name: &str, v6: bool) -> Result<IpAddr> {
+    let mut cmd = Command::new("ip");
+    cmd.args(["-o", if v6 { "-6" } else { "-4" }, "addr", "show", "dev", name]);
+    let output = crate::process::run(cmd, IP_TIMEOUT)?;
+    if output.status != 0 {
+        bail!("ip addr show dev {}: {}", name, output.stderr.trim());
+    }
+    // "2: eth1    inet 192.168.10.2/24 brd ... scope global eth1"
+    output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace().skip_while(|w| !w.starts_with("inet"));
+            words.next()?;
+            words.next()?.split('/').next()?.parse::<IpAddr>().ok()
+        })
+        .find(|ip| !matches!(ip, IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80))
+        .ok_or_else(|| anyhow!("interface {} has no {} address", name, family(v6)))
+}
+
+fn family(v6: bool) -> &'static str {
+    if v6 {
+        "IPv6"
+    } else {
+        "IPv4"
+    }
+}
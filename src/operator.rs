@@ -0,0 +1,126 @@
+//! Semi-automated procedures: `I pause for operator action "..."` waits for someone at the bench
+//! to confirm, by pressing Enter on the console or, on a run without one, with `POST /confirm` to
+//! the `[operator] listen` address.
+
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::http::{Request, Response};
+
+/// Wait when the step gives no timeout
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+
+const POLL: Duration = Duration::from_millis(50);
+
+/// How the operator confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Console,
+    Http,
+}
+
+impl std::fmt::Display for Confirmation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Console => "console",
+            Self::Http => "HTTP",
+        })
+    }
+}
+
+/// Lines typed on stdin, read by one thread for the whole run so a wait that timed out doesn't
+/// leave a reader behind to swallow the next line
+fn console() -> &'static Mutex<Receiver<String>> {
+    static LINES: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+    LINES.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(rx)
+    })
+}
+
+/// The next line typed on the console; None once stdin is closed
+pub fn read_line() -> Option<String> {
+    console().lock().unwrap_or_else(|e| e.into_inner()).recv().ok()
+}
+
+/// Ask for `action` and block until the operator confirms it, on the console or, with `listen`, by
+/// HTTP. Fails after `timeout`, or at once when there is neither a console nor `listen`.
+pub fn wait(action: &str, timeout: Duration, listen: Option<&str>) -> Result<Confirmation> {
+    let listener = match listen {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr).with_context(|| format!("listen on {}", addr))?;
+            listener.set_nonblocking(true)?;
+            Some(listener)
+        }
+        None => None,
+    };
+    let console = console().lock().unwrap_or_else(|e| e.into_inner());
+    // Enter pressed before the question doesn't answer it
+    while console.try_recv().is_ok() {}
+    let over_http = listen.map(|a| format!(" or POST http://{}/confirm", a)).unwrap_or_default();
+    println!("OPERATOR ACTION: {} (press Enter when done{}; timeout {})", action, over_http, humantime::format_duration(timeout));
+    let deadline = Instant::now() + timeout;
+    let mut has_console = true;
+    loop {
+        match console.try_recv() {
+            Ok(_) => return Ok(Confirmation::Console),
+            Err(TryRecvError::Disconnected) if listener.is_none() => bail!("no console to confirm \"{}\" on; set [operator] listen to confirm over HTTP", action),
+            Err(TryRecvError::Disconnected) => has_console = false,
+            Err(TryRecvError::Empty) => {}
+        }
+        if let Some(listener) = &listener {
+            if answer(listener, action)? {
+                return Ok(Confirmation::Http);
+            }
+        }
+        if Instant::now() >= deadline {
+            let on = if has_console { "" } else { " (no console)" };
+            bail!("operator did not confirm \"{}\" within {}{}", action, humantime::format_duration(timeout), on);
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+/// Answer the requests waiting on `listener`: `GET /action` tells what is asked, `POST /confirm`
+/// confirms it. True once confirmed.
+fn answer(listener: &TcpListener, action: &str) -> Result<bool> {
+    loop {
+        let conn = match listener.accept() {
+            Ok((conn, _)) => conn,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e).context("accept operator connection"),
+        };
+        conn.set_nonblocking(false)?;
+        conn.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&conn);
+        let (response, confirmed) = match Request::read(&mut reader) {
+            Ok(Some(req)) => match (req.method.as_str(), req.path.trim_matches('/')) {
+                ("GET", "action") => (Response::ok(json!({ "action": action })), false),
+                ("POST", "confirm") => (Response::ok(json!({ "confirmed": action })), true),
+                (_, "action" | "confirm") => (Response::error(405, format!("{} not allowed on {}", req.method, req.path)), false),
+                _ => (Response::error(404, format!("no route {}", req.path)), false),
+            },
+            Ok(None) => continue,
+            Err(e) => (Response::unreadable(&e), false),
+        };
+        if let Err(e) = response.write(&mut &conn) {
+            log::warn!(target: "runner", "operator response: {}", e);
+        }
+        if confirmed {
+            return Ok(true);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Custom cucumber expression parameter types. Values are checked when a step matches, so a typo
+//! fails with the reason instead of a step mismatch or a timeout. Use them in your own steps too:
+//!
+//! ```ignore
+//! #[then(expr = "{message} arrives within {duration}")]
+//! async fn arrives(world: &mut MyWorld, name: MessageName, window: DurationParam) -> Result<()> { ... }
+//! ```
+
+use anyhow::{anyhow, bail, Context};
+use cucumber::Parameter;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::codec;
+use crate::config;
+use crate::proto_dyn::ProtoDyn;
+
+/// Why a step argument was rejected. The step macros panic with its `Debug` form, so that prints
+/// just the reason (an `anyhow::Error` would add a backtrace).
+pub struct ParamError(anyhow::Error);
+
+impl fmt::Debug for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+impl From<anyhow::Error> for ParamError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(e)
+    }
+}
+
+/// `{message}`: a message name from the descriptor set, short or fully qualified
+#[derive(Debug, Clone, PartialEq, Parameter)]
+#[param(name = "message", regex = r"[\w.]+")]
+pub struct MessageName(pub String);
+
+impl FromStr for MessageName {
+    type Err = ParamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // without a usable descriptor set the broker reports the problem on first use
+        if let Ok(proto) = ProtoDyn::new() {
+            if proto.message_desc(s).is_err() && !codec::is_configured(s) {
+                let names = proto.message_names();
+                let known: Vec<&str> = names.iter().map(|n| n.rsplit('.').next().unwrap_or(n)).collect();
+                return Err(anyhow!("unknown message {}; known messages: {}", s, known.join(", ")).into());
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl fmt::Display for MessageName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// `{duration}`: "500ms", "2s", "2.5 s", "1m 30s"
+#[derive(Debug, Clone, Copy, PartialEq, Parameter)]
+#[param(name = "duration", regex = r"\d+(?:\.\d+)? ?[a-z]+(?: \d+(?:\.\d+)? ?[a-z]+)*")]
+pub struct DurationParam(pub Duration);
+
+impl FromStr for DurationParam {
+    type Err = ParamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(config::parse_duration(s)?))
+    }
+}
+
+/// `{endpoint}`: host, host:port, a bare IPv6 address or [IPv6]:port
+#[derive(Debug, Clone, PartialEq, Parameter)]
+#[param(name = "endpoint", regex = r"\S+")]
+pub struct Endpoint(pub String);
+
+impl FromStr for Endpoint {
+    type Err = ParamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        check_endpoint(s)?;
+        Ok(Self(s.to_string()))
+    }
+}
+
+fn check_endpoint(s: &str) -> anyhow::Result<()> {
+    if s.contains("://") {
+        bail!("endpoint {} must be host[:port], without a scheme", s);
+    }
+    let port = if let Some(rest) = s.strip_prefix('[') {
+        let (host, after) = rest.split_once(']').ok_or_else(|| anyhow!("endpoint {} has no closing ]", s))?;
+        if host.is_empty() {
+            bail!("endpoint {} has an empty host", s);
+        }
+        match after {
+            "" => None,
+            _ => Some(after.strip_prefix(':').ok_or_else(|| anyhow!("endpoint {}: expected :port after ]", s))?),
+        }
+    } else {
+        match s.split_once(':') {
+            // more than one colon: a bare IPv6 address
+            Some((_, rest)) if rest.contains(':') => None,
+            Some(("", _)) => bail!("endpoint {} has an empty host", s),
+            Some((_, port)) => Some(port),
+            None => None,
+        }
+    };
+    if let Some(port) = port {
+        port.parse::<u16>().with_context(|| format!("endpoint {} has an invalid port", s))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
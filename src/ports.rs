@@ -0,0 +1,77 @@
+//! Free local ports for SUT simulators, so scenarios running side by side don't fight over
+//! endpoints. Endpoints in `[endpoints]` use `{auto_port}` where a port should be allocated.
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::TcpListener;
+use std::sync::Mutex;
+
+/// Placeholder replaced with an allocated port in `[endpoints]` templates
+pub const AUTO_PORT: &str = "{auto_port}";
+
+/// How many ports the OS may hand out that are already leased before giving up
+const ATTEMPTS: usize = 100;
+
+/// Ports leased to running scenarios
+static LEASED: Mutex<Option<HashSet<u16>>> = Mutex::new(None);
+
+/// A port no other scenario of this run is using; it is free again once dropped
+#[derive(Debug, PartialEq, Eq)]
+pub struct PortLease(u16);
+
+impl PortLease {
+    /// Ask the OS for a free port, skipping ports still leased to other scenarios. The port is
+    /// released by the OS right away, so it is only reserved within this run.
+    pub fn allocate() -> Result<Self> {
+        for _ in 0..ATTEMPTS {
+            let port = TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr()).context("allocate port")?.port();
+            let mut leased = LEASED.lock().unwrap_or_else(|e| e.into_inner());
+            if leased.get_or_insert_with(HashSet::new).insert(port) {
+                return Ok(Self(port));
+            }
+        }
+        bail!("no free port after {} attempts", ATTEMPTS)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Drop for PortLease {
+    fn drop(&mut self) {
+        if let Some(leased) = LEASED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            leased.remove(&self.0);
+        }
+    }
+}
+
+/// `[endpoints]` resolved for one scenario: each template containing `{auto_port}` gets its own port
+#[derive(Debug, Default)]
+pub struct Endpoints {
+    /// Scenario variables: `<name>` for each endpoint and `<name>_port` for its allocated port
+    pub vars: BTreeMap<String, String>,
+    /// Held until the scenario ends
+    pub leases: Vec<PortLease>,
+}
+
+impl Endpoints {
+    pub fn resolve(templates: &HashMap<String, String>) -> Result<Self> {
+        let mut endpoints = Self::default();
+        for (name, template) in templates {
+            let endpoint = if template.contains(AUTO_PORT) {
+                let lease = PortLease::allocate().with_context(|| format!("endpoint {}", name))?;
+                endpoints.vars.insert(format!("{}_port", name), lease.port().to_string());
+                let endpoint = template.replace(AUTO_PORT, &lease.port().to_string());
+                endpoints.leases.push(lease);
+                endpoint
+            } else {
+                template.clone()
+            };
+            // credentials in endpoints come from [secrets]
+            let endpoint = crate::vars::substitute(&endpoint, &HashMap::new()).with_context(|| format!("endpoint {}", name))?;
+            endpoints.vars.insert(name.clone(), endpoint);
+        }
+        Ok(endpoints)
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How often a running command is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Outcome of a finished command
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    /// Exit code; -1 when the process was killed by a signal
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Run `cmd` to completion, killing it once `timeout` has passed
+pub fn run(mut cmd: Command, timeout: Duration) -> Result<CommandOutput> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("run {}", program))?;
+    // drain the pipes on their own threads so a chatty command can't block on a full pipe
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut out = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut out);
+            }
+            String::from_utf8_lossy(&out).to_string()
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{} did not finish within {}", program, humantime::format_duration(timeout));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    Ok(CommandOutput {
+        status: status.code().unwrap_or(-1),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
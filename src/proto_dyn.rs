@@ -1,57 +1,383 @@
 
-use anyhow::{anyhow, Result, Context};
-use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, ReflectMessage, Value as PbValue};
+use anyhow::{anyhow, bail, Result, Context};
+use prost_reflect::{DescriptorPool, DynamicMessage, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor, ReflectMessage, Value as PbValue};
 use prost_reflect::prost::Message as ProstMessage;
-use prost_types::FileDescriptorSet;
 use serde_json::Value as JsonValue;
 use base64::Engine;
 use base64::engine::general_purpose;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+use crate::config::{Config, ProtoConfig, SchemaConfig};
+use crate::jsonpath::{self, Segment};
+use crate::matchers::{self, MatchOptions};
+use crate::schema;
+use crate::validate::{self, Violation};
+use crate::watch::Snapshot;
 
+#[cfg(feature = "embedded-descriptor")]
 fn descriptor_pool() -> Result<DescriptorPool> {
     let bytes = include_bytes!("descriptor.bin");
     if bytes.is_empty() {
         anyhow::bail!("src/descriptor.bin is empty; generate it with protoc --descriptor_set_out=src/descriptor.bin --include_imports proto/PingPong.proto");
     }
-    let descriptor_set = FileDescriptorSet::decode(&bytes[..])
-        .context("failed to decode descriptor.bin as FileDescriptorSet")?;
-    let pool = DescriptorPool::from_file_descriptor_set(descriptor_set)
-        .context("failed to create descriptor pool from FileDescriptorSet")?;
-    Ok(pool)
+    let config = &Config::global().proto;
+    if let Some(reason) = stale_reason(bytes, env!("BDD_DESCRIPTOR_HASH"), config.expected_hash.as_deref()) {
+        if config.fail_on_stale {
+            bail!("stale descriptor set: {}", reason);
+        }
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| eprintln!("warning: stale descriptor set: {}", reason));
+    }
+    decode_pool(bytes).context("src/descriptor.bin")
+}
+
+/// Stable hash of an encoded descriptor set (FNV-1a, 64 bit), as recorded by the build and
+/// compared with `[proto] expected_hash`
+pub fn descriptor_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+    format!("fnv1a64:{:016x}", hash)
+}
+
+/// Why the embedded descriptor set `bytes` can't be trusted: it differs from the one the build
+/// compiled from the proto sources (`built`, empty when the build didn't compile), or from `expected`
+pub fn stale_reason(bytes: &[u8], built: &str, expected: Option<&str>) -> Option<String> {
+    let actual = descriptor_hash(bytes);
+    let mut reasons = Vec::new();
+    if !built.is_empty() && built != actual {
+        reasons.push(format!("src/descriptor.bin is {} but the proto sources compiled to {}; rebuild and check that src/ is writable", actual, built));
+    }
+    if let Some(expected) = expected.filter(|e| *e != actual) {
+        reasons.push(format!("src/descriptor.bin is {}, [proto] expected_hash is {}", actual, expected));
+    }
+    (!reasons.is_empty()).then(|| reasons.join("; "))
+}
+
+#[cfg(not(feature = "embedded-descriptor"))]
+fn descriptor_pool() -> Result<DescriptorPool> {
+    bail!("no descriptor set: set [proto] descriptor or files in bdd.toml, or build with feature embedded-descriptor")
+}
+
+/// Files a descriptor pool was loaded from, to load it again
+#[derive(Debug, Clone, Default)]
+struct Source {
+    /// Encoded FileDescriptorSet, used when there are no `files`
+    descriptor: Option<PathBuf>,
+    /// .proto sources to compile
+    files: Vec<PathBuf>,
+    includes: Vec<PathBuf>,
+}
+
+impl Source {
+    fn load(&self) -> Result<DescriptorPool> {
+        let bytes = match &self.descriptor {
+            _ if !self.files.is_empty() => compile(&self.files, &self.includes)?,
+            Some(path) => std::fs::read(path).with_context(|| format!("read {}", path.display()))?,
+            None => bail!("neither descriptor nor files set"),
+        };
+        decode_pool(&bytes)
+    }
+
+    /// The files whose change calls for a reload
+    fn paths(&self) -> Vec<PathBuf> {
+        if self.files.is_empty() { self.descriptor.iter().cloned().collect() } else { self.files.clone() }
+    }
+}
+
+impl From<&ProtoConfig> for Source {
+    fn from(config: &ProtoConfig) -> Self {
+        Self { descriptor: config.descriptor.clone(), files: config.files.clone(), includes: config.includes.clone() }
+    }
 }
 
+impl From<&SchemaConfig> for Source {
+    fn from(config: &SchemaConfig) -> Self {
+        Self { descriptor: config.descriptor.clone(), files: config.files.clone(), includes: config.includes.clone() }
+    }
+}
+
+/// Decode a FileDescriptorSet, keeping extension options such as validation rules
+fn decode_pool(bytes: &[u8]) -> Result<DescriptorPool> {
+    DescriptorPool::decode(bytes).context("failed to decode FileDescriptorSet")
+}
+
+/// Compile .proto files into an encoded FileDescriptorSet with protoc (`$PROTOC`, else from
+/// `PATH`), imports included. Without `includes`, each file's directory is an import path. There is
+/// no in-process compiler, so this fails where protoc isn't installed.
+fn compile(paths: &[PathBuf], includes: &[PathBuf]) -> Result<Vec<u8>> {
+    let protoc = std::env::var_os("PROTOC").unwrap_or_else(|| "protoc".into());
+    static SEQ: AtomicUsize = AtomicUsize::new(0);
+    let out = std::env::temp_dir().join(format!("bdd-descriptor-{}-{}.bin", std::process::id(), SEQ.fetch_add(1, Ordering::Relaxed)));
+    let mut cmd = std::process::Command::new(&protoc);
+    cmd.arg("--include_imports").arg(format!("--descriptor_set_out={}", out.display()));
+    if includes.is_empty() {
+        for dir in paths.iter().filter_map(|p| p.parent()) {
+            cmd.arg(format!("-I{}", if dir.as_os_str().is_empty() { Path::new(".") } else { dir }.display()));
+        }
+    }
+    cmd.args(includes.iter().map(|dir| format!("-I{}", dir.display()))).args(paths);
+    let output = cmd.output().with_context(|| format!("run {}; compiling .proto files needs protoc, set PROTOC to its path", protoc.to_string_lossy()))?;
+    if !output.status.success() {
+        bail!("{} failed: {}", protoc.to_string_lossy(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let bytes = std::fs::read(&out).with_context(|| format!("read {}", out.display()));
+    let _ = std::fs::remove_file(&out);
+    bytes
+}
+
+/// Message types of a descriptor pool. Clones share the pool and its name index, and see a
+/// [`reload`](ProtoDyn::reload) made through any of them.
+#[derive(Clone)]
 pub struct ProtoDyn {
+    loaded: Arc<RwLock<Loaded>>,
+    /// Where the pool came from; None when built from bytes, which can't be reloaded
+    source: Option<Arc<Source>>,
+}
+
+struct Loaded {
     pool: DescriptorPool,
+    /// Messages by short name; the first one in pool order wins when packages share a name
+    short_names: HashMap<String, MessageDescriptor>,
+}
+
+impl Loaded {
+    fn new(pool: DescriptorPool) -> Self {
+        let mut short_names = HashMap::new();
+        for m in pool.all_messages() {
+            short_names.entry(m.name().to_string()).or_insert(m);
+        }
+        Self { pool, short_names }
+    }
 }
 
 impl ProtoDyn {
+    /// Message types of the `[proto]` files or descriptor set in bdd.toml, or the descriptor set
+    /// built into the crate (feature `embedded-descriptor`) when none is configured. Loaded and
+    /// indexed once per process, and watched for changes with `[proto] watch`.
     pub fn new() -> Result<Self> {
-        Ok(Self { pool: descriptor_pool()? })
+        static LOADED: OnceLock<Result<ProtoDyn, String>> = OnceLock::new();
+        let loaded = LOADED.get_or_init(|| {
+            let config = &Config::global().proto;
+            let proto = if config.files.is_empty() && config.descriptor.is_none() {
+                descriptor_pool().map(Self::with_pool)
+            } else {
+                Self::from_source(Source::from(config)).context("[proto] in bdd.toml")
+            };
+            if let (Ok(proto), Some(interval)) = (&proto, config.watch) {
+                proto.watch(interval);
+            }
+            proto.map_err(|e| format!("{:#}", e))
+        });
+        loaded.clone().map_err(|e| anyhow!(e))
     }
 
+    fn with_pool(pool: DescriptorPool) -> Self {
+        Self { loaded: Arc::new(RwLock::new(Loaded::new(pool))), source: None }
+    }
+
+    fn from_source(source: Source) -> Result<Self> {
+        let pool = source.load()?;
+        Ok(Self { source: Some(Arc::new(source)), ..Self::with_pool(pool) })
+    }
+
+    fn loaded(&self) -> std::sync::RwLockReadGuard<'_, Loaded> {
+        self.loaded.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Message types compiled from .proto sources at runtime, resolving imports against `includes`
+    /// (each file's directory when empty). Runs protoc, which must be installed where the tests run.
+    pub fn compile_protos(paths: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Result<Self> {
+        let files: Vec<PathBuf> = paths.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        let includes: Vec<PathBuf> = includes.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        Self::from_source(Source { descriptor: None, files, includes })
+    }
+
+    /// Message types from an encoded FileDescriptorSet instead of the built-in one
+    pub fn from_descriptor_set(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::with_pool(decode_pool(bytes)?))
+    }
+
+    /// Message types of `[schemas.<name>]` in bdd.toml, loaded and indexed once per process
+    pub fn named(name: &str) -> Result<Self> {
+        static LOADED: OnceLock<Mutex<HashMap<String, ProtoDyn>>> = OnceLock::new();
+        let mut loaded = LOADED.get_or_init(Mutex::default).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(proto) = loaded.get(name) {
+            return Ok(proto.clone());
+        }
+        let config = Config::global().schemas.get(name).ok_or_else(|| anyhow!("no schema \"{}\"; add [schemas.{}] to bdd.toml", name, name))?;
+        let proto = Self::from_source(Source::from(config)).with_context(|| format!("[schemas.{}] in bdd.toml", name))?;
+        if let Some(interval) = config.watch {
+            proto.watch(interval);
+        }
+        loaded.insert(name.to_string(), proto.clone());
+        Ok(proto)
+    }
+
+    /// Message types from a file: a .proto compiled with protoc, or else an encoded FileDescriptorSet
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.extension().is_some_and(|e| e == "proto") {
+            return Self::compile_protos(&[path], &[] as &[PathBuf]);
+        }
+        let source = Source { descriptor: Some(path.to_path_buf()), ..Source::default() };
+        Self::from_source(source).with_context(|| path.display().to_string())
+    }
+
+    /// Load the descriptor set or .proto files again, e.g. after an over-the-air schema update,
+    /// for this and every clone. On error the message types in use are kept. Message types built
+    /// from bytes, the embedded set included, have nothing to reload from.
+    pub fn reload(&self) -> Result<()> {
+        let source = self.source.as_ref().ok_or_else(|| anyhow!("message types built from bytes can't be reloaded; load them from files"))?;
+        let loaded = Loaded::new(source.load().context("reload descriptors")?);
+        let paths: Vec<String> = source.paths().iter().map(|p| p.display().to_string()).collect();
+        log::info!(target: "transport", "reloaded {} message types from {}", loaded.short_names.len(), paths.join(", "));
+        *self.loaded.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+        Ok(())
+    }
+
+    /// Check the source files every `interval` and reload when one changes, on a thread that
+    /// ends once every clone is dropped. A reload that fails is logged and retried on the next change.
+    pub fn watch(&self, interval: Duration) {
+        let Some(source) = self.source.clone() else { return };
+        let loaded = Arc::downgrade(&self.loaded);
+        let mut snapshot = Snapshot::take(source.paths());
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            let Some(loaded) = loaded.upgrade() else { return };
+            let now = Snapshot::take(source.paths());
+            if now.changed_since(&snapshot).is_empty() {
+                continue;
+            }
+            snapshot = now;
+            let proto = ProtoDyn { loaded, source: Some(source.clone()) };
+            if let Err(e) = proto.reload() {
+                log::warn!(target: "transport", "{:#}", e);
+            }
+        });
+    }
+
+    /// Constraints from validation options (see [`crate::validate`]) that `msg` breaks
+    pub fn validate(&self, msg: &DynamicMessage) -> Vec<Violation> {
+        validate::validate(msg)
+    }
+
+    /// The descriptor pool as an encoded FileDescriptorSet
+    pub fn descriptor_set(&self) -> Vec<u8> {
+        self.loaded().pool.encode_to_vec()
+    }
+
+    /// Message type by fully qualified or short name
     pub fn message_desc(&self, name: &str) -> Result<MessageDescriptor> {
-        // Try both fully qualified and short name
-        if let Some(m) = self.pool.get_message_by_name(name) {
+        let loaded = self.loaded();
+        if let Some(m) = loaded.pool.get_message_by_name(name) {
             return Ok(m);
         }
-        // Try searching by suffix (last segment)
-        for m in self.pool.all_messages() {
-            if let Some(n) = m.full_name().rsplit('.').next() {
-                if n == name {
-                    return Ok(m);
+        loaded.short_names.get(name).cloned().ok_or_else(|| anyhow!("message {} not found", name))
+    }
+
+    /// RPC method by `Service/Method`, the service named in full or by its short name
+    pub fn method_desc(&self, path: &str) -> Result<MethodDescriptor> {
+        let (service, method) = path.trim_start_matches('/').split_once('/').ok_or_else(|| anyhow!("expected Service/Method, got {}", path))?;
+        let loaded = self.loaded();
+        let found = loaded
+            .pool
+            .get_service_by_name(service)
+            .or_else(|| loaded.pool.services().find(|s| s.name() == service))
+            .ok_or_else(|| anyhow!("service {} not found", service))?;
+        let desc = found.methods().find(|m| m.name() == method);
+        desc.ok_or_else(|| anyhow!("service {} has no method {}", found.full_name(), method))
+    }
+
+    /// Full names of every message in the descriptor pool, sorted
+    pub fn message_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.loaded().pool.all_messages().map(|m| m.full_name().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// Generated struct for `msg` (feature `typed-messages`)
+    #[cfg(feature = "typed-messages")]
+    pub fn to_typed<T: ProstMessage + Default>(&self, msg: &DynamicMessage) -> Result<T> {
+        msg.transcode_to().with_context(|| format!("convert {} to a typed message", msg.descriptor().full_name()))
+    }
+
+    /// Dynamic view of a generated struct, looked up by its proto name (feature `typed-messages`)
+    #[cfg(feature = "typed-messages")]
+    pub fn from_typed<T: ProstMessage + prost::Name>(&self, value: &T) -> Result<DynamicMessage> {
+        let mut msg = DynamicMessage::new(self.message_desc(&T::full_name())?);
+        msg.transcode_from(value).with_context(|| format!("convert {} to a dynamic message", T::full_name()))?;
+        Ok(msg)
+    }
+
+    /// JSON Schema of the payloads accepted for message `name` (see [`crate::schema`])
+    pub fn json_schema(&self, name: &str) -> Result<JsonValue> {
+        Ok(schema::json_schema(&self.message_desc(name)?))
+    }
+
+    /// Enum type of the field at JSONPath `path` in message `name`; list elements and map values
+    /// are reached with `[n]` or a key
+    pub fn enum_field(&self, name: &str, path: &str) -> Result<EnumDescriptor> {
+        match self.field_kind(name, path)? {
+            (Kind::Enum(e), false) => Ok(e),
+            (_, true) => bail!("{} of {} is a repeated field; pick one element with [n]", path, name),
+            _ => bail!("{} of {} is not an enum field", path, name),
+        }
+    }
+
+    /// Check that JSONPath `path` names a field of message `name`
+    pub fn check_field(&self, name: &str, path: &str) -> Result<()> {
+        self.field_kind(name, path).map(|_| ())
+    }
+
+    /// Kind of the field at `path`, and whether it is a whole list or map rather than one element
+    pub fn field_kind(&self, name: &str, path: &str) -> Result<(Kind, bool)> {
+        let mut kind = Kind::Message(self.message_desc(name)?);
+        // set after a list or map field, until the next segment picks one element
+        let mut container: Option<&str> = None;
+        for segment in jsonpath::parse(path)? {
+            match (container.take(), segment) {
+                (Some("list"), Segment::Index(_) | Segment::Wildcard) | (Some("map"), Segment::Field(_)) => {}
+                (Some("list"), _) => bail!("{} of {} names a field of a list; pick one element with [n]", path, name),
+                (Some(_), _) => bail!("{} indexes into a map of {}; use a key", path, name),
+                (None, Segment::Field(field_name)) => {
+                    let Kind::Message(desc) = &kind else { bail!("{} of {} goes below a field with no fields", path, name) };
+                    let (field_kind, is_list, is_map) = match desc.get_field_by_name(&field_name) {
+                        Some(field) => (field.kind(), field.is_list(), field.is_map()),
+                        None => {
+                            let ext = desc.get_extension_by_json_name(&field_name).ok_or_else(|| anyhow!("{} has no field {}", desc.name(), field_name))?;
+                            (ext.kind(), ext.is_list(), ext.is_map())
+                        }
+                    };
+                    kind = match field_kind {
+                        Kind::Message(entry) if is_map => {
+                            container = Some("map");
+                            entry.map_entry_value_field().kind()
+                        }
+                        k => {
+                            container = is_list.then_some("list");
+                            k
+                        }
+                    };
                 }
+                (None, _) => bail!("{} of {} indexes a field that is not repeated", path, name),
             }
         }
-        Err(anyhow!("message {} not found", name))
+        Ok((kind, container.is_some()))
     }
 
+    /// Message `name` with the fields of `json`; proto2 extensions are set by their JSON name in
+    /// brackets, e.g. `{"[vendor.ext.temperature]": 21}`
     pub fn build_from_json(&self, name: &str, json: &JsonValue) -> Result<DynamicMessage> {
         let desc = self.message_desc(name)?;
         let mut msg = DynamicMessage::new(desc.clone());
         if let JsonValue::Object(map) = json {
             for (k, v) in map {
-                if let Some(field) = desc.get_field_by_name(k) {
-                    let val = json_to_pbvalue(&field.kind(), v, &self.pool)?;
+                if let Some(field) = find_field(&desc, k) {
+                    let val = json_to_pbvalue(&field.kind(), v)?;
                     msg.set_field(&field, val);
+                } else if let Some(ext) = desc.get_extension_by_json_name(k) {
+                    msg.set_extension(&ext, json_to_pbvalue(&ext.kind(), v)?);
                 } else {
                     return Err(anyhow!("unknown field {} for {}", k, name));
                 }
@@ -76,7 +402,15 @@ impl ProtoDyn {
     }
 }
 
-fn json_to_pbvalue(kind: &prost_reflect::Kind, v: &JsonValue, pool: &DescriptorPool) -> Result<PbValue> {
+/// Field `key` of `desc`: by name, or for a proto2 group also by its type name, as text format
+/// writes it (`Calibration` for `optional group Calibration = 2`, named `calibration`)
+fn find_field(desc: &MessageDescriptor, key: &str) -> Option<FieldDescriptor> {
+    desc.get_field_by_name(key).or_else(|| desc.fields().find(|f| f.is_group() && matches!(f.kind(), Kind::Message(m) if m.name() == key)))
+}
+
+/// Groups are nested messages here: prost-reflect gives them `Kind::Message` and encodes them
+/// with the group wire format
+fn json_to_pbvalue(kind: &prost_reflect::Kind, v: &JsonValue) -> Result<PbValue> {
     use prost_reflect::Kind;
     match kind {
         Kind::Bool => Ok(PbValue::Bool(v.as_bool().ok_or_else(|| anyhow!("expected bool"))?)),
@@ -96,8 +430,12 @@ fn json_to_pbvalue(kind: &prost_reflect::Kind, v: &JsonValue, pool: &DescriptorP
             let mut dm = DynamicMessage::new(m.clone());
             let obj = v.as_object().ok_or_else(|| anyhow!("expected object"))?;
             for (k, vv) in obj.iter() {
-                let f = dm.descriptor().get_field_by_name(k).ok_or_else(|| anyhow!("unknown field {}", k))?;
-                let val = json_to_pbvalue(&f.kind(), vv, pool)?;
+                if let Some(ext) = m.get_extension_by_json_name(k) {
+                    dm.set_extension(&ext, json_to_pbvalue(&ext.kind(), vv)?);
+                    continue;
+                }
+                let f = find_field(m, k).ok_or_else(|| anyhow!("unknown field {}", k))?;
+                let val = json_to_pbvalue(&f.kind(), vv)?;
                 dm.set_field(&f, val);
             }
             Ok(PbValue::Message(dm))
@@ -123,6 +461,9 @@ fn dynamic_to_json(msg: &DynamicMessage) -> JsonValue {
             map.insert(f.name().to_string(), pbvalue_to_json(&val));
         }
     }
+    for (ext, val) in msg.extensions() {
+        map.insert(ext.json_name().to_string(), pbvalue_to_json(val));
+    }
     JsonValue::Object(map)
 }
 
@@ -152,12 +493,94 @@ fn pbvalue_to_json(v: &PbValue) -> JsonValue {
 }
 
 pub fn json_partial_match(expected: &JsonValue, actual: &JsonValue) -> bool {
+    json_partial_match_checked(expected, actual).unwrap_or(false)
+}
+
+/// Partial match that dispatches `{"$name": ...}` objects to registered matchers;
+/// fails on directives no matcher is registered for.
+pub fn json_partial_match_checked(expected: &JsonValue, actual: &JsonValue) -> Result<bool> {
+    json_partial_match_with(expected, actual, MatchOptions::default())
+}
+
+pub fn json_partial_match_with(expected: &JsonValue, actual: &JsonValue, opts: MatchOptions) -> Result<bool> {
     use serde_json::Value::*;
     match (expected, actual) {
-        (Object(eo), Object(ao)) => eo.iter().all(|(k, ev)| ao.get(k).map(|av| json_partial_match(ev, av)).unwrap_or(false)),
+        (Object(directive), _) if matchers::is_directive(expected) => matchers::eval(directive, Some(actual)),
+        (Object(eo), Object(ao)) => {
+            for (k, ev) in eo {
+                let ok = match (ao.get(k), ev) {
+                    (Some(av), _) => json_partial_match_with(ev, av, opts)?,
+                    // a directive may also decide about absent fields
+                    (None, Object(directive)) if matchers::is_directive(ev) => matchers::eval(directive, None)?,
+                    (None, _) => false,
+                };
+                if !ok { return Ok(false); }
+            }
+            Ok(true)
+        }
         (Array(ea), Array(aa)) => {
-            ea.iter().all(|ev| aa.iter().any(|av| json_partial_match(ev, av)))
+            for ev in ea {
+                let mut found = false;
+                for av in aa {
+                    if json_partial_match_with(ev, av, opts)? { found = true; break; }
+                }
+                if !found { return Ok(false); }
+            }
+            Ok(true)
         }
-        _ => expected == actual,
+        (String(es), String(as_)) if opts.normalize_strings => Ok(matchers::normalize_str(es) == matchers::normalize_str(as_)),
+        _ => Ok(expected == actual),
+    }
+}
+
+/// Top-level fields of an expectation compared on the decoded message, without converting it to
+/// JSON. Only plain integer, bool, string and enum values are bound; lists, nested messages,
+/// floats, bytes and directives are left to the JSON comparison.
+#[derive(Debug, Clone)]
+pub struct BoundFields {
+    fields: Vec<(FieldDescriptor, PbValue)>,
+    complete: bool,
+}
+
+impl BoundFields {
+    /// Bind what can be bound of `expected` to fields of `desc`
+    pub fn bind(desc: &MessageDescriptor, expected: &JsonValue, opts: MatchOptions) -> Self {
+        let Some(map) = expected.as_object().filter(|_| !matchers::is_directive(expected)) else {
+            return Self { fields: Vec::new(), complete: false };
+        };
+        let mut fields = Vec::new();
+        for (key, value) in map {
+            let bound = desc
+                .get_field_by_name(key)
+                .filter(|f| !f.is_list() && !f.is_map())
+                .and_then(|f| bind_scalar(&f.kind(), value, opts).map(|v| (f, v)));
+            fields.extend(bound);
+        }
+        Self { complete: fields.len() == map.len(), fields }
+    }
+
+    /// False when a bound field is absent or differs, so the message cannot match
+    pub fn may_match(&self, msg: &DynamicMessage) -> bool {
+        self.fields.iter().all(|(f, want)| msg.has_field(f) && *msg.get_field(f) == *want)
+    }
+
+    /// Every expected field is bound, so [`may_match`](Self::may_match) decides on its own
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// The value a JSON comparison would accept, for kinds where that is exactly one value
+fn bind_scalar(kind: &Kind, v: &JsonValue, opts: MatchOptions) -> Option<PbValue> {
+    match (kind, v) {
+        (Kind::Bool, JsonValue::Bool(b)) => Some(PbValue::Bool(*b)),
+        (Kind::Int32 | Kind::Sint32 | Kind::Sfixed32, JsonValue::Number(n)) => n.as_i64().and_then(|i| i32::try_from(i).ok()).map(PbValue::I32),
+        (Kind::Int64 | Kind::Sint64 | Kind::Sfixed64, JsonValue::Number(n)) => n.as_i64().map(PbValue::I64),
+        (Kind::Uint32 | Kind::Fixed32, JsonValue::Number(n)) => n.as_u64().and_then(|u| u32::try_from(u).ok()).map(PbValue::U32),
+        (Kind::Uint64 | Kind::Fixed64, JsonValue::Number(n)) => n.as_u64().map(PbValue::U64),
+        (Kind::String, JsonValue::String(s)) if !opts.normalize_strings => Some(PbValue::String(s.clone())),
+        (Kind::Enum(e), JsonValue::String(s)) => e.get_value_by_name(s).map(|v| PbValue::EnumNumber(v.number())),
+        (Kind::Enum(_), JsonValue::Number(n)) => n.as_i64().and_then(|i| i32::try_from(i).ok()).map(PbValue::EnumNumber),
+        _ => None,
     }
 }
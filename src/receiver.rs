@@ -0,0 +1,324 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::{BufferConfig, EvictionPolicy, FramingConfig};
+use crate::report::Traffic;
+use crate::transport::Subscriber;
+
+/// How often the receive thread wakes up to check for commands and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A message taken off the subscriber by the background receiver
+#[derive(Debug, Clone)]
+pub struct Received {
+    /// Monotonic per-broker sequence number, in arrival order
+    pub seq: u64,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// When the subscriber handed the message over. Every connection reads the same monotonic
+    /// clock, so arrival times compare across connections.
+    pub at: Instant,
+    /// Set once an expectation matched this message, so it isn't matched twice
+    pub consumed: bool,
+}
+
+/// Messages received so far, oldest first
+#[derive(Debug, Default)]
+pub struct Inbox {
+    messages: VecDeque<Received>,
+    next_seq: u64,
+    limit: BufferConfig,
+    /// Messages evicted or discarded because the buffer was full, by topic
+    dropped: BTreeMap<String, u64>,
+    /// Frames discarded because their CRC didn't check out, by topic
+    crc_errors: BTreeMap<String, u64>,
+    /// Everything buffered or dropped for a full buffer, by topic
+    traffic: BTreeMap<String, Traffic>,
+    /// Messages already waiting in the subscriber when reading resumed after a stall, by topic
+    queued: BTreeMap<String, u64>,
+    overflowed: bool,
+}
+
+impl Inbox {
+    fn push(&mut self, topic: String, payload: Vec<u8>, at: Instant) {
+        let traffic = self.traffic.entry(topic.clone()).or_default();
+        traffic.received += 1;
+        traffic.received_bytes += payload.len() as u64;
+        if self.limit.capacity.is_some_and(|cap| self.messages.len() >= cap) {
+            match self.limit.policy {
+                EvictionPolicy::DropOldest if !self.messages.is_empty() => {
+                    let oldest = self.messages.pop_front().expect("not empty");
+                    *self.dropped.entry(oldest.topic).or_default() += 1;
+                }
+                policy => {
+                    self.overflowed |= policy == EvictionPolicy::Fail;
+                    *self.dropped.entry(topic).or_default() += 1;
+                    return;
+                }
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.messages.push_back(Received { seq, topic, payload, at, consumed: false });
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Received> {
+        self.messages.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Received> {
+        self.messages.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Messages dropped because the buffer was full, by topic; clearing the buffer keeps them
+    pub fn dropped(&self) -> &BTreeMap<String, u64> {
+        &self.dropped
+    }
+
+    /// Messages and bytes received so far, by topic; clearing the buffer keeps them
+    pub fn traffic(&self) -> &BTreeMap<String, Traffic> {
+        &self.traffic
+    }
+
+    /// Frames discarded because their CRC didn't check out, by topic; clearing the buffer keeps them
+    pub fn crc_errors(&self) -> &BTreeMap<String, u64> {
+        &self.crc_errors
+    }
+
+    /// Messages found waiting when reading resumed after a stall, by topic; clearing the buffer
+    /// keeps them
+    pub fn queued(&self) -> &BTreeMap<String, u64> {
+        &self.queued
+    }
+
+    /// Error once a message was discarded under the `fail` policy
+    pub fn check_overflow(&self) -> Result<()> {
+        if self.overflowed {
+            let total: u64 = self.dropped.values().sum();
+            bail!("receive buffer overflowed at {} messages; {} dropped", self.limit.capacity.unwrap_or_default(), total);
+        }
+        Ok(())
+    }
+
+    /// Drop buffered messages, all of them or only those on `topic`. Returns how many were removed.
+    pub fn clear(&mut self, topic: Option<&str>) -> usize {
+        let before = self.messages.len();
+        match topic {
+            Some(t) => self.messages.retain(|m| m.topic != t),
+            None => self.messages.clear(),
+        }
+        before - self.messages.len()
+    }
+}
+
+/// Decides from topic and payload whether a received message is buffered at all
+pub type IngressFilter = Arc<dyn Fn(&str, &[u8]) -> bool + Send + Sync>;
+
+enum Command {
+    Connect(String, mpsc::Sender<Result<()>>),
+    Disconnect(mpsc::Sender<()>),
+}
+
+struct Shared {
+    inbox: Mutex<Inbox>,
+    arrived: Condvar,
+    stop: AtomicBool,
+    filter: RwLock<Option<IngressFilter>>,
+    framing: RwLock<HashMap<String, FramingConfig>>,
+    /// Until when the thread leaves messages in the subscriber
+    stalled_until: Mutex<Option<Instant>>,
+}
+
+/// Background thread owning the subscriber; buffers everything it receives into an [`Inbox`]
+pub struct Receiver {
+    shared: Arc<Shared>,
+    commands: mpsc::Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Receiver {
+    /// Start receiving from `sub` into a buffer bounded by `limit`
+    pub fn spawn(sub: Box<dyn Subscriber>, limit: BufferConfig) -> Result<Self> {
+        let inbox = Inbox { limit, ..Inbox::default() };
+        let shared = Arc::new(Shared {
+            inbox: Mutex::new(inbox),
+            arrived: Condvar::new(),
+            stop: AtomicBool::new(false),
+            filter: RwLock::new(None),
+            framing: RwLock::default(),
+            stalled_until: Mutex::new(None),
+        });
+        let (commands, rx) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("bdd-receiver".to_string())
+            .spawn(move || receive_loop(sub, thread_shared, rx))
+            .context("spawn receiver thread")?;
+        Ok(Self { shared, commands, thread: Some(thread) })
+    }
+
+    /// Connect the subscriber to another address
+    pub fn connect(&self, address: &str) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.commands
+            .send(Command::Connect(address.to_string(), tx))
+            .map_err(|_| anyhow!("receiver thread is not running"))?;
+        rx.recv().map_err(|_| anyhow!("receiver thread is not running"))?
+    }
+
+    /// Disconnect the subscriber; messages already buffered stay
+    pub fn disconnect(&self) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.commands.send(Command::Disconnect(tx)).map_err(|_| anyhow!("receiver thread is not running"))?;
+        rx.recv().map_err(|_| anyhow!("receiver thread is not running"))
+    }
+
+    /// Drop messages `filter` rejects as they arrive, before they are buffered. Replaces any earlier
+    /// filter; messages already buffered stay.
+    pub fn set_filter(&self, filter: Option<IngressFilter>) {
+        *self.shared.filter.write().unwrap_or_else(|e| e.into_inner()) = filter;
+    }
+
+    /// Verify and strip the CRC of `topic` frames as they arrive, or stop doing so with None
+    pub fn set_framing(&self, topic: &str, framing: Option<FramingConfig>) {
+        let mut all = self.shared.framing.write().unwrap_or_else(|e| e.into_inner());
+        match framing {
+            Some(framing) => all.insert(topic.to_string(), framing),
+            None => all.remove(topic),
+        };
+    }
+
+    /// Leave messages in the subscriber until `until`, as a slow consumer would; they queue in the
+    /// transport or the SUT drops them. Replaces any stall still running.
+    pub fn stall(&self, until: Instant) {
+        *self.shared.stalled_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(until);
+    }
+
+    pub fn inbox(&self) -> MutexGuard<'_, Inbox> {
+        self.shared.inbox.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run `check` against the inbox now and every time a message arrives, until it returns
+    /// Some or `deadline` passes.
+    pub fn wait_until<T>(&self, deadline: Instant, mut check: impl FnMut(&mut Inbox) -> Option<T>) -> Option<T> {
+        let mut inbox = self.inbox();
+        loop {
+            if let Some(found) = check(&mut inbox) {
+                return Some(found);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            inbox = self
+                .shared
+                .arrived
+                .wait_timeout(inbox, deadline - now)
+                .map(|(guard, _)| guard)
+                .unwrap_or_else(|e| e.into_inner().0);
+        }
+    }
+
+    /// Stop the thread; the subscriber is closed when it exits
+    pub fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn receive_loop(mut sub: Box<dyn Subscriber>, shared: Arc<Shared>, commands: mpsc::Receiver<Command>) {
+    // reading what queued up during a stall, until the subscriber has nothing waiting
+    let mut draining = false;
+    while !shared.stop.load(Ordering::SeqCst) {
+        while let Ok(cmd) = commands.try_recv() {
+            match cmd {
+                Command::Connect(address, reply) => {
+                    log::debug!(target: "transport", "subscriber connecting to {}", address);
+                    let _ = reply.send(sub.connect(&address));
+                }
+                Command::Disconnect(reply) => {
+                    log::debug!(target: "transport", "subscriber disconnecting");
+                    sub.disconnect();
+                    let _ = reply.send(());
+                }
+            }
+        }
+        let stalled_until = *shared.stalled_until.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = stalled_until {
+            let now = Instant::now();
+            if now < until {
+                std::thread::sleep((until - now).min(POLL_INTERVAL));
+                continue;
+            }
+            log::debug!(target: "transport", "resuming reads after a stall");
+            *shared.stalled_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            draining = true;
+        }
+        let timeout = if draining { Duration::ZERO } else { POLL_INTERVAL };
+        let ((topic, payload), at) = match sub.recv(timeout) {
+            // stamped before framing and filtering, which take varying time
+            Ok(Some(m)) => (m, Instant::now()),
+            Ok(None) => {
+                draining = false;
+                continue;
+            }
+            Err(e) => {
+                eprintln!("receiver stopped: {:#}", e);
+                break;
+            }
+        };
+        if draining {
+            *shared.inbox.lock().unwrap_or_else(|e| e.into_inner()).queued.entry(topic.clone()).or_default() += 1;
+        }
+        let framing = shared.framing.read().unwrap_or_else(|e| e.into_inner()).get(&topic).copied();
+        let payload = match framing.map(|f| f.strip(&payload).map(<[u8]>::to_vec)) {
+            None => payload,
+            Some(Ok(stripped)) => stripped,
+            Some(Err(e)) => {
+                log::debug!(target: "transport", "discarded {}: {:#}", topic, e);
+                *shared.inbox.lock().unwrap_or_else(|e| e.into_inner()).crc_errors.entry(topic).or_default() += 1;
+                continue;
+            }
+        };
+        if let Some(filter) = shared.filter.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            if !filter(&topic, &payload) {
+                log::trace!(target: "transport", "filtered {} ({} bytes)", topic, payload.len());
+                continue;
+            }
+        }
+        log::trace!(target: "transport", "received {} ({} bytes)", topic, payload.len());
+        shared.inbox.lock().unwrap_or_else(|e| e.into_inner()).push(topic, payload, at);
+        shared.arrived.notify_all();
+    }
+}
+
+impl std::fmt::Debug for Receiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver")
+            .field("buffered", &self.inbox().len())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value as JsonValue;
+use std::time::Instant;
+
+use crate::broker::Broker;
+
+/// Expectation timeout when an `expect` command gives none
+pub const DEFAULT_TIMEOUT_MS: i32 = 5000;
+
+pub const HELP: &str = "\
+send <Name> [json]                 send a message
+expect <Name> [json] [timeout_ms]  wait for a partially matching message
+list messages                      message names in the descriptor pool
+dump last <n>                      the last n received messages
+clear [Name]                       drop buffered messages
+help                               this text
+quit                               exit";
+
+/// One REPL command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Send { name: String, body: JsonValue },
+    Expect { name: String, expected: JsonValue, timeout_ms: i32 },
+    ListMessages,
+    Dump(usize),
+    Clear(Option<String>),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        Ok(match word {
+            "send" => {
+                let (name, json) = name_and_rest(rest)?;
+                Self::Send { name, body: json_or_empty(json)? }
+            }
+            "expect" => {
+                let (name, rest) = name_and_rest(rest)?;
+                // a trailing integer after the JSON is the timeout
+                let (json, timeout_ms) = match rest.rsplit_once(char::is_whitespace) {
+                    Some((json, t)) if t.parse::<i32>().is_ok() => (json, t.parse()?),
+                    _ => match rest.parse::<i32>() {
+                        Ok(t) => ("", t),
+                        Err(_) => (rest, DEFAULT_TIMEOUT_MS),
+                    },
+                };
+                Self::Expect { name, expected: json_or_empty(json)?, timeout_ms }
+            }
+            "list" if rest == "messages" => Self::ListMessages,
+            "dump" => {
+                let n = rest.strip_prefix("last").unwrap_or(rest).trim();
+                Self::Dump(if n.is_empty() { 10 } else { n.parse().map_err(|_| anyhow!("dump last <n>: bad count {}", n))? })
+            }
+            "clear" => Self::Clear(Some(rest.to_string()).filter(|r| !r.is_empty())),
+            "help" | "?" => Self::Help,
+            "quit" | "exit" => Self::Quit,
+            other => bail!("unknown command {} (try help)", other),
+        })
+    }
+
+    /// Run the command against `broker` and return the text to print
+    pub fn run(&self, broker: &Broker) -> Result<String> {
+        Ok(match self {
+            Self::Send { name, body } => {
+                broker.send_message(name, body)?;
+                format!("sent {}", name)
+            }
+            Self::Expect { name, expected, timeout_ms } => {
+                let got = broker.expect_message(name, expected, *timeout_ms)?;
+                format!("{} after {:?} ({} bytes): {}", got.topic, got.waited, got.size, got.body)
+            }
+            Self::ListMessages => broker.message_names().join("\n"),
+            Self::Dump(n) => {
+                let now = Instant::now();
+                broker
+                    .recent(*n)
+                    .iter()
+                    .map(|c| format!("-{:.3}s {} ({} bytes): {}", now.saturating_duration_since(c.at).as_secs_f64(), c.topic, c.size, c.body))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Self::Clear(topic) => format!("cleared {}", broker.clear_received(topic.as_deref())),
+            Self::Help => HELP.to_string(),
+            Self::Quit => String::new(),
+        })
+    }
+}
+
+fn name_and_rest(s: &str) -> Result<(String, &str)> {
+    let (name, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+    if name.is_empty() {
+        bail!("missing message name");
+    }
+    Ok((name.to_string(), rest.trim()))
+}
+
+fn json_or_empty(s: &str) -> Result<JsonValue> {
+    if s.trim().is_empty() {
+        return Ok(serde_json::json!({}));
+    }
+    serde_json::from_str(s).map_err(|e| anyhow!("invalid JSON {}: {}", s, e))
+}
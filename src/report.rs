@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use cucumber::{cli, event, gherkin, parser, writer, Event, World, Writer};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How many of the slowest steps/waits are listed in the timing report
+const REPORT_TOP: usize = 10;
+
+/// Message waits recorded by Broker::expect_message, drained by the report at the end of the run
+static WAITS: Mutex<Vec<WaitTiming>> = Mutex::new(Vec::new());
+
+/// Traffic of the brokers shut down so far, by topic
+static TRAFFIC: Mutex<BTreeMap<String, Traffic>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone)]
+pub struct StepTiming {
+    pub feature: String,
+    pub scenario: String,
+    pub step: String,
+    pub duration: Duration,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WaitTiming {
+    pub message: String,
+    pub duration: Duration,
+    pub matched: bool,
+}
+
+/// Record how long an expectation waited for `message` (called by the broker)
+pub fn record_wait(message: &str, duration: Duration, matched: bool) {
+    if let Ok(mut waits) = WAITS.lock() {
+        waits.push(WaitTiming { message: message.to_string(), duration, matched });
+    }
+}
+
+/// Messages and payload bytes on a topic, each way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub sent: u64,
+    pub sent_bytes: u64,
+    pub received: u64,
+    pub received_bytes: u64,
+}
+
+impl Traffic {
+    pub fn messages(&self) -> u64 {
+        self.sent + self.received
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.sent_bytes + self.received_bytes
+    }
+
+    pub fn add(&mut self, other: &Traffic) {
+        self.sent += other.sent;
+        self.sent_bytes += other.sent_bytes;
+        self.received += other.received;
+        self.received_bytes += other.received_bytes;
+    }
+}
+
+/// Add a scenario's traffic to the run's (called by the broker on shutdown)
+pub fn record_traffic(traffic: &BTreeMap<String, Traffic>) {
+    if let Ok(mut total) = TRAFFIC.lock() {
+        for (topic, t) in traffic {
+            total.entry(topic.clone()).or_default().add(t);
+        }
+    }
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Flag steps taking longer than this in the timing report (e.g. 500ms, 2s)
+    #[arg(long, value_name = "duration", value_parser = humantime::parse_duration)]
+    pub slow_threshold: Option<Duration>,
+}
+
+/// Writer measuring wall-clock duration of every step; prints a timing report when the run finishes.
+/// Meant to be tee'd next to the regular output writer. Durations come from the event timestamps,
+/// as blocking steps delay event delivery to writers.
+#[derive(Debug, Default)]
+pub struct Timings {
+    cli: Cli,
+    started: HashMap<(String, usize, usize), SystemTime>,
+    steps: Vec<StepTiming>,
+}
+
+impl Timings {
+    pub fn new(cli: Cli) -> Self {
+        Self { cli, ..Self::default() }
+    }
+
+    pub fn steps(&self) -> &[StepTiming] {
+        &self.steps
+    }
+
+    fn on_step<W>(&mut self, feature: &gherkin::Feature, scenario: &gherkin::Scenario, step: &gherkin::Step, ev: &event::Step<W>, at: SystemTime) {
+        let key = (feature.name.clone(), scenario.position.line, step.position.line);
+        let passed = match ev {
+            event::Step::Started => {
+                self.started.insert(key, at);
+                return;
+            }
+            event::Step::Passed(..) => true,
+            event::Step::Failed(..) => false,
+            event::Step::Skipped => {
+                self.started.remove(&key);
+                return;
+            }
+        };
+        if let Some(start) = self.started.remove(&key) {
+            self.steps.push(StepTiming {
+                feature: feature.name.clone(),
+                scenario: scenario.name.clone(),
+                step: format!("{}{}", step.keyword, step.value),
+                duration: at.duration_since(start).unwrap_or_default(),
+                passed,
+            });
+        }
+    }
+
+    fn print_report(&self) {
+        let cli = &self.cli;
+        let seed = crate::seed::run_seed();
+        println!("[Seed]");
+        println!("{} (repeat the run's random data with --seed {})", seed, seed);
+
+        let total: Duration = self.steps.iter().map(|s| s.duration).sum();
+        println!("[Timings]");
+        println!("{} steps, {:.3}s total", self.steps.len(), total.as_secs_f64());
+
+        let mut slowest: Vec<&StepTiming> = self.steps.iter().collect();
+        slowest.sort_by_key(|s| std::cmp::Reverse(s.duration));
+        for s in slowest.iter().take(REPORT_TOP) {
+            let slow = cli.slow_threshold.is_some_and(|t| s.duration > t);
+            println!(
+                "  {:>9.3}s {} {} ({}: {}){}",
+                s.duration.as_secs_f64(),
+                if s.passed { "✔" } else { "✘" },
+                s.step,
+                s.feature,
+                s.scenario,
+                if slow { "  SLOW" } else { "" }
+            );
+        }
+        if let Some(threshold) = cli.slow_threshold {
+            let count = self.steps.iter().filter(|s| s.duration > threshold).count();
+            println!("{} step(s) slower than {}", count, humantime::format_duration(threshold));
+        }
+
+        let mut waits = WAITS.lock().map(|mut w| std::mem::take(&mut *w)).unwrap_or_default();
+        if !waits.is_empty() {
+            let total: Duration = waits.iter().map(|w| w.duration).sum();
+            println!("{} message waits, {:.3}s total", waits.len(), total.as_secs_f64());
+            waits.sort_by_key(|w| std::cmp::Reverse(w.duration));
+            for w in waits.iter().take(REPORT_TOP) {
+                println!(
+                    "  {:>9.3}s {}{}",
+                    w.duration.as_secs_f64(),
+                    w.message,
+                    if w.matched { "" } else { " (timeout)" }
+                );
+            }
+        }
+
+        let traffic = TRAFFIC.lock().map(|mut t| std::mem::take(&mut *t)).unwrap_or_default();
+        if !traffic.is_empty() {
+            println!("[Traffic]");
+            println!("  {:<24} {:>9} {:>11} {:>9} {:>11}", "topic", "sent", "bytes", "received", "bytes");
+            for (topic, t) in &traffic {
+                println!("  {:<24} {:>9} {:>11} {:>9} {:>11}", topic, t.sent, t.sent_bytes, t.received, t.received_bytes);
+            }
+        }
+
+        let coverage = crate::coverage::take();
+        if let (false, Ok(proto)) = (coverage.is_empty(), crate::proto_dyn::ProtoDyn::new()) {
+            let types = crate::conformance::message_types(&proto, &[]).unwrap_or_default();
+            println!("[Coverage]");
+            println!("{}", coverage.summary(&types));
+            for gap in coverage.gaps(&types) {
+                println!("  {}", gap);
+            }
+            for matched in coverage.matched_fields(&types) {
+                println!("  {}", matched);
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World> Writer<W> for Timings {
+    type Cli = cli::Empty;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, _: &Self::Cli) {
+        let Ok(Event { value, at, .. }) = ev else { return };
+        match value {
+            event::Cucumber::Feature(feature, event::Feature::Scenario(scenario, ev))
+            | event::Cucumber::Feature(feature, event::Feature::Rule(_, event::Rule::Scenario(scenario, ev))) => {
+                match &ev.event {
+                    event::Scenario::Step(step, sev) | event::Scenario::Background(step, sev) => {
+                        self.on_step(&feature, &scenario, step, sev, at)
+                    }
+                    _ => {}
+                }
+            }
+            event::Cucumber::Finished => self.print_report(),
+            _ => {}
+        }
+    }
+}
+
+// Timings keys steps by feature/scenario/line, so it doesn't care about event order
+impl writer::Normalized for Timings {}
+
+/// Cucumber's `Tee` for a `right` writer taking its options when built, like [`Timings`]: the pair
+/// keeps `left`'s CLI, so the run's writer options don't nest in `cli::Compose` and `cli::Empty`
+#[derive(Debug)]
+pub struct Beside<L, R> {
+    left: L,
+    right: R,
+}
+
+pub trait WriterBeside: Sized {
+    /// Pass every event to `right` too, after `self`
+    fn beside<R>(self, right: R) -> Beside<Self, R> {
+        Beside { left: self, right }
+    }
+}
+
+impl<T> WriterBeside for T {}
+
+#[async_trait(?Send)]
+impl<W: World, L: Writer<W>, R: Writer<W, Cli = cli::Empty>> Writer<W> for Beside<L, R> {
+    type Cli = L::Cli;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, cli: &Self::Cli) {
+        self.left.handle_event(ev.clone(), cli).await;
+        self.right.handle_event(ev, &cli::Empty).await;
+    }
+}
+
+impl<W, L: writer::Stats<W>, R> writer::Stats<W> for Beside<L, R>
+where
+    Self: Writer<W>,
+{
+    fn passed_steps(&self) -> usize {
+        self.left.passed_steps()
+    }
+    fn skipped_steps(&self) -> usize {
+        self.left.skipped_steps()
+    }
+    fn failed_steps(&self) -> usize {
+        self.left.failed_steps()
+    }
+    fn retried_steps(&self) -> usize {
+        self.left.retried_steps()
+    }
+    fn parsing_errors(&self) -> usize {
+        self.left.parsing_errors()
+    }
+    fn hook_errors(&self) -> usize {
+        self.left.hook_errors()
+    }
+}
+
+impl<L: writer::Normalized, R: writer::Normalized> writer::Normalized for Beside<L, R> {}
@@ -0,0 +1,251 @@
+//! Scenario results kept between runs, so an interrupted or partly failed suite can be resumed
+//! without repeating the scenarios that already passed.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use cucumber::gherkin::{Feature, Scenario};
+use cucumber::parser::{self, Parser};
+use cucumber::{event, writer, Event, World, Writer};
+use futures::stream::{LocalBoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Where results are kept unless `--results` says otherwise
+pub const DEFAULT_PATH: &str = "target/bdd-results.json";
+
+/// Bumped when the results file format changes; files of another version are ignored
+const VERSION: u32 = 1;
+
+/// Which scenarios a run selects from the previous results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Every scenario, starting the results afresh
+    #[default]
+    All,
+    /// Only the scenarios that failed last time they ran
+    RerunFailed,
+    /// Every scenario that hasn't passed yet, including those an interrupted run never reached
+    SkipPassed,
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Run only the scenarios that failed in the previous runs
+    #[arg(long, global = true, conflicts_with = "skip_passed")]
+    pub rerun_failed: bool,
+
+    /// Resume: run every scenario that hasn't passed in the previous runs
+    #[arg(long, global = true)]
+    pub skip_passed: bool,
+
+    /// File scenario results are kept in between runs
+    #[arg(long, value_name = "path", global = true, default_value = DEFAULT_PATH)]
+    pub results: PathBuf,
+}
+
+impl Cli {
+    pub fn mode(&self) -> Mode {
+        match (self.rerun_failed, self.skip_passed) {
+            (true, _) => Mode::RerunFailed,
+            (false, true) => Mode::SkipPassed,
+            (false, false) => Mode::All,
+        }
+    }
+
+    /// Results to build on: the previous ones when resuming, none for a full run
+    pub fn previous(&self) -> Result<Results> {
+        match self.mode() {
+            Mode::All => Ok(Results::default()),
+            Mode::RerunFailed if !self.results.exists() => {
+                bail!("--rerun-failed: no results from an earlier run in {}", self.results.display())
+            }
+            _ => Results::load(&self.results),
+        }
+    }
+}
+
+/// Scenarios by feature file (relative to the working directory, so CI checkouts agree), line and
+/// name; an edited scenario no longer matches its old result
+type Key = (String, usize, String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    path: String,
+    line: usize,
+    scenario: String,
+    passed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct File {
+    version: u32,
+    scenarios: Vec<Record>,
+}
+
+/// Whether each scenario passed the last time it ran
+#[derive(Debug, Clone, Default)]
+pub struct Results {
+    scenarios: BTreeMap<Key, bool>,
+}
+
+fn key(feature: &Feature, scenario: &Scenario) -> Key {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let path = feature.path.as_ref().map(|p| p.strip_prefix(&cwd).unwrap_or(p).display().to_string()).unwrap_or_default();
+    (path, scenario.position.line, scenario.name.clone())
+}
+
+impl Results {
+    /// Results saved in `path`; empty when there is no file yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+        };
+        let file: File = serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+        if file.version != VERSION {
+            return Ok(Self::default());
+        }
+        let scenarios = file.scenarios.into_iter().map(|r| ((r.path, r.line, r.scenario), r.passed)).collect();
+        Ok(Self { scenarios })
+    }
+
+    /// Write to `path` through a temporary file, so an interrupted run never leaves half a file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let scenarios = self
+            .scenarios
+            .iter()
+            .map(|((path, line, scenario), passed)| Record { path: path.clone(), line: *line, scenario: scenario.clone(), passed: *passed })
+            .collect();
+        let text = serde_json::to_string_pretty(&File { version: VERSION, scenarios })?;
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, text + "\n").with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("write {}", path.display()))
+    }
+
+    pub fn record(&mut self, feature: &Feature, scenario: &Scenario, passed: bool) {
+        self.scenarios.insert(key(feature, scenario), passed);
+    }
+
+    /// Whether the scenario passed last time, None if it never ran
+    pub fn passed(&self, feature: &Feature, scenario: &Scenario) -> Option<bool> {
+        self.scenarios.get(&key(feature, scenario)).copied()
+    }
+
+    /// Add the results of another run, e.g. another CI shard; its results win where both ran a scenario
+    pub fn merge(&mut self, other: Results) {
+        self.scenarios.extend(other.scenarios);
+    }
+
+    /// Scenarios that passed and failed
+    pub fn counts(&self) -> (usize, usize) {
+        let passed = self.scenarios.values().filter(|p| **p).count();
+        (passed, self.scenarios.len() - passed)
+    }
+
+    /// Whether `mode` runs the scenario
+    pub fn selects(&self, mode: Mode, feature: &Feature, scenario: &Scenario) -> bool {
+        match mode {
+            Mode::All => true,
+            Mode::RerunFailed => self.passed(feature, scenario) == Some(false),
+            Mode::SkipPassed => self.passed(feature, scenario) != Some(true),
+        }
+    }
+}
+
+/// Parser dropping the scenarios `mode` doesn't select from every feature read by `inner`
+#[derive(Debug, Clone)]
+pub struct Resume<P = parser::Basic> {
+    inner: P,
+    mode: Mode,
+    previous: Results,
+}
+
+impl<P> Resume<P> {
+    pub fn new(inner: P, mode: Mode, previous: Results) -> Self {
+        Self { inner, mode, previous }
+    }
+}
+
+impl<I, P: Parser<I>> Parser<I> for Resume<P> {
+    type Cli = P::Cli;
+    type Output = LocalBoxStream<'static, parser::Result<Feature>>;
+
+    fn parse(self, input: I, cli: Self::Cli) -> Self::Output {
+        let (mode, previous) = (self.mode, self.previous);
+        self.inner
+            .parse(input, cli)
+            .map(move |feature| {
+                feature.map(|mut f| {
+                    let scenarios = std::mem::take(&mut f.scenarios);
+                    f.scenarios = scenarios.into_iter().filter(|s| previous.selects(mode, &f, s)).collect();
+                    let mut rules = std::mem::take(&mut f.rules);
+                    for rule in &mut rules {
+                        let scenarios = std::mem::take(&mut rule.scenarios);
+                        rule.scenarios = scenarios.into_iter().filter(|s| previous.selects(mode, &f, s)).collect();
+                    }
+                    f.rules = rules;
+                    f
+                })
+            })
+            .boxed_local()
+    }
+}
+
+/// Writer saving each scenario's result as soon as it finishes. Meant to be tee'd next to the
+/// regular output writer.
+#[derive(Debug)]
+pub struct ResultStore {
+    path: PathBuf,
+    results: Results,
+    /// Whether a step or hook failed, per running scenario
+    failed: HashMap<Key, bool>,
+}
+
+impl ResultStore {
+    /// Store adding to `results` (see [`Cli::previous`]) in `path`
+    pub fn new(path: impl Into<PathBuf>, results: Results) -> Self {
+        Self { path: path.into(), results, failed: HashMap::new() }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World> Writer<W> for ResultStore {
+    type Cli = cucumber::cli::Empty;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, _: &Self::Cli) {
+        let Ok(Event { value, .. }) = ev else { return };
+        let (feature, scenario, ev) = match &value {
+            event::Cucumber::Feature(f, event::Feature::Scenario(s, ev))
+            | event::Cucumber::Feature(f, event::Feature::Rule(_, event::Rule::Scenario(s, ev))) => (f, s, &ev.event),
+            _ => return,
+        };
+        let id = key(feature, scenario);
+        match ev {
+            event::Scenario::Started => {
+                self.failed.insert(id, false);
+            }
+            event::Scenario::Step(_, event::Step::Failed(..))
+            | event::Scenario::Background(_, event::Step::Failed(..))
+            | event::Scenario::Hook(_, event::Hook::Failed(..)) => {
+                self.failed.insert(id, true);
+            }
+            event::Scenario::Finished => {
+                let failed = self.failed.remove(&id).unwrap_or_default();
+                self.results.record(feature, scenario, !failed);
+                if let Err(e) = self.results.save(&self.path) {
+                    eprintln!("saving scenario results: {:#}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// results are keyed by scenario, so arrival order doesn't matter
+impl writer::Normalized for ResultStore {}
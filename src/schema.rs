@@ -0,0 +1,71 @@
+//! JSON Schema (draft 2020-12) for message types, describing the JSON payloads the steps accept:
+//! proto field names, enums by name or number, bytes as base64 strings. Nested message types go
+//! into `$defs` under their full name, so recursive types are fine.
+
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use serde_json::{json, Map, Value as JsonValue};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Schema document for `desc`
+pub fn json_schema(desc: &MessageDescriptor) -> JsonValue {
+    let mut defs = Map::new();
+    let mut schema = object_schema(desc, &mut defs);
+    let root = schema.as_object_mut().expect("object schema");
+    // references back to the root message point at the document itself
+    defs.remove(desc.full_name());
+    let mut doc = Map::new();
+    doc.insert("$schema".into(), json!(DIALECT));
+    doc.insert("title".into(), json!(desc.full_name()));
+    doc.append(root);
+    if !defs.is_empty() {
+        doc.insert("$defs".into(), JsonValue::Object(defs));
+    }
+    JsonValue::Object(doc)
+}
+
+fn object_schema(desc: &MessageDescriptor, defs: &mut Map<String, JsonValue>) -> JsonValue {
+    // placeholder first, so a field referring back to this type doesn't recurse
+    defs.insert(desc.full_name().to_string(), JsonValue::Null);
+    let properties: Map<String, JsonValue> = desc.fields().map(|f| (f.name().to_string(), field_schema(&f, desc, defs))).collect();
+    json!({ "type": "object", "properties": properties, "additionalProperties": false })
+}
+
+fn field_schema(field: &FieldDescriptor, root: &MessageDescriptor, defs: &mut Map<String, JsonValue>) -> JsonValue {
+    if field.is_map() {
+        let Kind::Message(entry) = field.kind() else { unreachable!("map entry is a message") };
+        let value = kind_schema(&entry.map_entry_value_field().kind(), root, defs);
+        return json!({ "type": "object", "additionalProperties": value });
+    }
+    let item = kind_schema(&field.kind(), root, defs);
+    if field.is_list() {
+        json!({ "type": "array", "items": item })
+    } else {
+        item
+    }
+}
+
+fn kind_schema(kind: &Kind, root: &MessageDescriptor, defs: &mut Map<String, JsonValue>) -> JsonValue {
+    match kind {
+        Kind::Bool => json!({ "type": "boolean" }),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => json!({ "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX }),
+        Kind::Uint32 | Kind::Fixed32 => json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX }),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => json!({ "type": "integer" }),
+        Kind::Uint64 | Kind::Fixed64 => json!({ "type": "integer", "minimum": 0 }),
+        Kind::Float | Kind::Double => json!({ "type": "number" }),
+        Kind::String => json!({ "type": "string" }),
+        Kind::Bytes => json!({ "type": "string", "contentEncoding": "base64" }),
+        Kind::Enum(e) => {
+            let names: Vec<String> = e.values().map(|v| v.name().to_string()).collect();
+            json!({ "title": e.full_name(), "anyOf": [{ "enum": names }, { "type": "integer" }] })
+        }
+        Kind::Message(m) if m == root => json!({ "$ref": "#" }),
+        Kind::Message(m) => {
+            if !defs.contains_key(m.full_name()) {
+                let schema = object_schema(m, defs);
+                defs.insert(m.full_name().to_string(), schema);
+            }
+            json!({ "$ref": format!("#/$defs/{}", m.full_name()) })
+        }
+    }
+}
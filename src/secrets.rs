@@ -0,0 +1,72 @@
+//! Credentials named in `[secrets]` and referenced as `${secret:<name>}`. Values are read on first
+//! use and replaced with [`REDACTED`] in everything the harness writes: logs, step output, the
+//! event stream and triage bundles.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{Config, SecretSource};
+
+/// Shown in place of a secret value
+pub const REDACTED: &str = "***";
+
+/// How long a `command` source may run
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Values read so far, by name
+static VALUES: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// The value of secret `name` from `[secrets]`, read once per run
+pub fn get(name: &str) -> Result<String> {
+    if let Some(value) = VALUES.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|v| v.get(name)) {
+        return Ok(value.clone());
+    }
+    let source = Config::global().secrets.get(name).ok_or_else(|| anyhow!("unknown secret {} (add it to [secrets])", name))?;
+    let value = read(source).with_context(|| format!("secret {}", name))?;
+    register(name, &value);
+    Ok(value)
+}
+
+/// Read a secret from its source; a trailing newline is dropped
+pub fn read(source: &SecretSource) -> Result<String> {
+    let value = match source {
+        SecretSource::Env(var) => std::env::var(var).with_context(|| format!("environment variable {}", var))?,
+        SecretSource::File(path) => std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?,
+        SecretSource::Command(command) => {
+            let mut cmd = std::process::Command::new("sh");
+            cmd.args(["-c", command]);
+            let output = crate::process::run(cmd, COMMAND_TIMEOUT)?;
+            if output.status != 0 {
+                // stderr may echo the secret, so only the exit code is reported
+                bail!("command exited with {}", output.status);
+            }
+            output.stdout
+        }
+    };
+    Ok(value.strip_suffix('\n').map(|v| v.strip_suffix('\r').unwrap_or(v)).unwrap_or(&value).to_string())
+}
+
+/// Redact `value` from output from now on, as if it were a secret called `name`
+pub fn register(name: &str, value: &str) {
+    let mut values = VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    values.get_or_insert_with(HashMap::new).insert(name.to_string(), value.to_string());
+}
+
+/// `text` with every secret value read so far replaced by [`REDACTED`]
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let values = VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(values) = values.as_ref() else { return Cow::Borrowed(text) };
+    // longest first, so a secret containing another is redacted whole
+    let mut secrets: Vec<&String> = values.values().filter(|v| !v.is_empty()).collect();
+    secrets.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    let mut text = Cow::Borrowed(text);
+    for value in secrets {
+        if text.contains(value.as_str()) {
+            text = Cow::Owned(text.replace(value.as_str(), REDACTED));
+        }
+    }
+    text
+}
@@ -0,0 +1,149 @@
+//! Reproducible randomness. The run has one seed, from `--seed` or else the clock, and each
+//! scenario derives its own from it and its feature and scenario names, unless a `@seed:<n>` tag
+//! fixes it. Random bodies and randomized faults draw from the scenario's [`Rng`], so running again
+//! with the seed the report prints repeats them.
+
+use anyhow::{Context, Result};
+use cucumber::gherkin::{Feature, Scenario};
+use prost_reflect::{Kind, MessageDescriptor};
+use serde_json::{json, Map, Value as JsonValue};
+use std::sync::OnceLock;
+
+static RUN_SEED: OnceLock<u64> = OnceLock::new();
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Seed for random bodies and randomized faults, to repeat a run the report printed the seed of
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+}
+
+impl Cli {
+    pub fn init(&self) {
+        if let Some(seed) = self.seed {
+            let _ = RUN_SEED.set(seed);
+        }
+    }
+}
+
+/// The run's seed: `--seed`, or else one taken from the clock the first time it is asked for
+pub fn run_seed() -> u64 {
+    *RUN_SEED.get_or_init(|| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        mix(now.as_nanos() as u64 ^ u64::from(std::process::id()))
+    })
+}
+
+/// The scenario's seed: its `@seed:<n>` tag (scenario before feature), else the run's seed mixed
+/// with the feature and scenario names, so scenarios differ but each repeats with the same run seed
+pub fn for_scenario(feature: &Feature, scenario: &Scenario) -> Result<u64> {
+    let tag = scenario.tags.iter().chain(feature.tags.iter()).find_map(|t| t.strip_prefix("seed:"));
+    match tag {
+        Some(t) => t.trim().parse().with_context(|| format!("@seed:{}: expected an unsigned integer", t)),
+        None => Ok(mix(run_seed() ^ fnv1a(format!("{}\0{}", feature.name, scenario.name).as_bytes()))),
+    }
+}
+
+/// 64-bit FNV-1a, stable between Rust releases unlike std's hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// The SplitMix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// SplitMix64: small and fast, and the same sequence for a seed on every platform. Not for secrets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng(u64);
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(run_seed())
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// Uniform in `0..n`; 0 when `n` is 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+}
+
+/// A body for `desc` with every field the send steps can fill set to a random value, in the shape
+/// of [`crate::conformance::sample`]: the first field of each oneof, no repeated or map fields, and
+/// messages nested in themselves left out
+pub fn random_body(desc: &MessageDescriptor, rng: &mut Rng) -> JsonValue {
+    random_within(desc, rng, &mut Vec::new())
+}
+
+fn random_within(desc: &MessageDescriptor, rng: &mut Rng, path: &mut Vec<String>) -> JsonValue {
+    path.push(desc.full_name().to_string());
+    let mut body = Map::new();
+    for field in desc.fields() {
+        if field.is_list() || field.is_map() {
+            continue;
+        }
+        if let Some(oneof) = field.containing_oneof() {
+            if oneof.fields().next().is_some_and(|first| first.number() != field.number()) {
+                continue;
+            }
+        }
+        let value = match field.kind() {
+            Kind::Bool => json!(rng.chance(0.5)),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => json!(rng.next_u64() as i32),
+            Kind::Uint32 | Kind::Fixed32 => json!(rng.next_u64() as u32),
+            // 64-bit integers are strings in proto JSON
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => json!((rng.next_u64() as i64).to_string()),
+            Kind::Uint64 | Kind::Fixed64 => json!(rng.next_u64().to_string()),
+            Kind::Float | Kind::Double => json!((rng.unit() * 2000.0 - 1000.0) as f32),
+            Kind::String => json!(random_text(rng)),
+            Kind::Bytes => {
+                use base64::Engine as _;
+                let bytes: Vec<u8> = (0..rng.below(17)).map(|_| rng.next_u64() as u8).collect();
+                json!(base64::engine::general_purpose::STANDARD.encode(bytes))
+            }
+            Kind::Enum(e) => {
+                let values: Vec<_> = e.values().collect();
+                let value = values.get(rng.below(values.len() as u64) as usize);
+                json!(value.map(|v| v.name().to_string()).unwrap_or_default())
+            }
+            Kind::Message(m) if path.iter().any(|p| p == m.full_name()) => continue,
+            Kind::Message(m) => random_within(&m, rng, path),
+        };
+        body.insert(field.name().to_string(), value);
+    }
+    path.pop();
+    JsonValue::Object(body)
+}
+
+/// Up to 16 printable ASCII characters
+fn random_text(rng: &mut Rng) -> String {
+    (0..rng.below(17)).map(|_| char::from(b' ' + rng.below(95) as u8)).collect()
+}
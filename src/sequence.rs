@@ -0,0 +1,143 @@
+//! Stimulus scripts played by `I play message sequence from "<path>"`: a list of messages, each
+//! sent a delay after the one before. YAML files are converted with the system `yq`, since no YAML
+//! crate is available to this build; JSON and TOML are read directly.
+//!
+//! ```yaml
+//! - message: PingRequest
+//! - delay: 250ms
+//!   message: Status
+//!   body: { state: READY }
+//! ```
+//!
+//! In TOML the entries are `[[messages]]` tables.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::broker::Broker;
+
+/// How long `yq` may take converting a file
+const YQ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sleeping ends this long before a send is due; the rest is spun, for sub-millisecond timing
+const SPIN: Duration = Duration::from_millis(2);
+
+/// One message of a sequence
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    /// Time after the previous entry was due (or after the start, for the first)
+    #[serde(default, deserialize_with = "crate::config::opt_duration")]
+    pub delay: Option<Duration>,
+    pub message: String,
+    /// JSON body; an empty message when left out
+    #[serde(default)]
+    pub body: Option<JsonValue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sequence {
+    pub entries: Vec<Entry>,
+}
+
+/// How playing went
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Played {
+    pub sent: usize,
+    pub took: Duration,
+    /// Longest time a send started after it was due
+    pub max_lag: Duration,
+}
+
+impl Sequence {
+    /// Read a `.yaml`/`.yml`, `.json` or `.toml` sequence file, passing its text through `expand`
+    /// (scenario variables) first
+    pub fn load(path: &Path, expand: impl Fn(&str) -> Result<String>) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let text = expand(&text)?;
+        let value = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text)?,
+            Some("toml") => serde_json::to_value(toml::from_str::<toml::Value>(&text)?)?,
+            Some("yaml" | "yml") => yaml_to_json(&text)?,
+            _ => bail!("{}: sequence files are .yaml, .yml, .json or .toml", path.display()),
+        };
+        Self::from_json(value).with_context(|| format!("sequence {}", path.display()))
+    }
+
+    /// A list of entries, or an object holding them under `messages`
+    pub fn from_json(value: JsonValue) -> Result<Self> {
+        let list = match value {
+            JsonValue::Array(list) => list,
+            JsonValue::Object(mut map) => match map.remove("messages") {
+                Some(JsonValue::Array(list)) if map.is_empty() => list,
+                _ => bail!("expected a list of messages, or just `messages` holding one"),
+            },
+            other => bail!("expected a list of messages, got {}", other),
+        };
+        let entries = list
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| serde_json::from_value(entry).with_context(|| format!("entry {}", i + 1)))
+            .collect::<Result<Vec<Entry>>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Time from the start to the last send
+    pub fn duration(&self) -> Duration {
+        self.entries.iter().filter_map(|e| e.delay).sum()
+    }
+
+    /// Send every entry through `broker` when it is due. Delays add up from the start, so a late
+    /// send doesn't push back the ones after it.
+    pub fn play(&self, broker: &Broker) -> Result<Played> {
+        let start = Instant::now();
+        let mut due = start;
+        let mut max_lag = Duration::ZERO;
+        let empty = JsonValue::Object(Default::default());
+        for (i, entry) in self.entries.iter().enumerate() {
+            due += entry.delay.unwrap_or_default();
+            wait_until(due);
+            max_lag = max_lag.max(due.elapsed());
+            broker
+                .send_message(&entry.message, entry.body.as_ref().unwrap_or(&empty))
+                .with_context(|| format!("entry {} ({})", i + 1, entry.message))?;
+        }
+        Ok(Played { sent: self.entries.len(), took: start.elapsed(), max_lag })
+    }
+}
+
+fn wait_until(due: Instant) {
+    if let Some(left) = due.checked_duration_since(Instant::now()).and_then(|d| d.checked_sub(SPIN)) {
+        std::thread::sleep(left);
+    }
+    while Instant::now() < due {
+        std::hint::spin_loop();
+    }
+}
+
+/// YAML as JSON through `yq`: mikefarah's (`-o=json`) or the jq wrapper (JSON by default)
+pub(crate) fn yaml_to_json(text: &str) -> Result<JsonValue> {
+    static CONVERSIONS: AtomicUsize = AtomicUsize::new(0);
+    let n = CONVERSIONS.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("bdd-yaml-{}-{}", std::process::id(), n));
+    std::fs::create_dir_all(&dir)?;
+    let file = dir.join("input.yaml");
+    std::fs::write(&file, text)?;
+    let convert = |args: &[&str]| {
+        let mut cmd = Command::new("yq");
+        cmd.args(args).arg(&file);
+        crate::process::run(cmd, YQ_TIMEOUT)
+    };
+    let output = convert(&["-o=json", "."]).and_then(|out| if out.status == 0 { Ok(out) } else { convert(&["."]) });
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = output.map_err(|e| anyhow!("YAML needs the system yq ({:#}); or write the file as .json or .toml", e))?;
+    if output.status != 0 {
+        bail!("yq: {}", output.stderr.trim());
+    }
+    serde_json::from_str(&output.stdout).context("yq output")
+}
@@ -0,0 +1,77 @@
+use serde_json::{json, Value as JsonValue};
+use std::time::Instant;
+
+use crate::broker::Broker;
+use crate::http::{Request, Response};
+
+/// Expectation timeout when the request has no `timeout_ms`
+pub const DEFAULT_TIMEOUT_MS: i32 = 5000;
+
+/// Route one control request to `broker`:
+///
+/// - `GET /health`
+/// - `POST /messages/<Name>` with the JSON body to send
+/// - `POST /expect/<Name>?timeout_ms=5000` with the expected (partial) JSON body
+/// - `GET /captures/<Name>` lists buffered messages on a topic
+/// - `DELETE /captures[/<Name>]` clears buffered messages
+pub fn handle(broker: &Broker, req: &Request) -> Response {
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["health"]) => Response::ok(json!({ "ok": true })),
+        ("POST", ["messages", name]) => send(broker, name, req),
+        ("POST", ["expect", name]) => expect(broker, name, req),
+        ("GET", ["captures", name]) => captures(broker, name),
+        ("DELETE", ["captures"]) => Response::ok(json!({ "cleared": broker.clear_received(None) })),
+        ("DELETE", ["captures", name]) => Response::ok(json!({ "cleared": broker.clear_received(Some(name)) })),
+        (_, ["health"] | ["messages", _] | ["expect", _] | ["captures"] | ["captures", _]) => {
+            Response::error(405, format!("{} not allowed on {}", req.method, req.path))
+        }
+        _ => Response::error(404, format!("no route {}", req.path)),
+    }
+}
+
+fn send(broker: &Broker, name: &str, req: &Request) -> Response {
+    let body = match req.json() {
+        Ok(b) => b,
+        Err(e) => return Response::error(400, format!("invalid JSON body: {:#}", e)),
+    };
+    match broker.send_message(name, &body) {
+        Ok(()) => Response::ok(json!({ "sent": name })),
+        Err(e) => Response::error(400, format!("{:#}", e)),
+    }
+}
+
+fn expect(broker: &Broker, name: &str, req: &Request) -> Response {
+    let expected = match req.json() {
+        Ok(b) => b,
+        Err(e) => return Response::error(400, format!("invalid JSON body: {:#}", e)),
+    };
+    let timeout_ms = match req.query.get("timeout_ms").map(|t| t.parse::<i32>()) {
+        None => DEFAULT_TIMEOUT_MS,
+        Some(Ok(t)) => t,
+        Some(Err(_)) => return Response::error(400, "timeout_ms must be an integer"),
+    };
+    match broker.expect_message(name, &expected, timeout_ms) {
+        Ok(got) => Response::ok(json!({
+            "topic": got.topic,
+            "body": got.body,
+            "size": got.size,
+            "waited_ms": got.waited.as_millis() as u64,
+        })),
+        Err(e) if e.to_string().starts_with("timeout") => Response::error(408, format!("{:#}", e)),
+        Err(e) => Response::error(400, format!("{:#}", e)),
+    }
+}
+
+fn captures(broker: &Broker, name: &str) -> Response {
+    let now = Instant::now();
+    match broker.captured(name) {
+        Ok(captured) => Response::ok(JsonValue::Array(
+            captured
+                .into_iter()
+                .map(|c| json!({ "body": c.body, "size": c.size, "age_ms": now.saturating_duration_since(c.at).as_millis() as u64 }))
+                .collect(),
+        )),
+        Err(e) => Response::error(400, format!("{:#}", e)),
+    }
+}
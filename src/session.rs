@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use cucumber::{event, gherkin, parser, writer, Event, World, Writer};
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::events::Scope;
+
+/// Scenario logs by feature file and scenario line, so the writer can find what a scenario recorded
+type Key = (Option<PathBuf>, String, usize);
+
+static SESSIONS: Mutex<Option<HashMap<Key, SessionLog>>> = Mutex::new(None);
+
+/// Indentation of session lines under their step in the default output
+const INDENT: &str = "      ";
+
+#[derive(Debug)]
+struct Entry {
+    at: SystemTime,
+    line: String,
+}
+
+/// A file a step attached to the scenario with [`MyWorld::attach`](crate::steps::MyWorld::attach)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// Bus traffic and notes recorded while a scenario runs. [`SessionWriter`] prints them under the
+/// step they happened in. Clones share the log.
+#[derive(Debug, Clone, Default)]
+pub struct SessionLog {
+    entries: Arc<Mutex<Vec<Entry>>>,
+    /// Connection name shown in front of traffic lines
+    label: Option<Arc<str>>,
+    /// The scenario, for event stream records
+    scope: Option<Arc<Scope>>,
+}
+
+impl SessionLog {
+    /// Log shared with the scenario's world, registered for [`SessionWriter`]
+    pub fn for_scenario(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Self {
+        let log = Self { scope: Some(Arc::new(Scope::new(feature, scenario))), ..Self::default() };
+        let mut sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.get_or_insert_with(HashMap::new).insert(key(feature, scenario), log.clone());
+        log
+    }
+
+    /// The same log, with traffic lines marked as coming from connection `name`
+    pub fn labelled(&self, name: &str) -> Self {
+        Self { label: Some(name.into()), ..self.clone() }
+    }
+
+    /// Connection name given by [`labelled`](Self::labelled)
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The scenario of a log made by [`for_scenario`](Self::for_scenario)
+    pub fn scope(&self) -> Option<&Scope> {
+        self.scope.as_deref()
+    }
+
+    pub fn sent(&self, topic: &str, body: &JsonValue) {
+        self.traffic("→", topic, body);
+    }
+
+    pub fn received(&self, topic: &str, body: &JsonValue) {
+        self.traffic("←", topic, body);
+    }
+
+    /// Free text shown under the current step
+    pub fn note(&self, text: impl Into<String>) {
+        self.push(text.into());
+    }
+
+    fn traffic(&self, arrow: &str, topic: &str, body: &JsonValue) {
+        match &self.label {
+            Some(label) => self.push(format!("{} [{}] {} {}", arrow, label, topic, body)),
+            None => self.push(format!("{} {} {}", arrow, topic, body)),
+        }
+    }
+
+    fn push(&self, line: String) {
+        // step output is runner info, so -q hides it
+        if !crate::logging::enabled("runner", log::Level::Info) {
+            return;
+        }
+        let line = crate::secrets::redact(&line).into_owned();
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).push(Entry { at: SystemTime::now(), line });
+    }
+
+    /// Remove and return the lines recorded up to `until`
+    pub fn take_until(&self, until: SystemTime) -> Vec<String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let split = entries.iter().position(|e| e.at > until).unwrap_or(entries.len());
+        entries.drain(..split).map(|e| e.line).collect()
+    }
+}
+
+fn key(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Key {
+    (feature.path.clone(), feature.name.clone(), scenario.position.line)
+}
+
+fn registered(feature: &gherkin::Feature, scenario: &gherkin::Scenario, finished: bool) -> Option<SessionLog> {
+    let mut sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let sessions = sessions.as_mut()?;
+    if finished {
+        sessions.remove(&key(feature, scenario))
+    } else {
+        sessions.get(&key(feature, scenario)).cloned()
+    }
+}
+
+/// Writer wrapper adding each step's session lines to the output right after the step, as scenario
+/// log events, and redacting secrets from step failures. Wrap the (summarized) writer that normalizes, so the lines stay with their step when
+/// scenarios run concurrently. Lines are assigned to steps by the event timestamps.
+#[derive(Debug)]
+pub struct SessionWriter<Wr> {
+    inner: Wr,
+}
+
+impl<Wr> SessionWriter<Wr> {
+    pub fn new(inner: Wr) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World, Wr: Writer<W>> Writer<W> for SessionWriter<Wr> {
+    type Cli = Wr::Cli;
+
+    async fn handle_event(&mut self, mut ev: parser::Result<Event<event::Cucumber<W>>>, cli: &Self::Cli) {
+        if let Ok(Event { value, .. }) = &mut ev {
+            redact_failure(value);
+        }
+        let log = match &ev {
+            Ok(Event { value, at, .. }) => session_event(value, *at),
+            Err(_) => None,
+        };
+        self.inner.handle_event(ev, cli).await;
+        if let Some(log) = log {
+            self.inner.handle_event(Ok(Event::new(log)), cli).await;
+        }
+    }
+}
+
+/// Hide secrets in the panic message of a failed step
+fn redact_failure<W>(value: &mut event::Cucumber<W>) {
+    let event::Cucumber::Feature(_, event::Feature::Scenario(_, ev) | event::Feature::Rule(_, event::Rule::Scenario(_, ev))) = value else {
+        return;
+    };
+    let (event::Scenario::Step(_, event::Step::Failed(_, _, _, event::StepError::Panic(info)))
+    | event::Scenario::Background(_, event::Step::Failed(_, _, _, event::StepError::Panic(info)))) = &mut ev.event
+    else {
+        return;
+    };
+    let message = match (info.downcast_ref::<String>(), info.downcast_ref::<&str>()) {
+        (Some(s), _) => s.as_str(),
+        (None, Some(s)) => s,
+        _ => return,
+    };
+    if let Cow::Owned(redacted) = crate::secrets::redact(message) {
+        *info = Arc::new(redacted);
+    }
+}
+
+/// The log event to follow `value`, when it ends a step that recorded something
+fn session_event<W>(value: &event::Cucumber<W>, at: SystemTime) -> Option<event::Cucumber<W>> {
+    let (feature, rule, scenario, ev) = match value {
+        event::Cucumber::Feature(f, event::Feature::Scenario(s, ev)) => (f, None, s, ev),
+        event::Cucumber::Feature(f, event::Feature::Rule(r, event::Rule::Scenario(s, ev))) => (f, Some(r), s, ev),
+        _ => return None,
+    };
+    let ended = match &ev.event {
+        event::Scenario::Step(_, step) | event::Scenario::Background(_, step) => {
+            matches!(step, event::Step::Passed(..) | event::Step::Failed(..))
+        }
+        event::Scenario::Finished => {
+            registered(feature, scenario, true);
+            return None;
+        }
+        _ => false,
+    };
+    if !ended {
+        return None;
+    }
+    let lines = registered(feature, scenario, false)?.take_until(at);
+    if lines.is_empty() {
+        return None;
+    }
+    let text: String = lines.iter().map(|l| format!("{}{}\n", INDENT, l)).collect();
+    let log = event::RetryableScenario { event: event::Scenario::Log(text), retries: ev.retries };
+    let feature = feature.clone();
+    Some(match rule {
+        Some(rule) => event::Cucumber::Feature(feature, event::Feature::Rule(rule.clone(), event::Rule::Scenario(scenario.clone(), log))),
+        None => event::Cucumber::Feature(feature, event::Feature::Scenario(scenario.clone(), log)),
+    })
+}
+
+impl<W, Wr: writer::Stats<W>> writer::Stats<W> for SessionWriter<Wr>
+where
+    Self: Writer<W>,
+{
+    fn passed_steps(&self) -> usize {
+        self.inner.passed_steps()
+    }
+
+    fn skipped_steps(&self) -> usize {
+        self.inner.skipped_steps()
+    }
+
+    fn failed_steps(&self) -> usize {
+        self.inner.failed_steps()
+    }
+
+    fn retried_steps(&self) -> usize {
+        self.inner.retried_steps()
+    }
+
+    fn parsing_errors(&self) -> usize {
+        self.inner.parsing_errors()
+    }
+
+    fn hook_errors(&self) -> usize {
+        self.inner.hook_errors()
+    }
+}
+
+// only adds scenario log events, which the wrapped writer orders with the rest
+impl<Wr: writer::Normalized> writer::Normalized for SessionWriter<Wr> {}
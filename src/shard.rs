@@ -0,0 +1,110 @@
+//! Splitting the suite across CI runners. Each scenario goes to one shard by a hash of its feature
+//! and scenario names, so the split is the same on every runner and between runs.
+
+use anyhow::{anyhow, bail, Result};
+use cucumber::gherkin::{Feature, Scenario};
+use cucumber::parser::{self, Parser};
+use futures::stream::{LocalBoxStream, StreamExt};
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+
+use crate::suite;
+
+/// Shard `index` of `count`, both counted from 1 as in `--shard 2/5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (index, count) = s.split_once('/').ok_or_else(|| anyhow!("expected <index>/<count>, e.g. 2/5"))?;
+        let (index, count): (u32, u32) = (index.trim().parse()?, count.trim().parse()?);
+        if index == 0 || index > count {
+            bail!("shard {} out of 1..={}", index, count);
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl Shard {
+    /// Whether the scenario runs on this shard. An ordered suite (`@order:<n>`) hands variables
+    /// between its scenarios, so it is kept whole on the shard of its feature.
+    pub fn selects(&self, feature: &Feature, scenario: &Scenario) -> bool {
+        let name = match suite::order(scenario) {
+            Ok(Some(_)) => feature.name.clone(),
+            _ => format!("{}\0{}", feature.name, scenario.name),
+        };
+        fnv1a(name.as_bytes()) % u64::from(self.count) == u64::from(self.index - 1)
+    }
+}
+
+/// 64-bit FNV-1a; unlike std's hasher it is guaranteed not to change between Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct Cli {
+    /// Run only this part of the suite, e.g. `2/5` for the second of five CI jobs
+    #[arg(long, value_name = "index/count", global = true)]
+    pub shard: Option<Shard>,
+}
+
+/// Parser dropping the scenarios of other shards from every feature read by `inner`
+#[derive(Debug, Clone)]
+pub struct Sharded<P = parser::Basic> {
+    inner: P,
+    shard: Option<Shard>,
+}
+
+impl<P> Sharded<P> {
+    /// Keeps every scenario when `shard` is None
+    pub fn new(inner: P, shard: Option<Shard>) -> Self {
+        Self { inner, shard }
+    }
+}
+
+impl<I, P: Parser<I>> Parser<I> for Sharded<P> {
+    type Cli = P::Cli;
+    type Output = LocalBoxStream<'static, parser::Result<Feature>>;
+
+    fn parse(self, input: I, cli: Self::Cli) -> Self::Output {
+        let shard = self.shard;
+        self.inner
+            .parse(input, cli)
+            .map(move |feature| {
+                let Some(shard) = shard else { return feature };
+                feature.map(|mut f| {
+                    let scenarios = std::mem::take(&mut f.scenarios);
+                    f.scenarios = scenarios.into_iter().filter(|s| shard.selects(&f, s)).collect();
+                    let mut rules = std::mem::take(&mut f.rules);
+                    for rule in &mut rules {
+                        let scenarios = std::mem::take(&mut rule.scenarios);
+                        rule.scenarios = scenarios.into_iter().filter(|s| shard.selects(&f, s)).collect();
+                    }
+                    f.rules = rules;
+                    f
+                })
+            })
+            .boxed_local()
+    }
+}
+
+/// Event stream lines of several shards as one stream, ordered by their `at` timestamps
+pub fn merge_events(streams: &[String]) -> Result<Vec<JsonValue>> {
+    let mut events = Vec::new();
+    for (n, stream) in streams.iter().enumerate() {
+        for (line_no, line) in stream.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let event: JsonValue = serde_json::from_str(line).map_err(|e| anyhow!("stream {} line {}: {}", n + 1, line_no + 1, e))?;
+            events.push(event);
+        }
+    }
+    // RFC 3339 timestamps in UTC with a fixed precision sort as text
+    events.sort_by(|a, b| a["at"].as_str().cmp(&b["at"].as_str()));
+    Ok(events)
+}
@@ -0,0 +1,186 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::Duration;
+
+use crate::config::{IpFamily, SomeIpConfig, SomeIpId};
+use crate::network::{Endpoint, Network};
+use crate::transport::{Publisher, Subscriber};
+
+/// SOME/IP header size; the length field counts everything after its own 8 bytes
+pub const HEADER_LEN: usize = 16;
+const PROTOCOL_VERSION: u8 = 0x01;
+const REQUEST_NO_RETURN: u8 = 0x01;
+const E_OK: u8 = 0x00;
+
+/// Header fields of one SOME/IP message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub service: u16,
+    pub method: u16,
+    pub client: u16,
+    pub session: u16,
+    pub interface_version: u8,
+    pub message_type: u8,
+    pub return_code: u8,
+}
+
+pub fn encode(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend(header.service.to_be_bytes());
+    out.extend(header.method.to_be_bytes());
+    out.extend((8 + payload.len() as u32).to_be_bytes());
+    out.extend(header.client.to_be_bytes());
+    out.extend(header.session.to_be_bytes());
+    out.extend([PROTOCOL_VERSION, header.interface_version, header.message_type, header.return_code]);
+    out.extend_from_slice(payload);
+    out
+}
+
+pub fn decode(datagram: &[u8]) -> Result<(Header, &[u8])> {
+    if datagram.len() < HEADER_LEN {
+        bail!("SOME/IP datagram of {} bytes is shorter than the header", datagram.len());
+    }
+    let u16_at = |i: usize| u16::from_be_bytes([datagram[i], datagram[i + 1]]);
+    let length = u32::from_be_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]) as usize;
+    if length < 8 || 8 + length > datagram.len() {
+        bail!("SOME/IP length {} does not fit a {} byte datagram", length, datagram.len());
+    }
+    if datagram[12] != PROTOCOL_VERSION {
+        bail!("unsupported SOME/IP protocol version {}", datagram[12]);
+    }
+    let header = Header {
+        service: u16_at(0),
+        method: u16_at(2),
+        client: u16_at(8),
+        session: u16_at(10),
+        interface_version: datagram[13],
+        message_type: datagram[14],
+        return_code: datagram[15],
+    };
+    Ok((header, &datagram[HEADER_LEN..8 + length]))
+}
+
+/// Topic <-> (service, method) table from `[someip.ids]`
+#[derive(Debug, Clone, Default)]
+struct Ids {
+    by_topic: HashMap<String, SomeIpId>,
+    by_id: HashMap<(u16, u16), String>,
+}
+
+impl Ids {
+    fn new(ids: &HashMap<String, SomeIpId>) -> Result<Self> {
+        let mut by_id = HashMap::new();
+        for (topic, id) in ids {
+            if let Some(other) = by_id.insert((id.service, id.method), topic.clone()) {
+                bail!("SOME/IP {:04X}.{:04X} is mapped to both {} and {}", id.service, id.method, other, topic);
+            }
+        }
+        Ok(Self { by_topic: ids.clone(), by_id })
+    }
+}
+
+/// Sends each topic as a REQUEST_NO_RETURN to the SUT's service/method
+#[derive(Debug)]
+pub struct SomeIpPublisher {
+    sock: UdpSocket,
+    remote: Option<SocketAddr>,
+    family: IpFamily,
+    port: u16,
+    client: u16,
+    interface_version: u8,
+    session: AtomicU16,
+    ids: Ids,
+}
+
+/// Receives SOME/IP datagrams and names them by their mapped topic
+#[derive(Debug)]
+pub struct SomeIpSubscriber {
+    sock: UdpSocket,
+    ids: Ids,
+    buf: Vec<u8>,
+}
+
+/// Publisher and subscriber sharing one UDP socket bound to `[someip] bind`. A wildcard bind
+/// address is narrowed to `[network] bind_address`, keeping the port.
+pub fn someip_pair(config: &SomeIpConfig, network: &Network) -> Result<(SomeIpPublisher, SomeIpSubscriber)> {
+    let ids = Ids::new(&config.ids)?;
+    let bind = match (config.bind.parse::<SocketAddr>(), &network.bind) {
+        (Ok(addr), Some(source)) if addr.ip().is_unspecified() => SocketAddr::new(source.ip(addr.is_ipv6())?, addr.port()).to_string(),
+        _ => config.bind.clone(),
+    };
+    let sock = UdpSocket::bind(&bind).with_context(|| format!("bind SOME/IP socket {}", bind))?;
+    let sub = SomeIpSubscriber { sock: sock.try_clone()?, ids: ids.clone(), buf: vec![0; 65536] };
+    let publisher = SomeIpPublisher {
+        sock,
+        remote: None,
+        family: network.family,
+        port: config.port,
+        client: config.client_id,
+        interface_version: config.interface_version,
+        session: AtomicU16::new(0),
+        ids,
+    };
+    Ok((publisher, sub))
+}
+
+impl Publisher for SomeIpPublisher {
+    /// `address` is the SUT's host or host:port; the port defaults to `[someip] port`
+    fn connect(&mut self, address: &str) -> Result<()> {
+        let endpoint: Endpoint = address.parse()?;
+        let addrs = endpoint.resolve(self.port, self.family)?;
+        // the socket can only reach addresses of the family it is bound in
+        let local = self.sock.local_addr().context("SOME/IP socket address")?;
+        let remote = addrs.iter().find(|a| a.is_ipv6() == local.is_ipv6()).ok_or_else(|| {
+            anyhow!("{} has no {} address, but the SOME/IP socket is bound to {}", endpoint.host, if local.is_ipv6() { "IPv6" } else { "IPv4" }, local)
+        })?;
+        self.remote = Some(*remote);
+        Ok(())
+    }
+
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        let remote = self.remote.ok_or_else(|| anyhow!("SOME/IP publisher is not connected"))?;
+        let id = self.ids.by_topic.get(topic).ok_or_else(|| anyhow!("no SOME/IP id for {}; add it under [someip.ids]", topic))?;
+        // session ids run 1..=0xFFFF, skipping 0
+        let session = self.session.fetch_add(1, Ordering::SeqCst) % 0xFFFF + 1;
+        let header = Header {
+            service: id.service,
+            method: id.method,
+            client: self.client,
+            session,
+            interface_version: self.interface_version,
+            message_type: REQUEST_NO_RETURN,
+            return_code: E_OK,
+        };
+        self.sock.send_to(&encode(&header, payload), remote).with_context(|| format!("send {} to {}", topic, remote))?;
+        Ok(())
+    }
+}
+
+impl Subscriber for SomeIpSubscriber {
+    fn connect(&mut self, _address: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        self.sock.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+        let len = match self.sock.recv_from(&mut self.buf) {
+            Ok((len, _)) => len,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => return Ok(None),
+            Err(e) => return Err(e).context("receive SOME/IP datagram"),
+        };
+        // errors and datagrams that aren't SOME/IP are skipped, not fatal
+        let Ok((header, payload)) = decode(&self.buf[..len]) else { return Ok(None) };
+        if header.return_code != E_OK {
+            return Ok(None);
+        }
+        let topic = self
+            .ids
+            .by_id
+            .get(&(header.service, header.method))
+            .cloned()
+            .unwrap_or_else(|| format!("{:04X}.{:04X}", header.service, header.method));
+        Ok(Some((topic, payload.to_vec())))
+    }
+}
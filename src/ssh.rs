@@ -0,0 +1,34 @@
+use anyhow::Result;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::config::HostConfig;
+use crate::process::{self, CommandOutput};
+
+/// Arguments for the system `ssh` client running `command` on `host`
+pub fn ssh_args(host: &HostConfig, command: &str) -> Vec<String> {
+    let mut args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+    if let Some(port) = host.port {
+        args.extend(["-p".to_string(), port.to_string()]);
+    }
+    if let Some(identity) = &host.identity {
+        args.extend(["-i".to_string(), identity.display().to_string()]);
+    }
+    for option in &host.options {
+        args.extend(["-o".to_string(), option.clone()]);
+    }
+    args.push(match &host.user {
+        Some(user) => format!("{}@{}", user, host.address),
+        None => host.address.clone(),
+    });
+    args.extend(["--".to_string(), command.to_string()]);
+    args
+}
+
+/// Run `command` on `host` over ssh. The remote exit code is returned as is; ssh itself
+/// failing to connect shows up as exit code 255.
+pub fn run(host: &HostConfig, command: &str, timeout: Duration) -> Result<CommandOutput> {
+    let mut cmd = Command::new("ssh");
+    cmd.args(ssh_args(host, command));
+    process::run(cmd, timeout)
+}
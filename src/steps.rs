@@ -1,14 +1,108 @@
 use cucumber::{given, when, then, World};
-use cucumber::gherkin::Step; // <-- Step contains the DocString
-use crate::broker::Broker;
+use cucumber::gherkin::{self, Step}; // <-- Step contains the DocString
+use crate::aggregate::{self, Aggregate, Comparison, Monotonic, RateStats, SequenceReport};
+use crate::broker::{Broker, Captured, READY_TIMEOUT};
+use crate::config::{self, Config, Readiness};
+use crate::csv;
+#[cfg(feature = "db")]
+use crate::db::{self, Database};
+use crate::device::Device;
+use crate::fixtures::Fixtures;
+use crate::grpc::{GrpcClient, GrpcStream, Status};
+use crate::http::SseClient;
+use crate::jsonpath;
+use crate::operator;
+use crate::params::{DurationParam, Endpoint, MessageName};
+use crate::ports::{Endpoints, PortLease};
+use crate::proto_dyn::ProtoDyn;
+use crate::seed::{self, Rng};
+use crate::process::{self, CommandOutput};
+use crate::sequence::{Played, Sequence};
+use crate::session::{Attachment, SessionLog};
+use crate::suite;
+use crate::tls;
+use crate::tmpdir::ScenarioTmp;
+use crate::triage;
+use crate::vars;
+use crate::matchers::{self, MatchOptions};
+use prost_reflect::EnumDescriptor;
 use serde_json::Value as JsonValue;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use futures::future::{FutureExt, LocalBoxFuture};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Default time an expectation step waits for its message
+pub const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Default time a command step may run before it is killed
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(World, Debug)]
 pub struct MyWorld {
     pub broker: Option<Broker>,
     pub default_ip: String,
     pub sub_port: u16,
+    /// Scenario deadline (from @timeout:<duration> or [scenario] timeout); waits never block past it.
+    /// Checked, not enforced: a step blocking anywhere else runs on past it.
+    pub deadline: Option<Instant>,
+    pub scenario_timeout: Option<Duration>,
+    /// Time all the scenario's expectations may wait in total, from @expect_budget:<duration> or
+    /// [scenario] expect_budget
+    pub expect_budget: Option<Duration>,
+    /// Time expectations have waited so far
+    pub expect_spent: Cell<Duration>,
+    /// Named points in time set by `I mark time as "<name>"`
+    pub markers: HashMap<String, Instant>,
+    /// Fields left out of every comparison ([matching] ignore_fields plus `I ignore fields ...`)
+    pub ignore_fields: Vec<String>,
+    /// Matcher macros: [matching.macros] plus those loaded from fixtures
+    pub macros: HashMap<String, JsonValue>,
+    /// Messages gathered by `I collect <Topic> messages for <duration>`, per topic
+    pub collected: HashMap<String, Vec<Captured>>,
+    /// Extra connections opened by `I open <transport> connection "<name>"`, besides `broker`
+    pub connections: HashMap<String, Broker>,
+    /// Server-Sent Events subscription opened by `I subscribe to SSE at "<url>"`
+    pub sse: Option<SseClient>,
+    /// gRPC connection opened by `I connect to gRPC at "<url>"`
+    pub grpc: Option<GrpcClient>,
+    /// The call opened by `I open stream <Service>/<Method>`
+    pub grpc_stream: Option<GrpcStream>,
+    /// Result of the last command step
+    pub command: Option<CommandOutput>,
+    /// Scenario variables, substituted for `${name}` in DocStrings and commands
+    pub vars: HashMap<String, String>,
+    /// Traffic and notes shown under each step in the output
+    pub session: SessionLog,
+    /// Failures of `softly <step>` steps, failing the scenario at its end
+    pub soft_failures: Vec<String>,
+    /// Position in the feature's ordered suite, from @order:<n>
+    pub order: Option<u32>,
+    /// Variables handed on to later ordered scenarios if this one passes
+    pub exports: HashMap<String, String>,
+    /// Ports allocated for `[endpoints]`, released at teardown
+    pub ports: Vec<PortLease>,
+    /// `${scenario_tmp}`, removed after the scenario unless it failed
+    pub tmp: Option<ScenarioTmp>,
+    /// Devices set up by `Given device "<id>" exists`, by id
+    pub devices: HashMap<String, Device>,
+    /// Files attached by steps with [`attach`](Self::attach)
+    pub attachments: Vec<Attachment>,
+    /// The scenario's seed (`${seed}`), from @seed:<n> or derived from the run's `--seed`
+    pub seed: u64,
+    /// Random bodies and randomized faults, seeded with `seed`
+    pub rng: Rng,
+    #[cfg(feature = "modbus")]
+    pub modbus: Option<crate::modbus::ModbusClient>,
+    #[cfg(feature = "db")]
+    pub database: Option<Database>,
+    /// Rows returned by the last `I query the database`
+    #[cfg(feature = "db")]
+    pub rows: Vec<JsonValue>,
 }
 
 impl Default for MyWorld {
@@ -17,51 +111,1430 @@ impl Default for MyWorld {
             broker: None,
             default_ip: "127.0.0.1".to_string(),
             sub_port: 4247,
+            deadline: None,
+            scenario_timeout: None,
+            expect_budget: None,
+            expect_spent: Cell::new(Duration::ZERO),
+            markers: HashMap::new(),
+            ignore_fields: Config::global().matching.ignore_fields.clone(),
+            macros: Config::global().matching.macros.clone(),
+            collected: HashMap::new(),
+            connections: HashMap::new(),
+            sse: None,
+            grpc: None,
+            grpc_stream: None,
+            command: None,
+            vars: HashMap::new(),
+            session: SessionLog::default(),
+            soft_failures: Vec::new(),
+            order: None,
+            exports: HashMap::new(),
+            ports: Vec::new(),
+            tmp: None,
+            devices: HashMap::new(),
+            attachments: Vec::new(),
+            seed: 0,
+            rng: Rng::default(),
+            #[cfg(feature = "modbus")]
+            modbus: None,
+            #[cfg(feature = "db")]
+            database: None,
+            #[cfg(feature = "db")]
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl MyWorld {
+    /// Release everything the scenario acquired; safe to call more than once
+    pub fn teardown(&mut self) {
+        if let Some(mut broker) = self.broker.take() {
+            broker.shutdown();
+        }
+        for (_, mut broker) in self.connections.drain() {
+            broker.shutdown();
+        }
+        if let Some(mut sse) = self.sse.take() {
+            sse.close();
+        }
+        self.grpc_stream = None;
+        if let Some(mut grpc) = self.grpc.take() {
+            grpc.close();
+        }
+        self.ports.clear();
+        #[cfg(feature = "modbus")]
+        {
+            self.modbus = None;
+        }
+    }
+
+    /// Attach a file to the scenario, e.g. a screenshot or an oscilloscope capture from your own
+    /// steps. It is noted under the step, sent whole to the event stream and packed into the triage
+    /// bundle if the scenario fails.
+    pub fn attach(&mut self, name: &str, data: impl Into<Vec<u8>>, mime: &str) {
+        let attachment = Attachment { name: name.to_string(), mime: mime.to_string(), data: data.into() };
+        self.session.note(format!("attached {} ({}, {} bytes)", attachment.name, attachment.mime, attachment.data.len()));
+        if crate::events::enabled() {
+            let fields = serde_json::json!({
+                "name": attachment.name,
+                "mime": attachment.mime,
+                "size": attachment.data.len(),
+                "data": base64::engine::general_purpose::STANDARD.encode(&attachment.data),
+            });
+            let fields = match self.session.scope() {
+                Some(scope) => scope.with(fields),
+                None => fields,
+            };
+            crate::events::emit("attachment", std::time::SystemTime::now(), fields);
+        }
+        self.attachments.push(attachment);
+    }
+
+    /// A connection recording its traffic in the scenario's session log
+    pub fn open_broker(&self, kind: &str, address: Option<&str>) -> Result<Broker> {
+        let mut broker = Broker::open(kind, address)?;
+        broker.set_session_log(self.session.clone());
+        Ok(broker)
+    }
+
+    /// Save buffered messages and the last command's output for the triage bundle
+    fn stage_triage(&self, feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Result<()> {
+        let Some(dir) = triage::ScenarioDir::create(feature, scenario)? else { return Ok(()) };
+        if let Some(broker) = &self.broker {
+            dir.captures("default", broker)?;
+        }
+        for (name, broker) in &self.connections {
+            dir.captures(name, broker)?;
+        }
+        for attachment in &self.attachments {
+            dir.attachment(&attachment.name, &attachment.data)?;
+        }
+        if let Some(command) = &self.command {
+            dir.file("command.txt", &format!("exit {}
+--- stdout
+{}
+--- stderr
+{}
+", command.status, command.stdout, command.stderr))?;
+        }
+        Ok(())
+    }
+
+    /// Fail once the scenario deadline has passed
+    pub fn check_deadline(&self) -> Result<()> {
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.scenario_timeout) {
+            if Instant::now() >= deadline {
+                anyhow::bail!("scenario timed out after {}", humantime::format_duration(timeout));
+            }
+        }
+        Ok(())
+    }
+
+    /// The named connection, or the broker started by `I run broker` when `name` is None
+    pub fn connection(&self, name: Option<&str>) -> Result<&Broker> {
+        match name {
+            None => Ok(self.broker.as_ref().expect("broker not started")),
+            Some(n) => self.connections.get(n).ok_or_else(|| anyhow::anyhow!("no connection \"{}\"", n)),
+        }
+    }
+
+    /// Messages gathered for `topic` by an earlier collect step
+    pub fn collected_for(&self, topic: &str) -> Result<&[Captured]> {
+        self.collected
+            .get(topic)
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow::anyhow!("no {} messages collected; use `I collect {} messages for <duration>` first", topic, topic))
+    }
+
+    /// Text with scenario variables substituted
+    pub fn expand(&self, text: &str) -> Result<String> {
+        vars::substitute(text, &self.vars)
+    }
+
+    /// The step's DocString with scenario variables substituted
+    pub fn docstring(&self, step: &Step) -> Result<Option<String>> {
+        step.docstring.as_deref().map(|doc| self.expand(doc)).transpose()
+    }
+
+    /// An expected body as it is compared: `[matching.macros]` expanded and ignored fields left out
+    pub fn expectation(&self, expected: &JsonValue) -> Result<JsonValue> {
+        let expected = matchers::expand_macros(expected, &self.macros)?;
+        Ok(matchers::without_fields(&expected, &self.ignore_fields))
+    }
+
+    /// Clamp a wait to the time left before the scenario deadline.
+    /// Returns the wait and whether it was cut short by the deadline.
+    pub fn wait_budget(&self, timeout: Duration) -> Result<(Duration, bool)> {
+        self.check_deadline()?;
+        match self.deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                Ok(if left < timeout { (left, true) } else { (timeout, false) })
+            }
+            None => Ok((timeout, false)),
+        }
+    }
+
+    /// Run an expectation waiting up to `timeout`, cut to what is left of the expectation budget
+    /// and the scenario deadline, and take the time it waited from the budget. Fails at once when
+    /// the budget is used up.
+    pub fn budgeted<T>(&self, timeout: Duration, wait: impl FnOnce(Duration) -> Result<T>) -> Result<T> {
+        let left = self.expect_budget.map(|total| total.saturating_sub(self.expect_spent.get()));
+        if let (Some(total), Some(Duration::ZERO)) = (self.expect_budget, left) {
+            anyhow::bail!("expectation budget of {} used up", humantime::format_duration(total));
+        }
+        let cut = left.is_some_and(|left| left < timeout);
+        let (timeout, clamped) = self.wait_budget(left.map_or(timeout, |left| left.min(timeout)))?;
+        let start = Instant::now();
+        let result = wait(timeout);
+        if let Some(total) = self.expect_budget {
+            self.expect_spent.set(self.expect_spent.get() + start.elapsed());
+            let left = total.saturating_sub(self.expect_spent.get());
+            log::info!(target: "runner", "expectation budget: {:?} of {} left", left, humantime::format_duration(total));
+        }
+        match result {
+            Err(e) if clamped => Err(e.context(format!(
+                "scenario timed out after {}",
+                humantime::format_duration(self.scenario_timeout.unwrap_or_default())
+            ))),
+            Err(e) if cut => Err(e.context(format!("expectation budget of {} used up", humantime::format_duration(self.expect_budget.unwrap_or_default())))),
+            result => result,
+        }
+    }
+}
+
+/// Scenario timeout from a @timeout:<duration> tag (scenario before feature), else the config default
+pub fn scenario_timeout(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Result<Option<Duration>> {
+    let tag = scenario.tags.iter().chain(feature.tags.iter()).find_map(|t| t.strip_prefix("timeout:"));
+    match tag {
+        Some(t) => Ok(Some(config::parse_duration(t)?)),
+        None => Ok(Config::global().scenario.timeout),
+    }
+}
+
+/// Expectation budget from a @expect_budget:<duration> tag (scenario before feature), else the config default
+pub fn expect_budget(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Result<Option<Duration>> {
+    let tag = scenario.tags.iter().chain(feature.tags.iter()).find_map(|t| t.strip_prefix("expect_budget:"));
+    match tag {
+        Some(t) => Ok(Some(config::parse_duration(t)?)),
+        None => Ok(Config::global().scenario.expect_budget),
+    }
+}
+
+/// Hook to pass to `Cucumber::after`: hands an ordered scenario's exports on, stages what a failed
+/// scenario received for the triage bundle, then tears the world down whether the scenario passed, failed or panicked,
+/// removing `${scenario_tmp}` unless it failed. Soft assertion failures left unchecked fail the scenario here.
+pub fn after_scenario<'a>(
+    feature: &'a gherkin::Feature,
+    _rule: Option<&'a gherkin::Rule>,
+    scenario: &'a gherkin::Scenario,
+    ev: &'a cucumber::event::ScenarioFinished,
+    mut world: Option<&'a mut MyWorld>,
+) -> LocalBoxFuture<'a, ()> {
+    async move {
+        let soft_failures = world.as_deref_mut().map(|w| std::mem::take(&mut w.soft_failures)).unwrap_or_default();
+        let failed = !soft_failures.is_empty()
+            || !matches!(ev, cucumber::event::ScenarioFinished::StepPassed | cucumber::event::ScenarioFinished::StepSkipped);
+        if let Ok(Some(_)) = suite::order(scenario) {
+            let exports = world.as_deref_mut().map(|w| std::mem::take(&mut w.exports)).unwrap_or_default();
+            suite::finish(feature, scenario, exports, !failed);
+        }
+        if let Some(world) = world {
+            if failed && crate::debug::pause_on_failure() {
+                let why = match ev {
+                    cucumber::event::ScenarioFinished::StepFailed(_, _, e) => format!("scenario {} failed: {}", scenario.name, e),
+                    cucumber::event::ScenarioFinished::BeforeHookFailed(_) => format!("scenario {} failed in its before hook", scenario.name),
+                    _ => format!("scenario {} failed: {}", scenario.name, soft_failures.join("; ")),
+                };
+                crate::debug::pause(world, &why).await;
+            } else if crate::debug::stepping() {
+                crate::debug::pause(world, &format!("scenario {} passed", scenario.name)).await;
+            }
+            if failed {
+                log::warn!(target: "runner", "scenario {}: seed {}; tag it @seed:{} to repeat its random data", scenario.name, world.seed, world.seed);
+                if let Err(e) = world.stage_triage(feature, scenario) {
+                    eprintln!("triage: {:#}", e);
+                }
+            }
+            world.teardown();
+            match world.tmp.take() {
+                Some(tmp) if failed => log::warn!(target: "runner", "scenario {}: files kept in {}", scenario.name, tmp.path().display()),
+                Some(tmp) => {
+                    if let Err(e) = tmp.remove() {
+                        log::warn!(target: "runner", "scenario {}: {:#}", scenario.name, e);
+                    }
+                }
+                None => {}
+            }
+        }
+        if !soft_failures.is_empty() {
+            panic!("{}", soft_failure_report(&soft_failures));
+        }
+    }
+    .boxed_local()
+}
+
+/// Hook to pass to `Cucumber::before`: starts the scenario deadline, session log and seed, allocates the
+/// `[endpoints]` ports and `${scenario_tmp}`, and gives an ordered scenario the variables exported before it
+pub fn before_scenario<'a>(
+    feature: &'a gherkin::Feature,
+    _rule: Option<&'a gherkin::Rule>,
+    scenario: &'a gherkin::Scenario,
+    world: &'a mut MyWorld,
+) -> LocalBoxFuture<'a, ()> {
+    async move {
+        let timeout = scenario_timeout(feature, scenario)
+            .unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        world.scenario_timeout = timeout;
+        world.deadline = timeout.map(|t| Instant::now() + t);
+        world.expect_budget = expect_budget(feature, scenario).unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        world.session = SessionLog::for_scenario(feature, scenario);
+        world.seed = seed::for_scenario(feature, scenario).unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        world.rng = Rng::new(world.seed);
+        world.vars.insert("seed".to_string(), world.seed.to_string());
+        let endpoints = Endpoints::resolve(&Config::global().endpoints).unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        world.vars.extend(endpoints.vars);
+        world.ports = endpoints.leases;
+        let tmp = ScenarioTmp::create(&scenario.name).unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        world.vars.insert("scenario_tmp".to_string(), tmp.path().display().to_string());
+        world.tmp = Some(tmp);
+        world.order = suite::order(scenario).unwrap_or_else(|e| panic!("scenario '{}': {:#}", scenario.name, e));
+        if world.order.is_some() {
+            let handoff = suite::handoff(feature);
+            if let Some(failed) = &handoff.failed {
+                log::warn!(target: "runner", "scenario {}: earlier ordered scenario '{}' failed", scenario.name, failed);
+                world.session.note(format!("earlier ordered scenario '{}' failed; its exports are missing", failed));
+            }
+            world.vars.extend(handoff.vars);
+        }
+        log::debug!(target: "runner", "scenario {}: timeout {:?}", scenario.name, timeout);
+        if crate::debug::stepping() {
+            crate::debug::pause(world, &format!("scenario {} starting", scenario.name)).await;
         }
     }
+    .boxed_local()
 }
 
-#[given(regex = r"I run broker")]
+#[given(expr = "I run broker")]
 async fn run_broker_default(world: &mut MyWorld) -> Result<()> {
     let ip = world.default_ip.clone();
-    let broker = Broker::new()?;
-    broker.connect(&ip)?;
-    world.broker = Some(broker);
+    start_broker(world, &ip)
+}
+
+#[given(expr = "I run broker at {endpoint}")]
+async fn run_broker_at_ip(world: &mut MyWorld, ip: Endpoint) -> Result<()> {
+    let ip = world.expand(&ip.0)?;
+    start_broker(world, &ip)
+}
+
+fn start_broker(world: &mut MyWorld, ip: &str) -> Result<()> {
+    world.broker = Some(world.open_broker("zmq", Some(ip))?);
     Ok(())
 }
 
-#[given(regex = r"I run broker at (\S+)")]
-async fn run_broker_at_ip(world: &mut MyWorld, ip: String) -> Result<()> {
-    let broker = Broker::new()?;
-    broker.connect(&ip)?;
-    world.broker = Some(broker);
+#[cfg(feature = "someip")]
+#[given(expr = r"I connect to SOME\/IP at {endpoint}")]
+async fn connect_someip(world: &mut MyWorld, address: Endpoint) -> Result<()> {
+    world.broker = Some(world.open_broker("someip", Some(&world.expand(&address.0)?))?);
     Ok(())
 }
 
-#[when(regex = r"I send message (\w+)")]
-async fn send_message(world: &mut MyWorld, name: String, step: &Step) -> Result<()> {
-    let broker = world.broker.as_ref().expect("broker not started");
+#[cfg(feature = "can")]
+#[given(regex = r#"^I connect to (?:CAN interface "([^"]+)"|the CAN bus)$"#)]
+async fn connect_can(world: &mut MyWorld, interface: String) -> Result<()> {
+    world.broker = Some(world.open_broker("can", Some(interface.as_str()).filter(|i| !i.is_empty()))?);
+    Ok(())
+}
+
+/// `with schema "<name>"` resolves the connection's messages with `[schemas.<name>]`, e.g. the
+/// device side of a bridge speaking an older version than the cloud side
+#[given(regex = r#"^I open (\S+) connection "(\w+)"(?: (?:at|on) (\S+))?(?: with schema "(\w+)")?$"#)]
+async fn open_connection(world: &mut MyWorld, kind: String, name: String, address: String, schema: String) -> Result<()> {
+    let kind = kind.to_lowercase().replace('/', "");
+    let address = match address.as_str() {
+        "" if kind == "zmq" => Some(world.default_ip.clone()),
+        "" => None,
+        a => Some(world.expand(a)?),
+    };
+    let mut broker = world.open_broker(&kind, address.as_deref())?;
+    broker.set_session_log(world.session.labelled(&name));
+    if !schema.is_empty() {
+        broker.set_schema(ProtoDyn::named(&schema)?);
+    }
+    if let Some(mut old) = world.connections.insert(name, broker) {
+        old.shutdown();
+    }
+    Ok(())
+}
+
+/// Chaos: drops the connection for a while to exercise the SUT's reconnect and buffering
+#[when(regex = r#"^I disconnect (?:the broker|connection "(\w+)") for (.+)$"#)]
+async fn disconnect_for(world: &mut MyWorld, connection: String, down: DurationParam) -> Result<()> {
+    let (down, clamped) = world.wait_budget(down.0)?;
+    let broker = match connection.as_str() {
+        "" => world.broker.as_mut().expect("broker not started"),
+        c => world.connections.get_mut(c).ok_or_else(|| anyhow::anyhow!("no connection \"{}\"", c))?,
+    };
+    broker.bounce(down)?;
+    if clamped {
+        world.check_deadline()?;
+    }
+    world.session.note(format!("reconnected after {:?}", down));
+    Ok(())
+}
+
+/// Semi-automated procedures: blocks until the operator confirms the action on the console, or
+/// over HTTP with `[operator] listen`. Waits 10 minutes unless the step gives a timeout.
+#[when(regex = r#"^I pause for operator action "(.+)"(?: \(timeout (.+)\))?$"#)]
+async fn operator_action(world: &mut MyWorld, action: String, timeout: String) -> Result<()> {
+    let timeout = if timeout.is_empty() { operator::DEFAULT_TIMEOUT } else { config::parse_duration(&timeout)? };
+    let (timeout, clamped) = world.wait_budget(timeout)?;
+    let action = world.expand(&action)?;
+    let confirmed = operator::wait(&action, timeout, Config::global().operator.listen.as_deref());
+    if clamped && confirmed.is_err() {
+        world.check_deadline()?;
+    }
+    world.session.note(format!("operator confirmed \"{}\" on the {}", action, confirmed?));
+    Ok(())
+}
+
+/// Fault injection: the next send on the topic goes out twice, to check the SUT's deduplication.
+/// Both send times are noted when it happens.
+#[when(regex = r#"^I duplicate the next message sent on topic (\w+)(?: on connection "(\w+)")?$"#)]
+async fn duplicate_next(world: &mut MyWorld, topic: String, connection: String) -> Result<()> {
+    world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.duplicate_next(&topic);
+    Ok(())
+}
+
+/// Randomized fault injection: duplicates the next send on the topic with probability `p`, drawn
+/// from the scenario's seed
+#[when(regex = r#"^I duplicate the next message sent on topic (\w+) with probability ([\d.]+)(?: on connection "(\w+)")?$"#)]
+async fn maybe_duplicate_next(world: &mut MyWorld, topic: String, p: f64, connection: String) -> Result<()> {
+    if !(0.0..=1.0).contains(&p) {
+        anyhow::bail!("probability {} out of 0..=1", p);
+    }
+    let duplicate = world.rng.chance(p);
+    world.session.note(format!("duplicating the next {}: {} (seed {})", topic, if duplicate { "yes" } else { "no" }, world.seed));
+    if duplicate {
+        world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.duplicate_next(&topic);
+    }
+    Ok(())
+}
+
+#[when(expr = "I send message {message}")]
+async fn send_message(world: &mut MyWorld, name: MessageName, step: &Step) -> Result<()> {
+    send_message_on(world, &name.0, step, None)
+}
+
+#[when(expr = "I send message {message} on connection {string}")]
+async fn send_message_on_connection(world: &mut MyWorld, name: MessageName, connection: String, step: &Step) -> Result<()> {
+    send_message_on(world, &name.0, step, Some(&connection))
+}
+
+/// For topics that differ from the message name, e.g. with a routing suffix: `on topic "tlm/${device}"`
+#[when(regex = r#"^I send message ([\w.]+) on topic "([^"]+)"(?: on connection "(\w+)")?:?$"#)]
+async fn send_message_on_topic(world: &mut MyWorld, name: MessageName, topic: String, connection: String, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let topic = world.expand(&topic)?;
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    broker.send_message_on_topic(&topic, &name.0, &message_body(world, step)?)
+}
 
-    let body: JsonValue = if let Some(ref doc) = step.docstring {
+/// Schema evolution: the DocString body encoded with another version of the message, from a
+/// descriptor set or .proto file, e.g. `encoded with descriptors "old_v1.bin"`, or a `[schemas]` name
+#[when(regex = r#"^I send message ([\w.]+) encoded with descriptors "([^"]+)"(?: on connection "(\w+)")?:?$"#)]
+async fn send_message_with_descriptors(world: &mut MyWorld, name: MessageName, path: String, connection: String, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let path = world.expand(&path)?;
+    let schema = if Config::global().schemas.contains_key(&path) { ProtoDyn::named(&path)? } else { ProtoDyn::load(Path::new(&path))? };
+    world.session.note(format!("{} encoded with {}", name.0, path));
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    broker.send_message_with(&schema, &name.0, &message_body(world, step)?)
+}
+
+/// Over-the-air schema update: the connection's descriptor set or .proto files are read again,
+/// for every connection sharing them
+#[when(regex = r#"^I reload the descriptors(?: on connection "(\w+)")?$"#)]
+async fn reload_descriptors(world: &mut MyWorld, connection: String) -> Result<()> {
+    world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.reload_schema()?;
+    world.session.note("descriptors reloaded".to_string());
+    Ok(())
+}
+
+/// Every field the send steps can fill set to a random value from the scenario's seed; the body sent
+/// is noted with the seed so a failure shows what went out
+#[when(regex = r#"^I send message ([\w.]+) with random fields(?: on connection "(\w+)")?$"#)]
+async fn send_random_message(world: &mut MyWorld, name: MessageName, connection: String) -> Result<()> {
+    world.check_deadline()?;
+    let desc = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.message_desc(&name.0)?;
+    let body = seed::random_body(&desc, &mut world.rng);
+    world.session.note(format!("random {} (seed {}): {}", name.0, world.seed, body));
+    world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.send_message(&name.0, &body)
+}
+
+/// Variables, devices and matcher macros from a .toml or .json file, for short Backgrounds
+#[given(regex = r#"^fixtures from "([^"]+)"$"#)]
+async fn load_fixtures(world: &mut MyWorld, path: String) -> Result<()> {
+    let fixtures = Fixtures::load(Path::new(&world.expand(&path)?))?;
+    world.session.note(format!(
+        "{}: {} variables, {} devices, {} macros",
+        path,
+        fixtures.vars.len(),
+        fixtures.devices.len(),
+        fixtures.macros.len()
+    ));
+    world.vars.extend(fixtures.vars);
+    world.devices.extend(fixtures.devices.iter().map(|(id, config)| (id.clone(), Device::new(id, config))));
+    world.macros.extend(fixtures.macros);
+    Ok(())
+}
+
+/// A device from `[devices.<id>]`; a DocString JSON object adds to or overrides its fields
+#[given(regex = r#"^device "([^"]+)" exists:?$"#)]
+async fn device_exists(world: &mut MyWorld, id: String, step: &Step) -> Result<()> {
+    let mut device = Device::configured(&id)?;
+    if let JsonValue::Object(fields) = message_body(world, step)? {
+        device.fields.extend(fields);
+    }
+    world.devices.insert(id, device);
+    Ok(())
+}
+
+/// Sends the DocString body on the device's topic, with its identifying fields filled in
+#[when(regex = r#"^device "([^"]+)" sends ([\w.]+)(?: on connection "(\w+)")?:?$"#)]
+async fn device_sends(world: &mut MyWorld, id: String, name: MessageName, connection: String, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let device = world.devices.get(&id).ok_or_else(|| anyhow::anyhow!("no device \"{}\"; use `Given device \"{}\" exists` first", id, id))?;
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    let body = device.fill(&message_body(world, step)?, |field| broker.check_field(&name.0, field).is_ok())?;
+    broker.send_message_on_topic(&device.topic(&name.0), &name.0, &body)
+}
+
+/// Sends the DocString body with the named fields left out, e.g. to check how the SUT handles
+/// a missing required field; combine with enum numbers outside the enum for bad values
+#[when(expr = "I send message {message} without field(s) {}")]
+async fn send_message_without_fields(world: &mut MyWorld, name: MessageName, fields: String, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let broker = world.connection(None)?;
+    let mut body = message_body(world, step)?;
+    for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        let path = jsonpath::field_path(field);
+        broker.check_field(&name.0, &path)?;
+        jsonpath::remove(&mut body, &path)?;
+    }
+    broker.send_message_unchecked(&name.0, &body)
+}
+
+/// Sends the DocString body with a CRC that doesn't match it; the topic needs a `[framing]` CRC
+#[when(expr = "I send message {message} with a corrupted CRC")]
+async fn send_corrupted(world: &mut MyWorld, name: MessageName, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    world.connection(None)?.send_corrupted(&name.0, &message_body(world, step)?)
+}
+
+/// Sends the DocString body with a corrupted CRC and fails if the SUT still answers with `reply`
+#[then(expr = "the SUT rejects {message} with a corrupted CRC, sending no {message} within {duration}(:)")]
+async fn rejects_corrupted(world: &mut MyWorld, name: MessageName, reply: MessageName, within: DurationParam, step: &Step) -> Result<()> {
+    let (window, clamped) = world.wait_budget(within.0)?;
+    let broker = world.connection(None)?;
+    let since = Instant::now();
+    broker.send_corrupted(&name.0, &message_body(world, step)?)?;
+    broker.expect_silence(&reply.0, since, window)?;
+    if clamped {
+        world.check_deadline()?;
+    }
+    Ok(())
+}
+
+fn send_message_on(world: &MyWorld, name: &str, step: &Step, connection: Option<&str>) -> Result<()> {
+    world.check_deadline()?;
+    let broker = world.connection(connection)?;
+    broker.send_message(name, &message_body(world, step)?)?;
+    Ok(())
+}
+
+/// JSON body from the step's DocString, or an empty message without one
+fn message_body(world: &MyWorld, step: &Step) -> Result<JsonValue> {
+    Ok(match world.docstring(step)? {
+        Some(doc) => serde_json::from_str(&doc).expect("invalid JSON in DocString"),
+        None => serde_json::json!({}),
+    })
+}
+
+/// For lossy transports: resends while no reply arrives. The DocString is the body sent, and any
+/// reply counts. To check the reply too, give a table instead: | request | reply |, with an
+/// optional header row.
+#[when(expr = "I send message {message} and expect {message} within {duration}, retrying up to {int} time(s)(:)")]
+async fn send_and_expect(world: &mut MyWorld, name: MessageName, reply: MessageName, within: DurationParam, retries: u32, step: &Step) -> Result<()> {
+    let broker = world.connection(None)?;
+    let (body, expected) = match step.table.as_ref() {
+        Some(table) => {
+            let rows: Vec<&[String]> = table.rows.iter().map(Vec::as_slice).filter(|row| !matches!(row, [q, r] if q == "request" && r == "reply")).collect();
+            let [[request, reply_body]] = rows.as_slice() else { anyhow::bail!("give one row: | request | reply |, got {:?}", table.rows) };
+            let parse = |json: &str| -> Result<JsonValue> {
+                match json.trim() {
+                    "" => Ok(serde_json::json!({})),
+                    json => serde_json::from_str(&world.expand(json)?).with_context(|| format!("invalid JSON: {}", json)),
+                }
+            };
+            (parse(request)?, world.expectation(&parse(reply_body)?)?)
+        }
+        None => (message_body(world, step)?, serde_json::json!({})),
+    };
+    let (_, attempts) = world.budgeted(within.0 * (retries + 1), |window| {
+        broker.send_and_expect(&name.0, &body, &reply.0, &expected, within.0, retries, Instant::now() + window)
+    })?;
+    log::debug!(target: "runner", "{} answered after {} attempt(s)", reply.0, attempts);
+    world.session.note(format!("{} answered after {} of up to {} attempts", reply.0, attempts, retries + 1));
+    Ok(())
+}
+
+#[then(expr = "I expect message {message}")]
+async fn expect_message(world: &mut MyWorld, name: MessageName, step: &Step) -> Result<()> {
+    expect_message_since(world, &name.0, step, None, None)
+}
+
+#[then(expr = "I expect message {message} on connection {string}")]
+async fn expect_message_on_connection(world: &mut MyWorld, name: MessageName, connection: String, step: &Step) -> Result<()> {
+    expect_message_since(world, &name.0, step, None, Some(&connection))
+}
+
+#[then(expr = "I expect message {message} received after marker {string}")]
+async fn expect_message_after_marker(world: &mut MyWorld, name: MessageName, marker: String, step: &Step) -> Result<()> {
+    let since = *world.markers.get(&marker).ok_or_else(|| anyhow::anyhow!("unknown marker \"{}\"", marker))?;
+    expect_message_since(world, &name.0, step, Some(since), None)
+}
+
+/// Sends the messages listed in a YAML, JSON or TOML file, each when it is due; see `crate::sequence`
+#[when(expr = "I play message sequence from {string}")]
+async fn play_sequence(world: &mut MyWorld, path: String) -> Result<()> {
+    let path = world.expand(&path)?;
+    let sequence = Sequence::load(Path::new(&path), |text| world.expand(text))?;
+    let played = play(world, &path, &sequence)?;
+    world.session.note(format!(
+        "played {} messages in {:.3?} (sends at most {:.3?} late)",
+        played.sent, played.took, played.max_lag
+    ));
+    Ok(())
+}
+
+/// Play `sequence` (read from `path`) on the broker connection, unless it would run past the scenario deadline
+fn play(world: &MyWorld, path: &str, sequence: &Sequence) -> Result<Played> {
+    let (left, clamped) = world.wait_budget(sequence.duration())?;
+    if clamped {
+        anyhow::bail!(
+            "{} takes {} to send, but the scenario deadline is {} away",
+            path,
+            humantime::format_duration(sequence.duration()),
+            humantime::format_duration(left)
+        );
+    }
+    sequence.play(world.connection(None)?)
+}
+
+/// Replays a recorded trace: one message per CSV row, with the table mapping columns to field
+/// paths. Without a rate the messages go out back to back.
+#[when(regex = r#"^I send ([\w.]+) messages from CSV "([^"]+)" mapping columns to fields(?: at (\d+(?:\.\d+)?) per second)?:?$"#)]
+async fn send_csv(world: &mut MyWorld, name: MessageName, path: String, rate: String, step: &Step) -> Result<()> {
+    let table = step.table.as_ref().ok_or_else(|| anyhow::anyhow!("map columns to fields in a table: | column | field |"))?;
+    let mapping: Vec<(String, String)> = table
+        .rows
+        .iter()
+        .filter(|row| !matches!(row.as_slice(), [c, f] if c == "column" && f == "field"))
+        .map(|row| match row.as_slice() {
+            [column, field] => Ok((column.clone(), field.clone())),
+            _ => Err(anyhow::anyhow!("mapping rows are | column | field |, got {:?}", row)),
+        })
+        .collect::<Result<_>>()?;
+    let path = world.expand(&path)?;
+    let broker = world.connection(None)?;
+    let messages = csv::messages(broker, &name.0, &csv::Table::read(Path::new(&path))?, &mapping).with_context(|| path.clone())?;
+    let sequence = csv::sequence(&name.0, messages, (!rate.is_empty()).then(|| rate.parse()).transpose()?)?;
+    let played = play(world, &path, &sequence)?;
+    world.session.note(format!("sent {} {} messages in {:.3?} (sends at most {:.3?} late)", played.sent, name, played.took, played.max_lag));
+    Ok(())
+}
+
+/// Echo and store-and-forward checks: the message last sent on the topic, as encoded, is the
+/// expected body. Fields that legitimately differ can be left out.
+#[then(expr = "I expect message {message} equal to the last sent {message}")]
+async fn expect_equal_to_sent(world: &mut MyWorld, name: MessageName, sent: MessageName) -> Result<()> {
+    expect_equal_to_sent_ignoring(world, name, sent, String::new()).await
+}
+
+#[then(expr = "I expect message {message} equal to the last sent {message}, ignoring field(s) {}")]
+async fn expect_equal_to_sent_ignoring(world: &mut MyWorld, name: MessageName, sent: MessageName, fields: String) -> Result<()> {
+    let broker = world.connection(None)?;
+    let body = broker.last_sent(&sent.0).ok_or_else(|| anyhow::anyhow!("no {} message sent yet", sent))?;
+    let mut ignored = world.ignore_fields.clone();
+    ignored.extend(fields.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()));
+    expect_body(world, &name.0, &matchers::without_fields(&body, &ignored), None, None)
+}
+
+fn expect_message_since(world: &MyWorld, name: &str, step: &Step, since: Option<Instant>, connection: Option<&str>) -> Result<()> {
+    let expected: JsonValue = if let Some(ref doc) = world.docstring(step)? {
         serde_json::from_str(doc).expect("invalid JSON in DocString")
     } else {
         serde_json::json!({})
     };
+    expect_body(world, name, &world.expectation(&expected)?, since, connection)
+}
+
+fn expect_body(world: &MyWorld, name: &str, expected: &JsonValue, since: Option<Instant>, connection: Option<&str>) -> Result<()> {
+    let broker = world.connection(connection)?;
+    world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| {
+        let timeout_ms = timeout.as_millis() as i32;
+        match since {
+            Some(since) => broker.expect_message_after(name, expected, timeout_ms, since),
+            None => broker.expect_message(name, expected, timeout_ms),
+        }
+    })?;
+    Ok(())
+}
+
+/// For topics that differ from the message name: any topic matching the pattern (`*` for any
+/// run of characters, `?` for one) counts. The topic it arrived on is kept as `${matched_topic}`.
+#[then(regex = r#"^I expect a message on topic matching "([^"]+)" of type ([\w.]+)(?: on connection "(\w+)")?:?$"#)]
+async fn expect_on_topic(world: &mut MyWorld, pattern: String, name: MessageName, connection: String, step: &Step) -> Result<()> {
+    let pattern = world.expand(&pattern)?;
+    let expected = world.expectation(&message_body(world, step)?)?;
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    let got = world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| broker.expect_on_topic(&pattern, &name.0, &expected, timeout.as_millis() as i32))?;
+    world.vars.insert("matched_topic".to_string(), got.topic);
+    Ok(())
+}
+
+/// Each message must arrive after the one in the row above, all of them within one `within` from
+/// the start of the step. Rows are | message | body |, with an optional header row.
+#[then(regex = r"^I expect messages in order within (.+):$")]
+async fn expect_in_order(world: &mut MyWorld, within: String, step: &Step) -> Result<()> {
+    let table = step.table.as_ref().ok_or_else(|| anyhow::anyhow!("list the messages in a table: | message | body |"))?;
+    let mut rows = Vec::new();
+    for row in table.rows.iter().filter(|row| !matches!(row.as_slice(), [m, b] if m == "message" && b == "body")) {
+        let [name, body] = row.as_slice() else { anyhow::bail!("rows are | message | body |, got {:?}", row) };
+        let body = match body.trim() {
+            "" => serde_json::json!({}),
+            json => serde_json::from_str(&world.expand(json)?).with_context(|| format!("invalid JSON for {}: {}", name, json))?,
+        };
+        rows.push((name.clone(), world.expectation(&body)?));
+    }
+    let broker = world.connection(None)?;
+    world.budgeted(config::parse_duration(&within)?, |window| {
+        let deadline = Instant::now() + window;
+        let mut since: Option<Instant> = None;
+        for (i, (name, expected)) in rows.iter().enumerate() {
+            let got = match since {
+                Some(since) => broker.expect_after_until(name, expected, since, deadline),
+                None => broker.expect_until(name, expected, deadline),
+            };
+            since = Some(got.with_context(|| format!("message {} of {} in order", i + 1, rows.len()))?.at);
+        }
+        Ok(())
+    })
+}
 
-    broker.send_message(&name, &body)?;
+/// Alternatives are separated by ` | `, each a message name optionally followed by `matching <json>`.
+/// The name of the one that arrived is kept as `${matched_alternative}`.
+#[then(regex = r"^I expect one of: (.+)$")]
+async fn expect_one_of(world: &mut MyWorld, alternatives: String) -> Result<()> {
+    let mut parsed = Vec::new();
+    for alternative in alternatives.split(" | ") {
+        let (name, expected) = match alternative.trim().split_once(" matching ") {
+            Some((name, json)) => {
+                let json = world.expand(json)?;
+                (name.trim(), serde_json::from_str(&json).with_context(|| format!("invalid JSON for {}: {}", name.trim(), json))?)
+            }
+            None => (alternative.trim(), serde_json::json!({})),
+        };
+        parsed.push((name.to_string(), world.expectation(&expected)?));
+    }
+    let alternatives: Vec<(&str, &JsonValue)> = parsed.iter().map(|(name, expected)| (name.as_str(), expected)).collect();
+    let broker = world.connection(None)?;
+    let (_, got) = world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| broker.expect_one_of(&alternatives, timeout.as_millis() as i32))?;
+    world.vars.insert("matched_alternative".to_string(), got.topic);
     Ok(())
 }
 
-#[then(regex = r"I expect message (\w+)")]
-async fn expect_message(world: &mut MyWorld, name: String, step: &Step) -> Result<()> {
+#[when(expr = "I clear all received messages")]
+async fn clear_all_received(world: &mut MyWorld) -> Result<()> {
     let broker = world.broker.as_ref().expect("broker not started");
+    broker.clear_received(None);
+    Ok(())
+}
 
-    let expected: JsonValue = if let Some(ref doc) = step.docstring {
-        serde_json::from_str(doc).expect("invalid JSON in DocString")
-    } else {
-        serde_json::json!({})
+#[when(expr = "I clear received {message} messages")]
+async fn clear_received(world: &mut MyWorld, name: MessageName) -> Result<()> {
+    let broker = world.broker.as_ref().expect("broker not started");
+    broker.clear_received(Some(&name.0));
+    Ok(())
+}
+
+#[when(expr = "I mark time as {string}")]
+async fn mark_time(world: &mut MyWorld, name: String) -> Result<()> {
+    world.markers.insert(name, Instant::now());
+    Ok(())
+}
+
+#[then(expr = "field {word} of the last {message}( message) equals {}")]
+async fn field_of_last_equals(world: &mut MyWorld, path: String, name: MessageName, expected: String) -> Result<()> {
+    let name = name.0;
+    let broker = world.broker.as_ref().expect("broker not started");
+    let body = broker.last_message(&name)?.ok_or_else(|| anyhow::anyhow!("no {} message received", name))?;
+    let actual = jsonpath::select_one(&body, &jsonpath::field_path(&path))?;
+    let expected = parse_literal(&expected);
+    if !values_equal(actual, &expected) {
+        anyhow::bail!("{} of the last {} is {}, expected {}", path, name, actual, expected);
+    }
+    Ok(())
+}
+
+#[then(expr = "field {word} of the last {message}( message) is one of [{}]")]
+async fn field_of_last_is_one_of(world: &mut MyWorld, path: String, name: MessageName, allowed: String) -> Result<()> {
+    let (name, path) = (name.0, jsonpath::field_path(&path));
+    let broker = world.broker.as_ref().expect("broker not started");
+    let desc = broker.enum_field(&name, &path)?;
+    let allowed: Vec<&str> = allowed.split(',').map(str::trim).filter(|a| !a.is_empty()).collect();
+    if let Some(unknown) = allowed.iter().find(|a| desc.get_value_by_name(a).is_none()) {
+        let values: Vec<String> = desc.values().map(|v| v.name().to_string()).collect();
+        anyhow::bail!("{} is not a value of {} (values: {})", unknown, desc.name(), values.join(", "));
+    }
+    let actual = last_enum_name(broker, &name, &path, &desc)?;
+    if !allowed.contains(&actual.as_str()) {
+        anyhow::bail!("{} of the last {} is {}, expected one of {}", path, name, actual, allowed.join(", "));
+    }
+    Ok(())
+}
+
+/// Name of enum field `path` of the last `name` message
+fn last_enum_name(broker: &Broker, name: &str, path: &str, desc: &EnumDescriptor) -> Result<String> {
+    let body = broker.last_message(name)?.ok_or_else(|| anyhow::anyhow!("no {} message received", name))?;
+    // an enum left out of a present parent holds its first value
+    Ok(match jsonpath::select(&body, path)?.as_slice() {
+        [] if jsonpath::parent_present(&body, path) => desc.default_value().name().to_string(),
+        [] => anyhow::bail!("{} of the last {} matched nothing", path, name),
+        [JsonValue::Number(n)] => {
+            let n = n.as_i64().unwrap_or_default() as i32;
+            desc.get_value(n).map(|v| v.name().to_string()).unwrap_or_else(|| format!("<unknown {}>", n))
+        }
+        [JsonValue::String(s)] => s.clone(),
+        [other] => anyhow::bail!("{} of the last {} is {}, not an enum value", path, name, other),
+        many => anyhow::bail!("{} matched {} values, expected one", path, many.len()),
+    })
+}
+
+/// `<condition> then <step>` runs the step only when the condition holds, so one scenario can
+/// handle SUT variants. Conditions are `field <path> of the last <message> is [not] <value>` and
+/// `"<text>" is [not] <value>`, e.g. `"${matched_alternative}" is "PongReply"`.
+#[given(regex = r#"^(?:if )?(field \S+ of the last \w+(?: message)?|"[^"]*") (is not|is) (.+?) then (.+)$"#)]
+#[when(regex = r#"^(?:if )?(field \S+ of the last \w+(?: message)?|"[^"]*") (is not|is) (.+?) then (.+)$"#)]
+#[then(regex = r#"^(?:if )?(field \S+ of the last \w+(?: message)?|"[^"]*") (is not|is) (.+?) then (.+)$"#)]
+async fn conditional_step(world: &mut MyWorld, subject: String, op: String, value: String, then: String, step: &Step) -> Result<()> {
+    let holds = condition_holds(world, &subject, &world.expand(&value)?)? != (op == "is not");
+    if !holds {
+        world.session.note(format!("skipped: {}", then));
+        return Ok(());
+    }
+    run_step(world, &then, step).await
+}
+
+fn condition_holds(world: &MyWorld, subject: &str, value: &str) -> Result<bool> {
+    let expected = parse_literal(value);
+    if let Some(text) = subject.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return Ok(values_equal(&parse_literal(&world.expand(text)?), &expected));
+    }
+    let (path, name) = subject
+        .strip_prefix("field ")
+        .and_then(|s| s.trim_end_matches(" message").split_once(" of the last "))
+        .ok_or_else(|| anyhow::anyhow!("invalid condition {}", subject))?;
+    let (name, path) = (name, jsonpath::field_path(path));
+    let broker = world.connection(None)?;
+    // enum fields compare by value name
+    if let (Ok(desc), JsonValue::String(expected)) = (broker.enum_field(name, &path), &expected) {
+        return Ok(&last_enum_name(broker, name, &path, &desc)? == expected);
+    }
+    let body = broker.last_message(name)?.ok_or_else(|| anyhow::anyhow!("no {} message received", name))?;
+    Ok(values_equal(jsonpath::select_one(&body, &path)?, &expected))
+}
+
+/// Run the step definition matching `text` as if it were a step of its own, with `parent`'s
+/// DocString and table
+async fn run_step(world: &mut MyWorld, text: &str, parent: &Step) -> Result<()> {
+    let (step_fn, context) = find_step(text, parent)?;
+    step_fn(world, context).await;
+    Ok(())
+}
+
+pub(crate) fn find_step(text: &str, parent: &Step) -> Result<(cucumber::Step<MyWorld>, cucumber::step::Context)> {
+    let collection = MyWorld::collection();
+    for ty in [gherkin::StepType::Given, gherkin::StepType::When, gherkin::StepType::Then] {
+        let step = Step { ty, value: text.to_string(), ..parent.clone() };
+        let found = collection.find(&step).map_err(|e| anyhow::anyhow!("ambiguous step {}: {:?}", text, e))?;
+        if let Some((step_fn, _, _, context)) = found {
+            return Ok((*step_fn, context));
+        }
+    }
+    anyhow::bail!("no step matches \"{}\"", text)
+}
+
+/// `softly <step>` runs the step but only records its failure, so one run reports every failed
+/// check of a scenario. Recorded failures fail the scenario when it ends, or earlier at
+/// `no soft assertions failed`.
+#[given(regex = r"^softly (.+)$")]
+#[when(regex = r"^softly (.+)$")]
+#[then(regex = r"^softly (.+)$")]
+async fn soft_step(world: &mut MyWorld, text: String, step: &Step) -> Result<()> {
+    let (step_fn, context) = find_step(&text, step)?;
+    if let Err(panic) = AssertUnwindSafe(step_fn(world, context)).catch_unwind().await {
+        let error = panic
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| panic.downcast_ref::<&str>().copied())
+            .unwrap_or("step panicked");
+        world.session.note(format!("soft failure: {}", error));
+        world.soft_failures.push(format!("{}: {}", text, error));
+    }
+    Ok(())
+}
+
+#[then(expr = "no soft assertions failed")]
+async fn no_soft_failures(world: &mut MyWorld) -> Result<()> {
+    let failures = std::mem::take(&mut world.soft_failures);
+    if !failures.is_empty() {
+        anyhow::bail!("{}", soft_failure_report(&failures));
+    }
+    Ok(())
+}
+
+fn soft_failure_report(failures: &[String]) -> String {
+    let list: Vec<String> = failures.iter().map(|f| format!("  {}", f)).collect();
+    format!("{} soft assertion(s) failed:\n{}", failures.len(), list.join("\n"))
+}
+
+#[then(expr = "the last {message} message passes validation")]
+async fn last_passes_validation(world: &mut MyWorld, name: MessageName) -> Result<()> {
+    let broker = world.broker.as_ref().expect("broker not started");
+    let violations = broker.validate_last(&name.0)?;
+    if !violations.is_empty() {
+        let list: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+        anyhow::bail!("the last {} message fails validation:\n{}", name, list.join("\n"));
+    }
+    Ok(())
+}
+
+/// Step argument as JSON (`"OK"`, `3`, `true`), falling back to a bare string
+fn parse_literal(s: &str) -> JsonValue {
+    serde_json::from_str(s.trim()).unwrap_or_else(|_| JsonValue::String(s.trim().to_string()))
+}
+
+/// JSON equality where numbers compare by value (1 == 1.0)
+fn values_equal(a: &JsonValue, b: &JsonValue) -> bool {
+    match (a, b) {
+        (JsonValue::Number(x), JsonValue::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+#[given(expr = "I ignore field(s) {}")]
+async fn ignore_fields(world: &mut MyWorld, fields: String) -> Result<()> {
+    world.ignore_fields.extend(fields.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()));
+    Ok(())
+}
+
+#[when(expr = "I collect {message} messages for {duration}")]
+async fn collect_messages(world: &mut MyWorld, name: MessageName, window: DurationParam) -> Result<()> {
+    let (name, window) = (name.0, window.0);
+    let (budget, clamped) = world.wait_budget(window)?;
+    let broker = world.broker.as_ref().expect("broker not started");
+    let collected = broker.collect(&name, budget)?;
+    if clamped {
+        world.check_deadline()?;
+    }
+    world.session.note(format!("collected {} {} messages", collected.len(), name));
+    world.collected.insert(name, collected);
+    Ok(())
+}
+
+#[then(regex = r"^the collected (\w+) messages have (strictly )?(increasing|decreasing) field (\S+)$")]
+async fn collected_monotonic(world: &mut MyWorld, name: String, strict: String, direction: String, field: String) -> Result<()> {
+    let values = aggregate::values(world.collected_for(&name)?, &field, field_default(world, &name, &field))?;
+    let order = Monotonic::new(direction == "increasing", !strict.is_empty());
+    if let Some(i) = order.first_violation(&values) {
+        anyhow::bail!(
+            "{} is not {}{} over collected {}: {} followed by {} (message {})",
+            field, strict, direction, name, values[i - 1], values[i], i
+        );
+    }
+    Ok(())
+}
+
+#[then(regex = r"^the (min|minimum|max|maximum|avg|average|mean|sum|count) of field (\S+) over collected (\w+) is (above|below|greater than|less than|at least|at most|equal to|exactly) (-?\d+(?:\.\d+)?)$")]
+async fn collected_aggregate(world: &mut MyWorld, agg: String, field: String, name: String, cmp: String, limit: String) -> Result<()> {
+    let values = aggregate::values(world.collected_for(&name)?, &field, field_default(world, &name, &field))?;
+    let value = agg.parse::<Aggregate>()?.apply(&values)?;
+    let limit: f64 = limit.parse()?;
+    if !cmp.parse::<Comparison>()?.holds(value, limit) {
+        anyhow::bail!("{} of {} over {} collected {} is {}, expected {} {}", agg, field, values.len(), name, value, cmp, limit);
+    }
+    Ok(())
+}
+
+#[then(regex = r"^message (\w+) is published at (\d+(?:\.\d+)?) ?Hz (?:±|\+/-) ?(\d+(?:\.\d+)?) ?% over (.+)$")]
+async fn published_at_rate(world: &mut MyWorld, name: String, hz: String, tolerance: String, window: String) -> Result<()> {
+    let window = config::parse_duration(&window)?;
+    let (budget, clamped) = world.wait_budget(window)?;
+    let broker = world.broker.as_ref().expect("broker not started");
+    let arrivals = broker.arrivals(&name, budget);
+    if clamped {
+        world.check_deadline()?;
+    }
+    let stats = RateStats::from_arrivals(&arrivals)?;
+    world.session.note(format!(
+        "{}: {} messages, {:.3} Hz, intervals {:?}..{:?}",
+        name, stats.count, stats.rate_hz, stats.min_interval, stats.max_interval
+    ));
+    stats.check(hz.parse()?, tolerance.parse()?)
+}
+
+/// Value a collected `name` message's `field` holds when decoding left it out, if it is a singular
+/// numeric, bool or enum field of a proto message type
+fn field_default(world: &MyWorld, name: &str, field: &str) -> Option<f64> {
+    match world.broker.as_ref()?.field_kind(name, &jsonpath::field_path(field)) {
+        Ok((kind, false)) => aggregate::scalar_default(&kind),
+        _ => None,
+    }
+}
+
+/// Sequence numbers of `name` in `field` (or its `[sequence_fields]` entry), from the collected
+/// messages if there are any, otherwise everything buffered; the report is noted in the session
+fn sequence_report(world: &mut MyWorld, name: &str, field: String) -> Result<(String, SequenceReport)> {
+    let field = match field.as_str() {
+        "" => Config::global().sequence_fields.get(name).cloned().ok_or_else(|| {
+            anyhow::anyhow!("no sequence field for {}; name it in the step or under [sequence_fields]", name)
+        })?,
+        _ => field,
+    };
+    let messages = match world.collected.get(name) {
+        Some(collected) => collected.clone(),
+        None => world.broker.as_ref().expect("broker not started").captured(name)?,
+    };
+    let values: Vec<i64> = aggregate::values(&messages, &field, field_default(world, name, &field))?.into_iter().map(|v| v as i64).collect();
+    if values.is_empty() {
+        anyhow::bail!("no {} messages with field {} to check", name, field);
+    }
+    let report = SequenceReport::scan(&values);
+    world.session.note(format!("{} {}: {} messages, {}", name, field, values.len(), report));
+    Ok((field, report))
+}
+
+#[then(regex = r"^no (\w+) messages were lost or duplicated(?: \(sequence field (\S+)\))?$")]
+async fn no_lost_or_duplicated(world: &mut MyWorld, name: String, field: String) -> Result<()> {
+    let (field, report) = sequence_report(world, &name, field)?;
+    if !report.is_clean() {
+        anyhow::bail!("{} sequence {}: {}", name, field, report);
+    }
+    Ok(())
+}
+
+/// Slow consumer: the SUT keeps publishing while nothing is read, so its queues fill up
+#[when(regex = r#"^I stop reading(?: on connection "(\w+)")? for (.+)$"#)]
+async fn stop_reading(world: &mut MyWorld, connection: String, period: DurationParam) -> Result<()> {
+    world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?.stall(period.0);
+    Ok(())
+}
+
+/// Checks the gaps in the sequence numbers after a stall; what arrived in the catch-up burst was
+/// queued rather than dropped
+#[then(regex = r"^the SUT dropped at most (\d+) (\w+) messages?(?: \(sequence field (\S+)\))?$")]
+async fn dropped_at_most(world: &mut MyWorld, limit: i64, name: String, field: String) -> Result<()> {
+    let (field, report) = sequence_report(world, &name, field)?;
+    let queued = world.broker.as_ref().expect("broker not started").queued().get(&name).copied().unwrap_or_default();
+    world.session.note(format!("{}: {} queued while not reading", name, queued));
+    if report.lost() > limit {
+        anyhow::bail!("{} sequence {}: {}, expected at most {} lost ({} were queued)", name, field, report, limit, queued);
+    }
+    Ok(())
+}
+
+/// Waits by the `[readiness]` check; with the default fixed delay, until the ZMQ handshakes are done
+#[then(regex = r#"^the (?:broker connection|connection "(\w+)") is ready$"#)]
+async fn connection_ready(world: &mut MyWorld, name: String) -> Result<()> {
+    let config = &Config::global().readiness;
+    let check = match config.check {
+        Readiness::Delay => Readiness::Monitor,
+        check => check,
+    };
+    let (timeout, _) = world.wait_budget(config.timeout.unwrap_or(READY_TIMEOUT))?;
+    let broker = match name.as_str() {
+        "" => world.broker.as_mut().expect("broker not started"),
+        n => world.connections.get_mut(n).ok_or_else(|| anyhow::anyhow!("no connection \"{}\"", n))?,
     };
+    broker.wait_ready(check, timeout)
+}
+
+#[then(expr = "no messages were dropped")]
+async fn no_messages_dropped(world: &mut MyWorld) -> Result<()> {
+    let dropped = world.broker.as_ref().expect("broker not started").dropped();
+    if !dropped.is_empty() {
+        let counts: Vec<String> = dropped.iter().map(|(topic, n)| format!("{} {}", topic, n)).collect();
+        anyhow::bail!("the receive buffer was full and dropped messages: {}; raise [buffer] capacity", counts.join(", "));
+    }
+    Ok(())
+}
+
+/// Orders the last message of each kind by arrival, across connections; `within` bounds the gap
+#[then(regex = r#"^message (\w+)(?: on "(\w+)")? arrived (after|before) message (\w+)(?: on "(\w+)")?(?:,? within (.+))?$"#)]
+async fn arrived_in_order(world: &mut MyWorld, name: String, connection: String, order: String, other: String, other_connection: String, within: String) -> Result<()> {
+    let label = |name: &str, connection: &str| match connection {
+        "" => name.to_string(),
+        c => format!("{} on \"{}\"", name, c),
+    };
+    let (ours, theirs) = (label(&name, &connection), label(&other, &other_connection));
+    let arrival = |name: &str, connection: &str, label: &str| -> Result<Instant> {
+        let got = world.connection(Some(connection).filter(|c| !c.is_empty()))?.last_captured(name)?;
+        Ok(got.ok_or_else(|| anyhow::anyhow!("no {} received", label))?.at)
+    };
+    let (ours_at, theirs_at) = (arrival(&name, &connection, &ours)?, arrival(&other, &other_connection, &theirs)?);
+    let (earlier, later) = if order == "after" { (theirs_at, ours_at) } else { (ours_at, theirs_at) };
+    let Some(gap) = later.checked_duration_since(earlier) else {
+        let wrong = if order == "after" { "before" } else { "after" };
+        anyhow::bail!("{} arrived {:?} {} {}", ours, earlier.duration_since(later), wrong, theirs);
+    };
+    if !within.is_empty() {
+        let limit = config::parse_duration(&within)?;
+        if gap > limit {
+            anyhow::bail!("{} arrived {:?} {} {}, more than {:?}", ours, gap, order, theirs, limit);
+        }
+    }
+    world.session.note(format!("{} arrived {:?} {} {}", ours, gap, order, theirs));
+    Ok(())
+}
+
+/// Payload bytes sent and received on a topic since the connection opened
+#[then(regex = r#"^topic (\w+)(?: on connection "(\w+)")? used (less|more) than (\d+(?:\.\d+)?) ?(B|bytes|kB|KB|KiB|MB|MiB) during this scenario$"#)]
+async fn topic_bytes(world: &mut MyWorld, topic: String, connection: String, cmp: String, limit: f64, unit: String) -> Result<()> {
+    let scale = match unit.as_str() {
+        "kB" | "KB" => 1e3,
+        "KiB" => 1024.0,
+        "MB" => 1e6,
+        "MiB" => 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    let traffic = broker.traffic().get(&topic).copied().unwrap_or_default();
+    let bytes = traffic.bytes() as f64;
+    let ok = if cmp == "less" { bytes < limit * scale } else { bytes > limit * scale };
+    if !ok {
+        anyhow::bail!(
+            "topic {} used {} bytes ({} sent in {}, {} received in {}), expected {} than {} {}",
+            topic, traffic.bytes(), traffic.sent_bytes, traffic.sent, traffic.received_bytes, traffic.received, cmp, limit, unit
+        );
+    }
+    Ok(())
+}
 
-    let _got = broker.expect_message(&name, &expected, 5000)?;
+/// Messages per second both ways on a topic, averaged since the connection opened
+#[then(regex = r#"^topic (\w+)(?: on connection "(\w+)")? averaged (less|more) than (\d+(?:\.\d+)?) messages per second during this scenario$"#)]
+async fn topic_rate(world: &mut MyWorld, topic: String, connection: String, cmp: String, limit: f64) -> Result<()> {
+    let broker = world.connection(Some(connection.as_str()).filter(|c| !c.is_empty()))?;
+    let messages = broker.traffic().get(&topic).map_or(0, |t| t.messages());
+    let uptime = broker.uptime();
+    let rate = messages as f64 / uptime.as_secs_f64().max(f64::EPSILON);
+    let ok = if cmp == "less" { rate < limit } else { rate > limit };
+    if !ok {
+        anyhow::bail!("topic {} averaged {:.2} messages per second ({} in {:?}), expected {} than {}", topic, rate, messages, uptime, cmp, limit);
+    }
+    Ok(())
+}
+
+#[then(expr = "no frames failed the CRC check")]
+async fn no_crc_errors(world: &mut MyWorld) -> Result<()> {
+    let errors = world.broker.as_ref().expect("broker not started").crc_errors();
+    if !errors.is_empty() {
+        let counts: Vec<String> = errors.iter().map(|(topic, n)| format!("{} {}", topic, n)).collect();
+        anyhow::bail!("frames with a bad CRC were discarded: {}", counts.join(", "));
+    }
+    Ok(())
+}
+
+#[then(regex = r"^the last (\w+) message is (smaller|larger) than (\d+) bytes$")]
+async fn last_message_size(world: &mut MyWorld, name: String, cmp: String, limit: usize) -> Result<()> {
+    let broker = world.broker.as_ref().expect("broker not started");
+    let last = broker.last_captured(&name)?.ok_or_else(|| anyhow::anyhow!("no {} message received", name))?;
+    let ok = if cmp == "smaller" { last.size < limit } else { last.size > limit };
+    if !ok {
+        anyhow::bail!("the last {} message is {} bytes, expected {} than {}", name, last.size, cmp, limit);
+    }
+    Ok(())
+}
+
+#[given(regex = r#"^I subscribe to SSE at "([^"]+)"(?: with TLS profile "(\w+)")?$"#)]
+async fn subscribe_sse(world: &mut MyWorld, url: String, profile: String) -> Result<()> {
+    let (timeout, _) = world.wait_budget(DEFAULT_EXPECT_TIMEOUT)?;
+    let profile = tls::profile(Some(profile.as_str()).filter(|p| !p.is_empty()))?;
+    world.sse = Some(SseClient::connect_with(&world.expand(&url)?, &profile, timeout)?);
+    Ok(())
+}
+
+#[then(expr = "I expect SSE event {string}( matching)(:)")]
+async fn expect_sse_event(world: &mut MyWorld, event: String, step: &Step) -> Result<()> {
+    let sse = world.sse.as_ref().ok_or_else(|| anyhow::anyhow!("no SSE subscription; use `I subscribe to SSE at \"<url>\"` first"))?;
+    let expected: JsonValue = match world.docstring(step)? {
+        Some(ref doc) => serde_json::from_str(doc)?,
+        None => serde_json::json!({}),
+    };
+    let expected = world.expectation(&expected)?;
+    let opts = MatchOptions::from_config(&Config::global().matching);
+    world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| sse.expect_event(&event, &expected, timeout, opts))?;
+    Ok(())
+}
+
+/// Calls use the `[proto]` services unless `with descriptors` names a descriptor set, .proto file
+/// or `[schemas]` entry holding them
+#[given(regex = r#"^I connect to gRPC at "([^"]+)"(?: with descriptors "([^"]+)")?(?: with TLS profile "(\w+)")?$"#)]
+async fn connect_grpc(world: &mut MyWorld, url: String, descriptors: String, profile: String) -> Result<()> {
+    let (timeout, _) = world.wait_budget(DEFAULT_EXPECT_TIMEOUT)?;
+    let proto = match world.expand(&descriptors)?.as_str() {
+        "" => ProtoDyn::new()?,
+        name if Config::global().schemas.contains_key(name) => ProtoDyn::named(name)?,
+        path => ProtoDyn::load(Path::new(path))?,
+    };
+    let profile = tls::profile(Some(profile.as_str()).filter(|p| !p.is_empty()))?;
+    world.grpc_stream = None;
+    world.grpc = Some(GrpcClient::connect(&world.expand(&url)?, &profile, proto, timeout)?);
+    Ok(())
+}
+
+const NO_STREAM: &str = "no gRPC stream; use `I open stream <Service>/<Method>` first";
+
+fn grpc_stream(world: &mut MyWorld) -> Result<&mut GrpcStream> {
+    world.grpc_stream.as_mut().ok_or_else(|| anyhow::anyhow!(NO_STREAM))
+}
+
+/// Unary and server-streaming methods are sent the DocString (or an empty request) and closed for
+/// sending; client and bidi streaming send the DocString, if any, and take more with `I send stream item`
+#[when(regex = r"^I open (?:gRPC )?stream ([\w.]+/\w+):?$")]
+async fn open_stream(world: &mut MyWorld, method: String, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let grpc = world.grpc.as_ref().ok_or_else(|| anyhow::anyhow!("no gRPC connection; use `I connect to gRPC at \"<url>\"` first"))?;
+    let request = step.docstring.is_some().then(|| message_body(world, step)).transpose()?;
+    let mut stream = grpc.open(&method)?;
+    let (timeout, _) = world.wait_budget(DEFAULT_EXPECT_TIMEOUT)?;
+    if !stream.method().is_client_streaming() {
+        stream.send(&request.unwrap_or_else(|| serde_json::json!({})), timeout)?;
+        stream.close_send()?;
+    } else if let Some(request) = request {
+        stream.send(&request, timeout)?;
+    }
+    world.session.note(format!("opened stream {}", method));
+    world.grpc_stream = Some(stream);
+    Ok(())
+}
+
+#[when(expr = "I send stream item(:)")]
+async fn send_stream_item(world: &mut MyWorld, step: &Step) -> Result<()> {
+    let body = message_body(world, step)?;
+    let (timeout, _) = world.wait_budget(DEFAULT_EXPECT_TIMEOUT)?;
+    grpc_stream(world)?.send(&body, timeout)
+}
+
+#[when(expr = "I close the stream")]
+async fn close_stream(world: &mut MyWorld) -> Result<()> {
+    grpc_stream(world)?.close_send()
+}
+
+/// Replies not matching are skipped; each reply is matched at most once
+#[then(expr = "I receive {int} stream item(s)( matching)(:)")]
+async fn receive_stream_items(world: &mut MyWorld, count: usize, step: &Step) -> Result<()> {
+    let expected = world.expectation(&message_body(world, step)?)?;
+    let opts = MatchOptions::from_config(&Config::global().matching);
+    let stream = world.grpc_stream.as_ref().ok_or_else(|| anyhow::anyhow!(NO_STREAM))?;
+    world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| stream.expect_items(count, &expected, timeout, opts))?;
+    Ok(())
+}
+
+/// `status` is a gRPC status code name such as `OK` or `NOT_FOUND`, or its number
+#[then(expr = "the stream ends with status {word}")]
+async fn stream_ends_with_status(world: &mut MyWorld, status: String) -> Result<()> {
+    let code = Status::code_of(&status).ok_or_else(|| anyhow::anyhow!("unknown gRPC status {}; use a name such as NOT_FOUND, or a number", status))?;
+    let stream = world.grpc_stream.as_ref().ok_or_else(|| anyhow::anyhow!(NO_STREAM))?;
+    let got = world.budgeted(DEFAULT_EXPECT_TIMEOUT, |timeout| stream.finish(timeout))?;
+    if got.code != code {
+        anyhow::bail!("{} ended with status {}, expected {}", stream.method().full_name(), got, status);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+#[given(expr = "I connect to database {string}")]
+async fn connect_database(world: &mut MyWorld, url: String) -> Result<()> {
+    world.database = Some(Database::parse(&world.expand(&url)?)?);
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+#[when(expr = "I query the database(:)")]
+async fn query_database(world: &mut MyWorld, step: &Step) -> Result<()> {
+    world.check_deadline()?;
+    let database = world.database.as_ref().ok_or_else(|| anyhow::anyhow!("no database; use `I connect to database \"<url>\"` first"))?;
+    let sql = world.docstring(step)?.ok_or_else(|| anyhow::anyhow!("the SQL query goes in a DocString"))?;
+    world.rows = database.query(&sql)?;
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+#[then(expr = "the query returns {int} row(s)")]
+async fn query_row_count(world: &mut MyWorld, count: usize) -> Result<()> {
+    if world.rows.len() != count {
+        anyhow::bail!("query returned {} rows, expected {}: {}", world.rows.len(), count, JsonValue::Array(world.rows.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+#[then(expr = "the query result matches(:)")]
+async fn query_result_matches(world: &mut MyWorld, step: &Step) -> Result<()> {
+    let expected: JsonValue = match world.docstring(step)? {
+        Some(ref doc) => serde_json::from_str(doc)?,
+        None => anyhow::bail!("the expected rows go in a DocString"),
+    };
+    let expected = match world.expectation(&expected)? {
+        JsonValue::Array(rows) => rows,
+        row => vec![row],
+    };
+    let opts = MatchOptions::from_config(&Config::global().matching);
+    if let Some(missing) = db::unmatched_row(&expected, &world.rows, opts)? {
+        anyhow::bail!("no row matching {} in query result {}", missing, JsonValue::Array(world.rows.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ssh")]
+#[when(regex = r#"^I run "(.+)" on host "([^"]+)"$"#)]
+async fn run_on_host(world: &mut MyWorld, command: String, host: String) -> Result<()> {
+    let profile = Config::global()
+        .hosts
+        .get(&host)
+        .ok_or_else(|| anyhow::anyhow!("unknown host \"{}\"; add it under [hosts.{}] in bdd.toml", host, host))?;
+    let (timeout, _) = world.wait_budget(DEFAULT_COMMAND_TIMEOUT)?;
+    let output = crate::ssh::run(profile, &world.expand(&command)?, timeout)?;
+    world.session.note(format!("{} on {}: exit {}", command, host, output.status));
+    world.command = Some(output);
+    Ok(())
+}
+
+#[when(regex = r#"^I run command "(.+)" locally(?: within (.+))?$"#)]
+async fn run_command_locally(world: &mut MyWorld, command: String, timeout: String) -> Result<()> {
+    let timeout = match timeout.as_str() {
+        "" => DEFAULT_COMMAND_TIMEOUT,
+        t => config::parse_duration(t)?,
+    };
+    let (timeout, _) = world.wait_budget(timeout)?;
+    let mut cmd = std::process::Command::new("sh");
+    cmd.args(["-c", &world.expand(&command)?]);
+    let output = process::run(cmd, timeout)?;
+    world.session.note(format!("{}: exit {}", command, output.status));
+    world.command = Some(output);
+    Ok(())
+}
+
+#[when(regex = r#"^I store the command (stdout|stderr|output) as "(\w+)"$"#)]
+async fn store_command_output(world: &mut MyWorld, stream: String, name: String) -> Result<()> {
+    let output = last_command(world)?;
+    let value = if stream == "stderr" { &output.stderr } else { &output.stdout };
+    let value = value.trim().to_string();
+    world.vars.insert(name, value);
+    Ok(())
+}
+
+#[when(regex = r#"^I export variable "(\w+)"$"#)]
+async fn export_variable(world: &mut MyWorld, name: String) -> Result<()> {
+    if world.order.is_none() {
+        anyhow::bail!("only scenarios tagged @order:<n> can export variables");
+    }
+    let value = world.vars.get(&name).ok_or_else(|| anyhow::anyhow!("unknown scenario variable ${{{}}}", name))?;
+    world.exports.insert(name, value.clone());
+    Ok(())
+}
+
+fn last_command(world: &MyWorld) -> Result<&CommandOutput> {
+    world.command.as_ref().ok_or_else(|| anyhow::anyhow!("no command has been run"))
+}
+
+#[then(expr = "the command exits with code/status {int}")]
+async fn command_exit_code(world: &mut MyWorld, code: i32) -> Result<()> {
+    let output = last_command(world)?;
+    if output.status != code {
+        anyhow::bail!("command exited with {}, expected {}\nstdout: {}\nstderr: {}", output.status, code, output.stdout.trim(), output.stderr.trim());
+    }
+    Ok(())
+}
+
+#[then(regex = r#"^the command (stdout|stderr|output) (contains|does not contain) "(.*)"$"#)]
+async fn command_output_contains(world: &mut MyWorld, stream: String, cmp: String, text: String) -> Result<()> {
+    let output = last_command(world)?;
+    let captured = if stream == "stderr" { &output.stderr } else { &output.stdout };
+    if captured.contains(&text) != (cmp == "contains") {
+        anyhow::bail!("command {} {} \"{}\":\n{}", stream, cmp, text, captured.trim());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "modbus")]
+fn modbus(world: &mut MyWorld) -> Result<&mut crate::modbus::ModbusClient> {
+    world.modbus.as_mut().ok_or_else(|| anyhow::anyhow!("no Modbus connection; use `I connect to Modbus at \"<host:port>\"` first"))
+}
+
+#[cfg(feature = "modbus")]
+#[given(regex = r#"^I connect to Modbus at "([^"]+)"(?: unit (\d+))?$"#)]
+async fn connect_modbus(world: &mut MyWorld, address: String, unit: String) -> Result<()> {
+    let unit = if unit.is_empty() { 1 } else { unit.parse()? };
+    world.modbus = Some(crate::modbus::ModbusClient::connect(&world.expand(&address)?, unit, DEFAULT_EXPECT_TIMEOUT)?);
+    Ok(())
+}
+
+#[cfg(feature = "modbus")]
+#[when(regex = r"^I write (\d+(?:, ?\d+)*) to holding registers? (\d+)$")]
+async fn write_holding_registers(world: &mut MyWorld, values: String, address: u16) -> Result<()> {
+    let values = values.split(',').map(|v| v.trim().parse::<u16>()).collect::<Result<Vec<_>, _>>()?;
+    match values.as_slice() {
+        [one] => modbus(world)?.write_register(address, *one),
+        many => modbus(world)?.write_registers(address, many),
+    }
+}
+
+#[cfg(feature = "modbus")]
+#[when(regex = r"^I set coil (\d+) (on|off)$")]
+async fn set_coil(world: &mut MyWorld, address: u16, state: String) -> Result<()> {
+    modbus(world)?.write_coil(address, state == "on")
+}
+
+#[cfg(feature = "modbus")]
+#[then(regex = r"^holding register (\d+) is (above|below|greater than|less than|at least|at most|equal to|exactly) (\d+)$")]
+async fn holding_register_compares(world: &mut MyWorld, address: u16, cmp: String, limit: u16) -> Result<()> {
+    let value = modbus(world)?.read_holding_registers(address, 1)?[0];
+    if !cmp.parse::<Comparison>()?.holds(value as f64, limit as f64) {
+        anyhow::bail!("holding register {} is {}, expected {} {}", address, value, cmp, limit);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "modbus")]
+#[then(regex = r"^holding registers (\d+) hold (\d+(?:, ?\d+)*)$")]
+async fn holding_registers_hold(world: &mut MyWorld, address: u16, values: String) -> Result<()> {
+    let expected = values.split(',').map(|v| v.trim().parse::<u16>()).collect::<Result<Vec<_>, _>>()?;
+    let actual = modbus(world)?.read_holding_registers(address, expected.len() as u16)?;
+    if actual != expected {
+        anyhow::bail!("holding registers {}.. hold {:?}, expected {:?}", address, actual, expected);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "modbus")]
+#[then(regex = r"^coil (\d+) is (on|off)$")]
+async fn coil_is(world: &mut MyWorld, address: u16, state: String) -> Result<()> {
+    let on = modbus(world)?.read_coils(address, 1)?[0];
+    if on != (state == "on") {
+        anyhow::bail!("coil {} is {}, expected {}", address, if on { "on" } else { "off" }, state);
+    }
     Ok(())
 }
@@ -0,0 +1,107 @@
+//! Ordered suites, for bring-up flows whose scenarios build on each other. Scenarios tagged
+//! `@order:<n>` run one at a time, lowest `n` first, and can hand variables on to the ordered
+//! scenarios after them in the same feature.
+
+use anyhow::{Context, Result};
+use cucumber::gherkin::{Feature, Scenario};
+use cucumber::parser::{self, Parser};
+use futures::stream::{LocalBoxStream, StreamExt};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Suites by feature file and feature name
+type Key = (Option<PathBuf>, String);
+
+static SUITES: Mutex<Option<HashMap<Key, Handoff>>> = Mutex::new(None);
+
+/// What the ordered scenarios run so far left for the next one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Handoff {
+    /// Variables exported by scenarios that passed
+    pub vars: HashMap<String, String>,
+    /// The first ordered scenario that failed
+    pub failed: Option<String>,
+}
+
+/// Position from the scenario's `@order:<n>` tag, None for independent scenarios
+pub fn order(scenario: &Scenario) -> Result<Option<u32>> {
+    scenario
+        .tags
+        .iter()
+        .find_map(|t| t.strip_prefix("order:"))
+        .map(|n| n.parse().with_context(|| format!("invalid @order:{}", n)))
+        .transpose()
+}
+
+/// Put the ordered scenarios of `feature` (and of each rule) in `@order` sequence, in the slots
+/// they were written in, and tag them `@serial` so cucumber runs them one at a time. Scenarios
+/// with an invalid tag are left alone; [`order`] reports them when they start.
+pub fn sort(feature: &mut Feature) {
+    sort_scenarios(&mut feature.scenarios);
+    for rule in &mut feature.rules {
+        sort_scenarios(&mut rule.scenarios);
+    }
+}
+
+fn sort_scenarios(scenarios: &mut [Scenario]) {
+    let slots: Vec<usize> = (0..scenarios.len()).filter(|&i| matches!(order(&scenarios[i]), Ok(Some(_)))).collect();
+    let mut ordered: Vec<Scenario> = slots.iter().map(|&i| scenarios[i].clone()).collect();
+    ordered.sort_by_key(|s| order(s).ok().flatten());
+    for (slot, mut scenario) in slots.into_iter().zip(ordered) {
+        if !scenario.tags.iter().any(|t| t == "serial") {
+            scenario.tags.push("serial".to_string());
+        }
+        scenarios[slot] = scenario;
+    }
+}
+
+fn key(feature: &Feature) -> Key {
+    (feature.path.clone(), feature.name.clone())
+}
+
+/// Exports and failures of the ordered scenarios of `feature` that have finished
+pub fn handoff(feature: &Feature) -> Handoff {
+    let suites = SUITES.lock().unwrap_or_else(|e| e.into_inner());
+    suites.as_ref().and_then(|s| s.get(&key(feature))).cloned().unwrap_or_default()
+}
+
+/// Record a finished ordered scenario: its `exports` are handed on only if it passed
+pub fn finish(feature: &Feature, scenario: &Scenario, exports: HashMap<String, String>, passed: bool) {
+    let mut suites = SUITES.lock().unwrap_or_else(|e| e.into_inner());
+    let suite = suites.get_or_insert_with(HashMap::new).entry(key(feature)).or_default();
+    if passed {
+        suite.vars.extend(exports);
+    } else if suite.failed.is_none() {
+        suite.failed = Some(scenario.name.clone());
+    }
+}
+
+/// Parser putting the ordered scenarios of every feature read by `inner` in sequence
+#[derive(Debug, Clone)]
+pub struct Ordered<P = parser::Basic> {
+    inner: P,
+}
+
+impl<P> Ordered<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+impl<I, P: Parser<I>> Parser<I> for Ordered<P> {
+    type Cli = P::Cli;
+    type Output = LocalBoxStream<'static, parser::Result<Feature>>;
+
+    fn parse(self, input: I, cli: Self::Cli) -> Self::Output {
+        self.inner
+            .parse(input, cli)
+            .map(|feature| {
+                feature.map(|mut f| {
+                    sort(&mut f);
+                    f
+                })
+            })
+            .boxed_local()
+    }
+}
@@ -0,0 +1,126 @@
+//! TLS client connections for the HTTP-based modules, through the `openssl s_client` tool, which
+//! must be on `PATH`, rather than an in-process TLS library. Certificates and verification come from
+//! `[tls.<profile>]` in bdd.toml. A connection is only handed out once the handshake has finished and,
+//! unless the profile skips verification, openssl reports the server's certificate verified.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, TlsProfile};
+
+/// Profile used when a step names none
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// `[tls.<name>]`; without a name, `[tls.default]` or else verification against the system CAs
+pub fn profile(name: Option<&str>) -> Result<TlsProfile> {
+    let profiles = &Config::global().tls;
+    match name {
+        Some(name) => profiles.get(name).cloned().ok_or_else(|| anyhow!("unknown TLS profile {} (add [tls.{}])", name, name)),
+        None => Ok(profiles.get(DEFAULT_PROFILE).cloned().unwrap_or_default()),
+    }
+}
+
+/// Arguments for `openssl` connecting to `host:port` with `profile`
+pub fn openssl_args(host: &str, port: u16, profile: &TlsProfile) -> Vec<String> {
+    let bare = host.trim_matches(['[', ']']);
+    let ip = bare.parse::<std::net::IpAddr>().is_ok();
+    let mut args = vec!["s_client".to_string(), "-brief".to_string(), "-ign_eof".to_string(), "-connect".to_string(), format!("{}:{}", host, port)];
+    if !ip {
+        args.extend(["-servername".to_string(), bare.to_string()]);
+    }
+    if !profile.insecure_skip_verify {
+        let check = if ip { "-verify_ip" } else { "-verify_hostname" };
+        args.extend(["-verify_return_error".to_string(), check.to_string(), bare.to_string()]);
+    }
+    for (flag, path) in [("-CAfile", &profile.ca), ("-cert", &profile.cert), ("-key", &profile.key)] {
+        if let Some(path) = path {
+            args.extend([flag.to_string(), path.display().to_string()]);
+        }
+    }
+    args
+}
+
+/// How long a failed connection's openssl gets to finish reporting why
+const EXIT_GRACE: Duration = Duration::from_millis(500);
+
+/// A TLS connection, open until closed or dropped
+pub struct TlsStream {
+    child: Child,
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl TlsStream {
+    /// Connect and wait up to `timeout` for the handshake to finish and, unless the profile skips
+    /// verification, for openssl to report the certificate verified; the returned pipes carry the plaintext
+    pub fn connect(host: &str, port: u16, profile: &TlsProfile, timeout: Duration) -> Result<(Self, ChildStdin, ChildStdout)> {
+        Self::start(openssl_args(host, port, profile), profile, timeout)
+    }
+
+    /// [`connect`](Self::connect), offering `protocol` through ALPN, e.g. `h2` for HTTP/2
+    pub fn connect_alpn(host: &str, port: u16, profile: &TlsProfile, protocol: &str, timeout: Duration) -> Result<(Self, ChildStdin, ChildStdout)> {
+        let mut args = openssl_args(host, port, profile);
+        args.extend(["-alpn".to_string(), protocol.to_string()]);
+        Self::start(args, profile, timeout)
+    }
+
+    fn start(args: Vec<String>, profile: &TlsProfile, timeout: Duration) -> Result<(Self, ChildStdin, ChildStdout)> {
+        let mut child = Command::new("openssl")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("run openssl")?;
+        let writer = child.stdin.take().context("openssl stdin")?;
+        let reader = child.stdout.take().context("openssl stdout")?;
+        // -brief reports the handshake on stderr, ending with a "Verification: ..." line
+        let (verdict_tx, verdict) = mpsc::channel();
+        let stderr = child.stderr.take().map(|pipe| {
+            std::thread::spawn(move || {
+                let mut out = String::new();
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    if line.starts_with("Verification") {
+                        let _ = verdict_tx.send(line.clone());
+                    }
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                out
+            })
+        });
+        let stream = Self { child, stderr };
+        // the verdict comes once the handshake is done, whether or not verification is skipped
+        match verdict.recv_timeout(timeout) {
+            Ok(line) if line == "Verification: OK" || profile.insecure_skip_verify => {}
+            Ok(line) => bail!("certificate not verified: {}", line.trim_start_matches("Verification error:").trim()),
+            Err(RecvTimeoutError::Disconnected) => bail!("TLS handshake failed: {}", stream.failure()),
+            Err(RecvTimeoutError::Timeout) => bail!("no TLS handshake within {:?}", timeout),
+        }
+        Ok((stream, writer, reader))
+    }
+
+    /// Close a connection that failed and return what openssl reported, e.g. why the handshake failed
+    pub fn failure(mut self) -> String {
+        let deadline = Instant::now() + EXIT_GRACE;
+        while Instant::now() < deadline && matches!(self.child.try_wait(), Ok(None)) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.close();
+        self.stderr.take().and_then(|t| t.join().ok()).unwrap_or_default().trim().to_string()
+    }
+
+    pub fn close(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCENARIOS: AtomicUsize = AtomicUsize::new(0);
+
+/// Directory of its own for each scenario's files, `${scenario_tmp}` in steps. Removed when the
+/// scenario passes, kept for inspection when it fails.
+#[derive(Debug)]
+pub struct ScenarioTmp {
+    path: PathBuf,
+}
+
+impl ScenarioTmp {
+    /// Create `bdd-<pid>-<n>-<scenario>` under the system temp directory
+    pub fn create(scenario: &str) -> Result<Self> {
+        let n = SCENARIOS.fetch_add(1, Ordering::SeqCst) + 1;
+        let name: String = scenario.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).take(40).collect();
+        let path = std::env::temp_dir().join(format!("bdd-{}-{}-{}", std::process::id(), n, name));
+        std::fs::create_dir_all(&path).with_context(|| format!("create {}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Delete the directory and everything in it
+    pub fn remove(self) -> Result<()> {
+        std::fs::remove_dir_all(&self.path).with_context(|| format!("remove {}", self.path.display()))
+    }
+}
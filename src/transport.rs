@@ -0,0 +1,232 @@
+use anyhow::{bail, Context, Result};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use zmq::{Context as ZmqContext, Socket, SocketEvent, PAIR, SUB, XPUB};
+
+use crate::config::Readiness;
+use crate::network::{Endpoint, Network};
+
+/// Sending half of a transport, used from the step thread
+pub trait Publisher: Send {
+    /// Connect to `address` (transport specific, e.g. the SUT's IP for ZMQ)
+    fn connect(&mut self, address: &str) -> Result<()>;
+    /// Publish an encoded message on `topic`
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()>;
+    /// Drop connections and anything still queued
+    fn close(&mut self) {}
+    /// Wait until the connections made so far can carry messages, as far as `check` can tell.
+    /// Transports without connections are always ready.
+    fn wait_ready(&mut self, _check: Readiness, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Receiving half of a transport, owned by the background receiver thread
+pub trait Subscriber: Send {
+    fn connect(&mut self, address: &str) -> Result<()>;
+    /// Wait at most `timeout` for one message; None when nothing arrived
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>>;
+    /// Drop the connections made so far; `connect` may follow
+    fn disconnect(&mut self) {}
+}
+
+/// ZMQ XPUB socket connecting to tcp://<ip>:4246. It sends like a PUB socket, and also tells
+/// when the SUT has subscribed.
+pub struct ZmqPublisher {
+    sock: Socket,
+    endpoints: Vec<String>,
+    network: Network,
+    /// Connection events of this socket and of the subscriber's
+    monitors: [Monitor; 2],
+    /// Whether the SUT has subscribed to anything yet
+    subscribed: bool,
+}
+
+/// ZMQ SUB socket connecting to tcp://<ip>:4247, subscribed to every topic
+pub struct ZmqSubscriber {
+    sock: Socket,
+    endpoints: Vec<String>,
+    network: Network,
+}
+
+/// Publisher and subscriber sockets on a fresh ZMQ context, reaching the SUT through `network`
+pub fn zmq_pair(network: Network) -> Result<(ZmqPublisher, ZmqSubscriber)> {
+    let ctx = ZmqContext::new();
+    let pub_sock = ctx.socket(XPUB).context("create pub")?;
+    let sub_sock = ctx.socket(SUB).context("create sub")?;
+    sub_sock.set_subscribe(b"").context("subscribe")?;
+    // never block process exit / context termination on undelivered messages
+    pub_sock.set_linger(0).context("set pub linger")?;
+    sub_sock.set_linger(0).context("set sub linger")?;
+    // dual-stack, so IPv6 SUTs can be reached too
+    pub_sock.set_ipv6(true).context("set pub ipv6")?;
+    sub_sock.set_ipv6(true).context("set sub ipv6")?;
+    let monitors = [Monitor::attach(&ctx, &pub_sock, "pub")?, Monitor::attach(&ctx, &sub_sock, "sub")?];
+    Ok((
+        ZmqPublisher { sock: pub_sock, endpoints: Vec::new(), network: network.clone(), monitors, subscribed: false },
+        ZmqSubscriber { sock: sub_sock, endpoints: Vec::new(), network },
+    ))
+}
+
+/// `tcp://[<source>:0;]<ip>:<port>` for the SUT at `address`, a host or IPv6 literal. The host is
+/// resolved here, so a typo fails the connect step instead of leaving ZMQ reconnecting to nowhere.
+pub fn zmq_endpoint(network: &Network, address: &str, port: u16) -> Result<String> {
+    let endpoint: Endpoint = address.parse()?;
+    if endpoint.port.is_some() {
+        anyhow::bail!("ZMQ connects to ports 4246 and 4247 of {}; give just the host", endpoint.host);
+    }
+    Ok(match network.route(address, port)? {
+        (target, Some(source)) => format!("tcp://{};{}", SocketAddr::new(source, 0), target),
+        (target, None) => format!("tcp://{}", target),
+    })
+}
+
+impl Publisher for ZmqPublisher {
+    fn connect(&mut self, address: &str) -> Result<()> {
+        let endpoint = zmq_endpoint(&self.network, address, 4246)?;
+        self.sock.connect(&endpoint).with_context(|| format!("connect pub {}", endpoint))?;
+        self.endpoints.push(endpoint);
+        Ok(())
+    }
+
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        self.sock.send_multipart([topic.as_bytes(), payload], 0).context("send multipart")
+    }
+
+    fn close(&mut self) {
+        let _ = self.sock.set_linger(0);
+        for endpoint in self.endpoints.drain(..) {
+            let _ = self.sock.disconnect(&endpoint);
+        }
+        // a reconnected SUT subscribes anew
+        self.subscribed = false;
+    }
+
+    /// `monitor`: both sockets finished the ZMQ handshake with every endpoint. `subscription`:
+    /// that, and the SUT subscribed to our messages. Other checks aren't the transport's.
+    fn wait_ready(&mut self, check: Readiness, timeout: Duration) -> Result<()> {
+        if !matches!(check, Readiness::Monitor | Readiness::Subscription) {
+            return Ok(());
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            for monitor in &mut self.monitors {
+                monitor.read()?;
+            }
+            while let Ok(frame) = self.sock.recv_bytes(zmq::DONTWAIT) {
+                // 1 + topic to subscribe, 0 + topic to unsubscribe
+                self.subscribed |= frame.first() == Some(&1);
+            }
+            let connected = self.monitors.iter().all(|m| m.connected >= self.endpoints.len());
+            if connected && (self.subscribed || check == Readiness::Monitor) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                let [publisher, subscriber] = &self.monitors;
+                let mut reason = format!(
+                    "handshakes: pub {}/{}, sub {}/{}",
+                    publisher.connected,
+                    self.endpoints.len(),
+                    subscriber.connected,
+                    self.endpoints.len()
+                );
+                if connected {
+                    reason = "the SUT has not subscribed".to_string();
+                } else if let Some(event) = publisher.last_failure.or(subscriber.last_failure) {
+                    reason.push_str(&format!("; last {}", event));
+                }
+                bail!("ZMQ connection to {} not ready after {}: {}", self.endpoints.join(", "), humantime::format_duration(timeout), reason);
+            }
+            std::thread::sleep(READY_POLL);
+        }
+    }
+}
+
+/// How often readiness checks look for new events
+const READY_POLL: Duration = Duration::from_millis(10);
+
+static MONITORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Connection events of one socket, from its ZMQ monitor
+struct Monitor {
+    sock: Socket,
+    /// Peers the socket finished the handshake with and is still connected to
+    connected: usize,
+    /// Why the last connection attempt failed
+    last_failure: Option<&'static str>,
+}
+
+impl Monitor {
+    fn attach(ctx: &ZmqContext, sock: &Socket, name: &str) -> Result<Self> {
+        let endpoint = format!("inproc://bdd-monitor-{}-{}", name, MONITORS.fetch_add(1, Ordering::SeqCst));
+        let events = SocketEvent::HANDSHAKE_SUCCEEDED as i32
+            | SocketEvent::DISCONNECTED as i32
+            | SocketEvent::CONNECT_RETRIED as i32
+            | SocketEvent::HANDSHAKE_FAILED_NO_DETAIL as i32
+            | SocketEvent::HANDSHAKE_FAILED_PROTOCOL as i32
+            | SocketEvent::HANDSHAKE_FAILED_AUTH as i32;
+        sock.monitor(&endpoint, events).with_context(|| format!("monitor {} socket", name))?;
+        let monitor = ctx.socket(PAIR).context("create monitor")?;
+        monitor.set_linger(0).context("set monitor linger")?;
+        monitor.connect(&endpoint).with_context(|| format!("connect {}", endpoint))?;
+        Ok(Self { sock: monitor, connected: 0, last_failure: None })
+    }
+
+    /// Apply the events that arrived since the last read
+    fn read(&mut self) -> Result<()> {
+        while let Ok(frames) = self.sock.recv_multipart(zmq::DONTWAIT) {
+            // event id (u16) and value (u32), then the endpoint
+            let Some(event) = frames.first().filter(|f| f.len() >= 2).map(|f| u16::from_ne_bytes([f[0], f[1]])) else { continue };
+            match event {
+                e if e == SocketEvent::HANDSHAKE_SUCCEEDED as u16 => self.connected += 1,
+                e if e == SocketEvent::DISCONNECTED as u16 => self.connected = self.connected.saturating_sub(1),
+                e if e == SocketEvent::CONNECT_RETRIED as u16 => self.last_failure = Some("connection refused, retrying"),
+                _ => self.last_failure = Some("handshake failed"),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Subscriber for ZmqSubscriber {
+    fn connect(&mut self, address: &str) -> Result<()> {
+        let endpoint = zmq_endpoint(&self.network, address, 4247)?;
+        self.sock.connect(&endpoint).with_context(|| format!("connect sub {}", endpoint))?;
+        self.endpoints.push(endpoint);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        for endpoint in self.endpoints.drain(..) {
+            let _ = self.sock.disconnect(&endpoint);
+        }
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        // polled rather than through rcvtimeo, so no wait leaves a timeout behind on the socket
+        if self.sock.poll(zmq::POLLIN, timeout.as_millis() as i64).context("poll sub")? == 0 {
+            return Ok(None);
+        }
+        let topic = match self.sock.recv_bytes(zmq::DONTWAIT) {
+            Ok(frame) => frame,
+            Err(zmq::Error::EAGAIN) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // topic + payload frames; anything else isn't ours
+        if !self.sock.get_rcvmore()? {
+            return Ok(None);
+        }
+        let payload = self.sock.recv_bytes(0)?;
+        if self.sock.get_rcvmore()? {
+            while self.sock.get_rcvmore()? {
+                self.sock.recv_bytes(0)?;
+            }
+            log::debug!(target: "transport", "ignored a message with more than 2 frames");
+            return Ok(None);
+        }
+        // the frame becomes the topic without a copy unless it isn't UTF-8
+        let topic = String::from_utf8(topic).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+        Ok(Some((topic, payload)))
+    }
+}
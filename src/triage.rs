@@ -0,0 +1,221 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use cucumber::{event, gherkin, parser, writer, Event, World, Writer};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::broker::Broker;
+use crate::config::{Config, TriageConfig};
+use crate::proto_dyn::{self, ProtoDyn};
+use crate::secrets;
+
+/// Bumped when the bundle layout changes, so tooling reading bundles can tell
+pub const LAYOUT_VERSION: u32 = 2;
+
+/// How long creating the archive may take
+const TAR_TIMEOUT: Duration = Duration::from_secs(120);
+
+static SCENARIOS: AtomicUsize = AtomicUsize::new(0);
+
+/// Where failing scenarios are staged until the run ends; None when bundles are off
+fn staging(config: &TriageConfig) -> Option<PathBuf> {
+    config.dir.as_ref().map(|dir| dir.join(format!(".staging-{}", std::process::id())))
+}
+
+/// Files of one failing scenario, under `scenarios/<n>-<name>/` in the bundle
+#[derive(Debug)]
+pub struct ScenarioDir {
+    path: PathBuf,
+}
+
+impl ScenarioDir {
+    /// Directory for a failing scenario, or None when `[triage] dir` isn't set
+    pub fn create(feature: &gherkin::Feature, scenario: &gherkin::Scenario) -> Result<Option<Self>> {
+        let Some(staging) = staging(&Config::global().triage) else { return Ok(None) };
+        let n = SCENARIOS.fetch_add(1, Ordering::SeqCst) + 1;
+        let path = staging.join("scenarios").join(format!("{:03}-{}", n, slug(&scenario.name)));
+        std::fs::create_dir_all(&path).with_context(|| format!("create {}", path.display()))?;
+        let about = format!("feature: {}\nscenario: {}\nline: {}\n", feature.name, scenario.name, scenario.position.line);
+        std::fs::write(path.join("scenario.txt"), about)?;
+        Ok(Some(Self { path }))
+    }
+
+    /// Everything `broker` has buffered, decoded, as `<connection>.ndjson`
+    pub fn captures(&self, connection: &str, broker: &Broker) -> Result<()> {
+        let now = (Instant::now(), SystemTime::now());
+        let mut out = String::new();
+        for c in broker.recent(usize::MAX) {
+            let at = now.1 - now.0.saturating_duration_since(c.at);
+            let line = json!({ "at": humantime::format_rfc3339_micros(at).to_string(), "topic": c.topic, "size": c.size, "body": c.body });
+            writeln!(out, "{}", line)?;
+        }
+        let file = self.path.join(format!("{}.ndjson", slug(connection)));
+        std::fs::write(&file, secrets::redact(&out).as_bytes()).with_context(|| format!("write {}", file.display()))
+    }
+
+    /// A scenario attachment, as is, under `attachments/`
+    pub fn attachment(&self, name: &str, data: &[u8]) -> Result<()> {
+        let dir = self.path.join("attachments");
+        std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+        std::fs::write(dir.join(file_name(name)), data).with_context(|| format!("write attachment {}", name))
+    }
+
+    pub fn file(&self, name: &str, contents: &str) -> Result<()> {
+        std::fs::write(self.path.join(name), secrets::redact(contents).as_bytes()).with_context(|| format!("write {}", name))
+    }
+}
+
+/// File-name-safe version of `s`
+fn slug(s: &str) -> String {
+    let slug: String = s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    slug.chars().take(60).collect()
+}
+
+/// As [`slug`], keeping dots so the extension survives, but never leading
+fn file_name(s: &str) -> String {
+    let name: String = s.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect();
+    name.trim_start_matches('.').chars().take(60).collect()
+}
+
+#[derive(Debug, Clone)]
+struct Failure {
+    feature: String,
+    scenario: String,
+    line: usize,
+    step: String,
+    error: String,
+}
+
+/// Writer that packs a triage bundle when the run had failures and `[triage] dir` is set. Meant to
+/// be tee'd next to the regular output writer.
+#[derive(Debug, Default)]
+pub struct TriageBundle {
+    failures: Vec<Failure>,
+}
+
+impl TriageBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn finish(&self) {
+        let config = &Config::global().triage;
+        let Some(staging) = staging(config) else { return };
+        if self.failures.is_empty() {
+            let _ = std::fs::remove_dir_all(&staging);
+            return;
+        }
+        match bundle(config, &staging, &self.failures) {
+            Ok(path) => println!("triage bundle: {}", path.display()),
+            Err(e) => eprintln!("triage bundle failed: {:#}", e),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<W: World> Writer<W> for TriageBundle {
+    type Cli = cucumber::cli::Empty;
+
+    async fn handle_event(&mut self, ev: parser::Result<Event<event::Cucumber<W>>>, _: &Self::Cli) {
+        let Ok(Event { value, .. }) = ev else { return };
+        match value {
+            event::Cucumber::Feature(feature, event::Feature::Scenario(scenario, ev))
+            | event::Cucumber::Feature(feature, event::Feature::Rule(_, event::Rule::Scenario(scenario, ev))) => {
+                let (step, error) = match &ev.event {
+                    event::Scenario::Step(step, event::Step::Failed(_, _, _, err))
+                    | event::Scenario::Background(step, event::Step::Failed(_, _, _, err)) => {
+                        (format!("{}{}", step.keyword, step.value), err.to_string())
+                    }
+                    event::Scenario::Hook(which, event::Hook::Failed(..)) => (format!("{} hook", which), "hook failed".to_string()),
+                    _ => return,
+                };
+                let (feature, scenario, line) = (feature.name.clone(), scenario.name.clone(), scenario.position.line);
+                self.failures.push(Failure { feature, scenario, line, step, error });
+            }
+            event::Cucumber::Finished => self.finish(),
+            _ => {}
+        }
+    }
+}
+
+// failures are listed in the order they arrive
+impl writer::Normalized for TriageBundle {}
+
+/// Fill in the run-wide files next to the staged scenarios and pack everything as
+/// `<dir>/triage-<time>.tar.gz`, with a `triage-<time>/` root
+fn bundle(config: &TriageConfig, staging: &Path, failures: &[Failure]) -> Result<PathBuf> {
+    std::fs::create_dir_all(staging)?;
+    let created = SystemTime::now();
+    let name = format!("triage-{}", humantime::format_rfc3339_seconds(created).to_string().replace(':', ""));
+
+    let mut report = String::new();
+    for f in failures {
+        writeln!(report, "{}: {} (line {})\n  {}\n  {}\n", f.feature, f.scenario, f.line, f.step, f.error.replace('\n', "\n  "))?;
+    }
+    std::fs::write(staging.join("report.txt"), secrets::redact(&report).as_bytes())?;
+
+    let config_path = Config::path();
+    if config_path.exists() {
+        std::fs::create_dir_all(staging.join("config"))?;
+        std::fs::copy(&config_path, staging.join("config").join("bdd.toml")).context("copy config")?;
+    }
+
+    let descriptor = ProtoDyn::new().map(|p| p.descriptor_set());
+    let hash = match &descriptor {
+        Ok(bytes) => {
+            std::fs::create_dir_all(staging.join("descriptor"))?;
+            std::fs::write(staging.join("descriptor").join("descriptor.bin"), bytes)?;
+            std::fs::write(staging.join("descriptor").join("hash.txt"), proto_dyn::descriptor_hash(bytes) + "\n")?;
+            Some(proto_dyn::descriptor_hash(bytes))
+        }
+        Err(_) => None,
+    };
+
+    for log in &config.logs {
+        let Some(file_name) = log.file_name() else { continue };
+        std::fs::create_dir_all(staging.join("logs"))?;
+        if let Err(e) = copy_redacted(log, &staging.join("logs").join(file_name)) {
+            eprintln!("triage bundle: skipping {}: {}", log.display(), e);
+        }
+    }
+
+    if let Some(events) = crate::events::path() {
+        crate::events::flush();
+        std::fs::copy(events, staging.join("events.ndjson")).context("copy event stream")?;
+    }
+
+    let manifest = json!({
+        "layout_version": LAYOUT_VERSION,
+        "created": humantime::format_rfc3339_seconds(created).to_string(),
+        "harness_version": env!("CARGO_PKG_VERSION"),
+        "failed_scenarios": failures.len(),
+        "descriptor_hash": hash,
+        "config": config_path.exists().then(|| config_path.display().to_string()),
+    });
+    std::fs::write(staging.join("manifest.json"), serde_json::to_string_pretty(&manifest)? + "\n")?;
+
+    let dir = staging.parent().context("staging directory has no parent")?;
+    let root = dir.join(&name);
+    std::fs::rename(staging, &root).with_context(|| format!("rename {}", staging.display()))?;
+    let archive = dir.join(format!("{}.tar.gz", name));
+    let mut tar = std::process::Command::new("tar");
+    tar.arg("-czf").arg(&archive).arg("-C").arg(dir).arg(&name);
+    let output = crate::process::run(tar, TAR_TIMEOUT)?;
+    if output.status != 0 {
+        bail!("tar exited with {}: {}", output.status, output.stderr.trim());
+    }
+    std::fs::remove_dir_all(&root)?;
+    Ok(archive)
+}
+
+/// Copy a log, redacting secrets when it is text
+fn copy_redacted(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::read_to_string(from) {
+        Ok(text) => std::fs::write(to, secrets::redact(&text).as_bytes()),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => std::fs::copy(from, to).map(|_| ()),
+        Err(e) => Err(e),
+    }
+}
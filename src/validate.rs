@@ -0,0 +1,402 @@
+//! Field constraints from protoc-gen-validate (`(validate.rules)`) and protovalidate
+//! (`(buf.validate.field)`) options. Both put one rules message per field type (`string`, `int32`,
+//! `repeated`...) with mostly the same rule names, so the rules are read by name.
+//!
+//! Checked: `required`, `message.required`, `const`, `lt`/`lte`/`gt`/`gte`, `in`/`not_in`, string
+//! `len`/`min_len`/`max_len`/`min_bytes`/`max_bytes`/`pattern`/`prefix`/`suffix`/`contains`/
+//! `not_contains`/`ip`/`ipv4`/`ipv6`/`uuid`, bytes `len`/`min_len`/`max_len`, enum `defined_only`,
+//! repeated `min_items`/`max_items`/`unique`/`items`, map `min_pairs`/`max_pairs`. Other rules
+//! (CEL expressions, e-mail and URI formats...) are not checked.
+
+use prost_reflect::{Cardinality, DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, ReflectMessage, Value as PbValue};
+use regex::Regex;
+use serde_json::{Map, Value as JsonValue};
+use std::cmp::Ordering;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Option extensions holding field rules
+const RULE_EXTENSIONS: [&str; 2] = ["validate.rules", "buf.validate.field"];
+
+/// One failed constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Path of the field in the message, e.g. `header.name` or `items[2]`
+    pub field: String,
+    /// Rule that failed, e.g. `string.min_len`
+    pub rule: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.field, self.message, self.rule)
+    }
+}
+
+/// Every constraint `msg` breaks, nested messages included
+pub fn validate(msg: &DynamicMessage) -> Vec<Violation> {
+    let mut out = Vec::new();
+    check_message(msg, "", &mut out);
+    out
+}
+
+fn check_message(msg: &DynamicMessage, prefix: &str, out: &mut Vec<Violation>) {
+    for field in msg.descriptor().fields() {
+        let path = if prefix.is_empty() { field.name().to_string() } else { format!("{}.{}", prefix, field.name()) };
+        let present = msg.has_field(&field);
+        let value = msg.get_field(&field);
+        if let Some(rules) = field_rules(&field) {
+            check_field(&field, &rules, present, &value, &path, out);
+        }
+        // nested messages are checked against their own rules
+        match &*value {
+            PbValue::Message(m) if present => check_message(m, &path, out),
+            PbValue::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if let PbValue::Message(m) = item {
+                        check_message(m, &format!("{}[{}]", path, i), out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Mistakes in a JSON body about to be encoded as `desc` that encoding would hide: several members
+/// of one oneof set (the last wins), integers out of their type's range (they wrap), enum numbers
+/// the enum doesn't define, proto2 required fields left out, and text holding U+FFFD, left by a
+/// lossy UTF-8 decode somewhere before
+pub fn check_outgoing(desc: &MessageDescriptor, json: &JsonValue) -> Vec<Violation> {
+    let mut out = Vec::new();
+    if let JsonValue::Object(map) = json {
+        check_json_message(desc, map, "", &mut out);
+    }
+    out
+}
+
+fn check_json_message(desc: &MessageDescriptor, map: &Map<String, JsonValue>, prefix: &str, out: &mut Vec<Violation>) {
+    let path = |name: &str| if prefix.is_empty() { name.to_string() } else { format!("{}.{}", prefix, name) };
+    for oneof in desc.oneofs() {
+        let set: Vec<String> = oneof.fields().filter(|f| map.contains_key(f.name())).map(|f| f.name().to_string()).collect();
+        if set.len() > 1 {
+            let message = format!("sets {}; only one member of the oneof can be set", set.join(", "));
+            out.push(Violation { field: path(oneof.name()), rule: "oneof".into(), message });
+        }
+    }
+    for field in desc.fields() {
+        if field.cardinality() == Cardinality::Required && !map.contains_key(field.name()) {
+            out.push(Violation { field: path(field.name()), rule: "proto2.required".into(), message: "is required".into() });
+        }
+    }
+    for (name, value) in map {
+        // unknown fields fail the encoding with their own error
+        let Some(field) = desc.get_field_by_name(name) else { continue };
+        match value {
+            JsonValue::Array(items) if field.is_list() => {
+                for (i, item) in items.iter().enumerate() {
+                    check_json_value(&field.kind(), item, &format!("{}[{}]", path(name), i), out);
+                }
+            }
+            _ => check_json_value(&field.kind(), value, &path(name), out),
+        }
+    }
+}
+
+/// Paths of the fields marked `[deprecated = true]` that `json` sets, nested messages included
+pub fn deprecated_fields(desc: &MessageDescriptor, json: &JsonValue) -> Vec<String> {
+    let mut out = Vec::new();
+    deprecated_within(desc, json, "", &mut out);
+    out
+}
+
+fn deprecated_within(desc: &MessageDescriptor, json: &JsonValue, prefix: &str, out: &mut Vec<String>) {
+    let JsonValue::Object(map) = json else { return };
+    for (name, value) in map {
+        let Some(field) = desc.get_field_by_name(name) else { continue };
+        let path = if prefix.is_empty() { name.to_string() } else { format!("{}.{}", prefix, name) };
+        if field.field_descriptor_proto().options.as_ref().and_then(|o| o.deprecated).unwrap_or(false) {
+            out.push(path.clone());
+        }
+        if let Kind::Message(nested) = field.kind() {
+            match value {
+                JsonValue::Array(items) => items.iter().enumerate().for_each(|(i, item)| deprecated_within(&nested, item, &format!("{}[{}]", path, i), out)),
+                value => deprecated_within(&nested, value, &path, out),
+            }
+        }
+    }
+}
+
+fn check_json_value(kind: &Kind, value: &JsonValue, path: &str, out: &mut Vec<Violation>) {
+    let range = match kind {
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Some(("int32", i32::MIN as i128, i32::MAX as i128)),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Some(("int64", i64::MIN as i128, i64::MAX as i128)),
+        Kind::Uint32 | Kind::Fixed32 => Some(("uint32", 0, u32::MAX as i128)),
+        Kind::Uint64 | Kind::Fixed64 => Some(("uint64", 0, u64::MAX as i128)),
+        _ => None,
+    };
+    let integer = value.as_i64().map(i128::from).or_else(|| value.as_u64().map(i128::from));
+    let (rule, message) = match (range, kind, value) {
+        (Some((ty, min, max)), _, JsonValue::Number(_)) => match integer {
+            Some(n) if n < min || n > max => ("range", format!("{} is out of range for {}", n, ty)),
+            Some(_) => return,
+            None => ("range", format!("{} is not a whole number", value)),
+        },
+        (_, Kind::Enum(e), JsonValue::Number(_)) => match integer {
+            Some(n) if i32::try_from(n).ok().and_then(|n| e.get_value(n)).is_none() => ("enum", format!("{} is not a value of {}", n, e.name())),
+            Some(_) => return,
+            None => ("enum", format!("{} is not a whole number", value)),
+        },
+        (_, Kind::String, JsonValue::String(s)) if s.contains('\u{fffd}') => {
+            ("utf8", "holds U+FFFD replacement characters; the text was not valid UTF-8 where it came from".to_string())
+        }
+        (_, Kind::Message(m), JsonValue::Object(map)) => return check_json_message(m, map, path, out),
+        _ => return,
+    };
+    out.push(Violation { field: path.to_string(), rule: rule.into(), message });
+}
+
+/// Rules message attached to `field` by either option extension
+fn field_rules(field: &FieldDescriptor) -> Option<DynamicMessage> {
+    let options = field.options();
+    let pool = field.parent_pool();
+    RULE_EXTENSIONS.iter().find_map(|name| {
+        let ext = pool.get_extension_by_name(name)?;
+        if !options.has_extension(&ext) {
+            return None;
+        }
+        match &*options.get_extension(&ext) {
+            PbValue::Message(rules) => Some(rules.clone()),
+            _ => None,
+        }
+    })
+}
+
+/// Value of rule `name` if it is set
+fn rule(rules: &DynamicMessage, name: &str) -> Option<PbValue> {
+    let field = rules.descriptor().get_field_by_name(name)?;
+    rules.has_field(&field).then(|| rules.get_field(&field).into_owned())
+}
+
+fn flag(rules: &DynamicMessage, name: &str) -> bool {
+    matches!(rule(rules, name), Some(PbValue::Bool(true)))
+}
+
+fn check_field(field: &FieldDescriptor, rules: &DynamicMessage, present: bool, value: &PbValue, path: &str, out: &mut Vec<Violation>) {
+    if flag(rules, "required") && !present {
+        out.push(Violation { field: path.to_string(), rule: "required".into(), message: "is required".into() });
+        return;
+    }
+    // the type rules are whichever of `string`, `int32`, `repeated`... is set
+    let Some((kind, typed)) = rules.fields().find_map(|(f, v)| match v {
+        PbValue::Message(m) => Some((f.name().to_string(), m.clone())),
+        _ => None,
+    }) else {
+        return;
+    };
+    let mut violations = Vec::new();
+    match (kind.as_str(), value) {
+        ("message", _) if flag(&typed, "required") && !present => violations.push(("required".into(), "is required".into())),
+        ("repeated", PbValue::List(items)) => check_repeated(&typed, items, &mut violations),
+        ("map", PbValue::Map(map)) => check_count(&typed, "min_pairs", "max_pairs", map.len(), "pairs", &mut violations),
+        ("string", PbValue::String(s)) if !(s.is_empty() && flag(&typed, "ignore_empty")) => check_string(&typed, s, &mut violations),
+        ("bytes", PbValue::Bytes(b)) if !(b.is_empty() && flag(&typed, "ignore_empty")) => check_bytes(&typed, b, &mut violations),
+        ("enum", PbValue::EnumNumber(n)) => {
+            let defined = matches!(field.kind(), Kind::Enum(e) if e.get_value(*n).is_some());
+            if flag(&typed, "defined_only") && !defined {
+                violations.push(("defined_only".into(), format!("{} is not a defined enum value", n)));
+            }
+            check_number(&typed, &PbValue::EnumNumber(*n), &mut violations);
+        }
+        ("bool", PbValue::Bool(b)) => {
+            if let Some(PbValue::Bool(c)) = rule(&typed, "const") {
+                if c != *b {
+                    violations.push(("const".into(), format!("is {}, must be {}", b, c)));
+                }
+            }
+        }
+        (_, v) if !(is_zero(v) && flag(&typed, "ignore_empty")) => check_number(&typed, v, &mut violations),
+        _ => {}
+    }
+    for (name, message) in violations {
+        out.push(Violation { field: path.to_string(), rule: format!("{}.{}", kind, name), message });
+    }
+    // then each item against `repeated.items`
+    if let (PbValue::List(items), Some(PbValue::Message(item_rules))) = (value, rule(&typed, "items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_field(field, &item_rules, true, item, &format!("{}[{}]", path, i), out);
+        }
+    }
+}
+
+type Found = Vec<(String, String)>;
+
+fn check_repeated(rules: &DynamicMessage, items: &[PbValue], out: &mut Found) {
+    check_count(rules, "min_items", "max_items", items.len(), "items", out);
+    if flag(rules, "unique") {
+        if let Some(i) = (1..items.len()).find(|&i| items[..i].contains(&items[i])) {
+            out.push(("unique".into(), format!("item {} repeats an earlier item", i)));
+        }
+    }
+}
+
+fn check_count(rules: &DynamicMessage, min: &str, max: &str, count: usize, what: &str, out: &mut Found) {
+    if let Some(n) = rule(rules, min).and_then(|v| v.as_u64()) {
+        if (count as u64) < n {
+            out.push((min.into(), format!("has {} {}, at least {} required", count, what, n)));
+        }
+    }
+    if let Some(n) = rule(rules, max).and_then(|v| v.as_u64()) {
+        if count as u64 > n {
+            out.push((max.into(), format!("has {} {}, at most {} allowed", count, what, n)));
+        }
+    }
+}
+
+fn check_string(rules: &DynamicMessage, s: &str, out: &mut Found) {
+    let chars = s.chars().count() as u64;
+    let text = |name: &str| rule(rules, name).and_then(|v| v.as_str().map(str::to_string));
+    let num = |name: &str| rule(rules, name).and_then(|v| v.as_u64());
+    if let Some(c) = text("const").filter(|c| c != s) {
+        out.push(("const".into(), format!("is {:?}, must be {:?}", s, c)));
+    }
+    for (name, limit, actual, unit, ok) in [
+        ("len", num("len"), chars, "characters", Ordering::is_eq as fn(Ordering) -> bool),
+        ("min_len", num("min_len"), chars, "characters", Ordering::is_ge),
+        ("max_len", num("max_len"), chars, "characters", Ordering::is_le),
+        ("min_bytes", num("min_bytes"), s.len() as u64, "bytes", Ordering::is_ge),
+        ("max_bytes", num("max_bytes"), s.len() as u64, "bytes", Ordering::is_le),
+    ] {
+        if let Some(limit) = limit.filter(|l| !ok(actual.cmp(l))) {
+            out.push((name.into(), format!("has {} {}, limit {}", actual, unit, limit)));
+        }
+    }
+    if let Some(pattern) = text("pattern") {
+        match Regex::new(&pattern) {
+            Ok(re) if !re.is_match(s) => out.push(("pattern".into(), format!("{:?} does not match {}", s, pattern))),
+            Ok(_) => {}
+            Err(e) => out.push(("pattern".into(), format!("invalid pattern {}: {}", pattern, e))),
+        }
+    }
+    for (name, ok) in [
+        ("prefix", text("prefix").map(|p| s.starts_with(&p))),
+        ("suffix", text("suffix").map(|p| s.ends_with(&p))),
+        ("contains", text("contains").map(|p| s.contains(&p))),
+        ("not_contains", text("not_contains").map(|p| !s.contains(&p))),
+    ] {
+        if ok == Some(false) {
+            out.push((name.into(), format!("{:?} fails {} {:?}", s, name, text(name).unwrap_or_default())));
+        }
+    }
+    let listed = |name: &str| match rule(rules, name) {
+        Some(PbValue::List(l)) => Some(l.iter().any(|v| v.as_str() == Some(s))),
+        _ => None,
+    };
+    if listed("in") == Some(false) {
+        out.push(("in".into(), format!("{:?} is not one of the allowed values", s)));
+    }
+    if listed("not_in") == Some(true) {
+        out.push(("not_in".into(), format!("{:?} is a disallowed value", s)));
+    }
+    let ip = s.parse::<IpAddr>().ok();
+    for (name, ok) in [
+        ("ip", ip.is_some()),
+        ("ipv4", ip.is_some_and(|a| a.is_ipv4())),
+        ("ipv6", ip.is_some_and(|a| a.is_ipv6())),
+        ("uuid", is_uuid(s)),
+    ] {
+        if flag(rules, name) && !ok {
+            out.push((name.into(), format!("{:?} is not a valid {}", s, name)));
+        }
+    }
+}
+
+fn is_uuid(s: &str) -> bool {
+    let groups: Vec<&str> = s.split('-').collect();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12]) && groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn check_bytes(rules: &DynamicMessage, b: &[u8], out: &mut Found) {
+    let len = b.len() as u64;
+    for (name, ok) in [("len", Ordering::is_eq as fn(Ordering) -> bool), ("min_len", Ordering::is_ge), ("max_len", Ordering::is_le)] {
+        if let Some(limit) = rule(rules, name).and_then(|v| v.as_u64()).filter(|l| !ok(len.cmp(l))) {
+            out.push((name.into(), format!("has {} bytes, limit {}", len, limit)));
+        }
+    }
+}
+
+/// Numbers compared exactly as integers, or as floats when either side is one
+#[derive(Debug, Clone, Copy)]
+enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+impl Num {
+    fn of(v: &PbValue) -> Option<Self> {
+        Some(match v {
+            PbValue::I32(n) | PbValue::EnumNumber(n) => Num::Int(*n as i128),
+            PbValue::I64(n) => Num::Int(*n as i128),
+            PbValue::U32(n) => Num::Int(*n as i128),
+            PbValue::U64(n) => Num::Int(*n as i128),
+            PbValue::F32(n) => Num::Float(*n as f64),
+            PbValue::F64(n) => Num::Float(*n),
+            _ => return None,
+        })
+    }
+
+    fn cmp(self, other: Num) -> Option<Ordering> {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => Some(a.cmp(&b)),
+            (a, b) => a.float().partial_cmp(&b.float()),
+        }
+    }
+
+    fn float(self) -> f64 {
+        match self {
+            Num::Int(n) => n as f64,
+            Num::Float(f) => f,
+        }
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Num::Int(n) => write!(f, "{}", n),
+            Num::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+fn is_zero(v: &PbValue) -> bool {
+    Num::of(v).is_some_and(|n| n.float() == 0.0)
+}
+
+fn check_number(rules: &DynamicMessage, v: &PbValue, out: &mut Found) {
+    let Some(actual) = Num::of(v) else { return };
+    for (name, ok, word) in [
+        ("const", Ordering::is_eq as fn(Ordering) -> bool, "equal to"),
+        ("lt", Ordering::is_lt, "less than"),
+        ("lte", Ordering::is_le, "at most"),
+        ("gt", Ordering::is_gt, "greater than"),
+        ("gte", Ordering::is_ge, "at least"),
+    ] {
+        if let Some(limit) = rule(rules, name).as_ref().and_then(Num::of) {
+            if !actual.cmp(limit).is_some_and(ok) {
+                out.push((name.into(), format!("is {}, must be {} {}", actual, word, limit)));
+            }
+        }
+    }
+    let listed = |name: &str| match rule(rules, name) {
+        Some(PbValue::List(l)) => Some(l.iter().filter_map(Num::of).any(|n| n.cmp(actual) == Some(Ordering::Equal))),
+        _ => None,
+    };
+    if listed("in") == Some(false) {
+        out.push(("in".into(), format!("{} is not one of the allowed values", actual)));
+    }
+    if listed("not_in") == Some(true) {
+        out.push(("not_in".into(), format!("{} is a disallowed value", actual)));
+    }
+}
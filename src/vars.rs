@@ -0,0 +1,23 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Replace every `${name}` in `text` with the scenario variable `name`, and every `${secret:name}`
+/// with that secret; unknown names are an error
+pub fn substitute(text: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else { bail!("unclosed ${{ in {}", text) };
+        let name = after[..end].trim();
+        match (name.strip_prefix("secret:"), vars.get(name)) {
+            (Some(secret), _) => out.push_str(&crate::secrets::get(secret.trim())?),
+            (None, Some(value)) => out.push_str(value),
+            (None, None) => bail!("unknown scenario variable ${{{}}}", name),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
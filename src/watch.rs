@@ -0,0 +1,150 @@
+//! What a change to the suite's inputs affects, for `bdd-run --watch`. Feature files are compared
+//! scenario by scenario; files named in quotes by a step (sequences, CSV traces) affect the
+//! scenarios naming them; the config and the protos affect everything.
+
+use anyhow::{Context, Result};
+use cucumber::gherkin::{Feature, GherkinEnv, Scenario, Step};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Config;
+
+/// Modification times of the watched files; None for a file that doesn't exist
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot(BTreeMap<PathBuf, Option<SystemTime>>);
+
+impl Snapshot {
+    pub fn take(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self(paths.into_iter().map(|p| (p.clone(), std::fs::metadata(&p).and_then(|m| m.modified()).ok())).collect())
+    }
+
+    /// Files created, changed or removed since `earlier`
+    pub fn changed_since(&self, earlier: &Snapshot) -> Vec<PathBuf> {
+        let mut paths: BTreeSet<&PathBuf> = self.0.keys().collect();
+        paths.extend(earlier.0.keys());
+        paths.into_iter().filter(|p| self.0.get(*p).copied().flatten() != earlier.0.get(*p).copied().flatten()).cloned().collect()
+    }
+}
+
+/// What to run again
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rerun {
+    Nothing,
+    Everything,
+    /// Scenario names by feature file
+    Scenarios(BTreeMap<PathBuf, BTreeSet<String>>),
+}
+
+/// The parsed feature files under a directory, with the files their steps name
+#[derive(Debug, Clone, Default)]
+pub struct Suite {
+    pub features: BTreeMap<PathBuf, Feature>,
+}
+
+impl Suite {
+    /// Every `*.feature` under `dir`. A file that doesn't parse is left out, so fixing it counts as
+    /// a change.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut features = BTreeMap::new();
+        for path in feature_files(dir)? {
+            match Feature::parse_path(&path, GherkinEnv::default()) {
+                Ok(feature) => {
+                    features.insert(path, feature);
+                }
+                Err(e) => eprintln!("{}: {}", path.display(), e),
+            }
+        }
+        Ok(Self { features })
+    }
+
+    /// Files worth watching: the feature files, what their steps name, bdd.toml, the configured
+    /// descriptor or .proto files and the embedded protos under `proto/`
+    pub fn watched(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths: BTreeSet<PathBuf> = feature_files(dir)?.into_iter().collect();
+        for feature in self.features.values() {
+            for scenario in scenarios(feature) {
+                paths.extend(background(feature).iter().chain(&scenario.steps).flat_map(named_files));
+            }
+        }
+        paths.extend(global_files());
+        Ok(paths.into_iter().collect())
+    }
+
+    /// What `after` needs to run again, given the files that changed since `self` was loaded
+    pub fn affected(&self, after: &Suite, changed: &[PathBuf]) -> Rerun {
+        let global = global_files();
+        if changed.iter().any(|p| global.contains(p)) {
+            return Rerun::Everything;
+        }
+        let mut rerun: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+        for (path, feature) in &after.features {
+            let before = self.features.get(path);
+            let whole = before.is_none_or(|b| fingerprint(background(b)) != fingerprint(background(feature)));
+            for scenario in scenarios(feature) {
+                let edited = whole
+                    || !before.is_some_and(|b| scenarios(b).any(|s| s.name == scenario.name && fingerprint(&s.steps) == fingerprint(&scenario.steps)));
+                let names_changed = background(feature).iter().chain(&scenario.steps).flat_map(named_files).any(|f| changed.contains(&f));
+                if edited || names_changed {
+                    rerun.entry(path.clone()).or_default().insert(scenario.name.clone());
+                }
+            }
+        }
+        if rerun.is_empty() {
+            Rerun::Nothing
+        } else {
+            Rerun::Scenarios(rerun)
+        }
+    }
+}
+
+/// `*.feature` files under `dir`, sorted
+pub fn feature_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(feature_files(&path)?);
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("feature")) {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn scenarios(feature: &Feature) -> impl Iterator<Item = &Scenario> {
+    feature.scenarios.iter().chain(feature.rules.iter().flat_map(|r| &r.scenarios))
+}
+
+fn background(feature: &Feature) -> &[Step] {
+    feature.background.as_ref().map_or(&[], |b| b.steps.as_slice())
+}
+
+/// Steps as written, without their positions, so moving a scenario doesn't count as editing it
+fn fingerprint(steps: &[Step]) -> Vec<String> {
+    steps.iter().map(|s| format!("{}{} {:?} {:?}", s.keyword, s.value, s.docstring, s.table.as_ref().map(|t| &t.rows))).collect()
+}
+
+/// Quoted strings in the step naming an existing file, relative to the working directory
+fn named_files(step: &Step) -> Vec<PathBuf> {
+    step.value
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(PathBuf::from)
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Files a change to which affects every scenario
+fn global_files() -> Vec<PathBuf> {
+    let proto = &Config::global().proto;
+    let mut files = vec![Config::path()];
+    files.extend(proto.descriptor.iter().cloned());
+    files.extend(proto.files.iter().cloned());
+    if let Ok(entries) = std::fs::read_dir("proto") {
+        files.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "proto")));
+    }
+    files
+}
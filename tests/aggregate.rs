@@ -0,0 +1,82 @@
+use my_bdd::aggregate::{scalar_default, values, Aggregate, Comparison, Monotonic};
+use my_bdd::broker::Captured;
+use my_bdd::proto_dyn::ProtoDyn;
+use serde_json::json;
+use std::time::Instant;
+
+fn captured(body: serde_json::Value) -> Captured {
+    Captured { topic: "Telemetry".to_string(), body, at: Instant::now(), size: 0 }
+}
+
+#[test]
+fn aggregates_over_field_values() {
+    let msgs: Vec<Captured> = [-70, -80, -72].iter().map(|r| captured(json!({"rssi": r}))).collect();
+    let v = values(&msgs, "rssi", None).unwrap();
+    assert_eq!(Aggregate::Min.apply(&v).unwrap(), -80.0);
+    assert_eq!(Aggregate::Max.apply(&v).unwrap(), -70.0);
+    assert_eq!(Aggregate::Count.apply(&v).unwrap(), 3.0);
+    let avg = "average".parse::<Aggregate>().unwrap().apply(&v).unwrap();
+    assert!(Comparison::Above.holds(avg, -75.0));
+    assert!(Aggregate::Avg.apply(&[]).is_err());
+}
+
+#[test]
+fn monotonic_violation_is_located() {
+    let v = [1.0, 2.0, 2.0, 3.0];
+    assert_eq!(Monotonic::Increasing.first_violation(&v), None);
+    assert_eq!(Monotonic::StrictlyIncreasing.first_violation(&v), Some(2));
+    assert_eq!(Monotonic::new(false, false).first_violation(&v), Some(1));
+}
+
+#[test]
+fn nested_fields_and_non_numeric_values() {
+    let msgs = vec![captured(json!({"hdr": {"seq": 4}})), captured(json!({"hdr": {"seq": "x"}}))];
+    assert!(values(&msgs[..1], "hdr.seq", None).unwrap() == vec![4.0]);
+    assert!(values(&msgs, "$.hdr.seq", None).is_err());
+}
+
+#[test]
+fn fields_left_out_hold_their_default() {
+    // proto3 decoding leaves out fields at their default, so a stream of zeros has no seq at all
+    let msgs = vec![captured(json!({})), captured(json!({"hdr": {}})), captured(json!({"hdr": {"seq": 2}}))];
+    assert_eq!(values(&msgs, "seq", Some(0.0)).unwrap(), [0.0; 3]);
+    assert_eq!(values(&msgs, "hdr.seq", Some(0.0)).unwrap(), [0.0, 2.0]);
+    assert_eq!(values(&msgs, "hdr.seq", None).unwrap(), [2.0]);
+
+    let desc = ProtoDyn::new().unwrap().message_desc("Status").unwrap();
+    let kind = |name: &str| desc.get_field_by_name(name).unwrap().kind();
+    assert_eq!(scalar_default(&kind("state")), Some(0.0));
+    assert_eq!(scalar_default(&kind("components")), None);
+}
+
+#[test]
+fn rate_and_jitter() {
+    use my_bdd::aggregate::RateStats;
+    use std::time::Duration;
+    let t0 = Instant::now();
+    let at = |ms: u64| t0 + Duration::from_millis(ms);
+    let steady: Vec<Instant> = (0..11).map(|i| at(i * 1000)).collect();
+    let stats = RateStats::from_arrivals(&steady).unwrap();
+    assert!((stats.rate_hz - 1.0).abs() < 1e-9);
+    assert!(stats.check(1.0, 10.0).is_ok());
+    assert!(stats.check(2.0, 10.0).is_err());
+
+    // right average, but one 1.5 s gap
+    let jittery = vec![at(0), at(1500), at(2000), at(3000)];
+    assert!(RateStats::from_arrivals(&jittery).unwrap().check(1.0, 10.0).is_err());
+    assert!(RateStats::from_arrivals(&[at(0)]).is_err());
+}
+
+#[test]
+fn sequence_gaps_and_duplicates() {
+    use my_bdd::aggregate::SequenceReport;
+    let clean = SequenceReport::scan(&[1, 2, 4, 3, 5]);
+    assert!(clean.is_clean());
+    assert_eq!(clean.reordered, 1);
+
+    let report = SequenceReport::scan(&[10, 11, 11, 14, 16]);
+    assert_eq!(report.missing, vec![(12, 13), (15, 15)]);
+    assert_eq!(report.duplicates, vec![11]);
+    assert_eq!(report.lost(), 3);
+    assert_eq!(report.to_string(), "3 lost [12..=13, 15], duplicated [11], 0 reordered");
+}
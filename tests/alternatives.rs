@@ -0,0 +1,21 @@
+mod common;
+
+use common::loopback_broker;
+use serde_json::json;
+
+#[test]
+fn the_first_matching_alternative_wins() {
+    let broker = loopback_broker();
+    let (ack, nack, status) = (json!({ "message": "ack" }), json!({ "message": "nack" }), json!({}));
+    let alternatives = [("PongReply", &ack), ("PongReply", &nack), ("Status", &status)];
+
+    broker.send_message("PongReply", &json!({ "message": "nack" })).unwrap();
+    broker.send_message("Status", &json!({})).unwrap();
+    let (branch, got) = broker.expect_one_of(&alternatives, 2000).unwrap();
+    assert_eq!((branch, got.body["message"].as_str()), (1, Some("nack")));
+    // the nack was consumed, so the Status is next
+    assert_eq!(broker.expect_one_of(&alternatives, 2000).unwrap().0, 2);
+
+    let err = broker.expect_one_of(&alternatives, 100).unwrap_err();
+    assert_eq!(err.to_string(), "timeout waiting for any of PongReply, Status");
+}
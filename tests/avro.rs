@@ -0,0 +1,78 @@
+use my_bdd::avro::{Avro, Schema};
+use my_bdd::codec::Codec;
+use my_bdd::config::AvroConfig;
+use my_bdd::http::{self, Response};
+use serde_json::json;
+use std::net::TcpListener;
+
+const ORDER: &str = r#"{
+  "type": "record", "name": "Order", "namespace": "shop",
+  "fields": [
+    {"name": "id", "type": "long"},
+    {"name": "customer", "type": "string"},
+    {"name": "state", "type": {"type": "enum", "name": "State", "symbols": ["NEW", "PAID", "SHIPPED"]}},
+    {"name": "lines", "type": {"type": "array", "items": {"type": "record", "name": "Line", "fields": [
+      {"name": "sku", "type": "string"}, {"name": "qty", "type": "int"}, {"name": "price", "type": "double"}]}}},
+    {"name": "tags", "type": {"type": "map", "values": "string"}, "default": {}},
+    {"name": "note", "type": ["null", "string"], "default": null},
+    {"name": "digest", "type": {"type": "fixed", "name": "Digest", "size": 2}},
+    {"name": "next", "type": ["null", "Order"]}
+  ]
+}"#;
+
+#[test]
+fn avro_binary_round_trips() {
+    let schema = Schema::parse_str(r#"{"type": "record", "name": "T", "fields": [{"name": "id", "type": "long"}, {"name": "name", "type": "string"}]}"#).unwrap();
+    assert_eq!(schema.encode(&json!({"id": 1, "name": "a"})).unwrap(), [0x02, 0x02, b'a']);
+    assert_eq!(schema.encode(&json!({"id": -65, "name": ""})).unwrap(), [0x81, 0x01, 0x00]);
+
+    let schema = Schema::parse_str(ORDER).unwrap();
+    let line = json!({"sku": "A-1", "qty": 2, "price": 9.5});
+    let order = json!({
+        "id": 42, "customer": "ann", "state": "PAID", "lines": [line], "tags": {"channel": "web"}, "note": "ring twice",
+        "digest": "AQI=", "next": {"id": 43, "customer": "bob", "state": "NEW", "lines": [], "digest": "AAA=", "next": null}
+    });
+    let decoded = schema.decode(&schema.encode(&order).unwrap()).unwrap();
+    // defaults and nullable unions fill in what the body leaves out
+    let mut expected = order.clone();
+    expected["next"]["tags"] = json!({});
+    expected["next"]["note"] = json!(null);
+    assert_eq!(decoded, expected);
+
+    let err = |body| format!("{:#}", schema.encode(&body).unwrap_err());
+    assert!(err(json!({"id": 1})).contains("customer: missing"), "{}", err(json!({"id": 1})));
+    let bad = json!({"id": 1, "customer": "c", "state": "LOST", "lines": [], "digest": "AAA=", "next": null});
+    assert!(err(bad).contains("state: expected a shop.State symbol (NEW, PAID, SHIPPED)"));
+    let bad = json!({"id": 1, "customer": "c", "state": "NEW", "lines": [{"sku": "x", "qty": 1.5, "price": 1}], "digest": "AAA=", "next": null});
+    assert!(err(bad).contains("lines[0].qty: expected an int"));
+    let bad = json!({"id": 1, "customer": "c", "state": "NEW", "lines": [], "digest": "AAA=", "next": null, "extra": 1});
+    assert!(err(bad).contains("shop.Order has no field extra"));
+    assert!(schema.decode(&[0x02]).is_err());
+    assert!(Schema::parse_str(r#"{"type": "record", "name": "T", "fields": [{"name": "x", "type": "Missing"}]}"#).is_err());
+}
+
+#[test]
+fn registry_schemas_and_wire_format() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let registry = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        http::serve(listener, |req| match req.path.as_str() {
+            "/subjects/Order-value/versions/latest" => Response::ok(json!({"subject": "Order-value", "version": 3, "id": 7, "schema": ORDER})),
+            "/schemas/ids/7" => Response::ok(json!({"schema": ORDER})),
+            "/schemas/ids/8" => Response::ok(json!({"schemaType": "PROTOBUF", "schema": "syntax = \"proto3\";"})),
+            _ => Response::error(404, "Schema not found"),
+        })
+    });
+    let codec = Avro::new(AvroConfig { registry: Some(registry), ..AvroConfig::default() });
+
+    let order = json!({"id": 1, "customer": "c", "state": "NEW", "lines": [], "tags": {}, "note": null, "digest": "AAA=", "next": null});
+    let payload = codec.encode("Order", &order).unwrap();
+    assert_eq!(payload[..5], [0, 0, 0, 0, 7]);
+    assert_eq!(codec.decode("Order", &payload).unwrap(), order);
+
+    let err = |payload: &[u8]| format!("{:#}", codec.decode("Order", payload).unwrap_err());
+    assert!(err(&[1, 0, 0, 0, 7, 0]).contains("not the Confluent wire format"));
+    assert!(err(&[0, 0, 0, 0, 8, 0]).contains("is PROTOBUF, not Avro"));
+    assert!(err(&[0, 0, 0, 0, 9, 0]).contains("404"), "{}", err(&[0, 0, 0, 0, 9, 0]));
+    assert!(format!("{:#}", codec.encode("Invoice", &json!({})).unwrap_err()).contains("404"));
+}
@@ -1,10 +1,64 @@
-use cucumber::World;
-use my_bdd::steps::MyWorld;
+use cucumber::{cli, parser, runner, writer, World, WriterExt as _};
+use my_bdd::config::Config;
+use my_bdd::debug;
+use my_bdd::events::{self, EventStream};
+use my_bdd::history::{self, History};
+use my_bdd::i18n::Translated;
+use my_bdd::logging;
+use my_bdd::report::{self, Timings, WriterBeside as _};
+use my_bdd::resume::{self, Resume, ResultStore};
+use my_bdd::seed;
+use my_bdd::session::SessionWriter;
+use my_bdd::shard::{self, Sharded};
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+use my_bdd::suite::Ordered;
+use my_bdd::triage::TriageBundle;
 
-#[tokio::test]
-async fn run_bdd() {
-    MyWorld::cucumber()
-        .with_default_cli() // This ensures proper CLI handling
+/// The harness's own options, next to cucumber's
+#[derive(clap::Args)]
+struct CustomOpts {
+    #[command(flatten)]
+    logging: logging::Cli,
+    #[command(flatten)]
+    events: events::Cli,
+    #[command(flatten)]
+    resume: resume::Cli,
+    #[command(flatten)]
+    shard: shard::Cli,
+    #[command(flatten)]
+    debug: debug::Cli,
+    #[command(flatten)]
+    seed: seed::Cli,
+    #[command(flatten)]
+    report: report::Cli,
+    #[command(flatten)]
+    history: history::Cli,
+}
+
+#[tokio::main]
+async fn main() {
+    let opts: cli::Opts<parser::basic::Cli, runner::basic::Cli, writer::basic::Cli, CustomOpts> = cli::Opts::parsed();
+    let custom = &opts.custom;
+    logging::init(custom.logging.filter(opts.writer.verbose).unwrap_or_else(|e| panic!("{:#}", e)));
+    custom.events.init().unwrap_or_else(|e| panic!("{:#}", e));
+    custom.debug.init();
+    custom.seed.init();
+    let previous = custom.resume.previous().unwrap_or_else(|e| panic!("{:#}", e));
+    let store = ResultStore::new(&custom.resume.results, previous.clone());
+    let parser = Translated::new(parser::Basic::new(), &Config::global().translations).expect("invalid [[translations]]");
+    let parser = Sharded::new(Resume::new(Ordered::new(parser), custom.resume.mode(), previous), custom.shard.shard);
+    let writer = SessionWriter::new(writer::Basic::stdout().summarized())
+        .beside(Timings::new(custom.report.clone()))
+        .beside(EventStream::new())
+        .beside(TriageBundle::new())
+        .beside(store)
+        .beside(History::new(custom.history.clone()));
+    MyWorld::cucumber::<&str>()
+        .with_parser(parser)
+        .with_writer(writer)
+        .before(before_scenario)
+        .after(after_scenario)
+        .with_cli(opts)
         .run("tests/features/ping_pong.feature")
         .await;
 }
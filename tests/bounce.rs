@@ -0,0 +1,52 @@
+use anyhow::Result;
+use my_bdd::broker::Broker;
+use my_bdd::transport::{Publisher, Subscriber};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Calls = Arc<Mutex<Vec<String>>>;
+
+/// Transport halves recording what the broker asks of them
+struct Recording(Calls);
+
+impl Publisher for Recording {
+    fn connect(&mut self, address: &str) -> Result<()> {
+        self.0.lock().unwrap().push(format!("pub connect {}", address));
+        Ok(())
+    }
+    fn send(&self, _: &str, _: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.0.lock().unwrap().push("pub close".to_string());
+    }
+}
+
+impl Subscriber for Recording {
+    fn connect(&mut self, address: &str) -> Result<()> {
+        self.0.lock().unwrap().push(format!("sub connect {}", address));
+        Ok(())
+    }
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        std::thread::sleep(timeout);
+        Ok(None)
+    }
+    fn disconnect(&mut self) {
+        self.0.lock().unwrap().push("sub disconnect".to_string());
+    }
+}
+
+#[test]
+fn bounce_reconnects_to_the_same_address() {
+    let calls = Calls::default();
+    let mut broker = Broker::with_transport(Box::new(Recording(calls.clone())), Box::new(Recording(calls.clone()))).unwrap();
+    assert!(broker.bounce(Duration::ZERO).unwrap_err().to_string().contains("nothing to reconnect to"));
+
+    broker.connect("10.0.0.5").unwrap();
+    let started = Instant::now();
+    broker.bounce(Duration::from_millis(200)).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    let calls = calls.lock().unwrap().clone();
+    let expected = ["pub connect 10.0.0.5", "sub connect 10.0.0.5", "pub close", "sub disconnect", "pub connect 10.0.0.5", "sub connect 10.0.0.5"];
+    assert_eq!(calls, expected);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::loopback_broker;
+use cucumber::{cli, gherkin, writer, World as _, WriterExt as _};
+use futures::future::{FutureExt, LocalBoxFuture};
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+use std::time::{Duration, Instant};
+
+#[test]
+fn expectations_share_the_scenario_budget() {
+    let world = MyWorld { expect_budget: Some(Duration::from_millis(300)), ..MyWorld::default() };
+    let waited = world.budgeted(Duration::from_secs(5), |timeout| {
+        assert_eq!(timeout, Duration::from_millis(300));
+        std::thread::sleep(Duration::from_millis(200));
+        Ok(())
+    });
+    assert!(waited.is_ok());
+
+    let err = world
+        .budgeted(Duration::from_secs(5), |timeout| -> anyhow::Result<()> {
+            assert!(timeout <= Duration::from_millis(100), "{:?}", timeout);
+            std::thread::sleep(timeout);
+            anyhow::bail!("timeout waiting for Status")
+        })
+        .unwrap_err();
+    assert_eq!(format!("{:#}", err), "expectation budget of 300ms used up: timeout waiting for Status");
+
+    let err = world.budgeted(Duration::from_secs(5), |_| -> anyhow::Result<()> { panic!("no time left to wait") }).unwrap_err();
+    assert_eq!(err.to_string(), "expectation budget of 300ms used up");
+}
+
+/// The loopback echoes the PingRequest but nothing ever answers it with a PongReply
+const RESENDS: &str = r#"Feature: resends within the expectation budget
+  @expect_budget:300ms
+  Scenario: no answer
+    When I send message PingRequest and expect PongReply within 200ms, retrying up to 5 times
+"#;
+
+/// before_scenario, then a loopback broker as the default connection
+fn with_loopback<'a>(
+    feature: &'a gherkin::Feature,
+    rule: Option<&'a gherkin::Rule>,
+    scenario: &'a gherkin::Scenario,
+    world: &'a mut MyWorld,
+) -> LocalBoxFuture<'a, ()> {
+    async move {
+        before_scenario(feature, rule, scenario, world).await;
+        world.broker = Some(loopback_broker());
+    }
+    .boxed_local()
+}
+
+#[tokio::test]
+async fn resends_stop_when_the_budget_is_used_up() {
+    let dir = std::env::temp_dir().join(format!("bdd-budget-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let feature = dir.join("budget.feature");
+    std::fs::write(&feature, RESENDS).unwrap();
+
+    let start = Instant::now();
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(with_loopback)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let elapsed = start.elapsed();
+    let _ = std::fs::remove_dir_all(&dir);
+    // the failed scenario's ${scenario_tmp} is kept
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    assert_eq!(writer.scenarios_stats().failed, 1);
+    // six unanswered 200ms attempts would take 1.2s
+    assert!(elapsed < Duration::from_millis(900), "{:?}", elapsed);
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use my_bdd::config::{BufferConfig, Config, EvictionPolicy};
+use my_bdd::receiver::Receiver;
+use my_bdd::transport::Subscriber;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Hands out a fixed list of messages, then nothing
+struct Replay(VecDeque<(String, Vec<u8>)>);
+
+impl Subscriber for Replay {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        let next = self.0.pop_front();
+        if next.is_none() {
+            std::thread::sleep(timeout);
+        }
+        Ok(next)
+    }
+}
+
+/// Receiver that got A0..A3 and then B0, B1 into a buffer of 3
+fn received(policy: EvictionPolicy) -> Receiver {
+    let messages = ["A", "A", "A", "A", "B", "B"].iter().enumerate().map(|(i, t)| (t.to_string(), vec![i as u8])).collect();
+    let receiver = Receiver::spawn(Box::new(Replay(messages)), BufferConfig { capacity: Some(3), policy }).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    receiver.wait_until(deadline, |inbox| (inbox.dropped().values().sum::<u64>() == 3).then_some(())).expect("all messages received");
+    receiver
+}
+
+fn payloads(receiver: &Receiver) -> Vec<u8> {
+    receiver.inbox().iter().map(|m| m.payload[0]).collect()
+}
+
+#[test]
+fn drop_oldest_keeps_the_latest_messages() {
+    let receiver = received(EvictionPolicy::DropOldest);
+    assert_eq!(payloads(&receiver), [3, 4, 5]);
+    assert_eq!(receiver.inbox().dropped().get("A"), Some(&3));
+    assert!(receiver.inbox().check_overflow().is_ok());
+}
+
+#[test]
+fn drop_newest_and_fail_keep_the_first_messages() {
+    let receiver = received(EvictionPolicy::DropNewest);
+    assert_eq!(payloads(&receiver), [0, 1, 2]);
+    assert_eq!(receiver.inbox().dropped().iter().collect::<Vec<_>>(), [(&"A".to_string(), &1), (&"B".to_string(), &2)]);
+    assert!(receiver.inbox().check_overflow().is_ok());
+
+    let receiver = received(EvictionPolicy::Fail);
+    assert_eq!(payloads(&receiver), [0, 1, 2]);
+    let err = receiver.inbox().check_overflow().unwrap_err();
+    assert_eq!(err.to_string(), "receive buffer overflowed at 3 messages; 3 dropped");
+}
+
+#[test]
+fn buffer_config() {
+    let cfg = Config::parse("[buffer]\ncapacity = 500\npolicy = \"drop-newest\"\n").unwrap();
+    assert_eq!(cfg.buffer, BufferConfig { capacity: Some(500), policy: EvictionPolicy::DropNewest });
+    assert_eq!(Config::parse("").unwrap().buffer, BufferConfig { capacity: None, policy: EvictionPolicy::DropOldest });
+    assert!(Config::parse("[buffer]\npolicy = \"drop-random\"\n").is_err());
+}
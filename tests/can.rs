@@ -0,0 +1,22 @@
+#![cfg(feature = "can")]
+
+use my_bdd::can::CanIds;
+use my_bdd::config::Config;
+
+#[test]
+fn maps_topics_to_ids_both_ways() {
+    let cfg = Config::parse("[can]\ninterface = \"vcan0\"\nids = { PingRequest = 0x100, Telemetry = 0x18FF0001 }\n").unwrap();
+    let ids = CanIds::new(&cfg.can.ids).unwrap();
+    assert_eq!(ids.id("Telemetry").unwrap(), 0x18FF0001);
+    assert_eq!(ids.topic(0x100), "PingRequest");
+    assert_eq!(ids.topic(0x7), "0x7");
+    assert!(ids.id("Unknown").is_err());
+}
+
+#[test]
+fn rejects_duplicate_and_oversized_ids() {
+    let dup = Config::parse("[can.ids]\nA = 0x100\nB = 0x100\n").unwrap();
+    assert!(CanIds::new(&dup.can.ids).is_err());
+    let big = Config::parse("[can.ids]\nA = 0x20000000\n").unwrap();
+    assert!(CanIds::new(&big.can.ids).is_err());
+}
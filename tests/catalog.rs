@@ -0,0 +1,46 @@
+use my_bdd::catalog::{autocomplete, capture_groups, example, markdown, steps};
+
+#[test]
+fn lists_registered_steps() {
+    let all = steps();
+    let expect = all.iter().find(|s| s.pattern == r"^I expect message ([\w.]+)$").expect("expect step registered");
+    assert_eq!(expect.keyword, "Then");
+    assert_eq!(expect.parameters, ["{message}"]);
+    assert!(expect.location.starts_with("src/steps.rs:"));
+    assert!(markdown(&all).contains("| Then I expect message <message> |"));
+}
+
+#[test]
+fn capture_groups_skip_non_capturing_ones() {
+    assert_eq!(
+        capture_groups(r"^field (\S+) of the last (\w+)(?: message)? equals (.+)$"),
+        [r"\S+", r"\w+", ".+"]
+    );
+    assert_eq!(capture_groups(r#"^I run command "(.+)" locally(?: within (.+))?$"#), [".+", ".+"]);
+}
+
+#[test]
+fn expression_parameters_keep_their_type_names() {
+    let mark = steps().into_iter().find(|s| s.example == r#"I mark time as "<text>""#).expect("mark time step");
+    assert_eq!(mark.parameters, ["{string}"]);
+    let collect = steps().into_iter().find(|s| s.example.starts_with("I collect")).expect("collect step");
+    assert_eq!(collect.parameters, ["{message}", "{duration}"]);
+    assert_eq!(collect.example, "I collect <message> messages for <duration>");
+}
+
+#[test]
+fn examples_replace_groups_with_placeholders() {
+    assert_eq!(example(r"^the last (\w+) message is (smaller|larger) than (\d+) bytes$"), "the last <name> message is smaller than <number> bytes");
+    assert_eq!(example(r"^I ignore fields? (.+)$"), "I ignore fields <...>");
+    assert_eq!(example(r"^the collected (\w+) messages have (strictly )?(increasing|decreasing) field (\S+)$"), "the collected <name> messages have increasing field <value>");
+    assert_eq!(example(r"^message (\w+) is published at (\d+(?:\.\d+)?) ?Hz (?:±|\+/-) ?(\d+) ?% over (.+)$"), "message <name> is published at <number> Hz ± <number> % over <...>");
+}
+
+#[test]
+fn autocomplete_file_has_one_regex_literal_per_step() {
+    let all = steps();
+    let text = autocomplete(&all);
+    assert_eq!(text.lines().count(), all.len() + 1);
+    assert!(text.contains(r"Then(/^I expect message ([\w.]+)$/);"));
+    assert!(text.contains(r"(?:±|\+\/-)"));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use anyhow::Result;
+use common::loopback_broker;
+use my_bdd::codec::{self, Cbor, Codec, MessagePack};
+use serde_json::{json, Value as JsonValue};
+
+#[test]
+fn cbor_and_msgpack_round_trip() {
+    let body = json!({"a": 1, "b": [-1, -300, 70000, 1.5, true, null], "c": {"d": "text"}, "e": 18446744073709551615u64});
+    for codec in [&Cbor as &dyn Codec, &MessagePack] {
+        let payload = codec.encode("T", &body).unwrap();
+        assert_eq!(codec.decode("T", &payload).unwrap(), body);
+        assert!(codec.decode("T", &payload[..payload.len() - 1]).is_err());
+    }
+    assert_eq!(Cbor.encode("T", &json!({"a": 1})).unwrap(), [0xa1, 0x61, 0x61, 0x01]);
+    assert_eq!(MessagePack.encode("T", &json!({"a": 1})).unwrap(), [0x81, 0xa1, 0x61, 0x01]);
+    // byte strings come out as base64
+    assert_eq!(Cbor.decode("T", &[0x42, 0x01, 0x02]).unwrap(), json!("AQI="));
+    assert_eq!(MessagePack.decode("T", &[0xc4, 0x02, 0x01, 0x02]).unwrap(), json!("AQI="));
+}
+
+/// Payloads are the body's string form
+struct Plain;
+
+impl Codec for Plain {
+    fn encode(&self, _topic: &str, body: &JsonValue) -> Result<Vec<u8>> {
+        Ok(body.as_str().unwrap_or_default().as_bytes().to_vec())
+    }
+    fn decode(&self, _topic: &str, payload: &[u8]) -> Result<JsonValue> {
+        Ok(json!(String::from_utf8(payload.to_vec())?))
+    }
+}
+
+#[test]
+fn topics_use_their_codec() {
+    let mut broker = loopback_broker();
+    broker.set_codec("PongReply", "cbor").unwrap();
+    broker.set_codec("Telemetry", "msgpack").unwrap();
+    let err = broker.set_codec("Status", "thrift").unwrap_err().to_string();
+    assert!(err.contains("unknown codec thrift") && err.contains("avro, cbor, json, layout, msgpack, protobuf"), "{}", err);
+
+    broker.send_message("PongReply", &json!({"message": "hi"})).unwrap();
+    broker.send_message("Telemetry", &json!({"speed": 12.5, "tags": ["a"]})).unwrap();
+    broker.send_message("Status", &json!({"state": "READY"})).unwrap();
+    assert_eq!(broker.expect_message("Telemetry", &json!({"speed": 12.5}), 1000).unwrap().body, json!({"speed": 12.5, "tags": ["a"]}));
+    assert_eq!(broker.expect_message("PongReply", &json!({"message": "hi"}), 1000).unwrap().body, json!({"message": "hi"}));
+    broker.expect_message("Status", &json!({"state": "READY"}), 1000).unwrap();
+    assert!(broker.validate_last("Telemetry").is_err());
+
+    codec::register("plain", Plain).unwrap();
+    assert!(codec::register("protobuf", Plain).is_err());
+    broker.set_codec("Log", "plain").unwrap();
+    broker.send_message("Log", &json!("started")).unwrap();
+    broker.expect_message("Log", &json!("started"), 1000).unwrap();
+}
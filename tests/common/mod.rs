@@ -0,0 +1,73 @@
+//! The in-memory transport the broker tests share. Each test binary uses a part of it.
+#![allow(dead_code)]
+
+use anyhow::Result;
+use my_bdd::broker::Broker;
+use my_bdd::transport::{Publisher, Subscriber};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Everything sent comes straight back
+pub struct Loopback(pub Sender<(String, Vec<u8>)>);
+pub struct Inbound(pub Receiver<(String, Vec<u8>)>);
+
+impl Publisher for Loopback {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        Ok(self.0.send((topic.to_string(), payload.to_vec()))?)
+    }
+}
+
+impl Subscriber for Inbound {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        Ok(self.0.recv_timeout(timeout).ok())
+    }
+}
+
+/// Sends go nowhere and nothing arrives
+pub struct Silent;
+
+impl Publisher for Silent {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, _topic: &str, _payload: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Subscriber for Silent {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn recv(&mut self, timeout: Duration) -> Result<Option<(String, Vec<u8>)>> {
+        std::thread::sleep(timeout);
+        Ok(None)
+    }
+}
+
+/// A broker that receives everything it sends
+pub fn loopback_broker() -> Broker {
+    let (tx, rx) = channel();
+    Broker::with_transport(Box::new(Loopback(tx)), Box::new(Inbound(rx))).unwrap()
+}
+
+/// Whether protoc (`$PROTOC`, else from PATH) runs
+pub fn protoc_found() -> bool {
+    let protoc = std::env::var_os("PROTOC").unwrap_or_else(|| "protoc".into());
+    std::process::Command::new(&protoc).arg("--version").output().is_ok_and(|out| out.status.success())
+}
+
+/// [`protoc_found`], saying the test is skipped when it isn't
+pub fn protoc_available(test: &str) -> bool {
+    let found = protoc_found();
+    if !found {
+        eprintln!("skipping {}: protoc not found; set PROTOC or put it on PATH", test);
+    }
+    found
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use my_bdd::proto_dyn::ProtoDyn;
+
+#[test]
+fn compiles_proto_sources_at_runtime() {
+    if !common::protoc_available("compiles_proto_sources_at_runtime") {
+        return;
+    }
+    let proto = ProtoDyn::compile_protos(&["proto/Status.proto"], &["proto"]).unwrap();
+    assert_eq!(proto.message_names(), ["company.project.v1.Component", "company.project.v1.Status"]);
+    assert_eq!(proto.enum_field("Status", "$.state").unwrap().name(), "State");
+    // the file's directory is the import path by default
+    let proto = ProtoDyn::compile_protos(&["proto/PingPong.proto"], &[] as &[&str]).unwrap();
+    assert!(proto.message_desc("PongReply").is_ok());
+}
+
+#[test]
+fn reports_compiler_errors() {
+    let err = ProtoDyn::compile_protos(&["proto/Missing.proto"], &["proto"]).err().expect("missing file fails");
+    // without protoc, compiling fails before it reads any file
+    let expected = if common::protoc_found() { "failed" } else { "compiling .proto files needs protoc" };
+    assert!(format!("{:#}", err).contains(expected), "{:#}", err);
+}
+
+#[test]
+fn detects_stale_descriptor_sets() {
+    use my_bdd::proto_dyn::{descriptor_hash, stale_reason};
+    assert_eq!(descriptor_hash(b""), "fnv1a64:cbf29ce484222325");
+    let hash = descriptor_hash(b"set");
+    assert_eq!(stale_reason(b"set", &hash, Some(&hash)), None);
+    assert_eq!(stale_reason(b"set", "", None), None);
+    let reason = stale_reason(b"old", &hash, Some("fnv1a64:0")).unwrap();
+    assert!(reason.contains(&format!("the proto sources compiled to {}", hash)), "{}", reason);
+    assert!(reason.contains("expected_hash is fnv1a64:0"), "{}", reason);
+}
@@ -0,0 +1,42 @@
+use cucumber::{cli, writer, World as _, WriterExt as _};
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+
+const FEATURE: &str = r#"Feature: SUT variants
+  Scenario: only the branch for this variant runs
+    When I run command "echo v2" locally
+    And I store the command stdout as "variant"
+    When "${variant}" is "v1" then I run command "exit 3" locally
+    Then the command exits with code 0
+    When "${variant}" is not "v1" then I run command "exit 4" locally
+    Then the command exits with code 4
+
+  Scenario: a failing branch fails the scenario
+    When "x" is "x" then I run command "exit 5" locally
+    Then if "x" is "x" then the command exits with code 0
+"#;
+
+#[tokio::test]
+async fn conditional_steps_run_only_when_the_condition_holds() {
+    let dir = std::env::temp_dir().join(format!("bdd-conditional-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let feature = dir.join("variants.feature");
+    std::fs::write(&feature, FEATURE).unwrap();
+
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(before_scenario)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    // the failed scenario's ${scenario_tmp} is kept
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    let stats = writer.scenarios_stats();
+    assert_eq!((stats.passed, stats.failed), (1, 1));
+}
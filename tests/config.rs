@@ -0,0 +1,63 @@
+use my_bdd::config::Config;
+use std::time::Duration;
+
+#[test]
+fn empty_config_has_defaults() {
+    let cfg = Config::parse("").unwrap();
+    assert_eq!(cfg.scenario.timeout, None);
+}
+
+#[test]
+fn scenario_timeout_parses_humantime() {
+    let cfg = Config::parse("[scenario]\ntimeout = \"1m 30s\"\n").unwrap();
+    assert_eq!(cfg.scenario.timeout, Some(Duration::from_secs(90)));
+}
+
+#[test]
+fn expect_budget_parses() {
+    let cfg = Config::parse("[scenario]\nexpect_budget = \"20s\"\n").unwrap();
+    assert_eq!(cfg.scenario.expect_budget, Some(Duration::from_secs(20)));
+}
+
+#[test]
+fn invalid_duration_is_rejected() {
+    assert!(Config::parse("[scenario]\ntimeout = \"soon\"\n").is_err());
+}
+
+#[test]
+fn matching_ignore_fields() {
+    let cfg = Config::parse("[matching]\nignore_fields = [\"timestamp\", \"header.seq_no\"]\n").unwrap();
+    assert_eq!(cfg.matching.ignore_fields, vec!["timestamp", "header.seq_no"]);
+}
+
+#[test]
+fn step_style_durations() {
+    use my_bdd::config::parse_duration;
+    assert_eq!(parse_duration("5 s").unwrap(), Duration::from_secs(5));
+    assert_eq!(parse_duration("2.5s").unwrap(), Duration::from_millis(2500));
+    assert_eq!(parse_duration("1m 30s").unwrap(), Duration::from_secs(90));
+    assert!(parse_duration("5 parsecs").is_err());
+}
+
+#[test]
+fn host_profiles() {
+    let cfg = Config::parse("[hosts.dut]\naddress = \"10.0.0.5\"\nuser = \"root\"\nport = 2222\n").unwrap();
+    let dut = &cfg.hosts["dut"];
+    assert_eq!((dut.address.as_str(), dut.user.as_deref(), dut.port), ("10.0.0.5", Some("root"), Some(2222)));
+    assert!(Config::parse("[hosts.dut]\nuser = \"root\"\n").is_err());
+}
+
+#[test]
+fn runtime_proto_sources() {
+    let cfg = Config::parse("[proto]\ndescriptor = \"api/descriptor.bin\"\nfiles = [\"api/a.proto\"]\n").unwrap();
+    assert_eq!(cfg.proto.descriptor.as_deref(), Some(std::path::Path::new("api/descriptor.bin")));
+    assert_eq!(cfg.proto.files, [std::path::PathBuf::from("api/a.proto")]);
+    assert!(cfg.proto.includes.is_empty());
+}
+
+#[test]
+fn named_schemas() {
+    let cfg = Config::parse("[schemas.bus_v1]\ndescriptor = \"schemas/bus_v1.bin\"\n\n[schemas.cloud_v2]\nfiles = [\"api/v2/cloud.proto\"]\n").unwrap();
+    assert_eq!(cfg.schemas["bus_v1"].descriptor.as_deref(), Some(std::path::Path::new("schemas/bus_v1.bin")));
+    assert_eq!(cfg.schemas["cloud_v2"].files, [std::path::PathBuf::from("api/v2/cloud.proto")]);
+}
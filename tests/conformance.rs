@@ -0,0 +1,54 @@
+mod common;
+
+use cucumber::gherkin::{Feature, GherkinEnv};
+use my_bdd::conformance;
+use my_bdd::proto_dyn::ProtoDyn;
+use serde_json::json;
+
+const TREE: &str = r#"syntax = "proto3";
+package tree.v1;
+
+message Node {
+  string label = 1;
+  Node parent = 2;
+  repeated Node children = 3;
+  map<string, int32> weights = 4;
+  oneof payload {
+    bytes blob = 5;
+    uint64 count = 6;
+  }
+}
+"#;
+
+#[test]
+fn samples_fill_what_the_send_steps_take() {
+    if !common::protoc_available("samples_fill_what_the_send_steps_take") {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("bdd-conformance-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("tree.proto"), TREE).unwrap();
+    let proto = ProtoDyn::compile_protos(&[dir.join("tree.proto")], &[&dir]).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let sample = conformance::sample(&proto.message_desc("Node").unwrap());
+    assert_eq!(sample, json!({"label": "label", "blob": "AQI="}));
+    proto.build_from_json("Node", &sample).unwrap();
+    // map entries aren't scenarios
+    let types = conformance::message_types(&proto, &[]).unwrap();
+    assert_eq!(types.iter().map(|m| m.full_name()).collect::<Vec<_>>(), ["tree.v1.Node"]);
+}
+
+#[test]
+fn feature_has_a_scenario_per_message() {
+    let proto = ProtoDyn::new().unwrap();
+    let types = conformance::message_types(&proto, &[]).unwrap();
+    let text = conformance::feature(&proto, &types, &["PongReply".to_string(), "Status".to_string()]);
+    let feature = Feature::parse(&text, GherkinEnv::default()).unwrap();
+    assert_eq!(feature.scenarios.len(), types.len());
+    let status = feature.scenarios.iter().find(|s| s.name == "company.project.v1.Status is answered").unwrap();
+    assert_eq!(status.steps[0].value, "I send message Status");
+    let body: serde_json::Value = serde_json::from_str(status.steps[0].docstring.as_deref().unwrap()).unwrap();
+    assert_eq!(body, json!({"state": "READY"}));
+    assert_eq!(status.steps[1].value, "I expect one of: PongReply | Status");
+}
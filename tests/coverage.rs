@@ -0,0 +1,38 @@
+mod common;
+
+use common::loopback_broker;
+use my_bdd::conformance;
+use my_bdd::coverage;
+use my_bdd::proto_dyn::ProtoDyn;
+use serde_json::json;
+
+#[test]
+fn reports_types_and_fields_never_touched() {
+    let broker = loopback_broker();
+    broker.send_message("Status", &json!({"state": "READY"})).unwrap();
+    broker.expect_message("Status", &json!({"state": "READY"}), 1000).unwrap();
+    // named in an expectation counts, matched or not
+    assert!(broker.expect_message("Status", &json!({"components": [{"name": "db"}]}), 10).is_err());
+    broker.send_message("PingRequest", &json!({})).unwrap();
+
+    let proto = ProtoDyn::new().unwrap();
+    let types = conformance::message_types(&proto, &[]).unwrap();
+    let run = coverage::take();
+    assert_eq!(run.summary(&types), "2 of 4 message types sent, 2 asserted; 3 of 5 fields asserted");
+    assert_eq!(
+        run.gaps(&types),
+        [
+            "company.project.v1.Component never sent",
+            "company.project.v1.Component.state never asserted",
+            "company.project.v1.PingRequest never asserted",
+            "company.project.v1.PongReply never sent",
+            "company.project.v1.PongReply never asserted",
+        ]
+    );
+    // the components expectation never matched
+    assert_eq!(
+        run.matched_fields(&types),
+        ["company.project.v1.Component: no field matched", "company.project.v1.Status: matched state"]
+    );
+    assert!(coverage::take().is_empty());
+}
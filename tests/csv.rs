@@ -0,0 +1,37 @@
+mod common;
+
+use common::Silent;
+use my_bdd::broker::Broker;
+use my_bdd::csv::{self, Table};
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn csv_follows_rfc_4180_quoting() {
+    let table = Table::parse("t,state,note\r\n0.0,READY,\"fan, \"\"left\"\"\"\n\n0.1,2,\"two\nlines\"\n0.2,,x").unwrap();
+    assert_eq!(table.header, ["t", "state", "note"]);
+    assert_eq!(table.rows, [vec!["0.0", "READY", "fan, \"left\""], vec!["0.1", "2", "two\nlines"], vec!["0.2", "", "x"]]);
+    assert!(Table::parse("a,b\n1\n").unwrap_err().to_string().contains("row 2 has 1 fields"));
+    assert!(Table::parse("a\n\"open\n").is_err());
+}
+
+#[test]
+fn rows_become_messages() {
+    let broker = Broker::with_transport(Box::new(Silent), Box::new(Silent)).unwrap();
+    let table = Table::parse("t,state,note\n0.0,READY,fan\n0.1,2,\n0.2,,x\n").unwrap();
+    let mapping = [("state".to_string(), "state".to_string())];
+    let messages = csv::messages(&broker, "Status", &table, &mapping).unwrap();
+    assert_eq!(messages, [json!({"state": "READY"}), json!({"state": 2}), json!({})]);
+    let mapping = [("note".to_string(), "message".to_string())];
+    assert_eq!(csv::messages(&broker, "PongReply", &table, &mapping).unwrap()[0], json!({"message": "fan"}));
+
+    let err = csv::messages(&broker, "Status", &table, &[("speed".to_string(), "state".to_string())]).unwrap_err().to_string();
+    assert!(err.contains("no column speed (columns: t, state, note)"), "{}", err);
+    assert!(csv::messages(&broker, "Status", &table, &[("note".to_string(), "components".to_string())]).is_err());
+
+    let sequence = csv::sequence("Status", messages, Some(20.0)).unwrap();
+    assert_eq!(sequence.duration(), Duration::from_millis(100));
+    assert_eq!(csv::sequence("Status", Vec::new(), None).unwrap().duration(), Duration::ZERO);
+    assert!(csv::sequence("Status", Vec::new(), Some(0.0)).is_err());
+    sequence.play(&broker).unwrap();
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "db")]
+
+use my_bdd::db::{unmatched_row, Database};
+use my_bdd::matchers::MatchOptions;
+use serde_json::json;
+
+#[test]
+fn parses_database_urls() {
+    assert_eq!(Database::parse("sqlite://target/test.db").unwrap(), Database::Sqlite("target/test.db".to_string()));
+    assert!(matches!(Database::parse("postgres://u@db/app").unwrap(), Database::Postgres(_)));
+    assert!(Database::parse("mysql://db").is_err());
+}
+
+#[test]
+fn rows_match_in_any_order_and_once_each() {
+    let rows = vec![json!({"id": 1, "status": "OK"}), json!({"id": 2, "status": "FAIL"})];
+    let opts = MatchOptions::default();
+    assert!(unmatched_row(&[json!({"status": "FAIL"}), json!({"id": 1})], &rows, opts).unwrap().is_none());
+    let twice = [json!({"status": "OK"}), json!({"status": "OK"})];
+    assert_eq!(unmatched_row(&twice, &rows, opts).unwrap(), Some(&json!({"status": "OK"})));
+}
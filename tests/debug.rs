@@ -0,0 +1,20 @@
+use my_bdd::debug::Action;
+use my_bdd::repl::Command;
+
+#[test]
+fn parses_pause_commands() {
+    assert_eq!(Action::parse("use sensor").unwrap(), Action::Use(Some("sensor".to_string())));
+    assert_eq!(Action::parse("use").unwrap(), Action::Use(None));
+    assert_eq!(Action::parse("vars").unwrap(), Action::Vars);
+    assert_eq!(Action::parse("step I send message PingRequest").unwrap(), Action::Step("I send message PingRequest".to_string()));
+    assert_eq!(Action::parse("continue").unwrap(), Action::Continue);
+    assert_eq!(Action::parse("quit").unwrap(), Action::Abort);
+    assert!(Action::parse("step").is_err());
+}
+
+#[test]
+fn falls_back_to_repl_commands() {
+    assert_eq!(Action::parse("dump last 3").unwrap(), Action::Repl(Command::Dump(3)));
+    assert_eq!(Action::parse("send Ping").unwrap(), Action::Repl(Command::Send { name: "Ping".to_string(), body: serde_json::json!({}) }));
+    assert!(Action::parse("frobnicate").is_err());
+}
@@ -0,0 +1,18 @@
+use my_bdd::config::Config;
+use my_bdd::device::Device;
+use serde_json::json;
+
+#[test]
+fn devices_fill_their_fields_and_topics() {
+    let config = Config::parse("[devices.pump1]\ntopic_prefix = \"tlm/pump1/\"\nfields = { device_id = \"pump1\", site = \"A\" }\n").unwrap();
+    let device = Device::new("pump1", &config.devices["pump1"]);
+    assert_eq!(device.topic("Telemetry"), "tlm/pump1/Telemetry");
+    assert_eq!(Device::default().topic("Telemetry"), "Telemetry");
+
+    // only fields the message has, and never over what the step gives
+    let filled = device.fill(&json!({"speed": 3, "site": "B"}), |_| true).unwrap();
+    assert_eq!(filled, json!({"speed": 3, "site": "B", "device_id": "pump1"}));
+    let filled = device.fill(&json!({}), |f| f == "site").unwrap();
+    assert_eq!(filled, json!({"site": "A"}));
+    assert!(device.fill(&json!([1]), |_| true).is_err());
+}
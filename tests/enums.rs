@@ -0,0 +1,25 @@
+use my_bdd::proto_dyn::ProtoDyn;
+
+#[test]
+fn resolves_enum_fields_through_the_descriptor() {
+    let proto = ProtoDyn::new().unwrap();
+    let state = proto.enum_field("Status", "$.state").unwrap();
+    assert_eq!(state.name(), "State");
+    assert_eq!(state.get_value_by_name("IDLE").unwrap().number(), 2);
+    assert_eq!(proto.enum_field("Status", "$.components[0].state").unwrap().name(), "State");
+}
+
+#[test]
+fn rejects_paths_that_are_not_single_enum_fields() {
+    let proto = ProtoDyn::new().unwrap();
+    for (path, reason) in [
+        ("$.nope", "has no field nope"),
+        ("$.components", "repeated field"),
+        ("$.components.state", "field of a list"),
+        ("$.state[0]", "not repeated"),
+        ("$.components[0].name", "not an enum field"),
+    ] {
+        let err = proto.enum_field("Status", path).unwrap_err().to_string();
+        assert!(err.contains(reason), "{}: {}", path, err);
+    }
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::loopback_broker;
+use my_bdd::events;
+use serde_json::json;
+
+#[test]
+fn streams_bus_events_as_json_lines() {
+    let path = std::env::temp_dir().join(format!("bdd-events-{}.ndjson", std::process::id()));
+    events::open(&path).unwrap();
+    assert!(events::enabled());
+
+    let broker = loopback_broker();
+    broker.send_message("Status", &json!({ "state": "READY" })).unwrap();
+    broker.expect_message("Status", &json!({ "state": "READY" }), 2000).unwrap();
+    assert!(broker.expect_message("Status", &json!({}), 50).is_err());
+    events::flush();
+
+    let lines: Vec<serde_json::Value> =
+        std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    let kinds: Vec<&str> = lines.iter().map(|l| l["event"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["message_sent", "message_received", "match_attempted", "match_attempted"]);
+    assert_eq!(lines[0]["body"], json!({ "state": "READY" }));
+    assert_eq!(lines[1]["body"], json!({ "state": 1 }));
+    assert_eq!(lines[2]["outcome"], "matched");
+    assert_eq!(lines[3]["outcome"], "timeout");
+    assert!(lines.iter().all(|l| humantime::parse_rfc3339(l["at"].as_str().unwrap()).is_ok()));
+}
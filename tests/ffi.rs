@@ -0,0 +1,39 @@
+use my_bdd::ffi::*;
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+fn last_error() -> String {
+    let e = bdd_last_error();
+    assert!(!e.is_null());
+    unsafe { CStr::from_ptr(e) }.to_string_lossy().to_string()
+}
+
+#[test]
+fn send_and_expect_through_c_api() {
+    let ip = CString::new("127.0.0.1").unwrap();
+    let name = CString::new("PingRequest").unwrap();
+    let reply = CString::new("PongReply").unwrap();
+    unsafe {
+        let broker = bdd_broker_new(ip.as_ptr());
+        assert!(!broker.is_null());
+        assert_eq!(bdd_broker_send(broker, name.as_ptr(), ptr::null()), 0);
+        let mut out = ptr::null_mut();
+        assert_eq!(bdd_broker_expect(broker, reply.as_ptr(), ptr::null(), 50, &mut out), -1);
+        assert!(out.is_null());
+        assert!(last_error().contains("timeout"));
+        bdd_broker_free(broker);
+    }
+}
+
+#[test]
+fn bad_arguments_fail_without_crashing() {
+    let json = CString::new("{not json").unwrap();
+    let name = CString::new("PingRequest").unwrap();
+    unsafe {
+        assert!(bdd_broker_new(ptr::null()).is_null());
+        assert!(last_error().contains("ip is NULL"));
+        assert_eq!(bdd_broker_send(ptr::null_mut(), name.as_ptr(), json.as_ptr()), -1);
+        bdd_broker_free(ptr::null_mut());
+        bdd_string_free(ptr::null_mut());
+    }
+}
@@ -0,0 +1,40 @@
+use my_bdd::fixtures::Fixtures;
+use serde_json::json;
+
+const SITE_A: &str = r#"
+[vars]
+site = "A"
+
+[devices.pump1]
+topic_prefix = "tlm/pump1/"
+fields = { device_id = "pump1" }
+
+[macros]
+valid_header = { version = 1 }
+"#;
+
+#[test]
+fn fixtures_hold_vars_devices_and_macros() {
+    let fixtures = Fixtures::parse_toml(SITE_A).unwrap();
+    assert_eq!(fixtures.vars["site"], "A");
+    assert_eq!(fixtures.devices["pump1"].topic_prefix.as_deref(), Some("tlm/pump1/"));
+    assert_eq!(fixtures.macros["valid_header"], json!({"version": 1}));
+    assert!(Fixtures::parse_toml("[responders]\n").is_err());
+}
+
+#[test]
+fn fixture_files_are_toml_json_or_yaml() {
+    let dir = std::env::temp_dir().join(format!("bdd-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json = dir.join("site_b.json");
+    std::fs::write(&json, r#"{"vars": {"site": "B"}}"#).unwrap();
+    let yaml = dir.join("site_c.yaml");
+    std::fs::write(&yaml, "vars:\n  site: C\ndevices:\n  pump2:\n    topic_prefix: tlm/pump2/\n").unwrap();
+
+    assert_eq!(Fixtures::load(&json).unwrap().vars["site"], "B");
+    let loaded = Fixtures::load(&yaml);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.vars["site"], "C");
+    assert_eq!(loaded.devices["pump2"].topic_prefix.as_deref(), Some("tlm/pump2/"));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use anyhow::Result;
+use common::Inbound;
+use my_bdd::broker::Broker;
+use my_bdd::config::{ByteOrder, Crc, FramingConfig};
+use my_bdd::transport::Publisher;
+use serde_json::json;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+const CRC32: FramingConfig = FramingConfig { crc: Crc::Crc32, byte_order: ByteOrder::Little };
+
+#[test]
+fn crc_algorithms_match_their_check_values() {
+    let check = b"123456789";
+    assert_eq!(Crc::Crc32.checksum(check), 0xCBF4_3926);
+    assert_eq!(Crc::Crc32c.checksum(check), 0xE306_9283);
+    assert_eq!(Crc::Crc16Ccitt.checksum(check), 0x29B1);
+    assert_eq!(Crc::Crc16Modbus.checksum(check), 0x4B37);
+    assert_eq!(Crc::Crc8.checksum(check), 0xF4);
+
+    assert_eq!(CRC32.append(check)[9..], [0x26, 0x39, 0xf4, 0xcb]);
+    let modbus = FramingConfig { crc: Crc::Crc16Modbus, byte_order: ByteOrder::Big };
+    assert_eq!(modbus.append(check)[9..], [0x4b, 0x37]);
+    assert_eq!(modbus.strip(&modbus.append(check)).unwrap(), check);
+    let err = modbus.strip(&modbus.corrupt(check)).unwrap_err().to_string();
+    assert_eq!(err, "Crc16Modbus mismatch: frame carries 4bc8, payload gives 4b37");
+    assert!(CRC32.strip(&[1, 2]).is_err());
+}
+
+/// SUT answering PingRequests whose CRC checks out with a framed PongReply
+struct CheckingEcho(Sender<(String, Vec<u8>)>);
+
+impl Publisher for CheckingEcho {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        // an unframed Status for the receive side to discard
+        self.0.send(("Status".to_string(), vec![0x08, 0x01]))?;
+        if topic == "PingRequest" && CRC32.strip(payload).is_ok() {
+            self.0.send(("PongReply".to_string(), CRC32.append(&[])))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn frames_carry_a_crc_both_ways() {
+    let (tx, rx) = channel();
+    let mut broker = Broker::with_transport(Box::new(CheckingEcho(tx)), Box::new(Inbound(rx))).unwrap();
+    assert!(broker.send_corrupted("PingRequest", &json!({})).unwrap_err().to_string().contains("carry no CRC"));
+    broker.set_framing("PingRequest", Some(CRC32));
+    broker.set_framing("PongReply", Some(CRC32));
+    broker.set_framing("Status", Some(CRC32));
+
+    broker.send_message("PingRequest", &json!({})).unwrap();
+    broker.expect_message("PongReply", &json!({}), 1000).unwrap();
+
+    let since = Instant::now();
+    broker.send_corrupted("PingRequest", &json!({})).unwrap();
+    broker.expect_silence("PongReply", since, Duration::from_millis(200)).unwrap();
+    broker.send_message("PingRequest", &json!({})).unwrap();
+    let err = broker.expect_silence("PongReply", since, Duration::from_millis(500)).unwrap_err().to_string();
+    assert!(err.starts_with("PongReply arrived"), "{}", err);
+
+    assert_eq!(broker.crc_errors().get("Status"), Some(&3));
+    assert!(broker.captured("Status").unwrap().is_empty());
+}
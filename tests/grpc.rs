@@ -0,0 +1,299 @@
+use cucumber::{cli, writer, World as _, WriterExt as _};
+use my_bdd::config::TlsProfile;
+use my_bdd::grpc::{self, Frame, GrpcClient, Status};
+use my_bdd::hpack;
+use my_bdd::matchers::MatchOptions;
+use my_bdd::proto_dyn::ProtoDyn;
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto, ServiceDescriptorProto};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// test.v1.Telemetry: Subscribe streams `count` Readings, Echo sends each Reading back, Fail ends
+/// with NOT_FOUND and Missing isn't served
+fn descriptors() -> FileDescriptorSet {
+    let field = |name: &str, number: i32, kind: Type| FieldDescriptorProto {
+        name: Some(name.into()),
+        number: Some(number),
+        label: Some(Label::Optional as i32),
+        r#type: Some(kind as i32),
+        json_name: Some(name.into()),
+        ..Default::default()
+    };
+    let message = |name: &str, fields: Vec<FieldDescriptorProto>| DescriptorProto { name: Some(name.into()), field: fields, ..Default::default() };
+    let method = |name: &str, input: &str, output: &str, client: bool, server: bool| MethodDescriptorProto {
+        name: Some(name.into()),
+        input_type: Some(format!(".test.v1.{}", input)),
+        output_type: Some(format!(".test.v1.{}", output)),
+        client_streaming: Some(client),
+        server_streaming: Some(server),
+        ..Default::default()
+    };
+    FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("telemetry.proto".into()),
+            package: Some("test.v1".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![
+                message("SubscribeRequest", vec![field("count", 1, Type::Int32)]),
+                message("Reading", vec![field("seq", 1, Type::Int32), field("note", 2, Type::String)]),
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Telemetry".into()),
+                method: vec![
+                    method("Subscribe", "SubscribeRequest", "Reading", false, true),
+                    method("Echo", "Reading", "Reading", true, true),
+                    method("Fail", "SubscribeRequest", "Reading", false, false),
+                    method("Missing", "SubscribeRequest", "Reading", false, false),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+fn proto() -> ProtoDyn {
+    ProtoDyn::from_descriptor_set(&descriptors().encode_to_vec()).unwrap()
+}
+
+fn headers(conn: &mut TcpStream, id: u32, end: bool, headers: &[(&str, &str)]) {
+    let flags = grpc::END_HEADERS | if end { grpc::END_STREAM } else { 0 };
+    Frame::new(grpc::HEADERS, flags, id, hpack::encode(headers)).write(conn).unwrap();
+}
+
+fn reply(conn: &mut TcpStream, id: u32, msg: &[u8]) {
+    Frame::new(grpc::DATA, 0, id, grpc::frame_message(msg)).write(conn).unwrap();
+}
+
+/// An h2c gRPC server for [`descriptors`], giving each stream a send window of only 8 bytes
+fn serve(listener: TcpListener) {
+    let proto = proto();
+    for conn in listener.incoming() {
+        let Ok(mut conn) = conn else { return };
+        let proto = proto.clone();
+        std::thread::spawn(move || {
+            let mut preface = [0u8; 24];
+            conn.read_exact(&mut preface).unwrap();
+            assert_eq!(preface, grpc::PREFACE);
+            Frame::settings(&[(grpc::INITIAL_WINDOW_SIZE, 8)]).write(&mut conn).unwrap();
+            let mut decoder = hpack::Decoder::default();
+            let mut calls: HashMap<u32, (String, Vec<u8>)> = HashMap::new();
+            let ok = [(":status", "200"), ("content-type", "application/grpc")];
+            while let Ok(Some(frame)) = Frame::read(&mut conn) {
+                match frame.kind {
+                    grpc::SETTINGS if frame.flags & grpc::ACK == 0 => Frame::new(grpc::SETTINGS, grpc::ACK, 0, vec![]).write(&mut conn).unwrap(),
+                    grpc::HEADERS => {
+                        let headers = decoder.decode(frame.body().unwrap()).unwrap();
+                        let path = headers.iter().find(|(n, _)| n == ":path").unwrap().1.clone();
+                        match path.as_str() {
+                            "/test.v1.Telemetry/Missing" => self::headers(&mut conn, frame.stream, true, &[ok[0], ok[1], ("grpc-status", "12"), ("grpc-message", "no%20such%20method")]),
+                            "/test.v1.Telemetry/Echo" => self::headers(&mut conn, frame.stream, false, &ok),
+                            _ => {}
+                        }
+                        calls.insert(frame.stream, (path, Vec::new()));
+                    }
+                    grpc::DATA => {
+                        let (path, buf) = calls.get_mut(&frame.stream).unwrap();
+                        buf.extend_from_slice(frame.body().unwrap());
+                        if !frame.payload.is_empty() {
+                            let increment = (frame.payload.len() as u32).to_be_bytes().to_vec();
+                            Frame::new(grpc::WINDOW_UPDATE, 0, 0, increment.clone()).write(&mut conn).unwrap();
+                            Frame::new(grpc::WINDOW_UPDATE, 0, frame.stream, increment).write(&mut conn).unwrap();
+                        }
+                        for request in grpc::take_messages(buf).unwrap() {
+                            match path.as_str() {
+                                "/test.v1.Telemetry/Subscribe" => {
+                                    let count = proto.to_json_value(&proto.decode_message("SubscribeRequest", &request).unwrap())["count"].as_i64().unwrap();
+                                    headers(&mut conn, frame.stream, false, &ok);
+                                    for seq in 1..=count {
+                                        let reading = proto.build_from_json("Reading", &json!({"seq": seq, "note": "tick"})).unwrap();
+                                        reply(&mut conn, frame.stream, &reading.encode_to_vec());
+                                    }
+                                    headers(&mut conn, frame.stream, true, &[("grpc-status", "0")]);
+                                }
+                                "/test.v1.Telemetry/Echo" => reply(&mut conn, frame.stream, &request),
+                                _ => {
+                                    headers(&mut conn, frame.stream, false, &ok);
+                                    headers(&mut conn, frame.stream, true, &[("grpc-status", "5"), ("grpc-message", "device%20gone")]);
+                                }
+                            }
+                        }
+                        if frame.flags & grpc::END_STREAM != 0 && path == "/test.v1.Telemetry/Echo" {
+                            headers(&mut conn, frame.stream, true, &[("grpc-status", "0")]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+}
+
+fn server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    std::thread::spawn(move || serve(listener));
+    url
+}
+
+fn client(url: &str) -> GrpcClient {
+    GrpcClient::connect(url, &TlsProfile::default(), proto(), TIMEOUT).unwrap()
+}
+
+#[test]
+fn server_streaming() {
+    let url = server();
+    let client = client(&url);
+    let mut stream = client.open("Telemetry/Subscribe").unwrap();
+    stream.send(&json!({"count": 3}), TIMEOUT).unwrap();
+    stream.close_send().unwrap();
+    let items = stream.expect_items(3, &json!({"note": "tick"}), TIMEOUT, MatchOptions::default()).unwrap();
+    let seqs: Vec<_> = items.iter().map(|item| item["seq"].as_i64().unwrap()).collect();
+    assert_eq!(seqs, [1, 2, 3]);
+    assert_eq!(stream.finish(TIMEOUT).unwrap(), Status { code: 0, message: String::new() });
+    let err = stream.expect_items(1, &json!({}), TIMEOUT, MatchOptions::default()).unwrap_err();
+    assert_eq!(err.to_string(), r#"/test.v1.Telemetry/Subscribe ended after 0 of 1 items matching {} (3 received, status OK)"#);
+
+    // calls share the connection
+    let mut again = client.open("test.v1.Telemetry/Subscribe").unwrap();
+    again.send(&json!({"count": 1}), TIMEOUT).unwrap();
+    again.close_send().unwrap();
+    assert_eq!(again.expect_items(1, &json!({"seq": 1}), TIMEOUT, MatchOptions::default()).unwrap().len(), 1);
+}
+
+#[test]
+fn bidi_streaming_waits_for_the_send_window() {
+    let url = server();
+    let client = client(&url);
+    let mut stream = client.open("Telemetry/Echo").unwrap();
+    assert!(stream.method().is_client_streaming());
+    // each request is bigger than the 8-byte window the server grants
+    for seq in [7, 8] {
+        stream.send(&json!({"seq": seq, "note": "longer than the window"}), TIMEOUT).unwrap();
+        stream.expect_items(1, &json!({"seq": seq, "note": "longer than the window"}), TIMEOUT, MatchOptions::default()).unwrap();
+    }
+    let err = stream.expect_items(1, &json!({"seq": 9}), Duration::from_millis(100), MatchOptions::default()).unwrap_err();
+    assert!(err.to_string().starts_with(r#"0 of 1 /test.v1.Telemetry/Echo items matching {"seq":9} within"#), "{}", err);
+    stream.close_send().unwrap();
+    assert_eq!(stream.finish(TIMEOUT).unwrap().name(), "OK");
+    assert!(stream.send(&json!({}), TIMEOUT).is_err());
+}
+
+#[test]
+fn calls_end_with_their_status() {
+    let url = server();
+    let client = client(&url);
+    let mut failing = client.open("Telemetry/Fail").unwrap();
+    failing.send(&json!({}), TIMEOUT).unwrap();
+    let status = failing.finish(TIMEOUT).unwrap();
+    assert_eq!(status.to_string(), "NOT_FOUND: device gone");
+
+    // trailers-only answer
+    let mut missing = client.open("Telemetry/Missing").unwrap();
+    missing.close_send().unwrap();
+    assert_eq!(missing.finish(TIMEOUT).unwrap().to_string(), "UNIMPLEMENTED: no such method");
+
+    assert_eq!(client.open("Telemetry/Nope").unwrap_err().to_string(), "service test.v1.Telemetry has no method Nope");
+    assert_eq!(Status::code_of("not_found"), Some(5));
+    assert_eq!(Status::code_of("14"), Some(14));
+    assert_eq!(Status::code_of("GONE"), None);
+}
+
+#[test]
+fn refuses_servers_that_are_not_http2() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let _ = conn.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+    });
+    let err = GrpcClient::connect(&url, &TlsProfile::default(), proto(), TIMEOUT).unwrap_err();
+    assert!(format!("{:#}", err).contains("not an HTTP/2 server"), "{:#}", err);
+}
+
+#[test]
+fn grpc_framing() {
+    let mut buf = grpc::frame_message(b"abc");
+    buf.extend_from_slice(&grpc::frame_message(b""));
+    buf.extend_from_slice(&[0, 0, 0, 0, 5, b'x']);
+    assert_eq!(grpc::take_messages(&mut buf).unwrap(), [b"abc".to_vec(), Vec::new()]);
+    assert_eq!(buf, [0, 0, 0, 0, 5, b'x']);
+    assert!(grpc::take_messages(&mut vec![1, 0, 0, 0, 0]).is_err());
+
+    let padded = Frame::new(grpc::DATA, grpc::PADDED, 1, vec![2, b'h', b'i', 0, 0]);
+    assert_eq!(padded.body().unwrap(), b"hi");
+    let mut wire = Vec::new();
+    padded.write(&mut wire).unwrap();
+    assert_eq!(Frame::read(&mut &wire[..]).unwrap(), Some(padded));
+    assert_eq!(Frame::read(&mut &[][..]).unwrap(), None);
+}
+
+/// The last scenario expects the wrong status
+const STREAMS: &str = r#"Feature: gRPC streams
+  Background:
+    Given I connect to gRPC at "${url}" with descriptors "${descriptors}"
+
+  Scenario: server streaming
+    When I open stream Telemetry/Subscribe
+      """
+      {"count": 3}
+      """
+    Then I receive 2 stream items matching:
+      """
+      {"note": "tick"}
+      """
+    Then I receive 1 stream item
+    Then the stream ends with status OK
+
+  Scenario: bidi streaming
+    When I open stream test.v1.Telemetry/Echo
+    When I send stream item:
+      """
+      {"seq": 4}
+      """
+    Then I receive 1 stream item matching:
+      """
+      {"seq": 4}
+      """
+    When I close the stream
+    Then the stream ends with status OK
+
+  Scenario: failing call
+    When I open stream Telemetry/Fail
+    Then the stream ends with status OK
+"#;
+
+#[tokio::test]
+async fn stream_steps() {
+    let dir = std::env::temp_dir().join(format!("bdd-grpc-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let set = dir.join("telemetry.bin");
+    std::fs::write(&set, descriptors().encode_to_vec()).unwrap();
+    let feature = dir.join("grpc.feature");
+    let text = STREAMS.replace("${url}", &server()).replace("${descriptors}", &set.display().to_string());
+    std::fs::write(&feature, text).unwrap();
+
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(before_scenario)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    assert_eq!((writer.scenarios_stats().passed, writer.scenarios_stats().failed), (2, 1));
+}
@@ -0,0 +1,40 @@
+use my_bdd::history::{self, Run, ScenarioRun, StepRun, Summary};
+
+fn scenario(line: usize, passed: bool, wait_ms: u64) -> ScenarioRun {
+    ScenarioRun {
+        path: "features/ping.feature".to_string(),
+        line,
+        scenario: format!("scenario {}", line),
+        passed,
+        duration_ms: wait_ms + 10,
+        steps: vec![
+            StepRun { step: "When I send message PingRequest".to_string(), duration_ms: 10, passed: true },
+            StepRun { step: "Then I expect message PongReply".to_string(), duration_ms: wait_ms, passed },
+        ],
+        failure_topics: if passed { Vec::new() } else { vec!["PongReply".to_string()] },
+    }
+}
+
+#[test]
+fn summarizes_the_last_runs() {
+    let path = std::env::temp_dir().join(format!("bdd-history-{}.ndjson", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    assert!(history::load(&path, 5).unwrap().is_empty());
+    // the oldest run falls out of the last 3
+    for (passed, wait_ms) in [(false, 5000), (true, 100), (false, 1000), (true, 200)] {
+        let run = Run { started: "2026-10-15T10:00:00Z".to_string(), scenarios: vec![scenario(3, passed, wait_ms), scenario(9, true, 300)] };
+        history::append(&path, &run).unwrap();
+    }
+    std::fs::write(&path, std::fs::read_to_string(&path).unwrap() + "{\"started\": \"2026-10-15T11:0").unwrap();
+    let runs = history::load(&path, 3).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(runs.len(), 3);
+
+    let summary = Summary::of(&runs, 1);
+    assert_eq!((summary.flaky.len(), summary.flaky[0].line, summary.flaky[0].failed, summary.flaky[0].runs), (1, 3, 1, 3));
+    assert_eq!(summary.slowest[0].step, "Then I expect message PongReply");
+    // (100 + 1000 + 200 + 3 * 300) / 6 ms
+    assert_eq!(summary.slowest[0].mean.as_millis(), 366);
+    assert_eq!(summary.failure_topics, [("PongReply".to_string(), 1)]);
+    assert!(summary.to_string().contains("  1/3   failed  features/ping.feature:3 scenario 3"), "{}", summary);
+}
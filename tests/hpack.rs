@@ -0,0 +1,62 @@
+use my_bdd::hpack::{self, Decoder};
+
+fn hex(s: &str) -> Vec<u8> {
+    let s: String = s.split_whitespace().collect();
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+}
+
+fn pairs(headers: &[(String, String)]) -> Vec<(&str, &str)> {
+    headers.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect()
+}
+
+#[test]
+fn huffman_codes_match_rfc_7541() {
+    assert_eq!(hpack::huffman_encode(b"www.example.com"), hex("f1e3 c2e5 f23a 6ba0 ab90 f4ff"));
+    assert_eq!(hpack::huffman_encode(b"no-cache"), hex("a8eb 1064 9cbf"));
+    assert_eq!(hpack::huffman_decode(&hex("25a8 49e9 5bb8 e8b4 bf")).unwrap(), b"custom-value");
+    let all: Vec<u8> = (0..=255).collect();
+    assert_eq!(hpack::huffman_decode(&hpack::huffman_encode(&all)).unwrap(), all);
+    // padding must be the most significant bits of EOS, all ones, and shorter than a byte
+    assert!(hpack::huffman_decode(&[0xf1, 0xe3, 0x00]).is_err());
+    assert!(hpack::huffman_decode(&[0xff, 0xff]).is_err());
+}
+
+/// RFC 7541 C.4, requests with Huffman coding
+#[test]
+fn decodes_requests_sharing_a_dynamic_table() {
+    let mut decoder = Decoder::default();
+    let first = decoder.decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff")).unwrap();
+    assert_eq!(pairs(&first), [(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")]);
+    let second = decoder.decode(&hex("8286 84be 5886 a8eb 1064 9cbf")).unwrap();
+    assert_eq!(second[3].1, "www.example.com");
+    assert_eq!(second[4], ("cache-control".to_string(), "no-cache".to_string()));
+    let third = decoder.decode(&hex("8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf")).unwrap();
+    assert_eq!(pairs(&third)[1..], [(":scheme", "https"), (":path", "/index.html"), (":authority", "www.example.com"), ("custom-key", "custom-value")]);
+    let table: Vec<_> = decoder.table().collect();
+    assert_eq!(table, [("custom-key", "custom-value"), ("cache-control", "no-cache"), (":authority", "www.example.com")]);
+}
+
+/// RFC 7541 C.6, responses with Huffman coding evicting from a 256-byte table
+#[test]
+fn evicts_the_oldest_entries() {
+    let mut decoder = Decoder::with_table_size(256);
+    let blocks = [
+        "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 66e0 82a6 2d1b ff6e 919d 29ad 1718 63c7 8f0b 97c8 e9ae 82ae 43d3",
+        "4883 640e ffc1 c0bf",
+        "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a 839b d9ab 77ad 94e7 821d d7f2 e6c7 b335 dfdf cd5b 3960 d5af 2708 7f36 72c1 ab27 0fb5 291f 9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+    ];
+    let decoded: Vec<_> = blocks.iter().map(|b| decoder.decode(&hex(b)).unwrap()).collect();
+    assert_eq!(pairs(&decoded[1])[0], (":status", "307"));
+    assert_eq!(pairs(&decoded[2])[5], ("set-cookie", "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1"));
+    let table: Vec<_> = decoder.table().map(|(name, _)| name).collect();
+    assert_eq!(table, ["set-cookie", "content-encoding", "date"]);
+}
+
+#[test]
+fn literals_round_trip() {
+    let headers = [(":path", "/pkg.Telemetry/Subscribe"), ("content-type", "application/grpc"), ("grpc-status", "0"), ("x-long", &"v".repeat(300))];
+    let decoded = Decoder::default().decode(&hpack::encode(&headers)).unwrap();
+    assert_eq!(pairs(&decoded), headers);
+    assert!(Decoder::default().decode(&hex("be")).is_err());
+    assert!(Decoder::default().decode(&hex("3fe2 1f")).is_err());
+}
@@ -0,0 +1,40 @@
+use cucumber::parser::{self, Parser};
+use futures::executor::block_on;
+use futures::StreamExt;
+use my_bdd::config::Config;
+use my_bdd::i18n::{Translated, Translations};
+
+const CONFIG: &str = r#"
+[[translations]]
+pattern = "^j'envoie le message (\\w+)$"
+step = "I send message ${1}"
+
+[[translations]]
+pattern = "^j'attends le message (\\w+)$"
+step = "I expect message ${1}"
+"#;
+
+#[test]
+fn translate_first_matching_rule() {
+    let translations = Translations::new(&Config::parse(CONFIG).unwrap().translations).unwrap();
+    assert_eq!(translations.translate("j'envoie le message PingRequest").as_deref(), Some("I send message PingRequest"));
+    assert_eq!(translations.translate("I send message PingRequest"), None);
+}
+
+#[test]
+fn french_feature_reaches_english_steps() {
+    let dir = std::env::temp_dir().join(format!("bdd-i18n-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ping.feature");
+    std::fs::write(
+        &path,
+        "# language: fr\nFonctionnalité: Ping\n  Scénario: ping\n    Soit I run broker\n    Quand j'envoie le message PingRequest\n    Alors j'attends le message PongReply\n",
+    )
+    .unwrap();
+    let parser = Translated::new(parser::Basic::new(), &Config::parse(CONFIG).unwrap().translations).unwrap();
+    let features: Vec<_> = block_on(parser.parse(&path, Default::default()).collect());
+    std::fs::remove_dir_all(&dir).unwrap();
+    let feature = features.into_iter().next().unwrap().unwrap();
+    let steps: Vec<_> = feature.scenarios[0].steps.iter().map(|s| (s.keyword.trim(), s.value.as_str())).collect();
+    assert_eq!(steps, [("Soit", "I run broker"), ("Quand", "I send message PingRequest"), ("Alors", "I expect message PongReply")]);
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::loopback_broker;
+use serde_json::json;
+
+#[test]
+fn filtered_messages_are_never_buffered() {
+    let broker = loopback_broker();
+    broker.set_ingress_filter(|topic, _payload| !topic.starts_with("Pong"));
+    broker.send_message("PongReply", &json!({ "message": "chatter" })).unwrap();
+    broker.send_message("Status", &json!({ "state": "READY" })).unwrap();
+    broker.expect_message("Status", &json!({}), 2000).unwrap();
+    assert!(broker.captured("PongReply").unwrap().is_empty());
+    assert!(broker.dropped().is_empty());
+
+    broker.clear_ingress_filter();
+    broker.send_message("PongReply", &json!({ "message": "hello" })).unwrap();
+    let got = broker.expect_message("PongReply", &json!({}), 2000).unwrap();
+    assert_eq!(got.body, json!({ "message": "hello" }));
+}
@@ -0,0 +1,40 @@
+use my_bdd::jsonpath::{remove, select, select_one};
+use serde_json::json;
+
+#[test]
+fn selects_nested_fields_and_indices() {
+    let doc = json!({"payload": {"items": [{"status": "A"}, {"status": "B"}, {"status": "OK"}]}});
+    assert_eq!(select_one(&doc, "$.payload.items[2].status").unwrap(), &json!("OK"));
+    assert_eq!(select_one(&doc, "$['payload']['items'][-1].status").unwrap(), &json!("OK"));
+}
+
+#[test]
+fn quoted_names_may_hold_brackets() {
+    let doc = json!({"[vendor.ext.temperature]": 21});
+    assert_eq!(select_one(&doc, "$['[vendor.ext.temperature]']").unwrap(), &json!(21));
+}
+
+#[test]
+fn wildcard_selects_every_element() {
+    let doc = json!({"items": [{"v": 1}, {"v": 2}]});
+    assert_eq!(select(&doc, "$.items[*].v").unwrap(), vec![&json!(1), &json!(2)]);
+}
+
+#[test]
+fn missing_path_and_bad_syntax() {
+    let doc = json!({"a": 1});
+    assert!(select(&doc, "$.b").unwrap().is_empty());
+    assert!(select_one(&doc, "$.b").is_err());
+    assert!(select(&doc, "a.b").is_err());
+    assert!(select(&doc, "$.a[x]").is_err());
+}
+
+#[test]
+fn remove_drops_matched_nodes() {
+    let mut doc = json!({"id": 7, "header": {"id": 1, "seq": 2}, "items": [{"v": 1, "w": 0}, {"v": 2}]});
+    assert_eq!(remove(&mut doc, "$.header.id").unwrap(), 1);
+    assert_eq!(remove(&mut doc, "$.items[*].v").unwrap(), 2);
+    assert_eq!(remove(&mut doc, "$.missing").unwrap(), 0);
+    assert_eq!(doc, json!({"id": 7, "header": {"seq": 2}, "items": [{"w": 0}, {}]}));
+    assert!(remove(&mut doc, "$").is_err());
+}
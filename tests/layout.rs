@@ -0,0 +1,51 @@
+use my_bdd::config::{Config, LayoutConfig};
+use serde_json::json;
+
+fn layout(toml: &str) -> LayoutConfig {
+    let config: Config = toml::from_str(toml).unwrap();
+    config.layouts["Legacy"].clone()
+}
+
+#[test]
+fn packed_structs_round_trip() {
+    let legacy = layout(
+        r#"
+        [layouts.Legacy]
+        byte_order = "little"
+        fields = [
+          { name = "version", bits = 4 },
+          { name = "mode", bits = 3 },
+          { name = "armed", bits = 1 },
+          { name = "seq", type = "u16" },
+          { name = "temperature", type = "i16", byte_order = "big" },
+          { name = "ok", type = "bool" },
+          { name = "reserved", type = "pad", len = 1 },
+          { name = "serial", type = "bytes", len = 2 },
+          { name = "ratio", type = "f32" },
+        ]
+        "#,
+    );
+    assert_eq!(legacy.size().unwrap(), 13);
+    let body = json!({"version": 2, "mode": 5, "armed": 1, "seq": 0x0102, "temperature": -2, "ok": true, "serial": "q80=", "ratio": 0.5});
+    let payload = legacy.encode(&body).unwrap();
+    assert_eq!(payload, [0x2b, 0x02, 0x01, 0xff, 0xfe, 0x01, 0x00, 0xab, 0xcd, 0x00, 0x00, 0x00, 0x3f]);
+    assert_eq!(legacy.decode(&payload).unwrap(), body);
+    // left out means zero
+    assert_eq!(legacy.decode(&legacy.encode(&json!({"seq": 7})).unwrap()).unwrap()["temperature"], json!(0));
+
+    let err = |body| legacy.encode(&body).unwrap_err().to_string();
+    assert_eq!(err(json!({"mode": 8})), "mode: expected an unsigned 3-bit number, got 8");
+    assert_eq!(err(json!({"temperature": 40000})), "temperature: expected a signed 16-bit number, got 40000");
+    assert_eq!(err(json!({"reserved": 0})), "the layout has no field reserved");
+    assert_eq!(legacy.decode(&payload[1..]).unwrap_err().to_string(), "payload is 12 bytes, the layout 13");
+}
+
+#[test]
+fn layouts_must_be_byte_aligned() {
+    let misaligned = layout(r#"layouts.Legacy.fields = [{ name = "flag", bits = 1 }, { name = "seq", type = "u16" }]"#);
+    assert!(misaligned.size().unwrap_err().to_string().contains("seq starts mid-byte"));
+    let short = layout(r#"layouts.Legacy.fields = [{ name = "flag", bits = 3 }]"#);
+    assert!(short.size().unwrap_err().to_string().contains("end mid-byte"));
+    let unsized_bytes = layout(r#"layouts.Legacy.fields = [{ name = "serial", type = "bytes" }]"#);
+    assert!(unsized_bytes.size().unwrap_err().to_string().contains("need a len"));
+}
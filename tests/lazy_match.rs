@@ -0,0 +1,55 @@
+mod common;
+
+use common::{loopback_broker, Inbound, Loopback};
+use my_bdd::broker::Broker;
+use my_bdd::matchers::MatchOptions;
+use my_bdd::proto_dyn::{BoundFields, ProtoDyn};
+use prost_reflect::DynamicMessage;
+use prost_reflect::prost::Message;
+use serde_json::json;
+use std::sync::mpsc::channel;
+
+fn status_payload(text: &str) -> Vec<u8> {
+    let desc = ProtoDyn::new().unwrap().message_desc("Status").unwrap();
+    DynamicMessage::parse_text_format(desc, text).unwrap().encode_to_vec()
+}
+
+#[test]
+fn binds_scalar_and_enum_fields_only() {
+    let proto = ProtoDyn::new().unwrap();
+    let desc = proto.message_desc("Status").unwrap();
+    let opts = MatchOptions::default();
+    assert!(BoundFields::bind(&desc, &json!({ "state": "IDLE" }), opts).is_complete());
+    assert!(!BoundFields::bind(&desc, &json!({ "state": "IDLE", "components": [{ "name": "fan" }] }), opts).is_complete());
+    assert!(!BoundFields::bind(&desc, &json!({ "state": { "$ieq": "idle" } }), opts).is_complete());
+
+    let idle = proto.build_from_json("Status", &json!({ "state": "IDLE" })).unwrap();
+    assert!(BoundFields::bind(&desc, &json!({ "state": 2 }), opts).may_match(&idle));
+    assert!(!BoundFields::bind(&desc, &json!({ "state": "BUSY", "components": [] }), opts).may_match(&idle));
+}
+
+#[test]
+fn matches_the_same_messages_with_and_without_json() {
+    for skip_json in [false, true] {
+        let (tx, rx) = channel();
+        let mut broker = Broker::with_transport(Box::new(Loopback(tx.clone())), Box::new(Inbound(rx))).unwrap();
+        broker.set_match_options(MatchOptions { skip_json, ..Default::default() });
+        broker.send_message("PongReply", &json!({ "message": "idle" })).unwrap();
+        for status in ["state: BUSY components { name: 'fan' }", "state: IDLE components { name: 'pump' }", "state: IDLE components { name: 'fan' }"] {
+            tx.send(("Status".to_string(), status_payload(status))).unwrap();
+        }
+
+        let got = broker.expect_message("Status", &json!({ "state": "IDLE", "components": [{ "name": "fan" }] }), 2000).unwrap();
+        assert_eq!(got.body, json!({ "state": 2, "components": [{ "name": "fan" }] }));
+        let got = broker.expect_message("Status", &json!({ "state": "IDLE" }), 2000).unwrap();
+        assert_eq!(got.body["components"][0]["name"], "pump");
+        assert!(broker.expect_message("Status", &json!({ "state": "IDLE" }), 100).is_err());
+    }
+}
+
+#[test]
+fn unknown_messages_fail_without_waiting() {
+    let broker = loopback_broker();
+    let err = broker.expect_message("Nope", &json!({}), 60_000).unwrap_err();
+    assert_eq!(err.to_string(), "message company.project.v1.Nope not found");
+}
@@ -0,0 +1,25 @@
+use log::LevelFilter;
+use my_bdd::logging::LogFilter;
+
+#[test]
+fn verbosity_sets_the_default_level() {
+    assert_eq!(LogFilter::from_verbosity(true, 2).level("transport"), LevelFilter::Error);
+    assert_eq!(LogFilter::from_verbosity(false, 0).level("runner"), LevelFilter::Info);
+    assert_eq!(LogFilter::from_verbosity(false, 1).level("matcher"), LevelFilter::Debug);
+    assert_eq!(LogFilter::from_verbosity(false, 3).level("matcher"), LevelFilter::Trace);
+}
+
+#[test]
+fn directives_set_levels_per_target() {
+    let mut filter: LogFilter = "warn,transport=trace".parse().unwrap();
+    assert_eq!(filter.level("transport"), LevelFilter::Trace);
+    assert_eq!(filter.level("matcher"), LevelFilter::Warn);
+    filter.apply("matcher=debug").unwrap();
+    assert_eq!(filter.level("matcher"), LevelFilter::Debug);
+    // a bare level resets the per-target ones given before it
+    filter.apply("error").unwrap();
+    assert_eq!(filter.level("transport"), LevelFilter::Error);
+
+    assert_eq!(filter.apply("bus=debug").unwrap_err().to_string(), "unknown log target bus (use transport, matcher, runner)");
+    assert!("loud".parse::<LogFilter>().is_err());
+}
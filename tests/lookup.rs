@@ -0,0 +1,18 @@
+use my_bdd::proto_dyn::ProtoDyn;
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+#[test]
+fn short_names_resolve_to_the_first_package() {
+    let file = |package: &str| FileDescriptorProto {
+        name: Some(format!("{}.proto", package)),
+        package: Some(package.into()),
+        message_type: vec![DescriptorProto { name: Some("Status".into()), ..Default::default() }],
+        ..Default::default()
+    };
+    let set = FileDescriptorSet { file: vec![file("a.v1"), file("b.v1")] };
+    let proto = ProtoDyn::from_descriptor_set(&set.encode_to_vec()).unwrap();
+    assert_eq!(proto.message_desc("Status").unwrap().full_name(), "a.v1.Status");
+    assert_eq!(proto.message_desc("b.v1.Status").unwrap().full_name(), "b.v1.Status");
+    assert_eq!(proto.message_desc("v1.Status").unwrap_err().to_string(), "message v1.Status not found");
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::loopback_broker;
+use my_bdd::matchers;
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn last_sent_message_is_the_expectation() {
+    let broker = loopback_broker();
+    assert_eq!(broker.last_sent("Status"), None);
+
+    broker.send_message("Status", &json!({"state": "READY"})).unwrap();
+    // as encoded: enums by number
+    let sent = broker.last_sent("Status").unwrap();
+    assert_eq!(sent, json!({"state": 1}));
+    broker.expect_message("Status", &sent, 1000).unwrap();
+
+    broker.send_message("PongReply", &json!({"message": "first"})).unwrap();
+    broker.send_message("PongReply", &json!({"message": "second"})).unwrap();
+    let sent = broker.last_sent("PongReply").unwrap();
+    let got = broker.expect_message("PongReply", &sent, 1000).unwrap();
+    assert_eq!(got.body, json!({"message": "second"}));
+    broker.send_message("PongReply", &json!({"message": "third"})).unwrap();
+    assert!(broker.expect_message("PongReply", &sent, 200).is_err());
+    broker.expect_message("PongReply", &matchers::without_fields(&sent, &["message".to_string()]), 1000).unwrap();
+}
+
+#[test]
+fn validated_sends_fail_before_sending() {
+    let mut broker = loopback_broker();
+    broker.set_validate_sends(true);
+    let err = broker.send_message("Status", &json!({"state": 9})).unwrap_err().to_string();
+    assert_eq!(err, "Status not sent, it fails validation:\n  state: 9 is not a value of State (enum)");
+    assert_eq!(broker.last_sent("Status"), None);
+    broker.send_message_unchecked("Status", &json!({"state": 9})).unwrap();
+    broker.send_message("Status", &json!({"state": "BUSY"})).unwrap();
+}
+
+#[test]
+fn traffic_is_counted_per_topic() {
+    let broker = loopback_broker();
+    for message in ["a", "bc"] {
+        broker.send_message("PongReply", &json!({ "message": message })).unwrap();
+    }
+    broker.send_message("PingRequest", &json!({})).unwrap();
+    broker.expect_message("PingRequest", &json!({}), 1000).unwrap();
+
+    let traffic = broker.traffic();
+    let pong = traffic["PongReply"];
+    // field tag, length and text: 3 and 4 bytes
+    assert_eq!((pong.sent, pong.sent_bytes, pong.received, pong.received_bytes), (2, 7, 2, 7));
+    assert_eq!((pong.messages(), pong.bytes()), (4, 14));
+    assert_eq!((traffic["PingRequest"].sent, traffic["PingRequest"].bytes()), (1, 0));
+    // cleared messages still count
+    broker.clear_received(None);
+    assert_eq!(broker.traffic()["PongReply"].received, 2);
+}
+
+#[test]
+fn duplicated_send_goes_out_twice() {
+    let broker = loopback_broker();
+    broker.duplicate_next("PongReply");
+    broker.send_message("PongReply", &json!({"message": "once"})).unwrap();
+    broker.send_message("PongReply", &json!({"message": "later"})).unwrap();
+    broker.expect_message("PongReply", &json!({"message": "later"}), 1000).unwrap();
+
+    let bodies: Vec<_> = broker.captured("PongReply").unwrap().into_iter().map(|m| m.body["message"].clone()).collect();
+    assert_eq!(bodies, [json!("once"), json!("once"), json!("later")]);
+    let [first, second] = broker.duplicated("PongReply").unwrap();
+    assert!(second >= first);
+    assert_eq!(broker.traffic()["PongReply"].sent, 3);
+    assert!(broker.duplicated("PingRequest").is_none());
+}
+
+#[test]
+fn expectations_share_one_deadline() {
+    let broker = loopback_broker();
+    broker.send_message("PongReply", &json!({"message": "second"})).unwrap();
+    broker.send_message("PongReply", &json!({"message": "first"})).unwrap();
+    let deadline = std::time::Instant::now() + Duration::from_millis(300);
+    let first = broker.expect_until("PongReply", &json!({"message": "first"}), deadline).unwrap();
+    // "second" arrived earlier, so it can't follow "first"
+    let late = broker.expect_after_until("PongReply", &json!({"message": "second"}), first.at, deadline);
+    assert_eq!(late.unwrap_err().to_string(), "timeout waiting for PongReply");
+    assert!(std::time::Instant::now() >= deadline);
+    // the deadline has passed, so only what is buffered can still match
+    broker.expect_until("PongReply", &json!({"message": "second"}), deadline).unwrap();
+}
+
+#[test]
+fn messages_go_out_on_an_explicit_topic() {
+    let broker = loopback_broker();
+    broker.send_message_on_topic("tlm/device42", "PongReply", &json!({"message": "hi"})).unwrap();
+    assert_eq!(broker.traffic()["tlm/device42"].sent, 1);
+    assert!(!broker.traffic().contains_key("PongReply"));
+    assert_eq!(broker.last_sent("PongReply"), Some(json!({"message": "hi"})));
+    assert!(broker.send_message_on_topic("tlm/device42", "NoSuchMessage", &json!({})).is_err());
+}
+
+#[test]
+fn expectations_match_topic_patterns() {
+    use my_bdd::broker::topic_matches;
+    assert!(topic_matches("tlm/*", "tlm/device42"));
+    assert!(topic_matches("tlm/*/status", "tlm/a/b/status"));
+    assert!(topic_matches("tlm/device??", "tlm/device42"));
+    assert!(!topic_matches("tlm/*", "cmd/device42"));
+    assert!(!topic_matches("tlm/device?", "tlm/device42"));
+
+    let broker = loopback_broker();
+    broker.send_message_on_topic("tlm/device41", "PongReply", &json!({"message": "a"})).unwrap();
+    broker.send_message_on_topic("tlm/device42", "PongReply", &json!({"message": "b"})).unwrap();
+    let got = broker.expect_on_topic("tlm/*", "PongReply", &json!({"message": "b"}), 1000).unwrap();
+    assert_eq!(got.topic, "tlm/device42");
+    let err = broker.expect_on_topic("cmd/*", "PongReply", &json!({}), 50).unwrap_err();
+    assert_eq!(err.to_string(), "timeout waiting for PongReply on a topic matching cmd/*");
+}
+
+#[test]
+fn other_schema_versions_encode_and_decode() {
+    if !common::protoc_available("other_schema_versions_encode_and_decode") {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("bdd-schema-v2-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("pong_v2.proto");
+    std::fs::write(&path, "syntax = \"proto3\";\npackage company.project.v1;\nmessage PongReply {\n  string message = 1;\n  uint32 priority = 2;\n}\n").unwrap();
+    let v2 = my_bdd::proto_dyn::ProtoDyn::load(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let broker = loopback_broker();
+    broker.send_message_with(&v2, "PongReply", &json!({"message": "hi", "priority": 5})).unwrap();
+    assert_eq!(broker.last_sent("PongReply"), Some(json!({"message": "hi", "priority": 5})));
+    // the field v1 doesn't know is skipped on decoding
+    let got = broker.expect_message("PongReply", &json!({"message": "hi"}), 1000).unwrap();
+    assert_eq!(got.body, json!({"message": "hi"}));
+
+    // a connection bound to v2 decodes it too
+    let mut bridge = loopback_broker();
+    bridge.set_schema(v2);
+    bridge.send_message("PongReply", &json!({"message": "hi", "priority": 5})).unwrap();
+    let got = bridge.expect_message("PongReply", &json!({"priority": 5}), 1000).unwrap();
+    assert_eq!(got.body, json!({"message": "hi", "priority": 5}));
+}
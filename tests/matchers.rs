@@ -0,0 +1,93 @@
+use my_bdd::matchers::{self, Directive};
+use my_bdd::proto_dyn::{json_partial_match, json_partial_match_checked};
+use serde_json::{json, Value as JsonValue};
+
+fn semver(v: &JsonValue) -> Option<Vec<u64>> {
+    v.as_str()?.split('.').map(|p| p.parse().ok()).collect()
+}
+
+#[test]
+fn registered_directive_is_dispatched() {
+    matchers::register("$semver_gte", |d: &Directive, actual: Option<&JsonValue>| {
+        Ok(actual.and_then(semver) >= semver(d.arg))
+    })
+    .unwrap();
+    assert!(json_partial_match(&json!({"version": {"$semver_gte": "2.1.0"}}), &json!({"version": "2.10.0"})));
+    assert!(!json_partial_match(&json!({"version": {"$semver_gte": "2.1.0"}}), &json!({"version": "2.0.9"})));
+}
+
+#[test]
+fn directive_sees_absent_fields_and_options() {
+    matchers::register("$absent_or", |d: &Directive, actual: Option<&JsonValue>| {
+        Ok(actual.is_none() || actual == d.option("$value"))
+    })
+    .unwrap();
+    let expected = json!({"x": {"$absent_or": true, "$value": 3}});
+    assert!(json_partial_match(&expected, &json!({})));
+    assert!(json_partial_match(&expected, &json!({"x": 3})));
+    assert!(!json_partial_match(&expected, &json!({"x": 4})));
+}
+
+#[test]
+fn unknown_directive_is_an_error() {
+    assert!(json_partial_match_checked(&json!({"a": {"$nope": 1}}), &json!({"a": 1})).is_err());
+    assert!(matchers::register("nodollar", |_: &Directive, _: Option<&JsonValue>| Ok(true)).is_err());
+}
+
+#[test]
+fn ignored_fields_are_dropped_from_expectation() {
+    let ignore = vec!["timestamp".to_string(), "header.seq_no".to_string()];
+    let expected = json!({"timestamp": 1, "header": {"seq_no": 7, "timestamp": 2, "id": 3}, "seq_no": 9});
+    assert_eq!(
+        matchers::without_fields(&expected, &ignore),
+        json!({"header": {"id": 3}, "seq_no": 9})
+    );
+}
+
+#[test]
+fn string_directives_and_normalization() {
+    use my_bdd::matchers::MatchOptions;
+    use my_bdd::proto_dyn::json_partial_match_with;
+    assert!(json_partial_match(&json!({"s": {"$ieq": " ok "}}), &json!({"s": "OK"})));
+    assert!(json_partial_match(&json!({"s": {"$contains_str": "error"}}), &json!({"s": "io error: eof"})));
+    assert!(!json_partial_match(&json!({"s": {"$contains_str": "error"}}), &json!({"s": 3})));
+    let opts = MatchOptions { normalize_strings: true, ..Default::default() };
+    assert!(json_partial_match_with(&json!({"s": "a b"}), &json!({"s": " a   b\n"}), opts).unwrap());
+    assert!(!json_partial_match(&json!({"s": "a b"}), &json!({"s": " a   b\n"})));
+}
+
+#[test]
+fn within_handles_timestamp_shapes_and_units() {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    let near = json!({"$within": "2s"});
+    assert!(json_partial_match(&json!({"t": near}), &json!({"t": now.as_secs()})));
+    assert!(json_partial_match(&json!({"t": near}), &json!({"t": now.as_millis() as u64})));
+    assert!(json_partial_match(&json!({"t": near}), &json!({"t": {"seconds": now.as_secs(), "nanos": 5}})));
+    assert!(!json_partial_match(&json!({"t": near}), &json!({"t": now.as_secs() - 60})));
+    let of = json!({"t": {"$within": "500ms", "$of": 1_000_000, "$unit": "s"}});
+    assert!(json_partial_match(&of, &json!({"t": 1_000_000})));
+    assert!(json_partial_match(&json!({"t": {"$within": "1s", "$of": "2024-01-01T00:00:00Z"}}), &json!({"t": 1_704_067_200})));
+}
+
+#[test]
+fn macros_expand_from_config() {
+    let config = my_bdd::config::Config::parse(
+        r#"
+[matching.macros]
+valid_header = { version = 1, ts = { "$within" = "5s", "$of" = "now" } }
+ok_status = { header = "$match:valid_header", state = "OK" }
+loop = "$match:loop"
+"#,
+    )
+    .unwrap();
+    let macros = &config.matching.macros;
+    let expanded = matchers::expand_macros(&json!({"status": "$match:ok_status"}), macros).unwrap();
+    assert_eq!(expanded, json!({"status": {"header": {"version": 1, "ts": {"$within": "5s", "$of": "now"}}, "state": "OK"}}));
+    // other fields override the fragment's, nested messages field by field
+    let expanded = matchers::expand_macros(&json!({"$match": "ok_status", "header": {"version": 2}}), macros).unwrap();
+    assert_eq!(expanded["header"], json!({"version": 2, "ts": {"$within": "5s", "$of": "now"}}));
+
+    let err = matchers::expand_macros(&json!({"header": "$match:nope"}), macros).unwrap_err().to_string();
+    assert!(err.contains("unknown matcher macro nope") && err.contains("valid_header"), "{}", err);
+    assert!(matchers::expand_macros(&json!("$match:loop"), macros).unwrap_err().to_string().contains("cycle"));
+}
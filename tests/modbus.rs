@@ -0,0 +1,34 @@
+#![cfg(feature = "modbus")]
+
+use my_bdd::modbus::{decode_pdu, encode_frame, ModbusClient};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+#[test]
+fn frames_carry_mbap_header() {
+    assert_eq!(encode_frame(7, 1, &[0x03, 0x00, 0x64, 0x00, 0x02]), [0, 7, 0, 0, 0, 6, 1, 0x03, 0x00, 0x64, 0x00, 0x02]);
+}
+
+#[test]
+fn exceptions_are_errors() {
+    assert_eq!(decode_pdu(0x03, &[0x03, 0x02, 0x00, 0x2A]).unwrap(), [0x02, 0x00, 0x2A]);
+    let err = decode_pdu(0x03, &[0x83, 0x02]).unwrap_err().to_string();
+    assert!(err.contains("illegal data address"), "{}", err);
+}
+
+#[test]
+fn reads_holding_registers_from_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut req = [0u8; 12];
+        conn.read_exact(&mut req).unwrap();
+        // echo the transaction id, answer two registers: 42, 513
+        let reply = [req[0], req[1], 0, 0, 0, 7, req[6], 0x03, 4, 0, 42, 2, 1];
+        conn.write_all(&reply).unwrap();
+    });
+    let mut client = ModbusClient::connect(&addr.to_string(), 1, Duration::from_secs(2)).unwrap();
+    assert_eq!(client.read_holding_registers(100, 2).unwrap(), [42, 513]);
+}
@@ -0,0 +1,74 @@
+use my_bdd::config::IpFamily;
+use my_bdd::network::{BindAddress, Endpoint, Network};
+use my_bdd::transport::zmq_endpoint;
+use std::net::{IpAddr, Ipv4Addr};
+
+#[test]
+fn bind_address_is_an_address_or_an_interface() {
+    assert_eq!(BindAddress::parse("127.0.0.1").unwrap(), BindAddress::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    let lo = BindAddress::parse("lo").unwrap();
+    assert_eq!(lo, BindAddress::Interface("lo".to_string()));
+    assert_eq!(lo.ip(false).unwrap(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let err = format!("{:#}", BindAddress::parse("nosuchnic0").unwrap_err());
+    assert!(err.contains("neither an IP address nor a local interface") && err.contains("lo"), "{}", err);
+    // TEST-NET-1, never assigned locally
+    let err = format!("{:#}", BindAddress::parse("192.0.2.1").unwrap_err());
+    assert!(err.contains("not assigned to a local interface"), "{}", err);
+}
+
+#[test]
+fn endpoints_take_ipv6_literals_with_or_without_brackets() {
+    let parse = |text: &str| text.parse::<Endpoint>().map(|e| (e.host, e.port));
+    assert_eq!(parse("10.0.0.5").unwrap(), ("10.0.0.5".to_string(), None));
+    assert_eq!(parse("sut.lab:4246").unwrap(), ("sut.lab".to_string(), Some(4246)));
+    assert_eq!(parse("fd00::5").unwrap(), ("fd00::5".to_string(), None));
+    assert_eq!(parse("[fd00::5]").unwrap(), ("fd00::5".to_string(), None));
+    assert_eq!(parse("[fd00::5]:30490").unwrap(), ("fd00::5".to_string(), Some(30490)));
+    assert_eq!("[fd00::5]:30490".parse::<Endpoint>().unwrap().to_string(), "[fd00::5]:30490");
+    for bad in ["", ":4246", "[fd00::5", "[sut.lab]", "[fd00::5]4246", "fd00::5:4246x", "sut.lab:http"] {
+        assert!(bad.parse::<Endpoint>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn zmq_endpoints_resolve_the_host() {
+    let any = Network::default();
+    assert_eq!(zmq_endpoint(&any, "127.0.0.1", 4246).unwrap(), "tcp://127.0.0.1:4246");
+    assert_eq!(zmq_endpoint(&any, "::1", 4246).unwrap(), "tcp://[::1]:4246");
+    assert_eq!(zmq_endpoint(&any, "[::1]", 4247).unwrap(), "tcp://[::1]:4247");
+    let ipv4 = Network { family: IpFamily::Ipv4, ..Network::default() };
+    assert_eq!(zmq_endpoint(&ipv4, "localhost", 4247).unwrap(), "tcp://127.0.0.1:4247");
+    let err = format!("{:#}", zmq_endpoint(&ipv4, "::1", 4246).unwrap_err());
+    assert!(err.contains("has no IPv4 address"), "{}", err);
+    assert!(format!("{:#}", zmq_endpoint(&any, "no-such-host.invalid", 4246).unwrap_err()).contains("resolve no-such-host.invalid"));
+    assert!(format!("{:#}", zmq_endpoint(&any, "10.0.0.5:5555", 4246).unwrap_err()).contains("give just the host"));
+
+    let bound = Network { bind: Some(BindAddress::parse("127.0.0.1").unwrap()), ..Network::default() };
+    assert_eq!(zmq_endpoint(&bound, "127.0.0.1", 4246).unwrap(), "tcp://127.0.0.1:0;127.0.0.1:4246");
+    let err = format!("{:#}", zmq_endpoint(&bound, "::1", 4246).unwrap_err());
+    assert!(err.contains("resolves only to IPv6 addresses"), "{}", err);
+}
+
+#[test]
+fn zmq_endpoints_deliver() {
+    let ctx = zmq::Context::new();
+    for (host, bind) in [("127.0.0.1", Some("127.0.0.1")), ("127.0.0.1", Some("lo")), ("::1", None), ("::1", Some("::1"))] {
+        let sub = ctx.socket(zmq::SUB).unwrap();
+        sub.set_ipv6(true).unwrap();
+        sub.set_subscribe(b"").unwrap();
+        sub.bind(&format!("tcp://{}:*", host.parse::<Endpoint>().unwrap())).unwrap();
+        let port: u16 = sub.get_last_endpoint().unwrap().unwrap().rsplit(':').next().unwrap().parse().unwrap();
+
+        let network = Network { bind: bind.map(|b| BindAddress::parse(b).unwrap()), ..Network::default() };
+        let publisher = ctx.socket(zmq::PUB).unwrap();
+        publisher.set_ipv6(true).unwrap();
+        publisher.connect(&zmq_endpoint(&network, host, port).unwrap()).unwrap();
+        let received = (0..50).find_map(|_| {
+            publisher.send("ping", 0).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            sub.recv_string(zmq::DONTWAIT).ok()
+        });
+        assert_eq!(received, Some(Ok("ping".to_string())), "{} from {:?}", host, bind);
+    }
+}
@@ -0,0 +1,44 @@
+use my_bdd::operator::{self, Confirmation};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+fn free_addr() -> String {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+}
+
+fn request(addr: &str, request_line: &str) -> String {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut conn = loop {
+        match TcpStream::connect(addr) {
+            Ok(conn) => break conn,
+            Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => panic!("connect {}: {}", addr, e),
+        }
+    };
+    write!(conn, "{}\r\nContent-Length: 0\r\n\r\n", request_line).unwrap();
+    let mut response = String::new();
+    conn.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn confirms_over_http() {
+    let addr = free_addr();
+    let client = {
+        let addr = addr.clone();
+        std::thread::spawn(move || (request(&addr, "GET /action HTTP/1.1"), request(&addr, "POST /confirm HTTP/1.1")))
+    };
+    let how = operator::wait("insert the SIM card", Duration::from_secs(10), Some(&addr)).unwrap();
+    assert_eq!(how, Confirmation::Http);
+    let (action, confirm) = client.join().unwrap();
+    assert!(action.contains(r#""action":"insert the SIM card""#), "{}", action);
+    assert!(confirm.starts_with("HTTP/1.1 200"), "{}", confirm);
+}
+
+#[test]
+fn times_out_without_confirmation() {
+    let addr = free_addr();
+    let err = operator::wait("press the reset button", Duration::from_millis(200), Some(&addr)).unwrap_err();
+    assert!(err.to_string().contains("did not confirm \"press the reset button\" within 200ms"), "{}", err);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::loopback_broker;
+use cucumber::{cli, gherkin, writer, World as _, WriterExt as _};
+use futures::future::{FutureExt, LocalBoxFuture};
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+
+const FEATURE: &str = r#"Feature: arrival order across connections
+  Scenario: forwarded after ingest
+    When I send message PongReply on connection "device"
+    Then I expect message PongReply on connection "device"
+    When I send message Status on connection "cloud"
+    Then I expect message Status on connection "cloud"
+    And message Status on "cloud" arrived after message PongReply on "device"
+    And message PongReply on "device" arrived before message Status on "cloud", within 10s
+
+  Scenario: wrong order
+    When I send message Status on connection "cloud"
+    Then I expect message Status on connection "cloud"
+    When I send message PongReply on connection "device"
+    Then I expect message PongReply on connection "device"
+    And message Status on "cloud" arrived after message PongReply on "device"
+
+  Scenario: nothing received
+    Then message Status on "cloud" arrived after message PongReply on "device"
+"#;
+
+/// before_scenario, then loopback connections "device" and "cloud"
+fn with_connections<'a>(
+    feature: &'a gherkin::Feature,
+    rule: Option<&'a gherkin::Rule>,
+    scenario: &'a gherkin::Scenario,
+    world: &'a mut MyWorld,
+) -> LocalBoxFuture<'a, ()> {
+    async move {
+        before_scenario(feature, rule, scenario, world).await;
+        for name in ["device", "cloud"] {
+            let broker = loopback_broker();
+            world.connections.insert(name.to_string(), broker);
+        }
+    }
+    .boxed_local()
+}
+
+#[tokio::test]
+async fn arrival_order_compares_across_connections() {
+    let dir = std::env::temp_dir().join(format!("bdd-ordering-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let feature = dir.join("ordering.feature");
+    std::fs::write(&feature, FEATURE).unwrap();
+
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(with_connections)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    // the failed scenarios' ${scenario_tmp} is kept
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    let stats = writer.scenarios_stats();
+    assert_eq!((stats.passed, stats.failed), (1, 2));
+}
@@ -0,0 +1,28 @@
+use my_bdd::params::{DurationParam, Endpoint, MessageName};
+use std::time::Duration;
+
+#[test]
+fn message_names_must_exist_in_the_descriptor_set() {
+    assert_eq!("PingRequest".parse::<MessageName>().unwrap().0, "PingRequest");
+    assert!("company.project.v1.PongReply".parse::<MessageName>().is_ok());
+    let err = "PingRequets".parse::<MessageName>().unwrap_err().to_string();
+    assert!(err.contains("unknown message PingRequets"), "{}", err);
+    assert!(err.contains("PingRequest"), "{}", err);
+}
+
+#[test]
+fn durations_convert_at_match_time() {
+    assert_eq!("1m 30s".parse::<DurationParam>().unwrap().0, Duration::from_secs(90));
+    assert_eq!("2.5 s".parse::<DurationParam>().unwrap().0, Duration::from_millis(2500));
+    assert!("5 parsecs".parse::<DurationParam>().is_err());
+}
+
+#[test]
+fn endpoints_are_host_and_optional_port() {
+    for ok in ["10.0.0.5", "sut.local:4246", "::1", "[fe80::1%eth0]:30490"] {
+        assert!(ok.parse::<Endpoint>().is_ok(), "{}", ok);
+    }
+    for bad in ["tcp://10.0.0.5", "sut:99999", ":4246", "[::1"] {
+        assert!(bad.parse::<Endpoint>().is_err(), "{}", bad);
+    }
+}
@@ -0,0 +1,28 @@
+use my_bdd::ports::{Endpoints, PortLease};
+use std::collections::HashMap;
+
+#[test]
+fn leased_ports_are_not_handed_out_twice() {
+    let leases: Vec<PortLease> = (0..20).map(|_| PortLease::allocate().unwrap()).collect();
+    let mut ports: Vec<u16> = leases.iter().map(PortLease::port).collect();
+    ports.sort();
+    ports.dedup();
+    assert_eq!(ports.len(), leases.len());
+}
+
+#[test]
+fn endpoints_get_their_own_ports() {
+    let templates = HashMap::from([
+        ("sim".to_string(), "127.0.0.1:{auto_port}".to_string()),
+        ("api".to_string(), "http://127.0.0.1:{auto_port}/v1".to_string()),
+        ("gateway".to_string(), "10.0.0.5:8080".to_string()),
+    ]);
+    let endpoints = Endpoints::resolve(&templates).unwrap();
+    assert_eq!(endpoints.leases.len(), 2);
+    let vars = &endpoints.vars;
+    assert_eq!(vars["sim"], format!("127.0.0.1:{}", vars["sim_port"]));
+    assert_eq!(vars["api"], format!("http://127.0.0.1:{}/v1", vars["api_port"]));
+    assert_ne!(vars["sim_port"], vars["api_port"]);
+    assert_eq!(vars["gateway"], "10.0.0.5:8080");
+    assert!(!vars.contains_key("gateway_port"));
+}
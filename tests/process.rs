@@ -0,0 +1,18 @@
+use my_bdd::process::run;
+use std::process::Command;
+use std::time::Duration;
+
+#[test]
+fn captures_exit_code_and_output() {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+    let out = run(cmd, Duration::from_secs(5)).unwrap();
+    assert_eq!((out.status, out.stdout.as_str(), out.stderr.as_str()), (3, "out\n", "err\n"));
+}
+
+#[test]
+fn kills_commands_past_their_timeout() {
+    let mut cmd = Command::new("sleep");
+    cmd.arg("5");
+    assert!(run(cmd, Duration::from_millis(100)).is_err());
+}
@@ -0,0 +1,79 @@
+use my_bdd::proto_dyn::{json_partial_match, ProtoDyn};
+use my_bdd::validate;
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::descriptor_proto::ExtensionRange;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FieldOptions, FileDescriptorProto, FileDescriptorSet};
+use serde_json::json;
+
+/// vendor.bus.Reading with a group, a deprecated field and extensions 100 to 199, and
+/// vendor.ext.temperature = 100 extending it
+fn legacy_bus() -> ProtoDyn {
+    let field = |name: &str, number: i32, ty: Type| FieldDescriptorProto {
+        name: Some(name.into()),
+        number: Some(number),
+        label: Some(Label::Optional as i32),
+        r#type: Some(ty as i32),
+        ..Default::default()
+    };
+    let bus = FileDescriptorProto {
+        name: Some("bus.proto".into()),
+        package: Some("vendor.bus".into()),
+        syntax: Some("proto2".into()),
+        message_type: vec![DescriptorProto {
+            name: Some("Reading".into()),
+            field: vec![
+                field("sensor", 1, Type::String),
+                FieldDescriptorProto { type_name: Some(".vendor.bus.Reading.Calibration".into()), ..field("calibration", 2, Type::Group) },
+                FieldDescriptorProto { options: Some(FieldOptions { deprecated: Some(true), ..Default::default() }), ..field("raw", 3, Type::Int32) },
+            ],
+            nested_type: vec![DescriptorProto { name: Some("Calibration".into()), field: vec![field("offset", 1, Type::Int32)], ..Default::default() }],
+            extension_range: vec![ExtensionRange { start: Some(100), end: Some(200), options: None }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let ext = FileDescriptorProto {
+        name: Some("ext.proto".into()),
+        package: Some("vendor.ext".into()),
+        syntax: Some("proto2".into()),
+        dependency: vec!["bus.proto".into()],
+        extension: vec![FieldDescriptorProto { extendee: Some(".vendor.bus.Reading".into()), ..field("temperature", 100, Type::Int32) }],
+        ..Default::default()
+    };
+    ProtoDyn::from_descriptor_set(&FileDescriptorSet { file: vec![bus, ext] }.encode_to_vec()).unwrap()
+}
+
+#[test]
+fn extensions_round_trip_and_match() {
+    let proto = legacy_bus();
+    let body = json!({"sensor": "t1", "[vendor.ext.temperature]": 21});
+    let msg = proto.build_from_json("Reading", &body).unwrap();
+    let decoded = proto.decode_message("Reading", &proto.encode_message(&msg).unwrap()).unwrap();
+    let json = proto.to_json_value(&decoded);
+    assert_eq!(json, body);
+    assert!(json_partial_match(&json!({"[vendor.ext.temperature]": 21}), &json));
+    assert!(!json_partial_match(&json!({"[vendor.ext.temperature]": 22}), &json));
+
+    proto.check_field("Reading", "$['[vendor.ext.temperature]']").unwrap();
+    assert!(proto.build_from_json("Reading", &json!({"[vendor.ext.humidity]": 40})).is_err());
+}
+
+#[test]
+fn groups_are_nested_messages() {
+    let proto = legacy_bus();
+    let body = json!({"calibration": {"offset": -3}});
+    let msg = proto.build_from_json("Reading", &body).unwrap();
+    let decoded = proto.decode_message("Reading", &proto.encode_message(&msg).unwrap()).unwrap();
+    assert_eq!(proto.to_json_value(&decoded), body);
+    // the type name, as text format writes groups
+    let msg = proto.build_from_json("Reading", &json!({"Calibration": {"offset": -3}})).unwrap();
+    assert_eq!(proto.to_json_value(&msg), body);
+}
+
+#[test]
+fn deprecated_fields_are_found() {
+    let desc = legacy_bus().message_desc("Reading").unwrap();
+    assert_eq!(validate::deprecated_fields(&desc, &json!({"sensor": "t1", "raw": 7})), ["raw"]);
+    assert!(validate::deprecated_fields(&desc, &json!({"sensor": "t1"})).is_empty());
+}
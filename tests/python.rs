@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::process::Command;
+
+/// python/test_my_bdd.py against the cdylib built next to this test
+#[test]
+fn python_bindings() {
+    if Command::new("python3").arg("--version").output().is_err() {
+        eprintln!("skipping python_bindings: python3 not found");
+        return;
+    }
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(Path::parent).unwrap();
+    let lib = ["libmy_bdd.so", "libmy_bdd.dylib", "my_bdd.dll"].iter().map(|name| profile_dir.join(name)).find(|p| p.exists());
+    let lib = lib.unwrap_or_else(|| panic!("no my_bdd cdylib in {}", profile_dir.display()));
+    let out = Command::new("python3")
+        .args(["-m", "unittest", "-v", "test_my_bdd"])
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("python"))
+        .env("MY_BDD_LIB", &lib)
+        .env("PYTHONDONTWRITEBYTECODE", "1")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+}
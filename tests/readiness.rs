@@ -0,0 +1,49 @@
+use my_bdd::broker::Broker;
+use my_bdd::config::Readiness;
+use my_bdd::network::Network;
+use my_bdd::transport::zmq_pair;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// SUT on the fixed ZMQ ports answering every PingRequest with a PongReply
+fn echo_sut(stop: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    let ctx = zmq::Context::new();
+    let sub = ctx.socket(zmq::SUB).unwrap();
+    sub.set_subscribe(b"").unwrap();
+    sub.set_rcvtimeo(20).unwrap();
+    sub.bind("tcp://127.0.0.1:4246").unwrap();
+    let publisher = ctx.socket(zmq::PUB).unwrap();
+    publisher.bind("tcp://127.0.0.1:4247").unwrap();
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::SeqCst) {
+            if let Ok(frames) = sub.recv_multipart(0) {
+                if frames[0] == b"PingRequest" {
+                    publisher.send_multipart([&b"PongReply"[..], &[]], 0).unwrap();
+                }
+            }
+        }
+    })
+}
+
+#[test]
+fn connections_are_ready_once_the_sut_is_there() {
+    let (publisher, subscriber) = zmq_pair(Network::default()).unwrap();
+    let mut broker = Broker::with_transport(Box::new(publisher), Box::new(subscriber)).unwrap();
+    broker.connect("127.0.0.1").unwrap();
+
+    // nothing listening yet
+    let err = format!("{:#}", broker.wait_ready(Readiness::Monitor, Duration::from_millis(300)).unwrap_err());
+    assert!(err.contains("not ready after 300ms") && err.contains("pub 0/1"), "{}", err);
+    assert!(broker.probe("PingRequest", "PongReply", Duration::from_millis(300)).is_err());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let sut = echo_sut(stop.clone());
+    broker.wait_ready(Readiness::Monitor, Duration::from_secs(5)).unwrap();
+    broker.wait_ready(Readiness::Subscription, Duration::from_secs(5)).unwrap();
+    broker.probe("PingRequest", "PongReply", Duration::from_secs(5)).unwrap();
+    assert!(broker.probe("PingRequest", "Status", Duration::from_millis(300)).unwrap_err().to_string().contains("no Status in answer to PingRequest"));
+
+    stop.store(true, Ordering::SeqCst);
+    sut.join().unwrap();
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use my_bdd::proto_dyn::ProtoDyn;
+use std::time::{Duration, Instant};
+
+fn pong(fields: &str) -> String {
+    format!("syntax = \"proto3\";\npackage company.project.v1;\nmessage PongReply {{\n  string message = 1;\n{}}}\n", fields)
+}
+
+#[test]
+fn reload_reaches_every_clone() {
+    if !common::protoc_available("reload_reaches_every_clone") {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("bdd-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("pong.proto");
+    std::fs::write(&path, pong("")).unwrap();
+    let proto = ProtoDyn::load(&path).unwrap();
+    let clone = proto.clone();
+    assert!(clone.check_field("PongReply", "$.priority").is_err());
+
+    std::fs::write(&path, pong("  uint32 priority = 2;\n")).unwrap();
+    proto.reload().unwrap();
+    clone.check_field("PongReply", "$.priority").unwrap();
+
+    // a broken file keeps the message types in use
+    std::fs::write(&path, "syntax = \"proto3\";\nmessage {").unwrap();
+    assert!(proto.reload().is_err());
+    clone.check_field("PongReply", "$.priority").unwrap();
+
+    proto.watch(Duration::from_millis(20));
+    std::fs::write(&path, pong("  uint32 priority = 2;\n  string origin = 3;\n")).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while clone.check_field("PongReply", "$.origin").is_err() {
+        assert!(Instant::now() < deadline, "watcher did not reload");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bytes_cannot_be_reloaded() {
+    let proto = ProtoDyn::new().unwrap();
+    let copy = ProtoDyn::from_descriptor_set(&proto.descriptor_set()).unwrap();
+    assert!(copy.reload().is_err());
+}
@@ -0,0 +1,31 @@
+use my_bdd::repl::{Command, DEFAULT_TIMEOUT_MS};
+use serde_json::json;
+
+#[test]
+fn parses_send_and_expect() {
+    assert_eq!(Command::parse("send Ping {\"x\": 1}").unwrap(), Command::Send { name: "Ping".to_string(), body: json!({"x": 1}) });
+    assert_eq!(Command::parse("send Ping").unwrap(), Command::Send { name: "Ping".to_string(), body: json!({}) });
+    assert_eq!(
+        Command::parse("expect Pong {\"y\": 2} 2000").unwrap(),
+        Command::Expect { name: "Pong".to_string(), expected: json!({"y": 2}), timeout_ms: 2000 }
+    );
+    assert_eq!(
+        Command::parse("expect Pong").unwrap(),
+        Command::Expect { name: "Pong".to_string(), expected: json!({}), timeout_ms: DEFAULT_TIMEOUT_MS }
+    );
+    assert_eq!(
+        Command::parse("expect Pong 250").unwrap(),
+        Command::Expect { name: "Pong".to_string(), expected: json!({}), timeout_ms: 250 }
+    );
+}
+
+#[test]
+fn parses_other_commands() {
+    assert_eq!(Command::parse("list messages").unwrap(), Command::ListMessages);
+    assert_eq!(Command::parse("dump last 3").unwrap(), Command::Dump(3));
+    assert_eq!(Command::parse("clear Pong").unwrap(), Command::Clear(Some("Pong".to_string())));
+    assert_eq!(Command::parse("quit").unwrap(), Command::Quit);
+    assert!(Command::parse("send").is_err());
+    assert!(Command::parse("send Ping {oops").is_err());
+    assert!(Command::parse("frobnicate").is_err());
+}
@@ -0,0 +1,51 @@
+use cucumber::parser::{self, Parser};
+use futures::executor::block_on;
+use futures::StreamExt;
+use my_bdd::resume::{Mode, Results, Resume};
+
+const FEATURE: &str = "Feature: Soak
+  Scenario: flash
+    Given I run broker
+
+  Scenario: calibrate
+    Given I run broker
+
+  Scenario: soak
+    Given I run broker
+";
+
+fn scenarios(path: &std::path::Path, mode: Mode, previous: Results) -> Vec<String> {
+    let features: Vec<_> = block_on(Resume::new(parser::Basic::new(), mode, previous).parse(path, Default::default()).collect());
+    features.into_iter().flat_map(|f| f.unwrap().scenarios).map(|s| s.name).collect()
+}
+
+#[test]
+fn resumes_from_saved_results() {
+    let dir = std::env::temp_dir().join(format!("bdd-resume-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("soak.feature");
+    std::fs::write(&path, FEATURE).unwrap();
+
+    // an interrupted run: flash passed, calibrate failed, soak never started
+    let feature = block_on(parser::Basic::new().parse(&path, Default::default()).next()).unwrap().unwrap();
+    let mut results = Results::default();
+    results.record(&feature, &feature.scenarios[0], true);
+    results.record(&feature, &feature.scenarios[1], false);
+    let saved = dir.join("results").join("bdd-results.json");
+    results.save(&saved).unwrap();
+    let results = Results::load(&saved).unwrap();
+    assert_eq!(results.passed(&feature, &feature.scenarios[0]), Some(true));
+    assert_eq!(results.passed(&feature, &feature.scenarios[2]), None);
+
+    assert_eq!(scenarios(&path, Mode::RerunFailed, results.clone()), ["calibrate"]);
+    assert_eq!(scenarios(&path, Mode::SkipPassed, results.clone()), ["calibrate", "soak"]);
+    assert_eq!(scenarios(&path, Mode::All, results), ["flash", "calibrate", "soak"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_results_file_is_empty() {
+    let results = Results::load(std::path::Path::new("/nonexistent/bdd-results.json")).unwrap();
+    let feature = cucumber::gherkin::Feature::parse(FEATURE, Default::default()).unwrap();
+    assert_eq!(results.passed(&feature, &feature.scenarios[0]), None);
+}
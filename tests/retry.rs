@@ -0,0 +1,105 @@
+mod common;
+
+use anyhow::Result;
+use common::{loopback_broker, Inbound};
+use cucumber::{cli, gherkin, writer, World as _, WriterExt as _};
+use futures::future::{FutureExt, LocalBoxFuture};
+use my_bdd::broker::Broker;
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+use my_bdd::transport::Publisher;
+use serde_json::json;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+/// SUT answering each PingRequest with a PongReply, after losing the first `lost` sends
+struct LossyEcho {
+    replies: Sender<(String, Vec<u8>)>,
+    lost: AtomicU32,
+}
+
+impl Publisher for LossyEcho {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, topic: &str, _payload: &[u8]) -> Result<()> {
+        if self.lost.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_err() && topic == "PingRequest" {
+            self.replies.send(("PongReply".to_string(), Vec::new()))?;
+        }
+        Ok(())
+    }
+}
+
+fn broker(lost: u32) -> Broker {
+    let (tx, rx) = channel();
+    Broker::with_transport(Box::new(LossyEcho { replies: tx, lost: AtomicU32::new(lost) }), Box::new(Inbound(rx))).unwrap()
+}
+
+#[test]
+fn resends_until_answered() {
+    let window = Duration::from_millis(200);
+    let later = || Instant::now() + Duration::from_secs(10);
+    let (got, attempts) = broker(2).send_and_expect("PingRequest", &json!({}), "PongReply", &json!({}), window, 3, later()).unwrap();
+    assert_eq!((got.topic.as_str(), attempts), ("PongReply", 3));
+    assert_eq!(broker(0).send_and_expect("PingRequest", &json!({}), "PongReply", &json!({}), window, 3, later()).unwrap().1, 1);
+
+    let err = broker(5).send_and_expect("PingRequest", &json!({}), "PongReply", &json!({}), window, 2, later()).unwrap_err();
+    assert_eq!(err.to_string(), "no PongReply within 200ms of any of 3 PingRequest sends");
+}
+
+#[test]
+fn resends_stop_at_the_deadline() {
+    let start = Instant::now();
+    let err = broker(10).send_and_expect("PingRequest", &json!({}), "PongReply", &json!({}), Duration::from_millis(200), 9, start + Duration::from_millis(300)).unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(600), "waited {:?}", start.elapsed());
+    assert_eq!(err.to_string(), "no PongReply before the deadline, after 2 of up to 10 PingRequest sends");
+}
+
+/// The loopback echoes each Status sent, so only the scenario sending READY gets the reply it expects
+const EXPECTED_REPLY: &str = r#"Feature: the reply body is checked
+  Scenario: expected reply
+    When I send message Status and expect Status within 100ms, retrying up to 1 time:
+      | request            | reply              |
+      | {"state": "READY"} | {"state": "READY"} |
+
+  Scenario: other reply
+    When I send message Status and expect Status within 100ms, retrying up to 1 time:
+      | {"state": "BUSY"} | {"state": "READY"} |
+"#;
+
+fn with_loopback<'a>(
+    feature: &'a gherkin::Feature,
+    rule: Option<&'a gherkin::Rule>,
+    scenario: &'a gherkin::Scenario,
+    world: &'a mut MyWorld,
+) -> LocalBoxFuture<'a, ()> {
+    async move {
+        before_scenario(feature, rule, scenario, world).await;
+        world.broker = Some(loopback_broker());
+    }
+    .boxed_local()
+}
+
+#[tokio::test]
+async fn checks_the_reply_against_the_table() {
+    let dir = std::env::temp_dir().join(format!("bdd-retry-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let feature = dir.join("retry.feature");
+    std::fs::write(&feature, EXPECTED_REPLY).unwrap();
+
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(with_loopback)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    assert_eq!((writer.scenarios_stats().passed, writer.scenarios_stats().failed), (1, 1));
+}
@@ -0,0 +1,22 @@
+use my_bdd::proto_dyn::ProtoDyn;
+use serde_json::json;
+
+#[test]
+fn describes_fields_enums_and_nested_messages() {
+    let schema = ProtoDyn::new().unwrap().json_schema("Status").unwrap();
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["title"], "company.project.v1.Status");
+    assert_eq!(schema["additionalProperties"], false);
+    assert_eq!(schema["properties"]["state"]["anyOf"][0], json!({ "enum": ["STATE_UNSPECIFIED", "READY", "IDLE", "BUSY"] }));
+    assert_eq!(
+        schema["properties"]["components"],
+        json!({ "type": "array", "items": { "$ref": "#/$defs/company.project.v1.Component" } })
+    );
+    assert_eq!(schema["$defs"]["company.project.v1.Component"]["properties"]["name"], json!({ "type": "string" }));
+}
+
+#[test]
+fn unknown_message_is_an_error() {
+    let err = ProtoDyn::new().unwrap().json_schema("Nope").unwrap_err();
+    assert!(err.to_string().contains("message Nope not found"), "{}", err);
+}
@@ -0,0 +1,42 @@
+use cucumber::gherkin::{Feature, GherkinEnv};
+use my_bdd::proto_dyn::ProtoDyn;
+use my_bdd::seed::{self, Rng};
+
+const FEATURE: &str = "Feature: Fuzz
+  Scenario: one
+    Given I run broker
+  Scenario: two
+    Given I run broker
+
+  @seed:42
+  Scenario: pinned
+    Given I run broker
+";
+
+#[test]
+fn scenario_seeds_repeat_and_tags_pin_them() {
+    let feature = Feature::parse(FEATURE, GherkinEnv::default()).unwrap();
+    let seed_of = |name: &str| seed::for_scenario(&feature, feature.scenarios.iter().find(|s| s.name == name).unwrap()).unwrap();
+    assert_eq!(seed_of("one"), seed_of("one"));
+    assert_ne!(seed_of("one"), seed_of("two"));
+    assert_eq!(seed_of("pinned"), 42);
+}
+
+#[test]
+fn same_seed_same_sequence() {
+    let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+    let draws: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+    assert_eq!(draws, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+    assert_ne!(draws, (0..8).map(|_| Rng::new(8).next_u64()).collect::<Vec<_>>());
+    let mut rng = Rng::new(1);
+    assert!((0..1000).all(|_| rng.below(6) < 6 && (0.0..1.0).contains(&rng.unit())));
+}
+
+#[test]
+fn random_bodies_encode() {
+    let proto = ProtoDyn::new().unwrap();
+    let desc = proto.message_desc("Status").unwrap();
+    let body = seed::random_body(&desc, &mut Rng::new(3));
+    assert_eq!(body, seed::random_body(&desc, &mut Rng::new(3)));
+    proto.build_from_json("Status", &body).unwrap();
+}
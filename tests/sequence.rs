@@ -0,0 +1,71 @@
+mod common;
+
+use anyhow::Result;
+use common::Silent;
+use my_bdd::broker::Broker;
+use my_bdd::sequence::Sequence;
+use my_bdd::transport::Publisher;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Records when each message was sent
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<(String, Instant)>>>);
+
+impl Publisher for Recorder {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, topic: &str, _payload: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().push((topic.to_string(), Instant::now()));
+        Ok(())
+    }
+}
+
+fn load(name: &str, text: &str) -> Result<Sequence> {
+    let dir = std::env::temp_dir().join(format!("bdd-sequence-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    std::fs::write(&path, text)?;
+    let sequence = Sequence::load(&path, |text| Ok(text.replace("${state}", "READY")));
+    let _ = std::fs::remove_file(&path);
+    sequence
+}
+
+#[test]
+fn sequences_read_from_yaml_json_and_toml() {
+    let yaml = load("boot.yaml", "- message: PingRequest\n- delay: 50ms\n  message: Status\n  body: { state: ${state} }\n").unwrap();
+    let json = load("boot.json", r#"[{"message": "PingRequest"}, {"delay": "50ms", "message": "Status", "body": {"state": "${state}"}}]"#).unwrap();
+    let toml = load("boot.toml", "[[messages]]\nmessage = \"PingRequest\"\n[[messages]]\ndelay = \"50ms\"\nmessage = \"Status\"\nbody = { state = \"${state}\" }\n").unwrap();
+    assert_eq!(yaml, json);
+    assert_eq!(toml, json);
+    assert_eq!(json.entries[1].body, Some(json!({"state": "READY"})));
+    assert_eq!(json.duration(), Duration::from_millis(50));
+
+    let err = format!("{:#}", load("bad.json", r#"[{"message": "PingRequest", "delai": "1s"}]"#).unwrap_err());
+    assert!(err.contains("entry 1") && err.contains("delai"), "{}", err);
+    assert!(load("boot.txt", "").is_err());
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("bdd-sequence-test-{}", std::process::id())));
+}
+
+#[test]
+fn entries_are_sent_when_due() {
+    let recorder = Recorder::default();
+    let broker = Broker::with_transport(Box::new(recorder.clone()), Box::new(Silent)).unwrap();
+    let sequence = Sequence::from_json(json!([
+        {"message": "PingRequest"},
+        {"delay": "30ms", "message": "Status", "body": {"state": "IDLE"}},
+        {"message": "Status"},
+        {"delay": "20ms", "message": "PingRequest"},
+    ]))
+    .unwrap();
+    let start = Instant::now();
+    let played = sequence.play(&broker).unwrap();
+    assert_eq!(played.sent, 4);
+    let sent = recorder.0.lock().unwrap();
+    let offsets: Vec<(&str, u128)> = sent.iter().map(|(topic, at)| (topic.as_str(), at.duration_since(start).as_millis())).collect();
+    assert_eq!(offsets.iter().map(|(topic, _)| *topic).collect::<Vec<_>>(), ["PingRequest", "Status", "Status", "PingRequest"]);
+    assert!(offsets[1].1 >= 30 && offsets[3].1 >= 50 && offsets[3].1 < 80, "{:?}", offsets);
+    assert!(played.max_lag < Duration::from_millis(10), "{:?}", played);
+}
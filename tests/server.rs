@@ -0,0 +1,41 @@
+use my_bdd::broker::Broker;
+use my_bdd::http::{Request, Response, MAX_BODY};
+use my_bdd::server::handle;
+use std::collections::HashMap;
+use std::io::BufReader;
+
+fn request(raw: &str) -> Request {
+    Request::read(&mut BufReader::new(raw.as_bytes())).unwrap().unwrap()
+}
+
+#[test]
+fn parses_requests_with_query_and_body() {
+    let req = request("POST /expect/PongReply?timeout_ms=250 HTTP/1.1\r\nHost: x\r\nContent-Length: 7\r\n\r\n{\"a\":1}");
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.path, "/expect/PongReply");
+    assert_eq!(req.query, HashMap::from([("timeout_ms".to_string(), "250".to_string())]));
+    assert_eq!(req.json().unwrap(), serde_json::json!({"a": 1}));
+}
+
+#[test]
+fn refuses_bodies_over_the_limit() {
+    let raw = format!("POST /messages/PingRequest HTTP/1.1\r\nContent-Length: {}\r\n\r\n{{}}", MAX_BODY + 1);
+    let err = Request::read(&mut BufReader::new(raw.as_bytes())).unwrap_err();
+    assert_eq!(Response::unreadable(&err).status, 413);
+    let err = Request::read(&mut BufReader::new("GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n".as_bytes())).unwrap_err();
+    assert_eq!(Response::unreadable(&err).status, 400);
+}
+
+#[test]
+fn routes_control_requests() {
+    let broker = Broker::new().unwrap();
+    let status = |raw: &str| -> Response { handle(&broker, &request(raw)) };
+    assert_eq!(status("GET /health HTTP/1.1\r\n\r\n").status, 200);
+    assert_eq!(status("POST /messages/PingRequest HTTP/1.1\r\n\r\n").status, 200);
+    assert_eq!(status("POST /messages/NoSuchMessage HTTP/1.1\r\n\r\n").status, 400);
+    assert_eq!(status("POST /expect/PongReply?timeout_ms=20 HTTP/1.1\r\n\r\n").status, 408);
+    assert_eq!(status("GET /captures/PongReply HTTP/1.1\r\n\r\n").body, serde_json::json!([]));
+    assert_eq!(status("DELETE /captures HTTP/1.1\r\n\r\n").body, serde_json::json!({"cleared": 0}));
+    assert_eq!(status("GET /messages/PingRequest HTTP/1.1\r\n\r\n").status, 405);
+    assert_eq!(status("GET /nowhere HTTP/1.1\r\n\r\n").status, 404);
+}
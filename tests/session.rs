@@ -0,0 +1,21 @@
+mod common;
+
+use common::loopback_broker;
+use my_bdd::session::SessionLog;
+use serde_json::json;
+use std::time::SystemTime;
+
+#[test]
+fn records_sends_and_matches_in_order() {
+    let mut broker = loopback_broker();
+    let log = SessionLog::default();
+    broker.set_session_log(log.labelled("dut"));
+    broker.send_message("Status", &json!({ "state": "READY" })).unwrap();
+    log.note("between steps");
+    let step_ended = SystemTime::now();
+    broker.expect_message("Status", &json!({}), 2000).unwrap();
+
+    assert_eq!(log.take_until(step_ended), [r#"→ [dut] Status {"state":"READY"}"#, "between steps"]);
+    assert_eq!(log.take_until(SystemTime::now()), [r#"← [dut] Status {"state":1}"#]);
+    assert!(log.take_until(SystemTime::now()).is_empty());
+}
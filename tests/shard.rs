@@ -0,0 +1,55 @@
+use cucumber::gherkin::{Feature, GherkinEnv};
+use my_bdd::shard::{self, Shard};
+
+const FEATURE: &str = "Feature: Pumps
+  Scenario: one
+    Given I run broker
+  Scenario: two
+    Given I run broker
+  Scenario: three
+    Given I run broker
+  Scenario: four
+    Given I run broker
+
+  @order:1
+  Scenario: flash
+    Given I run broker
+  @order:2
+  Scenario: configure
+    Given I run broker
+";
+
+#[test]
+fn parse_shard() {
+    assert_eq!("2/5".parse::<Shard>().unwrap(), Shard { index: 2, count: 5 });
+    assert!("0/5".parse::<Shard>().is_err());
+    assert!("6/5".parse::<Shard>().is_err());
+    assert!("2".parse::<Shard>().is_err());
+}
+
+#[test]
+fn every_scenario_runs_on_exactly_one_shard() {
+    let feature = Feature::parse(FEATURE, GherkinEnv::default()).unwrap();
+    let shards: Vec<Shard> = (1..=3).map(|index| Shard { index, count: 3 }).collect();
+    let owner = |name: &str| {
+        let scenario = feature.scenarios.iter().find(|s| s.name == name).unwrap();
+        let owners: Vec<u32> = shards.iter().filter(|s| s.selects(&feature, scenario)).map(|s| s.index).collect();
+        assert_eq!(owners.len(), 1, "{} runs on shards {:?}", name, owners);
+        owners[0]
+    };
+    for name in ["one", "two", "three", "four"] {
+        owner(name);
+    }
+    // an ordered suite stays together
+    assert_eq!(owner("flash"), owner("configure"));
+}
+
+#[test]
+fn merged_events_are_ordered_by_time() {
+    let a = "{\"event\":\"run_started\",\"at\":\"2026-10-15T10:00:00.000000Z\"}\n{\"event\":\"run_finished\",\"at\":\"2026-10-15T10:00:09.000000Z\"}\n";
+    let b = "{\"event\":\"run_started\",\"at\":\"2026-10-15T10:00:01.000000Z\"}\n\n";
+    let events = shard::merge_events(&[a.to_string(), b.to_string()]).unwrap();
+    let at: Vec<_> = events.iter().map(|e| e["at"].as_str().unwrap()[17..19].to_string()).collect();
+    assert_eq!(at, ["00", "01", "09"]);
+    assert!(shard::merge_events(&["not json".to_string()]).is_err());
+}
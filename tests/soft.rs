@@ -0,0 +1,48 @@
+use cucumber::{cli, writer, World as _, WriterExt as _};
+use my_bdd::steps::{after_scenario, before_scenario, MyWorld};
+
+const FEATURE: &str = r#"Feature: soft assertions
+  Scenario: passing soft checks
+    When I run command "exit 2" locally
+    Then softly the command exits with code 2
+    And no soft assertions failed
+
+  Scenario: soft failures let the scenario go on
+    When I run command "exit 2" locally
+    Then softly the command exits with code 0
+    And softly the command exits with code 1
+    And the command exits with code 2
+
+  Scenario: soft failures are checked explicitly
+    When I run command "exit 2" locally
+    Then softly the command exits with code 0
+    And no soft assertions failed
+"#;
+
+#[tokio::test]
+async fn soft_failures_fail_the_scenario_at_its_end() {
+    let dir = std::env::temp_dir().join(format!("bdd-soft-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let feature = dir.join("soft.feature");
+    std::fs::write(&feature, FEATURE).unwrap();
+
+    let writer = MyWorld::cucumber()
+        .with_writer(writer::Basic::new(std::io::sink(), writer::Coloring::Never, writer::Verbosity::Default).summarized())
+        .with_cli(cli::Opts::<_, _, _, cli::Empty>::default())
+        .before(before_scenario)
+        .after(after_scenario)
+        .run(&feature)
+        .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    // the failed scenarios' ${scenario_tmp} is kept
+    let kept = format!("bdd-{}-", std::process::id());
+    for entry in std::fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&kept) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+    let stats = writer.scenarios_stats();
+    assert_eq!((stats.passed, stats.failed), (1, 2));
+    // every step of the soft-failing scenario ran
+    assert_eq!(writer.steps_stats().failed, 1);
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "someip")]
+
+use my_bdd::broker::Broker;
+use my_bdd::config::Config;
+use my_bdd::network::Network;
+use my_bdd::someip::{decode, encode, someip_pair, Header};
+use serde_json::json;
+use std::net::UdpSocket;
+
+#[test]
+fn header_round_trips() {
+    let header = Header { service: 0x1234, method: 0x8001, client: 1, session: 7, interface_version: 1, message_type: 2, return_code: 0 };
+    let bytes = encode(&header, &[1, 2, 3]);
+    assert_eq!(&bytes[..8], &[0x12, 0x34, 0x80, 0x01, 0, 0, 0, 11]);
+    let (decoded, payload) = decode(&bytes).unwrap();
+    assert_eq!((decoded, payload), (header, &[1u8, 2, 3][..]));
+    assert!(decode(&bytes[..10]).is_err());
+}
+
+#[test]
+fn broker_sends_over_someip() {
+    let sut = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let cfg = Config::parse("[someip]\nbind = \"127.0.0.1:0\"\nids = { PingRequest = { service = 0x1234, method = 0x0001 } }\n").unwrap();
+    let (publisher, subscriber) = someip_pair(&cfg.someip, &Network::default()).unwrap();
+    let mut broker = Broker::with_transport(Box::new(publisher), Box::new(subscriber)).unwrap();
+    broker.connect(&sut.local_addr().unwrap().to_string()).unwrap();
+    broker.send_message("PingRequest", &json!({})).unwrap();
+
+    let mut buf = [0u8; 1500];
+    let (len, _) = sut.recv_from(&mut buf).unwrap();
+    let (header, _) = decode(&buf[..len]).unwrap();
+    assert_eq!((header.service, header.method, header.session, header.message_type), (0x1234, 0x0001, 1, 0x01));
+}
@@ -0,0 +1,56 @@
+use my_bdd::http::{SseClient, SseParser, Url};
+use my_bdd::matchers::MatchOptions;
+use serde_json::json;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+#[test]
+fn parses_urls() {
+    assert_eq!(
+        Url::parse("http://gw:8080/events?topic=a").unwrap(),
+        Url { host: "gw".to_string(), port: 8080, path: "/events?topic=a".to_string(), tls: false }
+    );
+    assert_eq!(Url::parse("http://gw").unwrap().port, 80);
+    assert_eq!(Url::parse("http://[::1]:9000/s").unwrap().host, "[::1]");
+    assert_eq!(Url::parse("https://gw/events").unwrap(), Url { host: "gw".to_string(), port: 443, path: "/events".to_string(), tls: true });
+    assert!(Url::parse("gw:80").is_err());
+}
+
+#[test]
+fn parser_joins_data_lines_and_skips_comments() {
+    let mut p = SseParser::default();
+    let mut events = Vec::new();
+    for line in [": keepalive", "event: telemetry", "id: 7", "data: {\"a\":", "data: 1}", "", "data:plain", ""] {
+        events.extend(p.feed(line));
+    }
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].event, "telemetry");
+    assert_eq!(events[0].id.as_deref(), Some("7"));
+    assert_eq!(events[0].json(), json!({"a": 1}));
+    assert_eq!(events[1].event, "message");
+    assert_eq!(events[1].json(), json!("plain"));
+}
+
+#[test]
+fn client_matches_chunked_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 1024];
+        let _ = conn.read(&mut buf);
+        conn.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n").unwrap();
+        for body in ["event: telemetry\ndata: {\"seq\": 1, \"status\": \"OK\"}\n\n", "event: telemetry\ndata: {\"seq\": 2}\n\n"] {
+            write!(conn, "{:x}\r\n{}\r\n", body.len(), body).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    });
+
+    let sse = SseClient::connect(&format!("http://{}/events", addr), Duration::from_secs(2)).unwrap();
+    let timeout = Duration::from_secs(2);
+    let opts = MatchOptions::default();
+    sse.expect_event("telemetry", &json!({"seq": 2}), timeout, opts).unwrap();
+    sse.expect_event("telemetry", &json!({"status": "OK"}), timeout, opts).unwrap();
+    assert!(sse.expect_event("telemetry", &json!({"seq": 1}), Duration::from_millis(100), opts).is_err());
+}
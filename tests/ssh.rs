@@ -0,0 +1,13 @@
+#![cfg(feature = "ssh")]
+
+use my_bdd::config::Config;
+use my_bdd::ssh::ssh_args;
+
+#[test]
+fn ssh_args_from_profile() {
+    let cfg = Config::parse("[hosts.dut]\naddress = \"dut.lab\"\nuser = \"root\"\nport = 2222\noptions = [\"StrictHostKeyChecking=no\"]\n").unwrap();
+    assert_eq!(
+        ssh_args(&cfg.hosts["dut"], "systemctl restart app"),
+        ["-o", "BatchMode=yes", "-p", "2222", "-o", "StrictHostKeyChecking=no", "root@dut.lab", "--", "systemctl restart app"]
+    );
+}
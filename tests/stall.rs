@@ -0,0 +1,47 @@
+mod common;
+
+use anyhow::Result;
+use common::Inbound;
+use my_bdd::aggregate::{self, SequenceReport};
+use my_bdd::broker::Broker;
+use my_bdd::transport::Publisher;
+use serde_json::json;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::time::Duration;
+
+/// SUT publisher with a queue of 5 messages, dropping what doesn't fit like a ZMQ high-water mark
+struct Bounded(SyncSender<(String, Vec<u8>)>);
+
+impl Publisher for Bounded {
+    fn connect(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn send(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        let _ = self.0.try_send((topic.to_string(), payload.to_vec()));
+        Ok(())
+    }
+}
+
+#[test]
+fn stalled_reads_queue_then_drop() {
+    let (tx, rx) = sync_channel(5);
+    let mut broker = Broker::with_transport(Box::new(Bounded(tx)), Box::new(Inbound(rx))).unwrap();
+    broker.set_codec("Telemetry", "json").unwrap();
+    broker.send_message("Telemetry", &json!({"seq": 0})).unwrap();
+    broker.expect_message("Telemetry", &json!({"seq": 0}), 1000).unwrap();
+
+    broker.stall(Duration::from_millis(300));
+    // let the receive thread notice before publishing
+    std::thread::sleep(Duration::from_millis(100));
+    for seq in 1..=20 {
+        broker.send_message("Telemetry", &json!({ "seq": seq })).unwrap();
+    }
+    broker.expect_message("Telemetry", &json!({"seq": 5}), 1000).unwrap();
+    broker.send_message("Telemetry", &json!({"seq": 21})).unwrap();
+    broker.expect_message("Telemetry", &json!({"seq": 21}), 1000).unwrap();
+
+    assert_eq!(broker.queued().get("Telemetry"), Some(&5));
+    let values: Vec<i64> = aggregate::values(&broker.captured("Telemetry").unwrap(), "seq", None).unwrap().into_iter().map(|v| v as i64).collect();
+    let report = SequenceReport::scan(&values);
+    assert_eq!((report.lost(), report.missing), (15, vec![(6, 20)]));
+}
@@ -0,0 +1,48 @@
+use cucumber::gherkin::{Feature, GherkinEnv};
+use cucumber::parser::{self, Parser};
+use futures::executor::block_on;
+use futures::StreamExt;
+use my_bdd::suite::{self, Ordered};
+use std::collections::HashMap;
+
+const FEATURE: &str = "Feature: Bring-up
+  Scenario: independent
+    Given I run broker
+
+  @order:2
+  Scenario: configure
+    Given I run broker
+
+  @order:1
+  Scenario: flash
+    Given I run broker
+";
+
+#[test]
+fn ordered_scenarios_run_in_sequence_one_at_a_time() {
+    let dir = std::env::temp_dir().join(format!("bdd-suite-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bringup.feature");
+    std::fs::write(&path, FEATURE).unwrap();
+    let features: Vec<_> = block_on(Ordered::new(parser::Basic::new()).parse(&path, Default::default()).collect());
+    std::fs::remove_dir_all(&dir).unwrap();
+    let feature = features.into_iter().next().unwrap().unwrap();
+    let names: Vec<_> = feature.scenarios.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["independent", "flash", "configure"]);
+    let serial: Vec<_> = feature.scenarios.iter().map(|s| s.tags.iter().any(|t| t == "serial")).collect();
+    assert_eq!(serial, [false, true, true]);
+}
+
+#[test]
+fn exports_of_passed_scenarios_are_handed_on() {
+    let feature = Feature::parse(FEATURE.replace("Bring-up", "Handoff"), GherkinEnv::default()).unwrap();
+    let (flash, configure) = (&feature.scenarios[2], &feature.scenarios[1]);
+    assert_eq!(suite::order(flash).unwrap(), Some(1));
+    assert_eq!(suite::order(&feature.scenarios[0]).unwrap(), None);
+
+    suite::finish(&feature, flash, HashMap::from([("fw_version".to_string(), "1.2.3".to_string())]), true);
+    suite::finish(&feature, configure, HashMap::from([("serial_no".to_string(), "42".to_string())]), false);
+    let handoff = suite::handoff(&feature);
+    assert_eq!(handoff.vars, HashMap::from([("fw_version".to_string(), "1.2.3".to_string())]));
+    assert_eq!(handoff.failed.as_deref(), Some("configure"));
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::{Inbound, Loopback};
+use my_bdd::broker::Broker;
+use my_bdd::network::Network;
+use my_bdd::transport::{zmq_pair, Subscriber};
+use serde_json::json;
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
+
+#[test]
+fn unrelated_traffic_does_not_extend_a_wait() {
+    let (tx, rx) = channel();
+    let chatter = tx.clone();
+    let broker = Broker::with_transport(Box::new(Loopback(tx)), Box::new(Inbound(rx))).unwrap();
+    let noise = std::thread::spawn(move || {
+        let until = Instant::now() + Duration::from_millis(600);
+        while Instant::now() < until && chatter.send(("Status".to_string(), Vec::new())).is_ok() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    });
+    for _ in 0..2 {
+        let started = Instant::now();
+        assert!(broker.expect_message("PongReply", &json!({}), 200).is_err());
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(200) && waited < Duration::from_millis(400), "{:?}", waited);
+    }
+    noise.join().unwrap();
+}
+
+#[test]
+fn zmq_receive_timeouts_do_not_carry_over() {
+    let (_publisher, mut subscriber) = zmq_pair(Network::default()).unwrap();
+    let started = Instant::now();
+    assert!(subscriber.recv(Duration::from_millis(150)).unwrap().is_none());
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    let started = Instant::now();
+    assert!(subscriber.recv(Duration::ZERO).unwrap().is_none());
+    assert!(started.elapsed() < Duration::from_millis(100), "{:?}", started.elapsed());
+}
@@ -0,0 +1,102 @@
+use my_bdd::config::TlsProfile;
+use my_bdd::http::SseClient;
+use my_bdd::matchers::MatchOptions;
+use my_bdd::tls;
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn openssl_arguments_follow_the_profile() {
+    let strict = TlsProfile { ca: Some(PathBuf::from("ca.pem")), ..Default::default() };
+    assert_eq!(
+        tls::openssl_args("gw.lab", 8443, &strict).join(" "),
+        "s_client -brief -ign_eof -connect gw.lab:8443 -servername gw.lab -verify_return_error -verify_hostname gw.lab -CAfile ca.pem"
+    );
+    let lab = TlsProfile { insecure_skip_verify: true, cert: Some("c.pem".into()), key: Some("k.pem".into()), ..Default::default() };
+    assert_eq!(tls::openssl_args("10.0.0.5", 443, &lab).join(" "), "s_client -brief -ign_eof -connect 10.0.0.5:443 -cert c.pem -key k.pem");
+    assert!(tls::openssl_args("[::1]", 443, &TlsProfile::default()).contains(&"-verify_ip".to_string()));
+}
+
+/// `openssl s_server` with a fresh self-signed certificate for localhost, answering with `response`
+fn tls_server(dir: &Path, port: u16, response: &'static str) -> Child {
+    let status = Command::new("openssl")
+        .args(["req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1", "-subj", "/CN=localhost"])
+        .args(["-addext", "subjectAltName=DNS:localhost", "-keyout"])
+        .arg(dir.join("key.pem"))
+        .arg("-out")
+        .arg(dir.join("cert.pem"))
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let mut server = Command::new("openssl")
+        .args(["s_server", "-quiet", "-accept", &port.to_string(), "-cert"])
+        .arg(dir.join("cert.pem"))
+        .arg("-key")
+        .arg(dir.join("key.pem"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // s_server sends its stdin once a client is connected
+    let mut stdin = server.stdin.take().unwrap();
+    stdin.write_all(response.as_bytes()).unwrap();
+    std::mem::forget(stdin);
+    std::thread::sleep(Duration::from_millis(500));
+    server
+}
+
+#[test]
+fn sse_over_tls() {
+    let dir = std::env::temp_dir().join(format!("bdd-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut server = tls_server(&dir, port, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\nevent: telemetry\ndata: {\"seq\": 1}\n\n");
+    let url = format!("https://localhost:{}/events", port);
+
+    let trusted = TlsProfile { ca: Some(dir.join("cert.pem")), ..Default::default() };
+    let sse = SseClient::connect_with(&url, &trusted, Duration::from_secs(5)).unwrap();
+    sse.expect_event("telemetry", &json!({"seq": 1}), Duration::from_secs(2), MatchOptions::default()).unwrap();
+    let _ = server.kill();
+    let _ = server.wait();
+
+    // not signed by a CA the default profile trusts
+    let mut server = tls_server(&dir, port, "");
+    let untrusted = SseClient::connect_with(&url, &TlsProfile::default(), Duration::from_secs(5)).unwrap_err();
+    assert!(format!("{:#}", untrusted).contains("verify"), "{:#}", untrusted);
+    let _ = server.kill();
+    let _ = server.wait();
+
+    // unless the profile skips verification
+    let mut server = tls_server(&dir, port, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\nevent: telemetry\ndata: {}\n\n");
+    let lab = TlsProfile { insecure_skip_verify: true, ..Default::default() };
+    let sse = SseClient::connect_with(&url, &lab, Duration::from_secs(5)).unwrap();
+    sse.expect_event("telemetry", &json!({}), Duration::from_secs(2), MatchOptions::default()).unwrap();
+    let _ = server.kill();
+    let _ = server.wait();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_handshakes_fail_without_verification() {
+    let lab = TlsProfile { insecure_skip_verify: true, ..Default::default() };
+    // a plain HTTP server, not speaking TLS
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let _ = conn.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+    });
+    let err = tls::TlsStream::connect("127.0.0.1", port, &lab, Duration::from_secs(5)).err().unwrap();
+    assert!(err.to_string().starts_with("TLS handshake failed"), "{:#}", err);
+    server.join().unwrap();
+
+    // nothing listening
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let err = tls::TlsStream::connect("127.0.0.1", port, &lab, Duration::from_secs(5)).err().unwrap();
+    assert!(err.to_string().starts_with("TLS handshake failed"), "{:#}", err);
+}
@@ -0,0 +1,14 @@
+use my_bdd::tmpdir::ScenarioTmp;
+
+#[test]
+fn scenarios_get_separate_directories() {
+    let a = ScenarioTmp::create("Pump starts").unwrap();
+    let b = ScenarioTmp::create("Pump starts").unwrap();
+    assert_ne!(a.path(), b.path());
+    assert!(a.path().file_name().unwrap().to_str().unwrap().ends_with("Pump_starts"));
+    std::fs::write(a.path().join("out.bin"), b"data").unwrap();
+    let path = a.path().to_path_buf();
+    a.remove().unwrap();
+    assert!(!path.exists());
+    b.remove().unwrap();
+}
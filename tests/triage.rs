@@ -0,0 +1,45 @@
+mod common;
+
+use common::loopback_broker;
+use cucumber::{event, gherkin, Event, Writer};
+use my_bdd::steps::MyWorld;
+use my_bdd::triage::{ScenarioDir, TriageBundle};
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn packs_failed_scenarios_into_a_bundle() {
+    let dir = std::env::temp_dir().join(format!("bdd-triage-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("bdd.toml");
+    std::fs::write(&config, format!("[triage]\ndir = {:?}\n", dir.join("out"))).unwrap();
+    // the only test in this binary, so nothing has read the config yet
+    std::env::set_var("BDD_CONFIG", &config);
+
+    let text = "Feature: Pumps\n  Scenario: Pump starts\n    Then the pump runs\n";
+    let feature = Arc::new(gherkin::Feature::parse(text, gherkin::GherkinEnv::default()).unwrap());
+    let scenario = Arc::new(feature.scenarios[0].clone());
+    let step = Arc::new(scenario.steps[0].clone());
+
+    let broker = loopback_broker();
+    broker.send_message("Status", &json!({ "state": "BUSY" })).unwrap();
+    broker.expect_message("Status", &json!({}), 2000).unwrap();
+    let staged = ScenarioDir::create(&feature, &scenario).unwrap().expect("triage enabled");
+    staged.captures("default", &broker).unwrap();
+    staged.attachment("scope.png", &[0x89, b'P', b'N', b'G']).unwrap();
+
+    let failed = event::Scenario::Step(step, event::Step::Failed(None, None, None, event::StepError::NotFound));
+    let failed = event::Cucumber::scenario(feature.clone(), None, scenario.clone(), event::RetryableScenario { event: failed, retries: None });
+    let mut writer = TriageBundle::new();
+    for ev in [failed, event::Cucumber::Finished] {
+        Writer::<MyWorld>::handle_event(&mut writer, Ok(Event::new(ev)), &Default::default()).await;
+    }
+
+    let archive = std::fs::read_dir(dir.join("out")).unwrap().map(|e| e.unwrap().path()).find(|p| p.extension().is_some_and(|e| e == "gz"));
+    let listing = std::process::Command::new("tar").arg("-tzf").arg(archive.expect("bundle written")).output().unwrap();
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    for file in ["manifest.json", "report.txt", "config/bdd.toml", "descriptor/hash.txt", "scenarios/001-Pump_starts/default.ndjson", "scenarios/001-Pump_starts/attachments/scope.png"] {
+        assert!(listing.lines().any(|l| l.ends_with(file)), "{} missing from\n{}", file, listing);
+    }
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "typed-messages")]
+
+mod common;
+
+use common::loopback_broker;
+use my_bdd::messages::company::project::v1::{Component, State, Status};
+use my_bdd::proto_dyn::ProtoDyn;
+use prost::Message;
+use serde_json::json;
+
+fn status() -> Status {
+    Status { state: State::Idle as i32, components: vec![Component { name: "fan".into(), state: State::Ready as i32 }] }
+}
+
+#[test]
+fn generates_structs_for_the_protos() {
+    let status = status();
+    assert_eq!(status.state(), State::Idle);
+    assert_eq!(Status::decode(&status.encode_to_vec()[..]).unwrap(), status);
+}
+
+#[test]
+fn converts_between_typed_and_dynamic_messages() {
+    let proto = ProtoDyn::new().unwrap();
+    let dynamic = proto.from_typed(&status()).unwrap();
+    assert_eq!(proto.to_json_value(&dynamic), json!({ "state": 2, "components": [{ "name": "fan", "state": 1 }] }));
+    assert_eq!(proto.to_typed::<Status>(&dynamic).unwrap(), status());
+}
+
+#[test]
+fn expects_typed_messages() {
+    let broker = loopback_broker();
+    broker.send_message("Status", &json!({ "state": "BUSY" })).unwrap();
+    broker.send_typed(&status()).unwrap();
+    let idle = broker.expect_typed_matching(2000, |s: &Status| s.state() == State::Idle).unwrap();
+    assert_eq!(idle.components[0].name, "fan");
+    assert_eq!(broker.expect_typed::<Status>(2000).unwrap().state(), State::Busy);
+    let err = broker.expect_typed::<Status>(100).unwrap_err();
+    assert_eq!(err.to_string(), "timeout waiting for Status");
+}
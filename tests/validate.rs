@@ -0,0 +1,103 @@
+use my_bdd::proto_dyn::ProtoDyn;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, OneofDescriptorProto};
+use serde_json::{json, Value as JsonValue};
+
+/// Messages from tests/fixtures/validated.txtpb, with the rule definitions and descriptor.proto
+fn proto() -> ProtoDyn {
+    let mut pool = DescriptorPool::global();
+    let descriptor = pool.get_file_by_name("google/protobuf/descriptor.proto").unwrap().file_descriptor_proto().clone();
+    let set = pool.get_message_by_name("google.protobuf.FileDescriptorSet").unwrap();
+    let rules = DynamicMessage::parse_text_format(set, include_str!("fixtures/validate_rules.txtpb")).unwrap().encode_to_vec();
+    pool.decode_file_descriptor_set(&rules[..]).unwrap();
+    // parsed again with the rule extensions known
+    let set = pool.get_message_by_name("google.protobuf.FileDescriptorSet").unwrap();
+    let validated = DynamicMessage::parse_text_format(set, include_str!("fixtures/validated.txtpb")).unwrap().encode_to_vec();
+    let mut bytes = FileDescriptorSet { file: vec![descriptor] }.encode_to_vec();
+    bytes.extend(rules);
+    bytes.extend(validated);
+    ProtoDyn::from_descriptor_set(&bytes).unwrap()
+}
+
+fn rules_broken(proto: &ProtoDyn, name: &str, text: &str) -> Vec<String> {
+    let msg = DynamicMessage::parse_text_format(proto.message_desc(name).unwrap(), text).unwrap();
+    proto.validate(&msg).iter().map(|v| format!("{} {}", v.field, v.rule)).collect()
+}
+
+#[test]
+fn accepts_messages_within_their_rules() {
+    let proto = proto();
+    let config = r#"name: "edge-1" port: 4246 mode: FAST limits { max_retries: 3 ratio: 0.5 } tags: ["t-a", "t-b"]"#;
+    assert_eq!(rules_broken(&proto, "Config", config), Vec::<String>::new());
+    assert_eq!(rules_broken(&proto, "Device", r#"serial: "SN-0042" slot: 8"#), Vec::<String>::new());
+}
+
+#[test]
+fn reports_each_broken_rule_by_field() {
+    let proto = proto();
+    let config = r#"name: "E" port: 80 mode: 7 tags: ["t-a", "x", "t-a", "t-d"] id: "nope""#;
+    assert_eq!(
+        rules_broken(&proto, "Config", config),
+        [
+            "name string.min_len",
+            "name string.pattern",
+            "port uint64.gte",
+            "mode enum.defined_only",
+            "limits message.required",
+            "tags repeated.max_items",
+            "tags repeated.unique",
+            "tags[1] string.prefix",
+            "id string.uuid",
+        ]
+    );
+    assert_eq!(rules_broken(&proto, "Config", r#"name: "edge" port: 4246 limits { ratio: 1 }"#), ["mode enum.not_in", "limits.ratio double.lt"]);
+    assert_eq!(rules_broken(&proto, "Device", "slot: 9"), ["serial required", "slot int32.lte"]);
+    assert_eq!(rules_broken(&proto, "Device", r#"serial: "SN" slot: 1"#), ["serial string.min_len"]);
+}
+
+#[test]
+fn describes_violations() {
+    let proto = proto();
+    let msg = DynamicMessage::parse_text_format(proto.message_desc("Limits").unwrap(), "max_retries: 12 ratio: 0.5").unwrap();
+    let violations: Vec<String> = proto.validate(&msg).iter().map(|v| v.to_string()).collect();
+    assert_eq!(violations, ["max_retries: is 12, must be at most 10 (int32.lte)"]);
+}
+
+fn outgoing_broken(proto: &ProtoDyn, name: &str, body: JsonValue) -> Vec<String> {
+    my_bdd::validate::check_outgoing(&proto.message_desc(name).unwrap(), &body).iter().map(|v| format!("{} {}", v.field, v.rule)).collect()
+}
+
+#[test]
+fn outgoing_bodies_are_checked_before_encoding() {
+    let proto = proto();
+    let body = json!({"name": "edge\u{fffd}", "mode": 7, "limits": {"max_retries": 3_000_000_000u64}, "port": -1});
+    assert_eq!(outgoing_broken(&proto, "Config", body), ["limits.max_retries range", "mode enum", "name utf8", "port range"]);
+    assert_eq!(outgoing_broken(&proto, "Config", json!({"name": "edge", "mode": "FAST", "port": 4246})), Vec::<String>::new());
+
+    // proto2, with a required field and a oneof
+    let field = |name: &str, number, label: Label, oneof: Option<i32>| FieldDescriptorProto {
+        name: Some(name.into()),
+        number: Some(number),
+        label: Some(label as i32),
+        r#type: Some(Type::String as i32),
+        oneof_index: oneof,
+        ..Default::default()
+    };
+    let file = FileDescriptorProto {
+        name: Some("legacy.proto".into()),
+        package: Some("company.project.v1".into()),
+        syntax: Some("proto2".into()),
+        message_type: vec![DescriptorProto {
+            name: Some("Legacy".into()),
+            field: vec![field("id", 1, Label::Required, None), field("ip", 2, Label::Optional, Some(0)), field("host", 3, Label::Optional, Some(0))],
+            oneof_decl: vec![OneofDescriptorProto { name: Some("target".into()), ..Default::default() }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let proto = ProtoDyn::from_descriptor_set(&FileDescriptorSet { file: vec![file] }.encode_to_vec()).unwrap();
+    assert_eq!(outgoing_broken(&proto, "Legacy", json!({"ip": "10.0.0.5", "host": "sut"})), ["target oneof", "id proto2.required"]);
+    assert_eq!(outgoing_broken(&proto, "Legacy", json!({"id": "a", "host": "sut"})), Vec::<String>::new());
+}
@@ -0,0 +1,16 @@
+use my_bdd::vars::substitute;
+use std::collections::HashMap;
+
+#[test]
+fn substitutes_known_variables() {
+    let vars = HashMap::from([("fw".to_string(), "1.4.2".to_string())]);
+    assert_eq!(substitute(r#"{"version": "${fw}", "$within": "2s"}"#, &vars).unwrap(), r#"{"version": "1.4.2", "$within": "2s"}"#);
+    assert_eq!(substitute("flash ${ fw } now", &vars).unwrap(), "flash 1.4.2 now");
+}
+
+#[test]
+fn unknown_or_unclosed_variables_fail() {
+    let vars = HashMap::new();
+    assert!(substitute("${missing}", &vars).is_err());
+    assert!(substitute("${open", &vars).is_err());
+}
@@ -0,0 +1,39 @@
+use my_bdd::watch::{Rerun, Snapshot, Suite};
+use std::collections::{BTreeMap, BTreeSet};
+
+const FEATURE: &str = r#"Feature: watched
+  Scenario: ping
+    When I send message PingRequest
+    Then I expect message PongReply
+
+  Scenario: replay
+    When I play message sequence from "SEQUENCE"
+"#;
+
+#[test]
+fn reruns_edited_scenarios_and_those_naming_changed_files() {
+    let dir = std::env::temp_dir().join(format!("bdd-watch-{}", std::process::id()));
+    let features = dir.join("features");
+    std::fs::create_dir_all(&features).unwrap();
+    let sequence = dir.join("boot.yaml");
+    std::fs::write(&sequence, "messages: []\n").unwrap();
+    let feature = features.join("watched.feature");
+    let text = FEATURE.replace("SEQUENCE", &sequence.display().to_string());
+    std::fs::write(&feature, &text).unwrap();
+
+    let before = Suite::load(&features).unwrap();
+    let watched = before.watched(&features).unwrap();
+    assert!(watched.contains(&feature) && watched.contains(&sequence), "{:?}", watched);
+    let snapshot = Snapshot::take(watched.clone());
+    assert!(Snapshot::take(watched).changed_since(&snapshot).is_empty());
+
+    let only = |names: &[&str]| Rerun::Scenarios(BTreeMap::from([(feature.clone(), names.iter().map(|n| n.to_string()).collect::<BTreeSet<_>>())]));
+    assert_eq!(before.affected(&before, &[]), Rerun::Nothing);
+    assert_eq!(before.affected(&before, std::slice::from_ref(&sequence)), only(&["replay"]));
+    // moving a scenario isn't editing it
+    std::fs::write(&feature, text.replace("Feature: watched\n", "Feature: watched\n\n\n").replace("PongReply", "Status")).unwrap();
+    assert_eq!(before.affected(&Suite::load(&features).unwrap(), std::slice::from_ref(&feature)), only(&["ping"]));
+    assert_eq!(Suite::default().affected(&before, std::slice::from_ref(&feature)), only(&["ping", "replay"]));
+    assert_eq!(before.affected(&before, &[std::path::PathBuf::from("bdd.toml")]), Rerun::Everything);
+    let _ = std::fs::remove_dir_all(&dir);
+}